@@ -0,0 +1,16 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    println!("cargo:rerun-if-changed=proto");
+    // compiled with protox, so building doesn't need protoc installed
+    #[cfg(feature = "grpc")]
+    {
+        let files = protox::compile(["gdb_json.proto"], ["proto"])?;
+        prost_build::Config::new()
+            .service_generator(
+                tonic_build::configure()
+                    .build_client(false)
+                    .service_generator(),
+            )
+            .compile_fds(files)?;
+    }
+    Ok(())
+}
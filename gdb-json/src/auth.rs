@@ -0,0 +1,94 @@
+//! Keeping the servers that take commands, like `--ws`, to the clients of
+//! the user who started the session.
+//!
+//! Clients can send gdb any command, and gdb's `shell` runs anything, so
+//! every server takes a token made for the session. It's printed on stderr
+//! at startup and, with `--token-file`, written to a file only the user can
+//! read. Servers only listen on loopback addresses unless `--allow-remote`
+//! is given, since the token goes over the network in the clear.
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{ErrorKind, Read, Write},
+    net::SocketAddr,
+    os::unix::fs::OpenOptionsExt,
+    path::Path,
+    sync::Arc,
+};
+
+use anyhow::{bail, Context};
+
+#[derive(Clone)]
+pub struct Token(Arc<str>);
+
+impl Token {
+    /// A new random token.
+    pub fn generate() -> anyhow::Result<Self> {
+        let mut bytes = [0; 16];
+        File::open("/dev/urandom")
+            .and_then(|mut f| f.read_exact(&mut bytes))
+            .context("read /dev/urandom")?;
+        let hex: String = bytes.iter().map(|b| format!("{b:02x}")).collect();
+        Ok(Self(hex.into()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Whether `given` is the token, taking as long for any `given` of the
+    /// right length.
+    pub fn matches(&self, given: &str) -> bool {
+        let (a, b) = (self.0.as_bytes(), given.as_bytes());
+        a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+    }
+
+    /// Writes the token to `path`, readable only by the user.
+    pub fn write_to(&self, path: &Path) -> anyhow::Result<()> {
+        // a file that's already there keeps its permissions, and may be
+        // open elsewhere, so the token goes in a new one
+        match std::fs::remove_file(path) {
+            Err(e) if e.kind() != ErrorKind::NotFound => {
+                return Err(e).with_context(|| format!("remove {}", path.display()));
+            }
+            _ => {}
+        }
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(path)
+            .with_context(|| format!("create {}", path.display()))?;
+        writeln!(file, "{}", self.0).with_context(|| format!("write {}", path.display()))
+    }
+}
+
+/// Fails if `addr` isn't a loopback address and remote clients weren't
+/// allowed.
+pub fn check_bind(addr: &SocketAddr, allow_remote: bool) -> anyhow::Result<()> {
+    if !allow_remote && !addr.ip().is_loopback() {
+        bail!("{addr} isn't a loopback address, pass --allow-remote to serve on it anyway");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::Permissions, os::unix::fs::PermissionsExt};
+
+    use super::*;
+
+    #[test]
+    fn token_file_private() {
+        let path = std::env::temp_dir().join(format!("gdb-json-token-{}", std::process::id()));
+        std::fs::write(&path, "old").unwrap();
+        std::fs::set_permissions(&path, Permissions::from_mode(0o644)).unwrap();
+        let token = Token::generate().unwrap();
+        token.write_to(&path).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        let written = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(written.trim(), token.as_str());
+    }
+}
@@ -0,0 +1,225 @@
+//! Parsing the output of `thread apply all bt` (and `bt full`) into a frame
+//! list per thread.
+//!
+//! ```text
+//! Thread 2 (Thread 0x7ffff7d8a640 (LWP 1235) "worker"):
+//! #0  0x00007ffff7e8e0bb in __futex_abstimed_wait_common64 (...) at futex-internal.c:57
+//! #1  0x0000555555555200 in worker (arg=0x0) at t.c:12
+//! ```
+//!
+//! Each thread becomes `{"id", "target-id", "name", "stack"}` with frames in
+//! the shape [`parse_frame`] produces. Frames wrapped over several lines are
+//! joined, and `bt full` locals end up in the frame's `locals`.
+
+use std::{
+    io::{Read, Write},
+    path::Path,
+    sync::OnceLock,
+};
+
+use anyhow::Context;
+use regex::Regex;
+use serde_json::{json, Value};
+
+use crate::{
+    cli_log::{braces, parse_frame},
+    schema,
+};
+
+fn header_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r#"^Thread (\d+(?:\.\d+)?) \((.*?)(?: "([^"]*)")?\):$"#).unwrap())
+}
+
+fn local_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"^([A-Za-z_$][\w$.]*) = (.*)$").unwrap())
+}
+
+/// Whether `line` starts a thread's section, e.g.
+/// `Thread 1 (Thread 0x7ffff7d8b740 (LWP 1234) "main"):`.
+pub fn is_header(line: &str) -> bool {
+    header_re().is_match(line.trim_end())
+}
+
+struct Thread {
+    header: Value,
+    frames: Vec<String>,
+    locals: Vec<Vec<Value>>,
+}
+
+impl Thread {
+    fn finish(mut self) -> Value {
+        let stack: Vec<Value> = self
+            .frames
+            .iter()
+            .zip(self.locals)
+            .filter_map(|(text, locals)| {
+                let mut frame = parse_frame(text)?;
+                if !locals.is_empty() {
+                    frame["locals"] = locals.into();
+                }
+                Some(frame)
+            })
+            .collect();
+        self.header["stack"] = stack.into();
+        self.header
+    }
+}
+
+/// Parses `thread apply all bt` output. Lines outside thread sections are
+/// ignored.
+pub fn parse(text: &str) -> Vec<Value> {
+    let mut threads = Vec::new();
+    let mut current: Option<Thread> = None;
+    for line in text.lines() {
+        let line = line.trim_end();
+        if let Some(c) = header_re().captures(line) {
+            threads.extend(current.take().map(Thread::finish));
+            let mut header = json!({
+                "id": &c[1],
+                "target-id": &c[2],
+            });
+            if let Some(name) = c.get(3) {
+                header["name"] = name.as_str().into();
+            }
+            current = Some(Thread {
+                header,
+                frames: Vec::new(),
+                locals: Vec::new(),
+            });
+            continue;
+        }
+        let Some(thread) = &mut current else {
+            continue;
+        };
+        if line.starts_with('#') {
+            thread.frames.push(line.to_owned());
+            thread.locals.push(Vec::new());
+        } else if line.starts_with(char::is_whitespace) {
+            let (Some(frame), Some(locals)) = (thread.frames.last_mut(), thread.locals.last_mut())
+            else {
+                continue;
+            };
+            let text = line.trim();
+            let open = locals
+                .last_mut()
+                .filter(|l| braces(l["value"].as_str().unwrap_or_default()) > 0);
+            if let Some(last) = open {
+                // a multi-line value of the previous local
+                let value = format!("{}\n{}", last["value"].as_str().unwrap_or_default(), text);
+                last["value"] = value.into();
+            } else if let Some(c) = local_re().captures(text) {
+                locals.push(json!({"name": &c[1], "value": &c[2]}));
+            } else if locals.is_empty() && text != "No locals." {
+                frame.push(' ');
+                frame.push_str(text);
+            }
+        }
+    }
+    threads.extend(current.map(Thread::finish));
+    threads
+}
+
+/// `gdb-json parse-bt`: reads a `thread apply all bt` dump from `input` (or
+/// stdin for `-`) and writes one record with all threads.
+pub fn parse_file(input: &Path, compat: u32, mut out: impl Write) -> anyhow::Result<()> {
+    let mut text = String::new();
+    if input == Path::new("-") {
+        std::io::stdin().read_to_string(&mut text)
+    } else {
+        std::fs::File::open(input).and_then(|mut f| f.read_to_string(&mut text))
+    }
+    .with_context(|| format!("read {}", input.display()))?;
+    let mut record = json!({
+        "type": "backtrace",
+        "threads": parse(&text),
+    });
+    schema::downgrade(&mut record, compat);
+    serde_json::to_writer(&mut out, &record)?;
+    writeln!(out)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn threads() {
+        let threads = parse(
+            "\n\
+             Thread 2 (Thread 0x7ffff7d8a640 (LWP 1235) \"worker\"):\n\
+             #0  0x00007ffff7e8e0bb in __futex_abstimed_wait_common64 (private=0,\n    \
+                 cancel=true) at futex-internal.c:57\n\
+             #1  0x0000555555555200 in worker (arg=0x0) at t.c:12\n\
+             \n\
+             Thread 1 (Thread 0x7ffff7d8b740 (LWP 1234)):\n\
+             #0  main () at t.c:20\n",
+        );
+        assert_eq!(threads.len(), 2);
+        assert_eq!(threads[0]["id"], "2");
+        assert_eq!(threads[0]["target-id"], "Thread 0x7ffff7d8a640 (LWP 1235)");
+        assert_eq!(threads[0]["name"], "worker");
+        let stack = threads[0]["stack"].as_array().unwrap();
+        assert_eq!(stack.len(), 2);
+        // joined from two lines
+        assert_eq!(
+            stack[0]["args"],
+            json!([{"name": "private", "value": "0"}, {"name": "cancel", "value": "true"}])
+        );
+        assert_eq!(stack[0]["line"], "57");
+        assert_eq!(stack[1]["func"], "worker");
+        assert_eq!(threads[1]["name"], Value::Null);
+        assert_eq!(threads[1]["stack"][0]["func"], "main");
+    }
+
+    #[test]
+    fn locals() {
+        let threads = parse(
+            "Thread 1.1 (process 42):\n\
+             #0  f (n=3) at t.c:4\n        \
+                 i = 2\n        \
+                 p = {\n          x = 1,\n          y = 2\n        }\n\
+             #1  main () at t.c:9\n\
+             No locals.\n",
+        );
+        assert_eq!(threads[0]["id"], "1.1");
+        assert_eq!(
+            threads[0]["stack"][0]["locals"],
+            json!([
+                {"name": "i", "value": "2"},
+                {"name": "p", "value": "{\nx = 1,\ny = 2\n}"},
+            ])
+        );
+        assert_eq!(threads[0]["stack"][1].get("locals"), None);
+    }
+
+    #[test]
+    fn brace_char_locals() {
+        let threads = parse(
+            "Thread 1 (process 42):\n\
+             #0  main () at t.c:9\n        \
+                 c = 123 '{'\n        \
+                 n = 1\n\
+             #1  start () at t.c:2\n\
+             No locals.\n",
+        );
+        assert_eq!(
+            threads[0]["stack"][0]["locals"],
+            json!([
+                {"name": "c", "value": "123 '{'"},
+                {"name": "n", "value": "1"},
+            ])
+        );
+        assert_eq!(threads[0]["stack"][1]["func"], "start");
+    }
+
+    #[test]
+    fn headers() {
+        assert!(is_header(
+            "Thread 1 (Thread 0x7ffff7d8b740 (LWP 1234) \"main\"):"
+        ));
+        assert!(!is_header("[New Thread 0x7ffff7d8a640 (LWP 1235)]"));
+    }
+}
@@ -0,0 +1,175 @@
+//! Lossless handling of output that isn't valid UTF-8.
+//!
+//! Inferior output lines and the contents of console/target/log stream
+//! records can contain arbitrary bytes. When they don't decode to UTF-8 they
+//! are emitted with the raw bytes base64 encoded and `"encoding": "base64"`.
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde_json::{json, Value};
+
+/// Decodes the body of an MI c-string (without the surrounding quotes) to bytes.
+fn unescape(s: &[u8]) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(s.len());
+    let mut i = 0;
+    while i < s.len() {
+        let c = s[i];
+        i += 1;
+        if c != b'\\' {
+            out.push(c);
+            continue;
+        }
+        let e = *s.get(i)?;
+        i += 1;
+        out.push(match e {
+            b'n' => b'\n',
+            b't' => b'\t',
+            b'r' => b'\r',
+            b'a' => 0x07,
+            b'b' => 0x08,
+            b'f' => 0x0c,
+            b'v' => 0x0b,
+            b'e' => 0x1b,
+            b'0'..=b'7' => {
+                let mut n = (e - b'0') as u32;
+                for _ in 0..2 {
+                    match s.get(i) {
+                        Some(d @ b'0'..=b'7') => {
+                            n = n * 8 + (d - b'0') as u32;
+                            i += 1;
+                        }
+                        _ => break,
+                    }
+                }
+                n as u8
+            }
+            b'x' => {
+                let start = i;
+                while i < s.len() && s[i].is_ascii_hexdigit() && i - start < 2 {
+                    i += 1;
+                }
+                u8::from_str_radix(std::str::from_utf8(&s[start..i]).ok()?, 16).ok()?
+            }
+            c => c,
+        });
+    }
+    Some(out)
+}
+
+fn base64_record(ty: &str, bytes: &[u8]) -> Value {
+    json!({
+        "type": ty,
+        "message": STANDARD.encode(bytes),
+        "encoding": "base64",
+    })
+}
+
+fn strip_newline(line: &[u8]) -> &[u8] {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    line.strip_suffix(b"\r").unwrap_or(line)
+}
+
+/// The whole of `line` as a base64 `stdout` record.
+pub fn raw_record(line: &[u8]) -> Value {
+    base64_record("stdout", strip_newline(line))
+}
+
+/// Builds the record for `line` if it carries bytes that aren't valid
+/// UTF-8, otherwise returns `None` and the line goes through the MI parser.
+/// A stream record whose escapes don't decode is kept whole as `stdout`.
+pub fn binary_record(line: &[u8]) -> Option<Value> {
+    let line = strip_newline(line);
+
+    let stream = match line.first() {
+        Some(b'~') => Some("console"),
+        Some(b'@') => Some("target"),
+        Some(b'&') => Some("log"),
+        _ => None,
+    };
+    if let Some(ty) = stream {
+        if let Some(body) = line[1..]
+            .strip_prefix(b"\"")
+            .and_then(|l| l.strip_suffix(b"\""))
+        {
+            if let Some(bytes) = unescape(body) {
+                return std::str::from_utf8(&bytes)
+                    .is_err()
+                    .then(|| base64_record(ty, &bytes));
+            }
+        }
+    }
+
+    std::str::from_utf8(line).is_err().then(|| raw_record(line))
+}
+
+/// Turns base64 records back into (lossy) text for schema versions before 3.
+pub fn to_lossy_text(msg: &mut Value) {
+    if msg["encoding"] != "base64" {
+        return;
+    }
+    let Some(obj) = msg.as_object_mut() else {
+        return;
+    };
+    obj.remove("encoding");
+    if let Some(bytes) = obj["message"]
+        .as_str()
+        .and_then(|m| STANDARD.decode(m).ok())
+    {
+        obj["message"] = String::from_utf8_lossy(&bytes).into_owned().into();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes() {
+        assert_eq!(
+            unescape(br#"a\n\t\"\\\101\0\x41\xff\e\q"#),
+            Some(b"a\n\t\"\\A\0A\xff\x1bq".to_vec())
+        );
+        // stops at a non-digit, or after three digits
+        assert_eq!(unescape(br"\18\1234"), Some(b"\x018S4".to_vec()));
+        assert_eq!(unescape(br"a\"), None);
+        assert_eq!(unescape(br"\x"), None);
+        assert_eq!(unescape(br"\xg"), None);
+    }
+
+    #[test]
+    fn records() {
+        assert_eq!(binary_record(b"~\"hi\\n\"\n"), None);
+        assert_eq!(binary_record(b"^done\n"), None);
+        assert_eq!(
+            binary_record(br#"~"a\377b""#),
+            Some(
+                json!({"type": "console", "message": STANDARD.encode(b"a\xffb"), "encoding": "base64"})
+            )
+        );
+        assert_eq!(
+            binary_record(b"@\"\xff\"\r\n"),
+            Some(
+                json!({"type": "target", "message": STANDARD.encode(b"\xff"), "encoding": "base64"})
+            )
+        );
+        assert_eq!(
+            binary_record(b"out \xfe\n"),
+            Some(
+                json!({"type": "stdout", "message": STANDARD.encode(b"out \xfe"), "encoding": "base64"})
+            )
+        );
+    }
+
+    #[test]
+    fn bad_escapes_kept_whole() {
+        for line in [&b"~\"\xff\\\"\n"[..], b"&\"\xff\\x\""] {
+            let record = binary_record(line).unwrap();
+            assert_eq!(record["type"], "stdout");
+            let bytes = STANDARD
+                .decode(record["message"].as_str().unwrap())
+                .unwrap();
+            assert_eq!(bytes, line.strip_suffix(b"\n").unwrap_or(line));
+        }
+        // valid UTF-8 goes to the MI parser, which reports it
+        assert_eq!(binary_record(br#"~"\x""#), None);
+    }
+}
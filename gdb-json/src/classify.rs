@@ -0,0 +1,18 @@
+//! Telling MI records apart from plain CLI output mixed into the same
+//! stream, e.g. by `set logging` captures.
+
+/// Whether `line` looks like an MI record: an optional numeric token
+/// followed by one of the record prefixes, or the `(gdb)` prompt.
+pub fn is_mi(line: &str) -> bool {
+    let line = line.trim_end_matches(['\r', '\n']);
+    if line.trim_end() == "(gdb)" {
+        return true;
+    }
+    let rest = line.trim_start_matches(|c: char| c.is_ascii_digit());
+    match rest.chars().next() {
+        Some('^' | '*' | '+' | '=') => rest[1..].starts_with(|c: char| c.is_ascii_alphabetic()),
+        // stream records can't have a token
+        Some('~' | '@' | '&') => rest.len() == line.len() && rest[1..].starts_with('"'),
+        _ => false,
+    }
+}
@@ -0,0 +1,495 @@
+//! `gdb-json import-log`: turning classic CLI transcripts (`set logging`
+//! captures, terminal scrollback) into the records gdb-json emits for MI.
+//!
+//! Prompt lines become `command` records, stop banners become `stopped`
+//! notifications, `$N = ...` output and backtraces become `done` results
+//! shaped like `-data-evaluate-expression` and `-stack-list-frames`,
+//! `thread apply all bt` output becomes a `done` result with `threads` as
+//! [`backtrace::parse`] returns them, and everything else is kept as
+//! `console` text.
+
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Write},
+    path::Path,
+    sync::OnceLock,
+};
+
+use anyhow::Context;
+use gdb_types::StopReason;
+use regex::Regex;
+use serde_json::{json, Map, Value};
+
+use crate::{backtrace, schema};
+
+fn frame_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(
+            r"^(?:#(\d+)\s+)?(?:(0x[0-9a-fA-F]+) in )?(.+?) \((.*)\)(?: at (\S+):(\d+)| from (\S+))?\s*$",
+        )
+        .unwrap()
+    })
+}
+
+/// The chars of `s` and their indices, leaving out strings and char
+/// literals like `'{'`, quotes included.
+fn unquoted(s: &str) -> impl Iterator<Item = (usize, char)> + '_ {
+    let (mut quote, mut escaped) = (None, false);
+    s.char_indices().filter(move |&(_, c)| {
+        match (quote, c) {
+            (Some(_), _) if escaped => escaped = false,
+            (Some(_), '\\') => escaped = true,
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, _) => return true,
+        }
+        false
+    })
+}
+
+/// Splits `argc=1, argv=0x7fffffffe0a8` at the top level commas, ignoring
+/// the ones inside quotes and braces.
+fn split_args(s: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0i32;
+    let mut start = 0;
+    for (i, c) in unquoted(s) {
+        match c {
+            '{' | '(' | '[' | '<' => depth += 1,
+            '}' | ')' | ']' | '>' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(s[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if !s[start..].trim().is_empty() {
+        parts.push(s[start..].trim());
+    }
+    parts
+}
+
+/// Parses a frame as printed by `backtrace` and stop banners, e.g.
+/// `#1  0x0000555555555171 in main (argc=1, argv=0x7fffffffe0a8) at t.c:9`,
+/// into the shape MI uses for frames.
+pub fn parse_frame(s: &str) -> Option<Value> {
+    let c = frame_re().captures(s.trim())?;
+    let mut frame = Map::new();
+    if let Some(level) = c.get(1) {
+        frame.insert("level".into(), level.as_str().into());
+    }
+    if let Some(addr) = c.get(2) {
+        frame.insert("addr".into(), addr.as_str().into());
+    }
+    frame.insert("func".into(), c[3].into());
+    let args: Vec<Value> = split_args(&c[4])
+        .into_iter()
+        .map(|arg| match arg.split_once('=') {
+            Some((name, value)) => json!({"name": name, "value": value}),
+            None => json!({ "name": arg }),
+        })
+        .collect();
+    frame.insert("args".into(), args.into());
+    if let (Some(file), Some(line)) = (c.get(5), c.get(6)) {
+        frame.insert("file".into(), file.as_str().into());
+        frame.insert("line".into(), line.as_str().into());
+    }
+    if let Some(from) = c.get(7) {
+        frame.insert("from".into(), from.as_str().into());
+    }
+    Some(frame.into())
+}
+
+/// How many more `{` than `}` there are in `s`, outside of strings and
+/// chars.
+pub fn braces(s: &str) -> i32 {
+    unquoted(s)
+        .map(|(_, c)| match c {
+            '{' => 1,
+            '}' => -1,
+            _ => 0,
+        })
+        .sum()
+}
+
+enum Pending {
+    /// A stop banner waiting for the frame line that follows it.
+    Stop {
+        payload: Map<String, Value>,
+        banner: String,
+    },
+    Backtrace(Vec<String>),
+    /// `thread apply all bt` output, parsed once it ends.
+    Threads(String),
+    Print {
+        history: String,
+        value: String,
+    },
+}
+
+struct Importer {
+    pending: Option<Pending>,
+    banner_re: Regex,
+    signal_re: Regex,
+    watch_re: Regex,
+    exit_re: Regex,
+    print_re: Regex,
+}
+
+fn notify(message: &str, payload: Map<String, Value>) -> Value {
+    json!({
+        "type": "notify",
+        "token": null,
+        "message": message,
+        "payload": payload,
+    })
+}
+
+fn stop(reason: StopReason, thread: Option<&str>) -> Map<String, Value> {
+    let mut payload = Map::new();
+    payload.insert("reason".into(), reason.as_mi().into());
+    if let Some(thread) = thread {
+        payload.insert("thread-id".into(), thread.into());
+    }
+    payload
+}
+
+fn done(payload: Value) -> Value {
+    json!({
+        "type": "result",
+        "token": null,
+        "message": "done",
+        "payload": payload,
+    })
+}
+
+impl Importer {
+    fn new() -> Self {
+        let thread = r#"(?:Thread (\d+)(?: "[^"]*")? (?:hit|received) )?"#;
+        Self {
+            pending: None,
+            banner_re: Regex::new(&format!(
+                r"^{thread}(Temporary breakpoint|Breakpoint) (\d+), (.*)$"
+            ))
+            .unwrap(),
+            signal_re: Regex::new(&format!(
+                r"^(?:Program received |{thread})signal (\w+), (.*?)\.?$"
+            ))
+            .unwrap(),
+            watch_re: Regex::new(&format!(
+                r"^{thread}(Hardware watchpoint|Watchpoint|Hardware read watchpoint|Hardware access \(read/write\) watchpoint) (\d+): (.*)$"
+            ))
+            .unwrap(),
+            exit_re: Regex::new(
+                r"^\[Inferior (\d+) \(process (\d+)\) exited (normally|with code (\d+))\]$",
+            )
+            .unwrap(),
+            print_re: Regex::new(r"^\$(\d+) = (.*)$").unwrap(),
+        }
+    }
+
+    fn flush(&mut self) -> Option<Value> {
+        Some(match self.pending.take()? {
+            // setting a watchpoint prints the same banner as hitting one
+            Pending::Stop { payload, banner }
+                if payload.contains_key("wpt") && !payload.contains_key("value") =>
+            {
+                json!({
+                    "type": "console",
+                    "message": banner,
+                })
+            }
+            Pending::Stop { payload, .. } => notify("stopped", payload),
+            Pending::Backtrace(frames) => {
+                let stack: Vec<_> = frames.iter().filter_map(|f| parse_frame(f)).collect();
+                done(json!({ "stack": stack }))
+            }
+            Pending::Threads(text) => done(json!({ "threads": backtrace::parse(&text) })),
+            Pending::Print { history, value } => done(json!({
+                "history": history,
+                "value": value,
+            })),
+        })
+    }
+
+    /// Feeds one line, returning the records it completes.
+    fn line(&mut self, line: &str) -> Vec<Value> {
+        let mut out = Vec::new();
+
+        // continuations of multi-line output
+        match &mut self.pending {
+            Some(Pending::Print { value, .. }) if braces(value) > 0 => {
+                value.push('\n');
+                value.push_str(line);
+                return out;
+            }
+            Some(Pending::Backtrace(frames)) if line.starts_with(char::is_whitespace) => {
+                if let Some(last) = frames.last_mut() {
+                    last.push(' ');
+                    last.push_str(line.trim());
+                }
+                return out;
+            }
+            Some(Pending::Threads(text))
+                if line.is_empty()
+                    || line.starts_with(['#', ' ', '\t'])
+                    || backtrace::is_header(line) =>
+            {
+                text.push_str(line);
+                text.push('\n');
+                return out;
+            }
+            Some(Pending::Stop { payload, .. }) => {
+                if let Some(old) = line.strip_prefix("Old value = ") {
+                    payload.insert("value".into(), json!({ "old": old }));
+                    return out;
+                }
+                if let Some(new) = line.strip_prefix("New value = ") {
+                    if let Some(value) = payload.get_mut("value") {
+                        value["new"] = new.into();
+                    } else {
+                        payload.insert("value".into(), json!({ "new": new }));
+                    }
+                    return out;
+                }
+                if line.trim().is_empty() {
+                    return out;
+                }
+                if !payload.contains_key("frame") {
+                    if let Some(frame) = parse_frame(line) {
+                        payload.insert("frame".into(), frame);
+                        out.extend(self.flush());
+                        return out;
+                    }
+                }
+            }
+            _ => {}
+        }
+        if line.starts_with('#') && matches!(self.pending, Some(Pending::Backtrace(_))) {
+            if let Some(Pending::Backtrace(frames)) = &mut self.pending {
+                frames.push(line.to_owned());
+            }
+            return out;
+        }
+        out.extend(self.flush());
+
+        if let Some(command) = line.strip_prefix("(gdb)") {
+            out.push(json!({
+                "type": "command",
+                "message": command.trim(),
+            }));
+        } else if let Some(c) = self.banner_re.captures(line) {
+            let mut payload = stop(StopReason::BreakpointHit, c.get(1).map(|m| m.as_str()));
+            payload.insert("bkptno".into(), c[3].into());
+            if c[2].starts_with("Temporary") {
+                payload.insert("disp".into(), "del".into());
+            }
+            if let Some(frame) = parse_frame(&c[4]) {
+                payload.insert("frame".into(), frame);
+                out.push(notify("stopped", payload));
+            } else {
+                self.pending = Some(Pending::Stop {
+                    payload,
+                    banner: line.to_owned(),
+                });
+            }
+        } else if let Some(c) = self.signal_re.captures(line) {
+            let mut payload = stop(StopReason::SignalReceived, c.get(1).map(|m| m.as_str()));
+            payload.insert("signal-name".into(), c[2].into());
+            payload.insert("signal-meaning".into(), c[3].into());
+            self.pending = Some(Pending::Stop {
+                payload,
+                banner: line.to_owned(),
+            });
+        } else if let Some(c) = self.watch_re.captures(line) {
+            let reason = match &c[2] {
+                "Hardware read watchpoint" => StopReason::ReadWatchpointTrigger,
+                "Hardware access (read/write) watchpoint" => StopReason::AccessWatchpointTrigger,
+                _ => StopReason::WatchpointTrigger,
+            };
+            let mut payload = stop(reason, c.get(1).map(|m| m.as_str()));
+            payload.insert("wpt".into(), json!({"number": &c[3], "exp": &c[4]}));
+            self.pending = Some(Pending::Stop {
+                payload,
+                banner: line.to_owned(),
+            });
+        } else if let Some(c) = self.exit_re.captures(line) {
+            let mut payload = match c.get(4) {
+                Some(code) => {
+                    let mut p = stop(StopReason::Exited, None);
+                    p.insert("exit-code".into(), code.as_str().into());
+                    p
+                }
+                None => stop(StopReason::ExitedNormally, None),
+            };
+            payload.insert("id".into(), format!("i{}", &c[1]).into());
+            out.push(notify("stopped", payload));
+        } else if let Some(c) = self.print_re.captures(line) {
+            self.pending = Some(Pending::Print {
+                history: c[1].to_owned(),
+                value: c[2].to_owned(),
+            });
+        } else if backtrace::is_header(line) {
+            self.pending = Some(Pending::Threads(format!("{line}\n")));
+        } else if line.starts_with("#0 ") && parse_frame(line).is_some() {
+            self.pending = Some(Pending::Backtrace(vec![line.to_owned()]));
+        } else {
+            out.push(json!({
+                "type": "console",
+                "message": line,
+            }));
+        }
+        out
+    }
+}
+
+pub fn import(input: &Path, compat: u32, mut out: impl Write) -> anyhow::Result<()> {
+    let file = File::open(input).with_context(|| format!("open {}", input.display()))?;
+    let mut importer = Importer::new();
+    let mut emit = |mut record: Value| -> anyhow::Result<()> {
+        schema::downgrade(&mut record, compat);
+        serde_json::to_writer(&mut out, &record)?;
+        writeln!(out)?;
+        Ok(())
+    };
+    for line in BufReader::new(file).lines() {
+        let line = line.with_context(|| format!("read {}", input.display()))?;
+        for record in importer.line(line.trim_end_matches('\r')) {
+            emit(record)?;
+        }
+    }
+    if let Some(record) = importer.flush() {
+        emit(record)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn records(transcript: &str) -> Vec<Value> {
+        let mut importer = Importer::new();
+        let mut out: Vec<_> = transcript.lines().flat_map(|l| importer.line(l)).collect();
+        out.extend(importer.flush());
+        out
+    }
+
+    #[test]
+    fn frames() {
+        assert_eq!(
+            parse_frame("#1  0x0000555555555171 in main (argc=1, argv=0x7fffffffe0a8) at t.c:9"),
+            Some(json!({
+                "level": "1",
+                "addr": "0x0000555555555171",
+                "func": "main",
+                "args": [{"name": "argc", "value": "1"}, {"name": "argv", "value": "0x7fffffffe0a8"}],
+                "file": "t.c",
+                "line": "9",
+            }))
+        );
+        assert_eq!(
+            parse_frame("#2  0x00007ffff7829d90 in __libc_start_call_main () from /lib/libc.so.6"),
+            Some(json!({
+                "level": "2",
+                "addr": "0x00007ffff7829d90",
+                "func": "__libc_start_call_main",
+                "args": [],
+                "from": "/lib/libc.so.6",
+            }))
+        );
+        assert_eq!(parse_frame("Continuing."), None);
+    }
+
+    #[test]
+    fn args_and_braces() {
+        assert_eq!(
+            split_args(r#"p={x = 1, y = 2}, s="a, \"b\"", c=44 ','"#),
+            ["p={x = 1, y = 2}", r#"s="a, \"b\"""#, "c=44 ','"]
+        );
+        assert_eq!(braces(r#"{a = {b = 1}, s = "}}"#), 1);
+        assert_eq!(braces(r#"{s = "\"{"}"#), 0);
+        assert_eq!(braces(r"123 '{'"), 0);
+        assert_eq!(braces(r"{c = 39 '\'', d = 125 '}'"), 1);
+    }
+
+    #[test]
+    fn brace_chars() {
+        let records = records(
+            "(gdb) print c\n\
+             $1 = 123 '{'\n\
+             Breakpoint 2, main () at t.c:9\n\
+             (gdb) bt\n\
+             #0  main () at t.c:9",
+        );
+        assert_eq!(records[1]["payload"]["value"], "123 '{'");
+        assert_eq!(records[2]["type"], "notify");
+        assert_eq!(records[2]["payload"]["bkptno"], "2");
+        assert_eq!(records[3]["message"], "bt");
+    }
+
+    #[test]
+    fn transcript() {
+        let records = records(
+            "(gdb) run\n\
+             Breakpoint 1, main (argc=1) at t.c:5\n\
+             (gdb) print p\n\
+             $1 = {\n  x = 1\n}\n\
+             Program received signal SIGSEGV, Segmentation fault.\n\
+             0x0000555555555136 in f () at t.c:3\n\
+             [Inferior 1 (process 42) exited with code 01]",
+        );
+        let kinds: Vec<_> = records
+            .iter()
+            .map(|r| format!("{} {}", r["type"], r["message"]))
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                r#""command" "run""#,
+                r#""notify" "stopped""#,
+                r#""command" "print p""#,
+                r#""result" "done""#,
+                r#""notify" "stopped""#,
+                r#""notify" "stopped""#,
+            ]
+        );
+        assert_eq!(records[1]["payload"]["bkptno"], "1");
+        assert_eq!(records[1]["payload"]["frame"]["line"], "5");
+        assert_eq!(
+            records[3]["payload"],
+            json!({"history": "1", "value": "{\n  x = 1\n}"})
+        );
+        assert_eq!(records[4]["payload"]["signal-name"], "SIGSEGV");
+        assert_eq!(records[4]["payload"]["frame"]["func"], "f");
+        assert_eq!(records[5]["payload"]["reason"], "exited");
+        assert_eq!(records[5]["payload"]["exit-code"], "01");
+    }
+
+    #[test]
+    fn watchpoints() {
+        let records = records(
+            "Hardware watchpoint 2: x\n\
+             (gdb) continue\n\
+             \n\
+             Hardware watchpoint 2: x\n\
+             \n\
+             Old value = 1\n\
+             New value = 2\n\
+             main () at t.c:7",
+        );
+        assert_eq!(
+            records[0],
+            json!({"type": "console", "message": "Hardware watchpoint 2: x"})
+        );
+        assert_eq!(records[2], json!({"type": "console", "message": ""}));
+        assert_eq!(records.len(), 4);
+        let payload = &records[3]["payload"];
+        assert_eq!(payload["reason"], "watchpoint-trigger");
+        assert_eq!(payload["value"], json!({"old": "1", "new": "2"}));
+        assert_eq!(payload["frame"]["func"], "main");
+    }
+}
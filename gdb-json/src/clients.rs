@@ -0,0 +1,113 @@
+//! Handing records to the clients of `--ws`, `--listen` and `--grpc`.
+//!
+//! Every client gets a [`Subscription`] to the records from the time it
+//! connects on. A client that falls too far behind misses records rather
+//! than holding everyone up. At the end of the input the clients get a little
+//! while to send out what they have left.
+//!
+//! Result records also go to [`Subscription::results`], even the ones
+//! `--only` and `--drop` leave out, for clients waiting on the result of a
+//! command they sent.
+
+use std::{sync::Arc, time::Duration};
+
+use serde_json::Value;
+use tokio::sync::{broadcast, mpsc};
+
+/// How long [`Clients::finish`] waits for clients to catch up.
+const FINISH_TIMEOUT: Duration = Duration::from_secs(2);
+
+pub struct Clients {
+    records: broadcast::Sender<Arc<Value>>,
+    results: broadcast::Sender<Arc<Value>>,
+    alive: mpsc::Sender<()>,
+    /// Closed once every [`Subscription`] is gone.
+    done: mpsc::Receiver<()>,
+}
+
+impl Clients {
+    pub fn new() -> Self {
+        let (alive, done) = mpsc::channel(1);
+        Self {
+            records: broadcast::channel(1024).0,
+            results: broadcast::channel(1024).0,
+            alive,
+            done,
+        }
+    }
+
+    pub fn subscribe(&self) -> Subscription {
+        Subscription {
+            records: self.records.subscribe(),
+            results: self.results.subscribe(),
+            _alive: self.alive.clone(),
+        }
+    }
+
+    pub fn send(&self, msg: &Value) {
+        // records aren't kept for clients that connect later
+        let to_records = self.records.receiver_count() > 0;
+        let to_results = self.results.receiver_count() > 0 && is_result(msg);
+        if to_records || to_results {
+            let msg = Arc::new(msg.clone());
+            if to_results {
+                let _ = self.results.send(msg.clone());
+            }
+            if to_records {
+                let _ = self.records.send(msg);
+            }
+        }
+    }
+
+    /// Sends `msg` to [`Subscription::results`] only, for records that
+    /// aren't emitted.
+    pub fn send_unemitted(&self, msg: &Value) {
+        if self.results.receiver_count() > 0 && is_result(msg) {
+            let _ = self.results.send(Arc::new(msg.clone()));
+        }
+    }
+
+    /// Ends the records and waits for the clients to send out the rest.
+    pub async fn finish(self) {
+        let Self {
+            records,
+            results,
+            alive,
+            mut done,
+        } = self;
+        drop((records, results, alive));
+        let _ = tokio::time::timeout(FINISH_TIMEOUT, done.recv()).await;
+    }
+}
+
+fn is_result(msg: &Value) -> bool {
+    matches!(msg["type"].as_str(), Some("result" | "correlated"))
+}
+
+pub struct Subscription {
+    pub records: broadcast::Receiver<Arc<Value>>,
+    /// The `result` and `correlated` records, emitted or not.
+    pub results: broadcast::Receiver<Arc<Value>>,
+    _alive: mpsc::Sender<()>,
+}
+
+impl Subscription {
+    /// A subscription to the records from now on, for a new client.
+    pub fn resubscribe(&self) -> Self {
+        Self {
+            records: self.records.resubscribe(),
+            results: self.results.resubscribe(),
+            _alive: self._alive.clone(),
+        }
+    }
+
+    /// Waits for the end of the records, for tasks that only need to know
+    /// when to stop.
+    pub async fn closed(&mut self) {
+        loop {
+            if let Err(broadcast::error::RecvError::Closed) = self.records.recv().await {
+                return;
+            }
+        }
+    }
+}
@@ -0,0 +1,174 @@
+//! `gdb-json to-mi`: the other direction, turning JSON command objects into
+//! GDB/MI command lines, so a frontend can speak JSON both ways.
+//!
+//! A command is an object like
+//! `{"cmd": "-break-insert", "args": ["-t", "main"], "token": 7}`, which
+//! becomes `7-break-insert -t main`. Arguments that aren't plain words are
+//! written as MI C strings. `args` may hold strings, numbers and bools, and
+//! `args` and `token` are optional. A `cmd` that doesn't start with `-` is a
+//! CLI command, run with `-interpreter-exec console` so its output still
+//! comes back as MI records.
+
+use std::io::{BufRead, Write};
+
+use anyhow::{bail, Context};
+use serde_json::Value;
+
+/// Whether `arg` can be written without quotes.
+fn is_word(arg: &str) -> bool {
+    !arg.is_empty()
+        && !arg.starts_with('"')
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_graphic() && !matches!(c, '"' | '\\' | '{' | '}'))
+}
+
+/// `arg` as an MI C string.
+fn quote(arg: &str) -> String {
+    let mut quoted = String::with_capacity(arg.len() + 2);
+    quoted.push('"');
+    for c in arg.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn arg_text(arg: &Value) -> anyhow::Result<String> {
+    Ok(match arg {
+        Value::String(s) => s.clone(),
+        Value::Number(n) => n.to_string(),
+        Value::Bool(b) => b.to_string(),
+        _ => bail!("arguments must be strings, numbers or bools, got {arg}"),
+    })
+}
+
+/// The MI command line for the command object `cmd`, without the newline.
+pub fn to_mi(cmd: &Value) -> anyhow::Result<String> {
+    let Some(name) = cmd["cmd"].as_str() else {
+        bail!("expected a \"cmd\" string");
+    };
+    if name.is_empty() || name.contains(['\n', '\r']) {
+        bail!("invalid command {name:?}");
+    }
+    let args = match &cmd["args"] {
+        Value::Null => Vec::new(),
+        Value::Array(args) => args.iter().map(arg_text).collect::<anyhow::Result<_>>()?,
+        args => bail!("expected \"args\" to be an array, got {args}"),
+    };
+    let token = match &cmd["token"] {
+        Value::Null => String::new(),
+        token => token
+            .as_u64()
+            .filter(|&t| u32::try_from(t).is_ok())
+            .with_context(|| {
+                format!("expected \"token\" to be a non-negative integer, got {token}")
+            })?
+            .to_string(),
+    };
+
+    let mut line = token;
+    if name.starts_with('-') {
+        if name.contains(char::is_whitespace) {
+            bail!("MI command {name:?} contains whitespace, pass its arguments in \"args\"");
+        }
+        line.push_str(name);
+        for arg in &args {
+            line.push(' ');
+            if is_word(arg) {
+                line.push_str(arg);
+            } else {
+                line.push_str(&quote(arg));
+            }
+        }
+    } else {
+        let mut cli = name.to_owned();
+        for arg in &args {
+            cli.push(' ');
+            cli.push_str(arg);
+        }
+        line.push_str("-interpreter-exec console ");
+        line.push_str(&quote(&cli));
+    }
+    Ok(line)
+}
+
+/// Reads command objects, one per line, and writes the MI command line for
+/// each.
+pub fn translate(input: impl BufRead, mut out: impl Write) -> anyhow::Result<()> {
+    for (i, line) in input.lines().enumerate() {
+        let line = line.context("read commands")?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let cmd: Value =
+            serde_json::from_str(&line).with_context(|| format!("line {}: invalid JSON", i + 1))?;
+        let mi = to_mi(&cmd).with_context(|| format!("line {}", i + 1))?;
+        writeln!(out, "{mi}")?;
+        out.flush()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn mi(cmd: Value) -> String {
+        to_mi(&cmd).unwrap()
+    }
+
+    #[test]
+    fn mi_commands() {
+        assert_eq!(
+            mi(json!({"cmd": "-break-insert", "args": ["-t", "main"], "token": 7})),
+            "7-break-insert -t main"
+        );
+        assert_eq!(
+            mi(json!({"cmd": "-data-evaluate-expression", "args": ["a + b", 3, true]})),
+            r#"-data-evaluate-expression "a + b" 3 true"#
+        );
+        assert_eq!(mi(json!({"cmd": "-exec-run"})), "-exec-run");
+    }
+
+    #[test]
+    fn quoting() {
+        assert_eq!(
+            mi(json!({"cmd": "-x", "args": ["", "\"q\"", "a\\b", "{}", "l1\nl2\t", "é"]})),
+            r#"-x "" "\"q\"" "a\\b" "{}" "l1\nl2\t" "é""#
+        );
+    }
+
+    #[test]
+    fn cli_commands() {
+        assert_eq!(
+            mi(json!({"cmd": "print", "args": ["\"hi\""], "token": 1})),
+            r#"1-interpreter-exec console "print \"hi\"""#
+        );
+    }
+
+    #[test]
+    fn errors() {
+        for cmd in [
+            json!({"args": []}),
+            json!({"cmd": ""}),
+            json!({"cmd": "info\nrun"}),
+            json!({"cmd": "-exec-run --all"}),
+            json!({"cmd": "-x", "args": "a"}),
+            json!({"cmd": "-x", "args": [null]}),
+            json!({"cmd": "-x", "token": -1}),
+            json!({"cmd": "-x", "token": 1u64 << 32}),
+        ] {
+            assert!(to_mi(&cmd).is_err(), "{cmd}");
+        }
+    }
+}
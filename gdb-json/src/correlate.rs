@@ -0,0 +1,104 @@
+//! `--correlate`: grouping the records a command causes with its result.
+//!
+//! MI interleaves async records like `=breakpoint-modified` and console
+//! output between a command and its `^done`. In correlation mode the
+//! notifications, console and log records that come in after a prompt are
+//! held back until the next result. A result with a token is emitted as a
+//! single `correlated` record holding the result and the held back records:
+//!
+//! ```json
+//! {"type": "correlated", "token": 4, "result": {...}, "records": [...]}
+//! ```
+//!
+//! Before a result without a token, or a prompt (which is what ends a batch
+//! of records gdb sends on its own, like `*stopped`), the held back records
+//! are emitted as they are. Program output isn't held back.
+
+use serde_json::{json, Value};
+
+#[derive(Default)]
+pub struct Correlator {
+    held: Vec<Value>,
+}
+
+impl Correlator {
+    /// Feeds a record through, returning the records to emit now.
+    pub fn process(&mut self, msg: Value) -> Vec<Value> {
+        match msg["type"].as_str().unwrap_or_default() {
+            "notify" | "console" | "log" => {
+                self.held.push(msg);
+                Vec::new()
+            }
+            "result" if !msg["token"].is_null() => vec![json!({
+                "type": "correlated",
+                "token": msg["token"],
+                "result": msg,
+                "records": std::mem::take(&mut self.held),
+            })],
+            "result" | "done" => {
+                let mut out = std::mem::take(&mut self.held);
+                out.push(msg);
+                out
+            }
+            _ => vec![msg],
+        }
+    }
+
+    /// Emits the records held back at the end of the input.
+    pub fn finish(self) -> Vec<Value> {
+        self.held
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fed(records: &[Value]) -> Vec<Value> {
+        let mut correlator = Correlator::default();
+        let mut out: Vec<_> = records
+            .iter()
+            .flat_map(|r| correlator.process(r.clone()))
+            .collect();
+        out.extend(correlator.finish());
+        out
+    }
+
+    #[test]
+    fn with_token() {
+        let modified = json!({"type": "notify", "message": "breakpoint-modified"});
+        let console = json!({"type": "console", "message": "hi"});
+        let output = json!({"type": "target", "message": "out"});
+        let done = json!({"type": "result", "message": "done", "token": 4});
+        assert_eq!(
+            fed(&[
+                modified.clone(),
+                output.clone(),
+                console.clone(),
+                done.clone()
+            ]),
+            [
+                output,
+                json!({"type": "correlated", "token": 4, "result": done, "records": [modified, console]}),
+            ]
+        );
+    }
+
+    #[test]
+    fn without_token() {
+        let stopped = json!({"type": "notify", "message": "stopped"});
+        let prompt = json!({"type": "done"});
+        let done = json!({"type": "result", "message": "done"});
+        let log = json!({"type": "log", "message": "warning"});
+        assert_eq!(
+            fed(&[
+                stopped.clone(),
+                prompt.clone(),
+                log.clone(),
+                done.clone(),
+                stopped.clone()
+            ]),
+            [stopped.clone(), prompt, log, done, stopped]
+        );
+    }
+}
@@ -0,0 +1,1222 @@
+//! `gdb-json dap`: a Debug Adapter Protocol server on stdio, so editors that
+//! speak DAP, like VS Code, can debug with gdb through gdb-json. See
+//! <https://microsoft.github.io/debug-adapter-protocol/specification>.
+//!
+//! gdb is started with the MI interpreter on `launch` or `attach`, and each
+//! request becomes one or more MI commands whose results are matched up by
+//! token. MI records gdb sends on its own become events: `*stopped` and
+//! `*running` become `stopped` and `continued`, thread notifications become
+//! `thread`, and console, log and program output become `output`.
+//!
+//! Supported requests are `initialize`, `launch`, `attach`,
+//! `setBreakpoints`, `configurationDone`, `threads`, `stackTrace`, `scopes`,
+//! `variables`, `evaluate`, `continue`, `next`, `stepIn`, `stepOut`, `pause`,
+//! `cancel` and `disconnect`. Values are parsed with value-parser, so
+//! structs, arrays and maps can be expanded in the variables view.
+//!
+//! Requests are handled one at a time, but are read while one is waiting on
+//! gdb so it can be cancelled, like an `evaluate` of a huge expression. A
+//! cancelled request is answered right away with the message `cancelled`,
+//! and the result gdb sends for it later is dropped.
+//!
+//! `launch` and `attach` take `timeouts`, in milliseconds, for the MI
+//! commands of each class: `evaluate` for expressions and variables, `exec`
+//! for running and stepping, and `breakpoint` for setting and deleting them,
+//! like `{"evaluate": 5000}`. A request whose command takes longer fails
+//! with a message saying so, and with `interruptOnTimeout` the program is
+//! interrupted too.
+//!
+//! They also take how forks and execs are followed: `followFork` is
+//! `parent` or `child`, `detachOnFork` set to false keeps the other process
+//! as a second inferior whose threads show up alongside, and `followExec`
+//! is `same` or `new`, like gdb's `follow-fork-mode`, `detach-on-fork` and
+//! `follow-exec-mode`. Stops at fork and exec catchpoints are `stopped`
+//! events with the reason `fork` or `exec`.
+//!
+//! The threads, frames and locals gdb reports are cached until the program
+//! runs or stops again, so a client asking for the same stack twice in one
+//! stop only costs one MI command. An `evaluate` empties the cache, since
+//! it may assign to variables. Commands that don't depend on each other,
+//! like the ones for `setBreakpoints`, are sent back to back and matched up
+//! as their results come in, rather than waiting for each in turn.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    process::Stdio,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use anyhow::{anyhow, bail, Context};
+use gdb_types::StopReason;
+use serde_json::{json, Value};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader},
+    process::{Child, Command},
+    sync::{mpsc, oneshot},
+    task::JoinHandle,
+};
+
+use crate::command;
+
+/// The MI results that haven't come back yet, by token.
+type Pending = Arc<Mutex<HashMap<u32, oneshot::Sender<(String, Value)>>>>;
+
+/// Counts `*running` and `*stopped` records, so results from the same stop
+/// have the same count.
+type Stops = Arc<AtomicU64>;
+
+/// The longest message read, far more than any request needs.
+const MAX_MESSAGE: usize = 16 << 20;
+
+/// `msg` with the header in front.
+fn framed(msg: &Value) -> String {
+    let body = msg.to_string();
+    format!("Content-Length: {}\r\n\r\n{body}", body.len())
+}
+
+/// Reads one message, or `None` at the end of the input.
+async fn read_message(
+    input: &mut BufReader<impl AsyncRead + Unpin>,
+) -> anyhow::Result<Option<Value>> {
+    let mut len = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line).await? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                len = Some(
+                    value
+                        .trim()
+                        .parse::<usize>()
+                        .context("invalid Content-Length")?,
+                );
+            }
+        }
+    }
+    let len = len.context("missing Content-Length")?;
+    if len > MAX_MESSAGE {
+        bail!("Content-Length {len} is over the limit of {MAX_MESSAGE}");
+    }
+    let mut body = vec![0; len];
+    input.read_exact(&mut body).await?;
+    Ok(Some(
+        serde_json::from_slice(&body).context("invalid message")?,
+    ))
+}
+
+/// Sends messages to the client, numbering them as they go out.
+#[derive(Clone)]
+struct Client {
+    messages: mpsc::UnboundedSender<Value>,
+}
+
+impl Client {
+    /// A client and the task writing to it, which ends once every clone of
+    /// the client is gone.
+    fn new() -> (Self, JoinHandle<()>) {
+        let (messages, mut rx) = mpsc::unbounded_channel::<Value>();
+        let writer = tokio::spawn(async move {
+            let mut stdout = tokio::io::stdout();
+            let mut seq = 0;
+            while let Some(mut msg) = rx.recv().await {
+                seq += 1;
+                msg["seq"] = seq.into();
+                if stdout.write_all(framed(&msg).as_bytes()).await.is_err()
+                    || stdout.flush().await.is_err()
+                {
+                    break;
+                }
+            }
+        });
+        (Self { messages }, writer)
+    }
+
+    fn send(&self, msg: Value) {
+        // the client is gone, there's no one to tell
+        let _ = self.messages.send(msg);
+    }
+
+    fn event(&self, event: &str, body: Value) {
+        self.send(json!({"type": "event", "event": event, "body": body}));
+    }
+
+    fn respond(&self, req: &Value, result: anyhow::Result<Value>) {
+        let mut msg = json!({
+            "type": "response",
+            "request_seq": req["seq"],
+            "command": req["command"],
+        });
+        match result {
+            Ok(body) => {
+                msg["success"] = true.into();
+                msg["body"] = body;
+            }
+            Err(e) => {
+                msg["success"] = false.into();
+                msg["message"] = format!("{e:#}").into();
+            }
+        }
+        self.send(msg);
+    }
+}
+
+/// An MI payload field that gdb sends as a string but is a number.
+fn number(v: &Value) -> Option<u64> {
+    v.as_str()?.parse().ok()
+}
+
+/// Turns MI records gdb sends on its own into events, and hands results to
+/// whoever is waiting for them.
+async fn read_records(
+    output: impl AsyncRead + Unpin,
+    pending: Pending,
+    stops: Stops,
+    client: Client,
+) -> anyhow::Result<()> {
+    let mut lines = BufReader::new(output).lines();
+    let mut terminated = false;
+    while let Some(line) = lines.next_line().await? {
+        let msg = match gdbmi::parser::parse_message(&line) {
+            Ok(msg) => crate::message_to_json(msg),
+            // with no terminal of its own, the program's output ends up here
+            Err(_) => {
+                client.event(
+                    "output",
+                    json!({"category": "stdout", "output": line + "\n"}),
+                );
+                continue;
+            }
+        };
+        let payload = &msg["payload"];
+        if msg["type"] == "notify" && matches!(msg["message"].as_str(), Some("running" | "stopped"))
+        {
+            stops.fetch_add(1, Ordering::SeqCst);
+        }
+        match (msg["type"].as_str(), msg["message"].as_str()) {
+            (Some("result"), Some(message)) => {
+                let waiting = msg["token"]
+                    .as_u64()
+                    .and_then(|token| pending.lock().unwrap().remove(&(token as u32)));
+                if let Some(waiting) = waiting {
+                    let _ = waiting.send((message.to_owned(), payload.clone()));
+                }
+            }
+            (Some("notify"), Some("stopped")) => {
+                let reason = payload["reason"].as_str().map(StopReason::from_mi);
+                if let Some(
+                    StopReason::Exited | StopReason::ExitedNormally | StopReason::ExitedSignalled,
+                ) = reason
+                {
+                    let code = payload["exit-code"]
+                        .as_str()
+                        .and_then(|code| i64::from_str_radix(code, 8).ok())
+                        .unwrap_or(0);
+                    client.event("exited", json!({"exitCode": code}));
+                    client.event("terminated", json!({}));
+                    terminated = true;
+                    continue;
+                }
+                let reason = match reason {
+                    Some(StopReason::BreakpointHit) => "breakpoint",
+                    Some(StopReason::EndSteppingRange | StopReason::FunctionFinished) => "step",
+                    Some(StopReason::SignalReceived) => "exception",
+                    Some(StopReason::Fork | StopReason::Vfork) => "fork",
+                    Some(StopReason::Exec) => "exec",
+                    Some(
+                        StopReason::WatchpointTrigger
+                        | StopReason::ReadWatchpointTrigger
+                        | StopReason::AccessWatchpointTrigger,
+                    ) => "data breakpoint",
+                    _ => "pause",
+                };
+                let mut body = json!({"reason": reason, "allThreadsStopped": true});
+                if let Some(thread) = number(&payload["thread-id"]) {
+                    body["threadId"] = thread.into();
+                }
+                if let Some(signal) = payload["signal-meaning"].as_str() {
+                    body["text"] = signal.into();
+                } else if let Some(pid) = payload["newpid"].as_str() {
+                    body["text"] = format!("process {pid}").into();
+                } else if let Some(exec) = payload["new-exec"].as_str() {
+                    body["text"] = exec.into();
+                }
+                client.event("stopped", body);
+            }
+            (Some("notify"), Some("running")) => {
+                let thread = number(&payload["thread-id"]);
+                client.event(
+                    "continued",
+                    json!({"threadId": thread.unwrap_or(0), "allThreadsContinued": thread.is_none()}),
+                );
+            }
+            (Some("notify"), Some(message @ ("thread-created" | "thread-exited"))) => {
+                let reason = if message == "thread-created" {
+                    "started"
+                } else {
+                    "exited"
+                };
+                if let Some(thread) = number(&payload["id"]) {
+                    client.event("thread", json!({"reason": reason, "threadId": thread}));
+                }
+            }
+            (Some(ty @ ("console" | "target" | "log" | "stdout" | "stderr")), _) => {
+                let category = match ty {
+                    "console" => "console",
+                    "target" | "stdout" => "stdout",
+                    _ => "stderr",
+                };
+                client.event(
+                    "output",
+                    json!({"category": category, "output": msg["message"]}),
+                );
+            }
+            _ => {}
+        }
+    }
+    if !terminated {
+        client.event("terminated", json!({}));
+    }
+    Ok(())
+}
+
+/// The class an MI command's timeout is looked up by.
+fn class(cmd: &str) -> Option<&'static str> {
+    match cmd {
+        "-data-evaluate-expression" | "-stack-list-variables" => Some("evaluate"),
+        _ if cmd.starts_with("-exec-") => Some("exec"),
+        _ if cmd.starts_with("-break-") => Some("breakpoint"),
+        _ => None,
+    }
+}
+
+/// How long MI commands of each class may take, from `launch` or `attach`.
+#[derive(Debug, Default, PartialEq)]
+struct Timeouts {
+    limits: HashMap<&'static str, Duration>,
+    /// Whether to interrupt the program when a command times out.
+    interrupt: bool,
+}
+
+impl Timeouts {
+    fn from_args(args: &Value) -> anyhow::Result<Self> {
+        let mut limits = HashMap::new();
+        for (name, ms) in args["timeouts"].as_object().into_iter().flatten() {
+            let class = ["evaluate", "exec", "breakpoint"]
+                .into_iter()
+                .find(|class| class == name)
+                .with_context(|| format!("unknown timeout {name:?}"))?;
+            let ms = ms
+                .as_u64()
+                .with_context(|| format!("expected the {name} timeout in milliseconds"))?;
+            limits.insert(class, Duration::from_millis(ms));
+        }
+        Ok(Self {
+            limits,
+            interrupt: args["interruptOnTimeout"].as_bool().unwrap_or(false),
+        })
+    }
+
+    fn limit(&self, cmd: &str) -> Option<Duration> {
+        self.limits.get(class(cmd)?).copied()
+    }
+}
+
+/// A running gdb.
+struct Gdb {
+    /// `None` when gdb isn't our child, as in tests.
+    child: Option<Child>,
+    stdin: Box<dyn AsyncWrite + Unpin + Send>,
+    pending: Pending,
+    next_token: u32,
+    timeouts: Timeouts,
+    stops: Stops,
+    cache: Cache,
+}
+
+/// MI results by command line, from one stop.
+#[derive(Default)]
+struct Cache {
+    stop: u64,
+    results: HashMap<String, Value>,
+}
+
+impl Cache {
+    fn get(&mut self, stop: u64, line: &str) -> Option<&Value> {
+        if stop != self.stop {
+            self.stop = stop;
+            self.results.clear();
+        }
+        self.results.get(line)
+    }
+
+    fn insert(&mut self, stop: u64, line: String, payload: Value) {
+        if stop == self.stop {
+            self.results.insert(line, payload);
+        }
+    }
+}
+
+impl Gdb {
+    fn spawn(program: &str, args: &[String], client: &Client) -> anyhow::Result<Self> {
+        let mut child = Command::new(program)
+            .args(["--interpreter=mi3", "--quiet"])
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .with_context(|| format!("spawn {program}"))?;
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = child.stdout.take().expect("stdout is piped");
+        Ok(Self {
+            child: Some(child),
+            ..Self::new(stdin, stdout, client)
+        })
+    }
+
+    /// A gdb taking commands on `stdin` and sending records on `stdout`.
+    fn new(
+        stdin: impl AsyncWrite + Unpin + Send + 'static,
+        stdout: impl AsyncRead + Unpin + Send + 'static,
+        client: &Client,
+    ) -> Self {
+        let pending = Pending::default();
+        let stops = Stops::default();
+        let client = client.clone();
+        tokio::spawn({
+            let pending = pending.clone();
+            let stops = stops.clone();
+            async move {
+                if let Err(e) = read_records(stdout, pending, stops, client).await {
+                    eprintln!("gdb-json: reading from gdb: {e:#}");
+                }
+            }
+        });
+        Self {
+            child: None,
+            stdin: Box::new(stdin),
+            pending,
+            next_token: 1,
+            timeouts: Timeouts::default(),
+            stops,
+            cache: Cache::default(),
+        }
+    }
+
+    /// Like [`Gdb::exec`], for commands that only look at the stopped
+    /// program, whose results are cached until it runs or stops again.
+    async fn query(&mut self, cmd: &str, args: &[&str]) -> anyhow::Result<Value> {
+        let stop = self.stops.load(Ordering::SeqCst);
+        let key = command::to_mi(&json!({"cmd": cmd, "args": args}))?;
+        if let Some(payload) = self.cache.get(stop, &key) {
+            return Ok(payload.clone());
+        }
+        let payload = self.exec(cmd, args).await?;
+        // the program may have run while waiting
+        if self.stops.load(Ordering::SeqCst) == stop {
+            self.cache.insert(stop, key, payload.clone());
+        }
+        Ok(payload)
+    }
+
+    /// Runs an MI command and returns the payload of its result.
+    async fn exec(&mut self, cmd: &str, args: &[&str]) -> anyhow::Result<Value> {
+        let mut results = self.batch(&[(cmd, args.to_vec())]).await?;
+        results.pop().expect("a result for each command")
+    }
+
+    /// Writes MI commands back to back and returns the payload of each
+    /// one's result, in order, without waiting for one before sending the
+    /// next.
+    async fn batch(
+        &mut self,
+        cmds: &[(&str, Vec<&str>)],
+    ) -> anyhow::Result<Vec<anyhow::Result<Value>>> {
+        let mut lines = String::new();
+        let mut tokens = Vec::new();
+        for (i, (cmd, args)) in cmds.iter().enumerate() {
+            let token = self.next_token + i as u32;
+            lines.push_str(&command::to_mi(
+                &json!({"cmd": cmd, "args": args, "token": token}),
+            )?);
+            lines.push('\n');
+            tokens.push(token);
+        }
+        self.next_token += cmds.len() as u32;
+        let mut waiting = Vec::new();
+        for token in tokens {
+            let (tx, rx) = oneshot::channel();
+            self.pending.lock().unwrap().insert(token, tx);
+            waiting.push((token, rx));
+        }
+        self.stdin
+            .write_all(lines.as_bytes())
+            .await
+            .context("write to gdb")?;
+        let mut results = Vec::new();
+        for ((cmd, _), (token, rx)) in cmds.iter().zip(waiting) {
+            results.push(self.wait(cmd, token, rx).await);
+        }
+        Ok(results)
+    }
+
+    /// Waits for the result of `cmd`, sent with `token`.
+    async fn wait(
+        &mut self,
+        cmd: &str,
+        token: u32,
+        rx: oneshot::Receiver<(String, Value)>,
+    ) -> anyhow::Result<Value> {
+        let Some(limit) = self.timeouts.limit(cmd) else {
+            return result(rx.await.context("gdb exited")?);
+        };
+        match tokio::time::timeout(limit, rx).await {
+            Ok(done) => result(done.context("gdb exited")?),
+            Err(_) => {
+                // its result is of no use to anyone now
+                self.pending.lock().unwrap().remove(&token);
+                if self.timeouts.interrupt {
+                    self.stdin
+                        .write_all(b"-exec-interrupt\n")
+                        .await
+                        .context("write to gdb")?;
+                }
+                bail!("{cmd} timed out after {}ms", limit.as_millis());
+            }
+        }
+    }
+}
+
+/// The payload of an MI result, or its error message.
+fn result((message, payload): (String, Value)) -> anyhow::Result<Value> {
+    if message == "error" {
+        bail!(
+            "{}",
+            payload["msg"].as_str().unwrap_or("gdb reported an error")
+        );
+    }
+    Ok(payload)
+}
+
+/// What a `variablesReference` refers to, while the program is stopped.
+enum Ref {
+    Locals { thread: String, level: String },
+    Value(value_parser::Value<'static>),
+}
+
+/// The value inside wrappers like casts, references and smart pointers.
+fn unwrapped<'v>(v: &'v value_parser::Value<'static>) -> &'v value_parser::Value<'static> {
+    use value_parser::Value::*;
+    match v {
+        Truncated(v)
+        | Static(v)
+        | Encoded { value: v, .. }
+        | Reference { value: v, .. }
+        | Typed { value: v, .. }
+        | SmartPointer { pointer: v, .. }
+        | Described { value: Some(v), .. } => unwrapped(v),
+        v => v,
+    }
+}
+
+/// The children shown when `v` is expanded, if it has any.
+fn children(
+    v: &value_parser::Value<'static>,
+) -> Option<Vec<(String, value_parser::Value<'static>)>> {
+    use value_parser::Value::*;
+    Some(match unwrapped(v) {
+        Struct(fields) => fields
+            .iter()
+            .map(|(name, v)| (name.to_string(), v.clone()))
+            .collect(),
+        Map(entries) => entries
+            .iter()
+            .map(|(k, v)| (format!("[{}]", k.to_gdb_string()), v.clone()))
+            .collect(),
+        List(_) => {
+            let List(items) = v.expanded() else {
+                unreachable!("lists expand to lists");
+            };
+            items
+                .into_iter()
+                .enumerate()
+                .map(|(i, v)| (format!("[{i}]"), v))
+                .collect()
+        }
+        Pointer {
+            pointee: Some(pointee),
+            ..
+        } => vec![("*".to_owned(), (**pointee).clone())],
+        _ => return None,
+    })
+}
+
+struct Adapter {
+    client: Client,
+    gdb_program: String,
+    gdb: Option<Gdb>,
+    /// Whether `configurationDone` starts the program, as opposed to
+    /// continuing one that was attached to.
+    launch: Option<bool>,
+    stop_on_entry: bool,
+    /// The gdb breakpoint numbers set for each source file.
+    breakpoints: HashMap<String, Vec<String>>,
+    /// The thread and level of each frame id handed out, frame ids are the
+    /// index plus one.
+    frames: Vec<(String, String)>,
+    /// What each `variablesReference` refers to, references are the index
+    /// plus one.
+    refs: Vec<Ref>,
+}
+
+impl Adapter {
+    fn new(client: Client, gdb_program: &str) -> Self {
+        Self {
+            client,
+            gdb_program: gdb_program.to_owned(),
+            gdb: None,
+            launch: None,
+            stop_on_entry: false,
+            breakpoints: HashMap::new(),
+            frames: Vec::new(),
+            refs: Vec::new(),
+        }
+    }
+
+    fn gdb(&mut self) -> anyhow::Result<&mut Gdb> {
+        self.gdb.as_mut().context("not launched or attached yet")
+    }
+
+    /// Forgets frames and variables, which are only valid until the program
+    /// runs again.
+    fn resume(&mut self) {
+        self.frames.clear();
+        self.refs.clear();
+    }
+
+    fn frame(&self, args: &Value) -> anyhow::Result<(String, String)> {
+        let id = args["frameId"].as_u64().context("expected a frameId")?;
+        self.frames
+            .get((id as usize).wrapping_sub(1))
+            .cloned()
+            .context("unknown frame")
+    }
+
+    /// A variable in a `variables` or `evaluate` response.
+    fn variable(&mut self, name: String, value: &str) -> Value {
+        let (parsed, _) = value_parser::Parser::new(value).parse_value_lossy();
+        let parsed = parsed.into_owned();
+        let reference = match children(&parsed) {
+            Some(children) if !children.is_empty() => {
+                self.refs.push(Ref::Value(parsed));
+                self.refs.len()
+            }
+            _ => 0,
+        };
+        json!({"name": name, "value": value, "variablesReference": reference})
+    }
+
+    /// Starts gdb with `gdb_args`, for the `launch` or `attach` request with
+    /// `args`.
+    fn start_gdb(&mut self, gdb_args: &[String], args: &Value) -> anyhow::Result<()> {
+        if self.gdb.is_some() {
+            bail!("already launched or attached");
+        }
+        let timeouts = Timeouts::from_args(args)?;
+        let mut gdb = Gdb::spawn(&self.gdb_program, gdb_args, &self.client)?;
+        gdb.timeouts = timeouts;
+        self.gdb = Some(gdb);
+        Ok(())
+    }
+
+    /// Sets how forks and execs are followed, from `launch` or `attach`.
+    async fn follow(&mut self, args: &Value) -> anyhow::Result<()> {
+        let gdb = self.gdb()?;
+        if let Some(mode) = args["followFork"].as_str() {
+            gdb.exec("-gdb-set", &["follow-fork-mode", mode]).await?;
+        }
+        if let Some(detach) = args["detachOnFork"].as_bool() {
+            let detach = if detach { "on" } else { "off" };
+            gdb.exec("-gdb-set", &["detach-on-fork", detach]).await?;
+        }
+        if let Some(mode) = args["followExec"].as_str() {
+            gdb.exec("-gdb-set", &["follow-exec-mode", mode]).await?;
+        }
+        Ok(())
+    }
+
+    async fn handle(&mut self, command: &str, args: &Value) -> anyhow::Result<Value> {
+        Ok(match command {
+            "initialize" => json!({
+                "supportsConfigurationDoneRequest": true,
+                "supportsEvaluateForHovers": true,
+                "supportsCancelRequest": true,
+            }),
+            "launch" => {
+                let program = args["program"].as_str().context("expected a program")?;
+                let mut gdb_args = vec!["--args".to_owned(), program.to_owned()];
+                if let Some(program_args) = args["args"].as_array() {
+                    gdb_args.extend(
+                        program_args
+                            .iter()
+                            .filter_map(|a| a.as_str())
+                            .map(str::to_owned),
+                    );
+                }
+                self.start_gdb(&gdb_args, args)?;
+                if let Some(cwd) = args["cwd"].as_str() {
+                    self.gdb()?.exec("-environment-cd", &[cwd]).await?;
+                }
+                self.follow(args).await?;
+                self.launch = Some(true);
+                self.stop_on_entry = args["stopOnEntry"].as_bool().unwrap_or(false);
+                self.client.event("initialized", json!({}));
+                json!({})
+            }
+            "attach" => {
+                let pid = args["processId"]
+                    .as_u64()
+                    .or_else(|| args["processId"].as_str()?.parse().ok())
+                    .context("expected a processId")?;
+                self.start_gdb(&[], args)?;
+                self.follow(args).await?;
+                self.gdb()?
+                    .exec("-target-attach", &[&pid.to_string()])
+                    .await?;
+                self.launch = Some(false);
+                self.client.event("initialized", json!({}));
+                json!({})
+            }
+            "setBreakpoints" => {
+                let path = args["source"]["path"]
+                    .as_str()
+                    .context("expected a source path")?
+                    .to_owned();
+                let requested = args["breakpoints"].as_array().cloned().unwrap_or_default();
+                let lines = requested
+                    .iter()
+                    .map(|bp| bp["line"].as_u64().context("expected a line"))
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                let locations: Vec<_> = lines.iter().map(|line| format!("{path}:{line}")).collect();
+                let old = self.breakpoints.remove(&path).unwrap_or_default();
+                // deleted and set in one batch
+                let mut cmds = Vec::new();
+                if !old.is_empty() {
+                    cmds.push(("-break-delete", old.iter().map(String::as_str).collect()));
+                }
+                for (bp, location) in requested.iter().zip(&locations) {
+                    let mut mi_args = Vec::new();
+                    if let Some(condition) = bp["condition"].as_str() {
+                        mi_args.extend(["-c", condition]);
+                    }
+                    mi_args.push(location.as_str());
+                    cmds.push(("-break-insert", mi_args));
+                }
+                let mut results = self.gdb()?.batch(&cmds).await?;
+                let inserted = results.split_off(usize::from(!old.is_empty()));
+                let mut numbers = Vec::new();
+                let mut breakpoints = Vec::new();
+                for (line, result) in lines.into_iter().zip(inserted) {
+                    breakpoints.push(match result {
+                        Ok(payload) => {
+                            let bkpt = &payload["bkpt"];
+                            let number = bkpt["number"].as_str().unwrap_or("").to_owned();
+                            let id = number.parse::<u64>().ok();
+                            numbers.push(number);
+                            json!({
+                                "id": id,
+                                "verified": true,
+                                "line": number_or(&bkpt["line"], line),
+                            })
+                        }
+                        Err(e) => {
+                            json!({"verified": false, "line": line, "message": format!("{e:#}")})
+                        }
+                    });
+                }
+                self.breakpoints.insert(path, numbers);
+                if let Some(deleted) = results.pop() {
+                    deleted?;
+                }
+                json!({ "breakpoints": breakpoints })
+            }
+            "configurationDone" => {
+                let stop_on_entry = self.stop_on_entry;
+                match self.launch {
+                    Some(true) if stop_on_entry => {
+                        self.gdb()?.exec("-exec-run", &["--start"]).await?
+                    }
+                    Some(true) => self.gdb()?.exec("-exec-run", &[]).await?,
+                    Some(false) => self.gdb()?.exec("-exec-continue", &[]).await?,
+                    None => bail!("not launched or attached yet"),
+                };
+                json!({})
+            }
+            "threads" => {
+                let payload = self.gdb()?.query("-thread-info", &[]).await?;
+                let threads: Vec<_> = payload["threads"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|t| {
+                        let name = t["name"].as_str().or(t["target-id"].as_str()).unwrap_or("");
+                        Some(json!({"id": number(&t["id"])?, "name": name}))
+                    })
+                    .collect();
+                json!({ "threads": threads })
+            }
+            "stackTrace" => {
+                let thread = args["threadId"]
+                    .as_u64()
+                    .context("expected a threadId")?
+                    .to_string();
+                let payload = self
+                    .gdb()?
+                    .query("-stack-list-frames", &["--thread", &thread])
+                    .await?;
+                let frames = payload["stack"].as_array().cloned().unwrap_or_default();
+                let start = args["startFrame"].as_u64().unwrap_or(0) as usize;
+                let levels = match args["levels"].as_u64() {
+                    Some(0) | None => frames.len(),
+                    Some(levels) => levels as usize,
+                };
+                let mut stack_frames = Vec::new();
+                for frame in frames.iter().skip(start).take(levels) {
+                    let level = frame["level"].as_str().unwrap_or("0").to_owned();
+                    self.frames.push((thread.clone(), level));
+                    let mut stack_frame = json!({
+                        "id": self.frames.len(),
+                        "name": frame["func"].as_str().unwrap_or("??"),
+                        "line": number_or(&frame["line"], 0),
+                        "column": 0,
+                    });
+                    if let Some(path) = frame["fullname"].as_str() {
+                        stack_frame["source"] = json!({"name": frame["file"], "path": path});
+                    }
+                    stack_frames.push(stack_frame);
+                }
+                json!({"stackFrames": stack_frames, "totalFrames": frames.len()})
+            }
+            "scopes" => {
+                let (thread, level) = self.frame(args)?;
+                self.refs.push(Ref::Locals { thread, level });
+                json!({"scopes": [
+                    {"name": "Locals", "variablesReference": self.refs.len(), "expensive": false},
+                ]})
+            }
+            "variables" => {
+                let reference = args["variablesReference"]
+                    .as_u64()
+                    .context("expected a variablesReference")?;
+                let variables = match self.refs.get((reference as usize).wrapping_sub(1)) {
+                    Some(Ref::Locals { thread, level }) => {
+                        let mi_args = ["--thread", thread, "--frame", level, "--all-values"]
+                            .map(str::to_owned);
+                        let mi_args = mi_args.each_ref().map(String::as_str);
+                        let payload = self.gdb()?.query("-stack-list-variables", &mi_args).await?;
+                        payload["variables"]
+                            .as_array()
+                            .into_iter()
+                            .flatten()
+                            .map(|v| {
+                                let name = v["name"].as_str().unwrap_or("").to_owned();
+                                self.variable(name, v["value"].as_str().unwrap_or(""))
+                            })
+                            .collect::<Vec<_>>()
+                    }
+                    Some(Ref::Value(v)) => children(&v.clone())
+                        .unwrap_or_default()
+                        .into_iter()
+                        .map(|(name, v)| self.variable(name, &v.to_gdb_string()))
+                        .collect(),
+                    None => bail!("unknown variablesReference {reference}"),
+                };
+                json!({ "variables": variables })
+            }
+            "evaluate" => {
+                let expression = args["expression"]
+                    .as_str()
+                    .context("expected an expression")?;
+                let mut mi_args = Vec::new();
+                let frame;
+                if args["frameId"].is_u64() {
+                    frame = self.frame(args)?;
+                    mi_args.extend(["--thread", &frame.0, "--frame", &frame.1]);
+                }
+                mi_args.push(expression);
+                let gdb = self.gdb()?;
+                let payload = gdb.exec("-data-evaluate-expression", &mi_args).await?;
+                // the expression may have assigned to a variable
+                gdb.cache.results.clear();
+                let value = payload["value"].as_str().unwrap_or("");
+                let variable = self.variable(String::new(), value);
+                json!({"result": value, "variablesReference": variable["variablesReference"]})
+            }
+            "continue" => {
+                self.resume();
+                self.gdb()?.exec("-exec-continue", &[]).await?;
+                json!({"allThreadsContinued": true})
+            }
+            "next" | "stepIn" | "stepOut" => {
+                let thread = args["threadId"]
+                    .as_u64()
+                    .context("expected a threadId")?
+                    .to_string();
+                let cmd = match command {
+                    "next" => "-exec-next",
+                    "stepIn" => "-exec-step",
+                    _ => "-exec-finish",
+                };
+                self.resume();
+                self.gdb()?.exec(cmd, &["--thread", &thread]).await?;
+                json!({})
+            }
+            "pause" => {
+                self.gdb()?.exec("-exec-interrupt", &[]).await?;
+                json!({})
+            }
+            "disconnect" => {
+                if let Some(mut gdb) = self.gdb.take() {
+                    // gdb kills a program it launched and detaches from one
+                    // it attached to
+                    let _ = gdb.exec("-gdb-exit", &[]).await;
+                    if let Some(child) = &mut gdb.child {
+                        child.wait().await.context("wait for gdb")?;
+                    }
+                }
+                json!({})
+            }
+            _ => bail!("unsupported request {command:?}"),
+        })
+    }
+}
+
+/// An MI payload number, or `default` if it's missing.
+fn number_or(v: &Value, default: u64) -> u64 {
+    number(v).unwrap_or(default)
+}
+
+/// Answers the `cancel` request `req`, and any queued request it cancels.
+/// Returns whether it cancels `running`, the request being handled.
+fn cancel(
+    client: &Client,
+    req: &Value,
+    queued: &mut VecDeque<anyhow::Result<Value>>,
+    running: &Value,
+) -> bool {
+    let id = &req["arguments"]["requestId"];
+    queued.retain(|queued| match queued {
+        Ok(queued) if queued["seq"] == *id => {
+            client.respond(queued, Err(anyhow!("cancelled")));
+            false
+        }
+        _ => true,
+    });
+    client.respond(req, Ok(json!({})));
+    running["seq"] == *id
+}
+
+/// Serves DAP on stdio until the client disconnects, running `gdb_program`
+/// for each session.
+pub async fn serve(gdb_program: &str) -> anyhow::Result<()> {
+    let (client, writer) = Client::new();
+    let mut adapter = Adapter::new(client.clone(), gdb_program);
+    let (tx, mut requests) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        let mut stdin = BufReader::new(tokio::io::stdin());
+        loop {
+            let req = match read_message(&mut stdin).await {
+                Ok(Some(req)) if req["type"] != "request" => continue,
+                Ok(Some(req)) => Ok(req),
+                Ok(None) => break,
+                Err(e) => Err(e),
+            };
+            let failed = req.is_err();
+            if tx.send(req).is_err() || failed {
+                break;
+            }
+        }
+    });
+    let mut queued = VecDeque::new();
+    loop {
+        let req = match queued.pop_front() {
+            Some(req) => req,
+            None => match requests.recv().await {
+                Some(req) => req,
+                None => break,
+            },
+        };
+        let req = req?;
+        let command = req["command"].as_str().unwrap_or("").to_owned();
+        if command == "cancel" {
+            // what it cancels is already answered
+            cancel(&client, &req, &mut queued, &Value::Null);
+            continue;
+        }
+        let handling = adapter.handle(&command, &req["arguments"]);
+        tokio::pin!(handling);
+        let result = loop {
+            tokio::select! {
+                result = &mut handling => break result,
+                Some(next) = requests.recv() => match next {
+                    Ok(next) if next["command"] == "cancel" => {
+                        if cancel(&client, &next, &mut queued, &req) {
+                            break Err(anyhow!("cancelled"));
+                        }
+                    }
+                    next => queued.push_back(next),
+                },
+            }
+        };
+        client.respond(&req, result);
+        if command == "disconnect" {
+            break;
+        }
+    }
+    // let the responses out before exiting
+    drop((adapter, client));
+    writer.await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A client whose messages can be looked at.
+    fn client() -> (Client, mpsc::UnboundedReceiver<Value>) {
+        let (messages, rx) = mpsc::unbounded_channel();
+        (Client { messages }, rx)
+    }
+
+    /// The events sent for `mi`, gdb's output.
+    async fn events(mi: &str) -> Vec<Value> {
+        let (client, mut sent) = client();
+        read_records(mi.as_bytes(), Pending::default(), Stops::default(), client)
+            .await
+            .unwrap();
+        let mut events = Vec::new();
+        while let Ok(event) = sent.try_recv() {
+            events.push(event);
+        }
+        events
+    }
+
+    /// An adapter whose gdb answers each MI command with `answer`, and the
+    /// commands it was sent, without tokens.
+    fn fake_gdb(answer: fn(&str) -> &'static str) -> (Adapter, Arc<Mutex<Vec<String>>>) {
+        let (client, _) = client();
+        let (stdin, gdb_in) = tokio::io::duplex(4096);
+        let (mut gdb_out, stdout) = tokio::io::duplex(4096);
+        let commands = Arc::new(Mutex::new(Vec::new()));
+        tokio::spawn({
+            let commands = commands.clone();
+            async move {
+                let mut lines = BufReader::new(gdb_in).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    let (token, cmd) = line.split_at(line.find('-').unwrap_or(0));
+                    let reply = format!("{token}{}\n(gdb)\n", answer(cmd));
+                    commands.lock().unwrap().push(cmd.to_owned());
+                    if gdb_out.write_all(reply.as_bytes()).await.is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+        let mut adapter = Adapter::new(client, "gdb");
+        adapter.gdb = Some(Gdb::new(stdin, stdout, &adapter.client));
+        (adapter, commands)
+    }
+
+    fn request(seq: u64, command: &str, arguments: Value) -> Value {
+        json!({"type": "request", "seq": seq, "command": command, "arguments": arguments})
+    }
+
+    #[test]
+    fn cancelled() {
+        let (client, mut sent) = client();
+        let running = request(1, "evaluate", json!({"expression": "x"}));
+        let mut queued = VecDeque::from([
+            Ok(request(2, "threads", json!({}))),
+            Ok(request(3, "threads", json!({}))),
+        ]);
+        let req = request(4, "cancel", json!({"requestId": 3}));
+        assert!(!cancel(&client, &req, &mut queued, &running));
+        assert_eq!(queued.len(), 1);
+        let answered = sent.try_recv().unwrap();
+        assert_eq!(answered["request_seq"], 3);
+        assert_eq!(answered["success"], false);
+        assert_eq!(answered["message"], "cancelled");
+        let answered = sent.try_recv().unwrap();
+        assert_eq!(answered["request_seq"], 4);
+        assert_eq!(answered["success"], true);
+
+        let req = request(5, "cancel", json!({"requestId": 1}));
+        assert!(cancel(&client, &req, &mut queued, &running));
+        assert_eq!(queued.len(), 1);
+    }
+
+    #[test]
+    fn timeouts() {
+        let timeouts = Timeouts::from_args(&json!({
+            "timeouts": {"evaluate": 5000, "exec": 100},
+            "interruptOnTimeout": true,
+        }))
+        .unwrap();
+        assert_eq!(
+            timeouts.limit("-data-evaluate-expression"),
+            Some(Duration::from_secs(5))
+        );
+        assert_eq!(
+            timeouts.limit("-exec-next"),
+            Some(Duration::from_millis(100))
+        );
+        assert_eq!(timeouts.limit("-break-insert"), None);
+        assert_eq!(timeouts.limit("-thread-info"), None);
+        assert!(timeouts.interrupt);
+        assert_eq!(
+            Timeouts::from_args(&json!({})).unwrap(),
+            Timeouts::default()
+        );
+        for args in [
+            json!({"timeouts": {"eval": 1}}),
+            json!({"timeouts": {"exec": "1s"}}),
+        ] {
+            assert!(Timeouts::from_args(&args).is_err(), "{args}");
+        }
+    }
+
+    #[tokio::test]
+    async fn forks() {
+        let events = events(
+            "*stopped,reason=\"fork\",newpid=\"43\",thread-id=\"1\"\n\
+             *stopped,reason=\"exec\",new-exec=\"/bin/true\",thread-id=\"2\"\n",
+        )
+        .await;
+        assert_eq!(
+            events[0]["body"],
+            json!({"reason": "fork", "text": "process 43", "threadId": 1, "allThreadsStopped": true})
+        );
+        assert_eq!(events[1]["body"]["reason"], "exec");
+        assert_eq!(events[1]["body"]["text"], "/bin/true");
+    }
+
+    #[test]
+    fn cache() {
+        let mut cache = Cache::default();
+        assert_eq!(cache.get(0, "-thread-info"), None);
+        cache.insert(0, "-thread-info".into(), json!({"threads": []}));
+        assert_eq!(cache.get(0, "-thread-info"), Some(&json!({"threads": []})));
+        // ran and stopped again
+        assert_eq!(cache.get(2, "-thread-info"), None);
+        cache.insert(0, "-thread-info".into(), json!({}));
+        assert_eq!(cache.get(2, "-thread-info"), None);
+    }
+
+    #[tokio::test]
+    async fn framing() {
+        let msg = request(1, "threads", json!({}));
+        let input = framed(&msg) + &framed(&msg);
+        let mut input = BufReader::new(input.as_bytes());
+        assert_eq!(read_message(&mut input).await.unwrap(), Some(msg.clone()));
+        assert_eq!(read_message(&mut input).await.unwrap(), Some(msg));
+        assert_eq!(read_message(&mut input).await.unwrap(), None);
+
+        for bad in [
+            "Content-Length: 99999999999\r\n\r\n",
+            "Content-Length: x\r\n\r\n{}",
+            "Content-Type: json\r\n\r\n{}",
+            "Content-Length: 2\r\n\r\n{",
+        ] {
+            let mut input = BufReader::new(bad.as_bytes());
+            assert!(read_message(&mut input).await.is_err(), "{bad:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn requests_to_mi() {
+        let (mut adapter, commands) = fake_gdb(|cmd| match cmd.split(' ').next() {
+            Some("-break-insert") => r#"^done,bkpt={number="2",line="3"}"#,
+            Some("-thread-info") => r#"^done,threads=[{id="1",target-id="LWP 42"}]"#,
+            Some("-data-evaluate-expression") => {
+                r#"^error,msg="No symbol \"b\" in current context.""#
+            }
+            _ => "^done",
+        });
+        let body = adapter
+            .handle(
+                "setBreakpoints",
+                &json!({"source": {"path": "t.c"}, "breakpoints": [{"line": 3, "condition": "x > 1"}]}),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            body,
+            json!({"breakpoints": [{"id": 2, "verified": true, "line": 3}]})
+        );
+        let body = adapter.handle("threads", &json!({})).await.unwrap();
+        assert_eq!(body, json!({"threads": [{"id": 1, "name": "LWP 42"}]}));
+        adapter
+            .handle("next", &json!({"threadId": 1}))
+            .await
+            .unwrap();
+        let err = adapter
+            .handle("evaluate", &json!({"expression": "a + b"}))
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), r#"No symbol "b" in current context."#);
+        // the breakpoint set before is deleted first
+        adapter
+            .handle(
+                "setBreakpoints",
+                &json!({"source": {"path": "t.c"}, "breakpoints": []}),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            *commands.lock().unwrap(),
+            [
+                r#"-break-insert -c "x > 1" t.c:3"#,
+                "-thread-info",
+                "-exec-next --thread 1",
+                r#"-data-evaluate-expression "a + b""#,
+                "-break-delete 2",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn stopped_events() {
+        let events = events(
+            "*running,thread-id=\"all\"\n\
+             *stopped,reason=\"breakpoint-hit\",bkptno=\"1\",thread-id=\"1\"\n\
+             *stopped,reason=\"signal-received\",signal-name=\"SIGSEGV\",signal-meaning=\"Segmentation fault\",thread-id=\"2\"\n\
+             *stopped,reason=\"exited\",exit-code=\"010\"\n",
+        )
+        .await;
+        let events: Vec<_> = events
+            .iter()
+            .map(|e| json!([e["event"], e["body"]]))
+            .collect();
+        assert_eq!(
+            events,
+            [
+                json!(["continued", {"threadId": 0, "allThreadsContinued": true}]),
+                json!(["stopped", {"reason": "breakpoint", "threadId": 1, "allThreadsStopped": true}]),
+                json!(["stopped", {"reason": "exception", "threadId": 2, "text": "Segmentation fault", "allThreadsStopped": true}]),
+                json!(["exited", {"exitCode": 8}]),
+                json!(["terminated", {}]),
+            ]
+        );
+    }
+}
@@ -0,0 +1,202 @@
+//! `gdb-json diff`: comparing two recorded sessions.
+//!
+//! Both sessions are reduced to their significant events (command results,
+//! stops and breakpoint changes), which are aligned by kind so that an extra
+//! or missing event doesn't make everything after it differ. Aligned events
+//! whose payloads differ, after masking addresses that change from run to
+//! run, are reported as changed.
+
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Write},
+    path::Path,
+};
+
+use anyhow::Context;
+use gdb_types::StopEvent;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use similar::{capture_diff_slices, Algorithm, DiffOp};
+
+struct Event {
+    line: usize,
+    key: String,
+    record: Value,
+}
+
+fn read_events(path: &Path) -> anyhow::Result<Vec<Event>> {
+    let file = File::open(path).with_context(|| format!("open {}", path.display()))?;
+    let mut events = Vec::new();
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.with_context(|| format!("read {}", path.display()))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record: Value = serde_json::from_str(&line)
+            .with_context(|| format!("{}:{}: invalid JSON", path.display(), i + 1))?;
+        let message = record["message"].as_str().unwrap_or_default();
+        let key = match record["type"].as_str() {
+            Some("result") => format!("result {message}"),
+            Some("notify") if message == "stopped" => {
+                let stop = StopEvent::deserialize(&record["payload"]).unwrap_or_default();
+                format!(
+                    "stopped {} {}",
+                    stop.reason.map(|r| r.to_string()).unwrap_or_default(),
+                    stop.frame.and_then(|f| f.func).unwrap_or_default()
+                )
+            }
+            Some("notify") if message.starts_with("breakpoint-") => format!("notify {message}"),
+            _ => continue,
+        };
+        events.push(Event {
+            line: i + 1,
+            key,
+            record: normalize(&record["payload"]),
+        });
+    }
+    Ok(events)
+}
+
+/// Masks values that legitimately differ between runs, like addresses.
+fn normalize(v: &Value) -> Value {
+    match v {
+        Value::String(s)
+            if s.starts_with("0x") && s[2..].chars().all(|c| c.is_ascii_hexdigit()) =>
+        {
+            "<addr>".into()
+        }
+        Value::Array(a) => a.iter().map(normalize).collect(),
+        Value::Object(o) => o.iter().map(|(k, v)| (k.clone(), normalize(v))).collect(),
+        v => v.clone(),
+    }
+}
+
+fn side(e: &Event) -> Value {
+    json!({
+        "line": e.line,
+        "event": e.key,
+        "payload": e.record,
+    })
+}
+
+/// Writes one `diff` record per divergence and returns how many there were.
+pub fn diff(a: &Path, b: &Path, mut out: impl Write) -> anyhow::Result<usize> {
+    let a = read_events(a)?;
+    let b = read_events(b)?;
+    let a_keys: Vec<_> = a.iter().map(|e| e.key.as_str()).collect();
+    let b_keys: Vec<_> = b.iter().map(|e| e.key.as_str()).collect();
+
+    let missing = |e: &Event| json!({"type": "diff", "kind": "missing", "a": side(e), "b": null});
+    let extra = |e: &Event| json!({"type": "diff", "kind": "extra", "a": null, "b": side(e)});
+
+    let mut divergences = Vec::new();
+    for op in capture_diff_slices(Algorithm::Myers, &a_keys, &b_keys) {
+        let (old, new) = (op.old_range(), op.new_range());
+        if let DiffOp::Equal { .. } = op {
+            for (ea, eb) in a[old].iter().zip(&b[new]) {
+                if ea.record != eb.record {
+                    divergences.push(json!({
+                        "type": "diff",
+                        "kind": "changed",
+                        "a": side(ea),
+                        "b": side(eb),
+                    }));
+                }
+            }
+        } else {
+            divergences.extend(a[old].iter().map(missing));
+            divergences.extend(b[new].iter().map(extra));
+        }
+    }
+
+    for d in &divergences {
+        serde_json::to_writer(&mut out, d)?;
+        writeln!(out)?;
+    }
+    Ok(divergences.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    fn session(name: &str, records: &[Value]) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("gdb-json-diff-{}-{name}", std::process::id()));
+        let lines: Vec<_> = records.iter().map(Value::to_string).collect();
+        std::fs::write(&path, lines.join("\n")).unwrap();
+        path
+    }
+
+    fn stopped(func: &str, addr: &str, line: &str) -> Value {
+        json!({
+            "type": "notify",
+            "message": "stopped",
+            "payload": {"reason": "breakpoint-hit", "frame": {"func": func, "addr": addr, "line": line}},
+        })
+    }
+
+    /// The divergences between `a` and `b`, written to files named after
+    /// `test` so tests running at the same time don't share them.
+    fn divergences(test: &str, a: &[Value], b: &[Value]) -> Vec<Value> {
+        let (a, b) = (
+            session(&format!("{test}-a"), a),
+            session(&format!("{test}-b"), b),
+        );
+        let mut out = Vec::new();
+        let n = diff(&a, &b, &mut out).unwrap();
+        std::fs::remove_file(a).unwrap();
+        std::fs::remove_file(b).unwrap();
+        let divergences: Vec<Value> = out
+            .split(|&b| b == b'\n')
+            .filter(|l| !l.is_empty())
+            .map(|l| serde_json::from_slice(l).unwrap())
+            .collect();
+        assert_eq!(divergences.len(), n);
+        divergences
+    }
+
+    #[test]
+    fn addresses_masked() {
+        assert_eq!(
+            normalize(&json!({"addr": "0x401136", "value": "0xzz"})),
+            json!({"addr": "<addr>", "value": "0xzz"})
+        );
+        let console = json!({"type": "console", "message": "hi"});
+        assert!(divergences(
+            "addresses_masked",
+            &[stopped("main", "0x1149", "7"), console],
+            &[stopped("main", "0x5555555551a9", "7")]
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn changed() {
+        let d = divergences(
+            "changed",
+            &[stopped("main", "0x1", "7")],
+            &[stopped("main", "0x1", "8")],
+        );
+        assert_eq!(d.len(), 1);
+        assert_eq!(d[0]["kind"], "changed");
+        assert_eq!(d[0]["a"]["event"], "stopped breakpoint-hit main");
+        assert_eq!(d[0]["b"]["payload"]["frame"]["line"], "8");
+    }
+
+    #[test]
+    fn aligned_around_extra_events() {
+        let done = json!({"type": "result", "message": "done"});
+        let d = divergences(
+            "aligned_around_extra_events",
+            &[stopped("main", "0x1", "7"), done.clone()],
+            &[stopped("main", "0x1", "7"), stopped("f", "0x2", "3"), done],
+        );
+        assert_eq!(d.len(), 1);
+        assert_eq!(d[0]["kind"], "extra");
+        assert_eq!(d[0]["a"], Value::Null);
+        assert_eq!(d[0]["b"]["line"], 2);
+    }
+}
@@ -0,0 +1,168 @@
+//! Splitting the input into MI records.
+//!
+//! Records usually end at the next newline, however the input was read.
+//! gdb escapes newlines in C strings, but a console or target record that
+//! went through something that didn't can carry raw newlines inside its
+//! string. A line that looks like an MI record and ends inside a string is
+//! joined with the lines after it until the string ends, with the raw
+//! newlines escaped again, so the parser sees a single valid record. A
+//! string that runs into a line that is an MI record itself was never
+//! closed, and its record ends at the newline, so it can't swallow the
+//! records after it. Lines that aren't MI, like the program's output,
+//! always end at the newline.
+
+use crate::classify;
+
+/// How long a record can grow while waiting for its string to end. Past
+/// this the line that started it is taken as is.
+const MAX_RECORD: usize = 1 << 20;
+
+#[derive(Default)]
+pub struct Framer {
+    buf: Vec<u8>,
+    /// The record being joined, as far as `buf` has been scanned for it, so
+    /// a record that comes in many reads isn't scanned again for each.
+    record: Vec<u8>,
+    scanned: usize,
+    in_string: bool,
+    escaped: bool,
+}
+
+impl Framer {
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
+
+    /// Takes the next complete record, with its newline, if there is one.
+    pub fn next_record(&mut self) -> Option<Vec<u8>> {
+        if self.scanned == 0 {
+            let end = self.buf.iter().position(|&b| b == b'\n')?;
+            if !is_mi(&self.buf[..end]) {
+                return Some(self.buf.drain(..=end).collect());
+            }
+        }
+        while let Some(&b) = self.buf.get(self.scanned) {
+            match b {
+                b'\n' if self.in_string => match self.next_line_is_mi() {
+                    // the string was never closed, rather than holding the
+                    // records after it
+                    Some(true) => {
+                        self.record.push(b);
+                        return Some(self.take());
+                    }
+                    Some(false) => {
+                        self.record.extend_from_slice(b"\\n");
+                        self.scanned += 1;
+                        continue;
+                    }
+                    None => break,
+                },
+                b'\n' => {
+                    self.record.push(b);
+                    return Some(self.take());
+                }
+                _ if self.escaped => self.escaped = false,
+                b'\\' if self.in_string => self.escaped = true,
+                b'"' => self.in_string = !self.in_string,
+                _ => {}
+            }
+            self.record.push(b);
+            self.scanned += 1;
+        }
+        if self.buf.len() <= MAX_RECORD {
+            return None;
+        }
+        let end = self.buf.iter().position(|&b| b == b'\n')?;
+        self.record = self.buf[..=end].to_vec();
+        self.scanned = end;
+        Some(self.take())
+    }
+
+    /// Whether the line after the newline at `scanned` is an MI record, if
+    /// it's all there.
+    fn next_line_is_mi(&self) -> Option<bool> {
+        let line = &self.buf[self.scanned + 1..];
+        let end = line.iter().position(|&b| b == b'\n')?;
+        Some(is_mi(&line[..end]))
+    }
+
+    /// Takes the record, ending at `scanned`, out of the buffer.
+    fn take(&mut self) -> Vec<u8> {
+        self.buf.drain(..=self.scanned);
+        self.scanned = 0;
+        self.in_string = false;
+        self.escaped = false;
+        std::mem::take(&mut self.record)
+    }
+
+    /// Takes what's left at the end of the input, a line at a time.
+    pub fn finish(self) -> Vec<Vec<u8>> {
+        self.buf
+            .split_inclusive(|&b| b == b'\n')
+            .map(<[u8]>::to_vec)
+            .collect()
+    }
+}
+
+fn is_mi(line: &[u8]) -> bool {
+    classify::is_mi(&String::from_utf8_lossy(line))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(chunks: &[&str]) -> Vec<String> {
+        let mut framer = Framer::default();
+        let mut records = Vec::new();
+        for chunk in chunks {
+            framer.push(chunk.as_bytes());
+            while let Some(record) = framer.next_record() {
+                records.push(String::from_utf8(record).unwrap());
+            }
+        }
+        for record in framer.finish() {
+            records.push(String::from_utf8(record).unwrap());
+        }
+        records
+    }
+
+    #[test]
+    fn lines() {
+        assert_eq!(
+            frame(&["~\"a\\n\"\nhello\n^done\n", "(gdb)\n"]),
+            ["~\"a\\n\"\n", "hello\n", "^done\n", "(gdb)\n"]
+        );
+        assert_eq!(frame(&["^do", "ne\n^ex", "it"]), ["^done\n", "^exit"]);
+    }
+
+    #[test]
+    fn raw_newlines_in_strings() {
+        assert_eq!(
+            frame(&["~\"a\nb \\\"\nc\"\n=x\n"]),
+            ["~\"a\\nb \\\"\\nc\"\n", "=x\n"]
+        );
+        // a newline in a string of output that isn't MI isn't joined
+        assert_eq!(frame(&["say \"a\n", "b\"\n"]), ["say \"a\n", "b\"\n"]);
+    }
+
+    #[test]
+    fn joined_across_reads() {
+        assert_eq!(
+            frame(&["~\"a", "\n", "b", "\"\n", "^done\n"]),
+            ["~\"a\\nb\"\n", "^done\n"]
+        );
+    }
+
+    #[test]
+    fn unterminated_strings() {
+        assert_eq!(
+            frame(&["~\"abc\n", "^done\n", "12^done\n"]),
+            ["~\"abc\n", "^done\n", "12^done\n"]
+        );
+        assert_eq!(
+            frame(&["*stopped,reason=\"x\nmore\n&\"warning\"\n"]),
+            ["*stopped,reason=\"x\\nmore\n", "&\"warning\"\n"]
+        );
+    }
+}
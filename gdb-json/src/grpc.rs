@@ -0,0 +1,168 @@
+//! `--grpc`: serving the session over gRPC, for services in other languages
+//! that drive gdb through gdb-json. The service is in
+//! `proto/gdb_json.proto`.
+//!
+//! `Events` streams the records from the time of the call on, as JSON like
+//! everywhere else. `Command` sends a command to gdb like the ones on stdin,
+//! and if it has a token, waits for the result with that token and returns
+//! it, even if `--only` or `--drop` leave results out. It gives up after
+//! [`RESULT_TIMEOUT`], or sooner with a deadline set by the client.
+//!
+//! Calls need the session token (see [`crate::auth`]) in an
+//! `authorization: Bearer ...` metadata entry.
+
+use std::{collections::HashSet, net::SocketAddr, pin::Pin, time::Duration};
+
+use anyhow::Context;
+use futures_util::{stream, Stream};
+use serde_json::json;
+use tokio::sync::{broadcast, mpsc};
+use tonic::{service::Interceptor, transport::Server, Request, Response, Status};
+
+use crate::{auth::Token, clients::Subscription, command};
+
+/// How long `Command` waits for a result.
+const RESULT_TIMEOUT: Duration = Duration::from_secs(60);
+
+mod proto {
+    tonic::include_proto!("gdb_json");
+}
+
+use proto::{
+    session_server::{Session, SessionServer},
+    CommandReply, CommandRequest, Event, EventsRequest,
+};
+
+/// Serves on `addr`, streaming the records from `records` and sending
+/// commands to `commands`.
+pub async fn listen(
+    addr: SocketAddr,
+    token: Token,
+    records: Subscription,
+    commands: mpsc::Sender<String>,
+) -> anyhow::Result<()> {
+    // bound here so a bad address is an error at startup
+    let listener = tokio::net::TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("listen on {addr}"))?;
+    let service = SessionService {
+        records: records.resubscribe(),
+        commands,
+    };
+    let mut records = records;
+    tokio::spawn(async move {
+        let incoming = stream::unfold(listener, |listener| async move {
+            let stream = listener.accept().await.map(|(stream, _)| stream);
+            Some((stream, listener))
+        });
+        let served = Server::builder()
+            .add_service(SessionServer::with_interceptor(service, Auth(token)))
+            .serve_with_incoming_shutdown(incoming, records.closed())
+            .await;
+        if let Err(e) = served {
+            eprintln!("gdb-json: gRPC server: {e}");
+        }
+    });
+    Ok(())
+}
+
+#[derive(Clone)]
+struct Auth(Token);
+
+impl Interceptor for Auth {
+    fn call(&mut self, request: Request<()>) -> Result<Request<()>, Status> {
+        let given = request
+            .metadata()
+            .get("authorization")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "));
+        match given {
+            Some(token) if self.0.matches(token) => Ok(request),
+            _ => Err(Status::unauthenticated("missing or wrong token")),
+        }
+    }
+}
+
+struct SessionService {
+    records: Subscription,
+    commands: mpsc::Sender<String>,
+}
+
+type Events = Pin<Box<dyn Stream<Item = Result<Event, Status>> + Send>>;
+
+#[tonic::async_trait]
+impl Session for SessionService {
+    type EventsStream = Events;
+
+    async fn events(&self, request: Request<EventsRequest>) -> Result<Response<Events>, Status> {
+        let types: HashSet<String> = request.into_inner().types.into_iter().collect();
+        let events = stream::unfold(self.records.resubscribe(), move |mut sub| {
+            let types = types.clone();
+            async move {
+                loop {
+                    match sub.records.recv().await {
+                        Ok(record) => {
+                            let ty = record["type"].as_str().unwrap_or_default();
+                            if types.is_empty() || types.contains(ty) {
+                                let event = Event {
+                                    r#type: ty.to_owned(),
+                                    json: record.to_string(),
+                                };
+                                return Some((Ok(event), sub));
+                            }
+                        }
+                        Err(broadcast::error::RecvError::Lagged(n)) => {
+                            eprintln!("gdb-json: client fell behind, skipped {n} records");
+                        }
+                        Err(broadcast::error::RecvError::Closed) => return None,
+                    }
+                }
+            }
+        });
+        Ok(Response::new(Box::pin(events)))
+    }
+
+    async fn command(
+        &self,
+        request: Request<CommandRequest>,
+    ) -> Result<Response<CommandReply>, Status> {
+        let request = request.into_inner();
+        let cmd = json!({
+            "cmd": request.cmd,
+            "args": request.args,
+            "token": request.token,
+        });
+        command::to_mi(&cmd).map_err(|e| Status::invalid_argument(format!("{e:#}")))?;
+        // subscribed before sending, so the result can't be missed
+        let mut sub = self.records.resubscribe();
+        if self.commands.send(cmd.to_string()).await.is_err() {
+            return Err(Status::unavailable("gdb has exited"));
+        }
+        let Some(token) = request.token else {
+            return Ok(Response::new(CommandReply { result: None }));
+        };
+        let result = tokio::time::timeout(RESULT_TIMEOUT, async {
+            loop {
+                match sub.results.recv().await {
+                    Ok(record) if record["token"].as_u64() == Some(token) => {
+                        return Ok(record.to_string());
+                    }
+                    Ok(_) => {}
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        return Err(Status::data_loss(format!(
+                            "fell behind and missed {n} results, maybe this one"
+                        )));
+                    }
+                    Err(broadcast::error::RecvError::Closed) => {
+                        return Err(Status::unavailable("gdb exited before the result"));
+                    }
+                }
+            }
+        })
+        .await
+        .map_err(|_| Status::deadline_exceeded("no result in time"))??;
+        Ok(Response::new(CommandReply {
+            result: Some(result),
+        }))
+    }
+}
@@ -0,0 +1,92 @@
+//! Writing records to the systemd journal using its native protocol, see
+//! <https://systemd.io/JOURNAL_NATIVE_PROTOCOL/>.
+
+use std::os::unix::net::UnixDatagram;
+
+use anyhow::Context;
+use serde_json::Value;
+
+const SOCKET: &str = "/run/systemd/journal/socket";
+
+pub struct Journal {
+    socket: UnixDatagram,
+}
+
+fn push_field(buf: &mut Vec<u8>, key: &str, value: &str) {
+    buf.extend_from_slice(key.as_bytes());
+    if value.contains('\n') {
+        buf.push(b'\n');
+        buf.extend_from_slice(&(value.len() as u64).to_le_bytes());
+    } else {
+        buf.push(b'=');
+    }
+    buf.extend_from_slice(value.as_bytes());
+    buf.push(b'\n');
+}
+
+/// A string or, from `--typed`, a number as text.
+fn text(v: &Value) -> Option<String> {
+    match v {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+fn priority(ty: &str, msg: &Value) -> &'static str {
+    match ty {
+        "stderr" | "log" => "4",
+        "result" if msg["message"] == "error" => "3",
+        _ => "6",
+    }
+}
+
+impl Journal {
+    pub fn connect() -> anyhow::Result<Self> {
+        let socket = UnixDatagram::unbound().context("create journal socket")?;
+        socket
+            .connect(SOCKET)
+            .with_context(|| format!("connect to {SOCKET}"))?;
+        Ok(Self { socket })
+    }
+
+    /// Sends `msg` as one journal entry. Stream records use their text as
+    /// MESSAGE, MI records their serialized JSON; TYPE, THREAD, FILE and LINE
+    /// are set when the record has them.
+    pub fn send(&self, msg: &Value) -> anyhow::Result<()> {
+        let ty = msg["type"].as_str().unwrap_or_default();
+        let payload = &msg["payload"];
+        let frame = &payload["frame"];
+
+        let mut buf = Vec::new();
+        push_field(&mut buf, "SYSLOG_IDENTIFIER", "gdb-json");
+        push_field(&mut buf, "PRIORITY", priority(ty, msg));
+        push_field(&mut buf, "TYPE", ty);
+        match (ty, msg["message"].as_str()) {
+            ("notify" | "result", Some(message)) => {
+                push_field(&mut buf, "MI_MESSAGE", message);
+                push_field(&mut buf, "MESSAGE", &msg.to_string());
+            }
+            (_, Some(message)) => push_field(&mut buf, "MESSAGE", message),
+            (_, None) => push_field(&mut buf, "MESSAGE", &msg.to_string()),
+        }
+        if let Some(thread) = text(&payload["thread-id"]) {
+            push_field(&mut buf, "THREAD", &thread);
+        }
+        if let Some(file) = frame["fullname"]
+            .as_str()
+            .or_else(|| frame["file"].as_str())
+        {
+            push_field(&mut buf, "FILE", file);
+        }
+        if let Some(line) = text(&frame["line"]) {
+            push_field(&mut buf, "LINE", &line);
+        }
+        if let Some(encoding) = msg["encoding"].as_str() {
+            push_field(&mut buf, "ENCODING", encoding);
+        }
+
+        self.socket.send(&buf).context("send journal entry")?;
+        Ok(())
+    }
+}
@@ -0,0 +1,229 @@
+//! `--listen`: serving records over TCP or Unix-domain sockets, so several
+//! local tools can attach to one session.
+//!
+//! An address is `tcp:PORT` (on localhost), `tcp:HOST:PORT` or `unix:PATH`.
+//! Both ways are line-delimited JSON: each client gets the records from the
+//! time it connects on, one per line, and can send lines of its own. A line
+//! like `{"subscribe": ["notify:stopped", "console"]}` limits the records it
+//! gets to those of a `type`, or of a `type:message` (`null` or `[]` for all
+//! of them again); any other line is a JSON command (see `to-mi`) and goes
+//! to gdb like the ones on stdin.
+//!
+//! A client's first line has to be `{"token": "..."}` with the session token
+//! (see [`crate::auth`]), or it's sent an error and disconnected.
+
+use std::{
+    collections::HashSet, net::SocketAddr, os::unix::fs::FileTypeExt, path::PathBuf, time::Duration,
+};
+
+use anyhow::Context;
+use serde_json::Value;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader},
+    net::{TcpListener, UnixListener},
+    sync::{broadcast, mpsc},
+};
+
+use crate::{auth::Token, clients::Subscription};
+
+/// How long a client has to send the token.
+const AUTH_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Clone, Debug)]
+pub enum Addr {
+    Tcp(SocketAddr),
+    Unix(PathBuf),
+}
+
+pub fn parse_addr(s: &str) -> Result<Addr, String> {
+    if let Some(path) = s.strip_prefix("unix:") {
+        return Ok(Addr::Unix(path.into()));
+    }
+    let Some(addr) = s.strip_prefix("tcp:") else {
+        return Err(format!("expected tcp:PORT or unix:PATH, got {s:?}"));
+    };
+    let addr = match addr.parse::<u16>() {
+        Ok(port) => SocketAddr::from(([127, 0, 0, 1], port)),
+        Err(_) => addr
+            .parse()
+            .map_err(|_| format!("invalid address {addr:?}"))?,
+    };
+    Ok(Addr::Tcp(addr))
+}
+
+/// Listens on `addr`, sending each client the records from `records` and
+/// sending the commands clients send to `commands`.
+pub async fn listen(
+    addr: &Addr,
+    token: Token,
+    records: Subscription,
+    commands: mpsc::Sender<String>,
+) -> anyhow::Result<()> {
+    match addr {
+        Addr::Tcp(addr) => {
+            let listener = TcpListener::bind(addr)
+                .await
+                .with_context(|| format!("listen on {addr}"))?;
+            tokio::spawn(async move {
+                let mut records = records;
+                loop {
+                    tokio::select! {
+                        client = listener.accept() => match client {
+                            Ok((stream, _)) => attach(stream, &token, &records, &commands),
+                            Err(e) => eprintln!("gdb-json: accepting client: {e}"),
+                        },
+                        () = records.closed() => return,
+                    }
+                }
+            });
+        }
+        Addr::Unix(path) => {
+            // a socket left behind by an earlier session
+            if std::fs::metadata(path).is_ok_and(|m| m.file_type().is_socket()) {
+                std::fs::remove_file(path)
+                    .with_context(|| format!("remove stale socket {}", path.display()))?;
+            }
+            let listener = UnixListener::bind(path)
+                .with_context(|| format!("listen on {}", path.display()))?;
+            tokio::spawn(async move {
+                let mut records = records;
+                loop {
+                    tokio::select! {
+                        client = listener.accept() => match client {
+                            Ok((stream, _)) => attach(stream, &token, &records, &commands),
+                            Err(e) => eprintln!("gdb-json: accepting client: {e}"),
+                        },
+                        () = records.closed() => return,
+                    }
+                }
+            });
+        }
+    }
+    Ok(())
+}
+
+fn attach(
+    stream: impl AsyncRead + AsyncWrite + Send + 'static,
+    token: &Token,
+    records: &Subscription,
+    commands: &mpsc::Sender<String>,
+) {
+    let token = token.clone();
+    let records = records.resubscribe();
+    let commands = commands.clone();
+    tokio::spawn(async move {
+        if let Err(e) = serve(stream, &token, records, commands).await {
+            eprintln!("gdb-json: client: {e:#}");
+        }
+    });
+}
+
+/// Whether `record` is one of `types`, by its `type` or `type:message`.
+fn subscribed(types: &Option<HashSet<String>>, record: &Value) -> bool {
+    let (Some(types), Some(ty)) = (types, record["type"].as_str()) else {
+        return types.is_none();
+    };
+    types.contains(ty)
+        || record["message"]
+            .as_str()
+            .is_some_and(|message| types.contains(&format!("{ty}:{message}")))
+}
+
+/// The record types a `{"subscribe": [...]}` line asks for, `Some(None)`
+/// for all of them, or `None` if the line is something else.
+fn subscription(line: &str) -> Option<Option<HashSet<String>>> {
+    let Ok(Value::Object(msg)) = serde_json::from_str(line) else {
+        return None;
+    };
+    let types = match msg.get("subscribe")? {
+        Value::Array(types) if !types.is_empty() => types,
+        _ => return Some(None),
+    };
+    Some(Some(
+        types
+            .iter()
+            .filter_map(|ty| ty.as_str().map(str::to_owned))
+            .collect(),
+    ))
+}
+
+/// Whether `line` is `{"token": "..."}` with the session token.
+fn authenticates(line: &str, token: &Token) -> bool {
+    let Ok(Value::Object(msg)) = serde_json::from_str(line) else {
+        return false;
+    };
+    msg.get("token")
+        .and_then(Value::as_str)
+        .is_some_and(|given| token.matches(given))
+}
+
+async fn serve(
+    stream: impl AsyncRead + AsyncWrite,
+    token: &Token,
+    mut sub: Subscription,
+    commands: mpsc::Sender<String>,
+) -> anyhow::Result<()> {
+    let (rx, mut tx) = tokio::io::split(stream);
+    let mut lines = BufReader::new(rx).lines();
+    let first = tokio::time::timeout(AUTH_TIMEOUT, lines.next_line()).await;
+    if !matches!(first, Ok(Ok(Some(line))) if authenticates(&line, token)) {
+        tx.write_all(b"{\"error\":\"missing or wrong token\"}\n")
+            .await?;
+        tx.shutdown().await?;
+        anyhow::bail!("missing or wrong token");
+    }
+    let mut types = None::<HashSet<String>>;
+    loop {
+        tokio::select! {
+            record = sub.records.recv() => match record {
+                Ok(record) => {
+                    if subscribed(&types, &record) {
+                        tx.write_all(format!("{record}\n").as_bytes()).await?;
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    eprintln!("gdb-json: client fell behind, skipped {n} records");
+                }
+                Err(broadcast::error::RecvError::Closed) => {
+                    tx.shutdown().await?;
+                    return Ok(());
+                }
+            },
+            line = lines.next_line() => match line? {
+                Some(line) => match subscription(&line) {
+                    Some(subscribed) => types = subscribed,
+                    None => {
+                        if commands.send(line).await.is_err() {
+                            return Ok(());
+                        }
+                    }
+                },
+                None => return Ok(()),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn subscriptions() {
+        let types = subscription(r#"{"subscribe": ["notify:stopped", "console"]}"#).unwrap();
+        let stopped = json!({"type": "notify", "message": "stopped"});
+        let running = json!({"type": "notify", "message": "running"});
+        let console = json!({"type": "console", "message": "hi"});
+        assert!(subscribed(&types, &stopped));
+        assert!(!subscribed(&types, &running));
+        assert!(subscribed(&types, &console));
+        assert!(!subscribed(&types, &json!({})));
+
+        let all = subscription(r#"{"subscribe": []}"#).unwrap();
+        assert!(subscribed(&all, &running));
+        assert!(subscribed(&all, &json!({})));
+        assert_eq!(subscription(r#"{"cmd": "-exec-run"}"#), None);
+    }
+}
@@ -1,12 +1,279 @@
-use std::io::{self, BufRead};
+use std::{io::Write, path::PathBuf};
 
 use anyhow::Context;
+use clap::{Parser, Subcommand};
 use gdbmi::{
     parser::{Message, Response},
-    raw::{Dict, GeneralMessage},
+    raw::GeneralMessage,
 };
+use regex::Regex;
 use serde_json::json;
-use std::io::Write;
+use tokio::{
+    io::{AsyncRead, AsyncReadExt},
+    sync::mpsc,
+};
+
+mod auth;
+mod backtrace;
+mod binary;
+mod classify;
+mod cli_log;
+mod clients;
+mod command;
+mod correlate;
+mod dap;
+#[cfg(feature = "diff")]
+mod diff;
+mod framing;
+#[cfg(feature = "grpc")]
+mod grpc;
+#[cfg(feature = "journald")]
+mod journald;
+mod listen;
+mod replay;
+mod reverse;
+mod rotate;
+mod route;
+mod sanitizer;
+mod schema;
+mod scrub;
+mod spawn;
+mod stamp;
+mod stats;
+mod threads;
+mod typed;
+mod values;
+#[cfg(feature = "ws")]
+mod ws;
+
+#[derive(Parser)]
+#[command(about = "Convert GDB/MI output on stdin to JSON lines on stdout")]
+struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Classify threads whose name matches REGEX as CLASS, can be repeated
+    #[arg(long, value_name = "CLASS=REGEX", value_parser = threads::parse_class)]
+    thread_class: Vec<(String, Regex)>,
+
+    /// Count breakpoint hits and print a report when the input ends, as a
+    /// hit-stats record on stdout and a table on stderr
+    #[arg(long)]
+    hit_stats: bool,
+
+    /// Emit records in the shape of an older schema version
+    #[arg(long, value_name = "VERSION", default_value_t = schema::SCHEMA_VERSION, value_parser = schema::parse_version)]
+    compat: u32,
+
+    /// Send records of the given types to DEST instead of stdout, e.g.
+    /// `stdout,stderr=raw:program.log` or `log=null`; can be repeated
+    #[arg(long, value_name = "TYPES=DEST", value_parser = route::parse_route)]
+    route: Vec<route::Route>,
+
+    /// Where records without a --route go: stdout, stderr, journald, fd:N or
+    /// a file path
+    #[arg(long, value_name = "DEST")]
+    sink: Option<String>,
+
+    /// Write records without a --route to PATH, rotating it by
+    /// --rotate-size or --rotate-every
+    #[arg(long, value_name = "PATH", conflicts_with = "sink")]
+    out: Option<String>,
+
+    /// Rotate the --out file once it's SIZE big, e.g. `100M`
+    #[arg(long, value_name = "SIZE", requires = "out", value_parser = rotate::parse_size)]
+    rotate_size: Option<u64>,
+
+    /// Rotate the --out file once it's been written to for DURATION, e.g.
+    /// `1h` or `1d`. Checked when a record is written, so a quiet session's
+    /// file rotates with its next record
+    #[arg(long, value_name = "DURATION", requires = "out", value_parser = rotate::parse_duration)]
+    rotate_every: Option<std::time::Duration>,
+
+    /// How many rotated --out files to keep, as PATH.1 (the newest) to PATH.N
+    #[arg(long, value_name = "N", default_value_t = 5, requires = "out")]
+    rotate_keep: u32,
+
+    /// Emit lines that aren't MI records as "cli" records instead of
+    /// treating them as inferior output
+    #[arg(long)]
+    classify_cli: bool,
+
+    /// Stop with an error at the first line that doesn't parse, instead of
+    /// emitting a "parse_error" record for it and going on
+    #[arg(long)]
+    strict: bool,
+
+    /// Also emit a sanitizer-report record for each ASan/UBSan/TSan report
+    /// in the program's output
+    #[arg(long)]
+    sanitizer_reports: bool,
+
+    /// Replace `value` strings in payloads, like `"{x = 1, y = 2}"`, with
+    /// their structure as JSON, like `{"x": 1, "y": 2}`
+    #[arg(long)]
+    parse_values: bool,
+
+    /// Make numbers in stop, breakpoint and thread notifications JSON
+    /// numbers and flags bools, instead of MI's strings
+    #[arg(long)]
+    typed: bool,
+
+    /// Run gdb with the MI interpreter and convert its output instead of
+    /// stdin's, sending it the JSON commands read from stdin (see `to-mi`).
+    /// Takes the rest of the command line, e.g. `--spawn gdb --args ./prog`
+    #[arg(long, value_name = "GDB", num_args = 1.., allow_hyphen_values = true)]
+    spawn: Vec<String>,
+
+    /// Also serve records over WebSocket on ADDR, e.g. `127.0.0.1:9229`,
+    /// taking JSON commands from clients too. Clients can run anything
+    /// through gdb's `shell`, so they need the session token, and browsers
+    /// only get in from a --ws-allow-origin
+    #[cfg(feature = "ws")]
+    #[arg(long, value_name = "ADDR", requires = "spawn")]
+    ws: Option<std::net::SocketAddr>,
+
+    /// Let browser pages from ORIGIN, like `http://localhost:3000`, connect
+    /// to --ws; can be repeated
+    #[cfg(feature = "ws")]
+    #[arg(long, value_name = "ORIGIN", requires = "ws")]
+    ws_allow_origin: Vec<String>,
+
+    /// Also serve the records and a command RPC over gRPC on ADDR, e.g.
+    /// `127.0.0.1:50051` (see `proto/gdb_json.proto`). Clients can run
+    /// anything through gdb's `shell`, so they need the session token
+    #[cfg(feature = "grpc")]
+    #[arg(long, value_name = "ADDR", requires = "spawn")]
+    grpc: Option<std::net::SocketAddr>,
+
+    /// Also serve records as JSON lines on ADDR, `tcp:PORT`, `tcp:HOST:PORT`
+    /// or `unix:PATH`, taking JSON commands from clients too; can be
+    /// repeated. Clients can run anything through gdb's `shell`, so they
+    /// need the session token
+    #[arg(long, value_name = "ADDR", requires = "spawn", value_parser = listen::parse_addr)]
+    listen: Vec<listen::Addr>,
+
+    /// Also write the session token servers take to PATH, readable only by
+    /// the user
+    #[arg(long, value_name = "PATH", requires = "spawn")]
+    token_file: Option<PathBuf>,
+
+    /// Let servers listen on addresses other than loopback ones. Anyone who
+    /// can see the traffic can see the token and run anything through gdb
+    #[arg(long, requires = "spawn")]
+    allow_remote: bool,
+
+    /// Emit each result with a token as a "correlated" record that also
+    /// holds the notifications and console output that came in with it
+    #[arg(long)]
+    correlate: bool,
+
+    /// Write JSON indented, one record per block
+    #[arg(long, overrides_with = "compact")]
+    pretty: bool,
+
+    /// Write JSON compactly, one record per line (the default)
+    #[arg(long, overrides_with = "pretty")]
+    compact: bool,
+
+    /// Escape everything outside ASCII in JSON, for terminals that can't
+    /// show UTF-8
+    #[arg(long)]
+    ascii: bool,
+
+    /// Write records as `json`, or as `msgpack` or `cbor`, each prefixed
+    /// with its length as a 4-byte big-endian number
+    #[arg(long, value_name = "ENCODING", default_value = "json", value_parser = route::parse_encoding)]
+    encoding: route::Encoding,
+
+    /// Stamp every record with its wall-clock `time`, `elapsed_ns` since the
+    /// start and an incrementing `seq`
+    #[arg(long)]
+    timestamps: bool,
+
+    /// Turn records on stdin back into the MI lines they were made from,
+    /// to replay a recorded session
+    #[arg(long)]
+    reverse: bool,
+
+    /// Print a JSON Schema of the records gdb-json emits and exit
+    #[arg(long)]
+    print_schema: bool,
+
+    /// Only emit records of the given types, e.g. `notify,result`
+    #[arg(long, value_name = "TYPES", value_delimiter = ',')]
+    only: Vec<String>,
+
+    /// Don't emit records of the given types, e.g. `console,log`
+    #[arg(long, value_name = "TYPES", value_delimiter = ',')]
+    drop: Vec<String>,
+}
+
+impl Args {
+    /// Whether a server that takes the session token was asked for.
+    fn serves(&self) -> bool {
+        #[cfg(feature = "ws")]
+        if self.ws.is_some() {
+            return true;
+        }
+        #[cfg(feature = "grpc")]
+        if self.grpc.is_some() {
+            return true;
+        }
+        !self.listen.is_empty()
+    }
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Compare two recorded sessions and print a diff record for each
+    /// divergence, exiting with 1 if there are any
+    #[cfg(feature = "diff")]
+    Diff { a: PathBuf, b: PathBuf },
+    /// Anonymize a recorded session (paths, user names, addresses) and
+    /// optionally minimize it
+    Scrub {
+        input: PathBuf,
+        /// Keep only the records needed for CMD (run with `sh -c`, records
+        /// on stdin) to keep exiting successfully
+        #[arg(long, value_name = "CMD")]
+        minimize: Option<String>,
+    },
+    /// Convert a classic CLI transcript to records, as if it had been
+    /// recorded over MI
+    ImportLog { input: PathBuf },
+    /// Parse a `thread apply all bt` dump (`-` for stdin) into a record
+    /// with each thread's frames
+    ParseBt { input: PathBuf },
+    /// Turn JSON commands like `{"cmd": "-break-insert", "args": ["main"],
+    /// "token": 7}`, one per line on stdin, into MI command lines on stdout
+    ToMi,
+    /// Play a recorded session back on stdout, as fast as possible or at
+    /// the pace it was recorded with --timestamps
+    Replay {
+        input: PathBuf,
+        /// Keep the gaps between records, from their `elapsed_ns`
+        #[arg(long)]
+        realtime: bool,
+        /// Play N times as fast as recorded, e.g. `0.5` or `10`; implies
+        /// --realtime
+        #[arg(long, value_name = "N", value_parser = replay::parse_speed)]
+        speed: Option<f64>,
+        /// Wait for Enter on stdin before the record with `seq` N, the
+        /// records of TYPE or of TYPE:MESSAGE, like `notify:stopped`
+        #[arg(long, value_name = "N|TYPE[:MESSAGE]", value_parser = replay::parse_pause_at)]
+        pause_at: Vec<replay::PauseAt>,
+        /// Write the MI lines the records were made from, like --reverse
+        #[arg(long)]
+        mi: bool,
+    },
+    /// Serve the Debug Adapter Protocol on stdio, running gdb underneath
+    Dap {
+        /// The gdb to run
+        #[arg(long, default_value = "gdb")]
+        gdb: String,
+    },
+}
 
 fn gdb_to_json(v: gdbmi::raw::Value) -> serde_json::Value {
     match v {
@@ -19,72 +286,374 @@ fn gdb_to_json(v: gdbmi::raw::Value) -> serde_json::Value {
 fn gdb_token_to_json(t: gdbmi::Token) -> serde_json::Value {
     t.0.into()
 }
-fn main() -> anyhow::Result<()> {
-    let stdin = std::io::stdin();
-    let stdout = std::io::stdout();
-    let mut stdin = stdin.lock();
-    let mut stdout = stdout.lock();
-
-    let mut buf = String::new();
-    while stdin.read_line(&mut buf).context("read input")? != 0 {
-        let msg = gdbmi::parser::parse_message(&buf)
-            .with_context(|| format!("parsing message {buf:?}"))?;
-        buf.clear();
-
-        let msg = match msg {
-            Message::Response(resp) => match resp {
-                Response::Notify {
-                    token,
-                    message,
-                    payload,
-                } => {
-                    json!({
-                        "type": "notify",
-                        "token": token.map(gdb_token_to_json),
-                        "message": message,
-                        "payload": gdb_to_json(gdbmi::raw::Value::Dict(payload)),
-                    })
-                }
-                Response::Result {
-                    token,
-                    message,
-                    payload,
-                } => {
-                    json!({
-                        "type": "result",
-                        "token": token.map(gdb_token_to_json),
-                        "message": message,
-                        "payload": payload.map(|x| gdb_to_json(gdbmi::raw::Value::Dict(x))).unwrap_or(serde_json::Value::Null),
-                    })
-                }
-            },
-            Message::General(g) => match g {
-                GeneralMessage::Console(message) => json!({
-                    "type": "console",
-                    "message": message,
-                }),
-                GeneralMessage::Log(message) => json!({
-                    "type": "log",
-                    "message": message,
-                }),
-                GeneralMessage::Target(message) => json!({
-                    "type": "target",
-                    "message": message,
-                }),
-                GeneralMessage::Done => json!({"type": "done"}),
-                GeneralMessage::InferiorStdout(message) => json!({
-                    "type": "stdout",
+
+fn message_to_json(msg: Message) -> serde_json::Value {
+    match msg {
+        Message::Response(resp) => match resp {
+            Response::Notify {
+                token,
+                message,
+                payload,
+            } => {
+                json!({
+                    "type": "notify",
+                    "token": token.map(gdb_token_to_json),
                     "message": message,
-                }),
-                GeneralMessage::InferiorStderr(message) => json!({
-                    "type": "stderr",
+                    "payload": gdb_to_json(gdbmi::raw::Value::Dict(payload)),
+                })
+            }
+            Response::Result {
+                token,
+                message,
+                payload,
+            } => {
+                json!({
+                    "type": "result",
+                    "token": token.map(gdb_token_to_json),
                     "message": message,
-                }),
+                    "payload": payload.map(|x| gdb_to_json(gdbmi::raw::Value::Dict(x))).unwrap_or(serde_json::Value::Null),
+                })
+            }
+        },
+        Message::General(g) => match g {
+            GeneralMessage::Console(message) => json!({
+                "type": "console",
+                "message": message,
+            }),
+            GeneralMessage::Log(message) => json!({
+                "type": "log",
+                "message": message,
+            }),
+            GeneralMessage::Target(message) => json!({
+                "type": "target",
+                "message": message,
+            }),
+            GeneralMessage::Done => json!({"type": "done"}),
+            GeneralMessage::InferiorStdout(message) => json!({
+                "type": "stdout",
+                "message": message,
+            }),
+            GeneralMessage::InferiorStderr(message) => json!({
+                "type": "stderr",
+                "message": message,
+            }),
+        },
+    }
+}
+
+fn cli_record(line: &str) -> serde_json::Value {
+    json!({
+        "type": "cli",
+        "message": line.trim_end_matches(['\r', '\n']),
+    })
+}
+
+/// Reads records from `input` on a task of their own, so reading can go on
+/// while records are processed and written.
+fn read_records(
+    mut input: impl AsyncRead + Unpin + Send + 'static,
+) -> mpsc::Receiver<anyhow::Result<Vec<u8>>> {
+    let (tx, rx) = mpsc::channel(64);
+    tokio::spawn(async move {
+        let mut framer = framing::Framer::default();
+        let mut chunk = vec![0; 64 * 1024];
+        loop {
+            match input.read(&mut chunk).await {
+                Ok(0) => break,
+                Ok(n) => framer.push(&chunk[..n]),
+                Err(e) => {
+                    let _ = tx
+                        .send(Err(anyhow::Error::new(e).context("read input")))
+                        .await;
+                    return;
+                }
+            }
+            while let Some(record) = framer.next_record() {
+                if tx.send(Ok(record)).await.is_err() {
+                    return;
+                }
+            }
+        }
+        for record in framer.finish() {
+            if tx.send(Ok(record)).await.is_err() {
+                return;
+            }
+        }
+    });
+    rx
+}
+
+/// Which record types `--only` and `--drop` let through.
+#[derive(Default)]
+struct TypeFilter {
+    only: Vec<String>,
+    drop: Vec<String>,
+}
+
+impl TypeFilter {
+    fn emits(&self, msg: &serde_json::Value) -> bool {
+        let ty = msg["type"].as_str().unwrap_or("");
+        (self.only.is_empty() || self.only.iter().any(|t| t == ty))
+            && !self.drop.iter().any(|t| t == ty)
+    }
+}
+
+/// Writes records on a blocking thread of their own, since sinks are plain
+/// [`std::io::Write`]rs that may block on pipes and files.
+struct Output {
+    records: Option<mpsc::Sender<serde_json::Value>>,
+    writer: tokio::task::JoinHandle<anyhow::Result<()>>,
+    clients: clients::Clients,
+    filter: TypeFilter,
+    stamper: Option<stamp::Stamper>,
+}
+
+impl Output {
+    fn new(mut router: route::Router) -> Self {
+        let (tx, mut rx) = mpsc::channel::<serde_json::Value>(64);
+        let writer = tokio::task::spawn_blocking(move || {
+            while let Some(msg) = rx.blocking_recv() {
+                router.write(&msg)?;
+            }
+            Ok(())
+        });
+        Self {
+            records: Some(tx),
+            writer,
+            clients: clients::Clients::new(),
+            filter: TypeFilter::default(),
+            stamper: None,
+        }
+    }
+
+    async fn write(&mut self, mut msg: serde_json::Value) -> anyhow::Result<()> {
+        if !self.filter.emits(&msg) {
+            self.clients.send_unemitted(&msg);
+            return Ok(());
+        }
+        if let Some(stamper) = &mut self.stamper {
+            stamper.stamp(&mut msg);
+        }
+        self.clients.send(&msg);
+        let records = self.records.as_ref().expect("written after finish");
+        if records.send(msg).await.is_err() {
+            // the writer stopped, with the error that stopped it
+            self.records = None;
+            return (&mut self.writer).await?;
+        }
+        Ok(())
+    }
+
+    /// Waits for everything written to be written out.
+    async fn finish(mut self) -> anyhow::Result<()> {
+        self.records = None;
+        self.clients.finish().await;
+        self.writer.await?
+    }
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+    if args.print_schema {
+        let mut out = std::io::stdout().lock();
+        serde_json::to_writer_pretty(&mut out, &schema::json_schema())?;
+        return writeln!(out).context("write schema");
+    }
+    if args.reverse {
+        return reverse::reverse(std::io::stdin().lock(), std::io::stdout().lock());
+    }
+    match &args.command {
+        #[cfg(feature = "diff")]
+        Some(Command::Diff { a, b }) => {
+            let divergences = diff::diff(a, b, std::io::stdout().lock())?;
+            std::process::exit(if divergences == 0 { 0 } else { 1 });
+        }
+        Some(Command::Scrub { input, minimize }) => {
+            return scrub::scrub(input, minimize.as_deref(), std::io::stdout().lock());
+        }
+        Some(Command::ImportLog { input }) => {
+            return cli_log::import(input, args.compat, std::io::stdout().lock());
+        }
+        Some(Command::ParseBt { input }) => {
+            return backtrace::parse_file(input, args.compat, std::io::stdout().lock());
+        }
+        Some(Command::Replay {
+            input,
+            realtime,
+            speed,
+            pause_at,
+            mi,
+        }) => {
+            let options = replay::Options {
+                realtime: *realtime,
+                speed: *speed,
+                pause_at: pause_at.clone(),
+                mi: *mi,
+            };
+            return replay::play(input, &options, std::io::stdout().lock());
+        }
+        Some(Command::ToMi) => {
+            return command::translate(std::io::stdin().lock(), std::io::stdout().lock());
+        }
+        Some(Command::Dap { gdb }) => return dap::serve(gdb).await,
+        None => {}
+    }
+
+    let serves = args.serves();
+    let mut threads = threads::Threads::new(args.thread_class);
+    let mut hit_stats = args.hit_stats.then(stats::HitStats::default);
+    let mut sanitizers = args.sanitizer_reports.then(sanitizer::Sanitizers::default);
+    let mut correlator = args.correlate.then(correlate::Correlator::default);
+
+    let mut routes = args.route;
+    if let Some(sink) = args.sink {
+        routes.insert(
+            0,
+            route::parse_route(&format!("*={sink}")).map_err(anyhow::Error::msg)?,
+        );
+    }
+    if let Some(out) = args.out {
+        let policy = rotate::Policy {
+            size: args.rotate_size,
+            every: args.rotate_every,
+            keep: args.rotate_keep,
+            compat: args.compat,
+        };
+        routes.insert(0, route::Route::rotating(&out, policy));
+    }
+    let style = route::Style {
+        pretty: args.pretty,
+        ascii: args.ascii,
+        encoding: args.encoding,
+    };
+    let mut output = Output::new(route::Router::new(&routes, style)?);
+    output.filter = TypeFilter {
+        only: args.only,
+        drop: args.drop,
+    };
+    output.stamper = args.timestamps.then(stamp::Stamper::new);
+
+    let (gdb, mut records) = if args.spawn.is_empty() {
+        (None, read_records(tokio::io::stdin()))
+    } else {
+        let (commands, rx) = mpsc::channel(64);
+        let token = auth::Token::generate()?;
+        #[cfg(feature = "ws")]
+        if let Some(addr) = args.ws {
+            auth::check_bind(&addr, args.allow_remote)?;
+            let access = ws::Access {
+                token: token.clone(),
+                origins: args.ws_allow_origin,
+            };
+            ws::listen(addr, access, output.clients.subscribe(), commands.clone()).await?;
+        }
+        #[cfg(feature = "grpc")]
+        if let Some(addr) = args.grpc {
+            auth::check_bind(&addr, args.allow_remote)?;
+            let records = output.clients.subscribe();
+            grpc::listen(addr, token.clone(), records, commands.clone()).await?;
+        }
+        for addr in &args.listen {
+            if let listen::Addr::Tcp(addr) = addr {
+                auth::check_bind(addr, args.allow_remote)?;
+            }
+            listen::listen(
+                addr,
+                token.clone(),
+                output.clients.subscribe(),
+                commands.clone(),
+            )
+            .await?;
+        }
+        if serves {
+            eprintln!("gdb-json: session token {}", token.as_str());
+        }
+        if let Some(path) = &args.token_file {
+            token.write_to(path)?;
+        }
+        tokio::spawn(spawn::read_commands(commands));
+        let (gdb, stdout) = spawn::Gdb::spawn(&args.spawn, rx)?;
+        (Some(gdb), read_records(stdout))
+    };
+
+    while let Some(buf) = records.recv().await {
+        let buf = buf?;
+        let mut msg = match binary::binary_record(&buf) {
+            Some(msg) => msg,
+            None => match std::str::from_utf8(&buf) {
+                // binary_record takes these, this keeps the bytes if it ever doesn't
+                Err(_) => binary::raw_record(&buf),
+                Ok(line) if args.classify_cli && !classify::is_mi(line) => cli_record(line),
+                Ok(line) => match gdbmi::parser::parse_message(line) {
+                    Ok(msg) => message_to_json(msg),
+                    Err(e) if args.strict => {
+                        return Err(e).with_context(|| format!("parsing message {line:?}"))
+                    }
+                    Err(e) => json!({
+                        "type": "parse_error",
+                        "raw": line.trim_end_matches(['\r', '\n']),
+                        "error": e.to_string(),
+                    }),
+                },
             },
         };
 
-        serde_json::to_writer(&mut stdout, &msg).context("write message")?;
-        writeln!(stdout)?;
+        if args.parse_values {
+            values::parse_values(&mut msg);
+        }
+        threads.process(&mut msg);
+        if let Some(hit_stats) = &mut hit_stats {
+            hit_stats.process(&msg);
+        }
+        let reports = match &mut sanitizers {
+            Some(sanitizers) => sanitizers.process(&msg),
+            None => Vec::new(),
+        };
+        if args.typed {
+            typed::type_payload(&mut msg);
+        }
+        schema::downgrade(&mut msg, args.compat);
+        match &mut correlator {
+            Some(correlator) => {
+                for mut msg in correlator.process(msg) {
+                    if msg["type"] == "correlated" {
+                        schema::downgrade(&mut msg, args.compat);
+                    }
+                    output.write(msg).await?;
+                }
+            }
+            None => output.write(msg).await?,
+        }
+        for mut report in reports {
+            schema::downgrade(&mut report, args.compat);
+            output.write(report).await?;
+        }
+    }
+
+    for msg in correlator.map(|c| c.finish()).unwrap_or_default() {
+        output.write(msg).await?;
+    }
+    for mut report in sanitizers.map(|s| s.finish()).unwrap_or_default() {
+        schema::downgrade(&mut report, args.compat);
+        output.write(report).await?;
+    }
+
+    let hit_stats = hit_stats.map(|hit_stats| {
+        let mut report = hit_stats.report();
+        schema::downgrade(&mut report, args.compat);
+        report
+    });
+    if let Some(report) = &hit_stats {
+        output.write(report.clone()).await?;
+    }
+    output.finish().await?;
+    if let Some(report) = &hit_stats {
+        stats::HitStats::write_table(report, std::io::stderr()).context("write hit stats")?;
+    }
+
+    if let Some(gdb) = gdb {
+        gdb.wait().await?;
     }
     Ok(())
 }
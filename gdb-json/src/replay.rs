@@ -0,0 +1,246 @@
+//! `gdb-json replay`: playing a recorded session back, for UI demos and
+//! regression tests that need the records to come at the pace they did.
+//!
+//! Records are written as they were read, or with `--mi` as the MI lines
+//! they were made from, like `--reverse` writes them. By default they're
+//! written as fast as they can be. With `--realtime` they keep the gaps
+//! between the `elapsed_ns` that `--timestamps` recorded, and `--speed N`
+//! divides those gaps by N. Records without `elapsed_ns` are written right
+//! away.
+//!
+//! `--pause-at` waits for Enter on stdin before the records it matches: a
+//! number matches the record with that `seq`, anything else the records of
+//! that `type`, or `type:message` like `notify:stopped`. Time spent paused
+//! doesn't count, so the records after a pause keep their gaps.
+
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Write},
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+use serde_json::Value;
+
+use crate::reverse;
+
+/// Which records `--pause-at` waits before.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PauseAt {
+    Seq(u64),
+    Record { ty: String, message: Option<String> },
+}
+
+impl PauseAt {
+    fn matches(&self, msg: &Value) -> bool {
+        match self {
+            Self::Seq(seq) => msg["seq"].as_u64() == Some(*seq),
+            Self::Record { ty, message } => {
+                msg["type"] == **ty && message.as_ref().is_none_or(|m| msg["message"] == **m)
+            }
+        }
+    }
+}
+
+pub fn parse_pause_at(s: &str) -> Result<PauseAt, String> {
+    if let Ok(seq) = s.parse() {
+        return Ok(PauseAt::Seq(seq));
+    }
+    let (ty, message) = match s.split_once(':') {
+        Some((ty, message)) => (ty, Some(message.to_owned())),
+        None => (s, None),
+    };
+    if ty.is_empty() || message.as_deref() == Some("") {
+        return Err(format!("expected a seq, TYPE or TYPE:MESSAGE, got {s:?}"));
+    }
+    Ok(PauseAt::Record {
+        ty: ty.to_owned(),
+        message,
+    })
+}
+
+/// Parses a `--speed` like `0.5` or `10`.
+pub fn parse_speed(s: &str) -> Result<f64, String> {
+    s.parse::<f64>()
+        .ok()
+        .filter(|speed| speed.is_finite() && *speed > 0.0)
+        .ok_or_else(|| format!("expected a positive number, got {s:?}"))
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Options {
+    pub realtime: bool,
+    pub speed: Option<f64>,
+    pub pause_at: Vec<PauseAt>,
+    pub mi: bool,
+}
+
+impl Options {
+    /// How many times as fast as recorded to play, if the gaps are kept.
+    fn pace(&self) -> Option<f64> {
+        self.speed.or(self.realtime.then_some(1.0))
+    }
+}
+
+/// Plays the session recorded in `input` to `out`, reading Enter for
+/// `--pause-at` from stdin.
+pub fn play(input: &Path, options: &Options, out: impl Write) -> anyhow::Result<()> {
+    let file = File::open(input).with_context(|| format!("open {}", input.display()))?;
+    replay(BufReader::new(file), options, std::io::stdin().lock(), out)
+}
+
+fn replay(
+    input: impl BufRead,
+    options: &Options,
+    mut keys: impl BufRead,
+    mut out: impl Write,
+) -> anyhow::Result<()> {
+    // when a record was written, and its `elapsed_ns`
+    let mut clock: Option<(Instant, u64)> = None;
+    let mut mi = Vec::new();
+    for (i, line) in input.lines().enumerate() {
+        let line = line.context("read records")?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let msg: Value =
+            serde_json::from_str(&line).with_context(|| format!("line {}: invalid JSON", i + 1))?;
+        let elapsed = msg["elapsed_ns"].as_u64();
+        if let (Some(speed), Some(elapsed)) = (options.pace(), elapsed) {
+            let (then, then_elapsed) = *clock.get_or_insert((Instant::now(), elapsed));
+            let gap = elapsed.saturating_sub(then_elapsed) as f64 / 1e9 / speed;
+            // a tiny --speed can make the gap longer than anything can wait
+            let due = Duration::try_from_secs_f64(gap)
+                .ok()
+                .and_then(|gap| then.checked_add(gap))
+                .with_context(|| format!("line {}: can't wait {gap}s at --speed {speed}", i + 1))?;
+            if let Some(wait) = due.checked_duration_since(Instant::now()) {
+                std::thread::sleep(wait);
+            }
+        }
+        if options.pause_at.iter().any(|p| p.matches(&msg)) {
+            out.flush()?;
+            eprintln!(
+                "gdb-json: paused before line {}, press Enter to go on",
+                i + 1
+            );
+            keys.read_line(&mut String::new()).context("read stdin")?;
+            if let Some(elapsed) = elapsed {
+                clock = Some((Instant::now(), elapsed));
+            }
+        }
+        if options.mi {
+            mi.clear();
+            reverse::to_mi(&msg, &mut mi).with_context(|| format!("line {}", i + 1))?;
+            out.write_all(&mi)?;
+        } else {
+            writeln!(out, "{line}")?;
+        }
+        out.flush()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn session(records: &[Value]) -> String {
+        records.iter().map(|r| format!("{r}\n")).collect()
+    }
+
+    fn played(input: &str, options: &Options, keys: &str) -> String {
+        let mut out = Vec::new();
+        replay(input.as_bytes(), options, keys.as_bytes(), &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn pause_at() {
+        assert_eq!(parse_pause_at("12"), Ok(PauseAt::Seq(12)));
+        assert_eq!(
+            parse_pause_at("notify:stopped"),
+            Ok(PauseAt::Record {
+                ty: "notify".into(),
+                message: Some("stopped".into())
+            })
+        );
+        assert_eq!(
+            parse_pause_at("result"),
+            Ok(PauseAt::Record {
+                ty: "result".into(),
+                message: None
+            })
+        );
+        for bad in ["", ":x", "notify:"] {
+            assert!(parse_pause_at(bad).is_err(), "{bad:?}");
+        }
+        for bad in ["0", "-1", "inf", "x"] {
+            assert!(parse_speed(bad).is_err(), "{bad:?}");
+        }
+        assert_eq!(parse_speed("0.5"), Ok(0.5));
+    }
+
+    #[test]
+    fn as_recorded_and_as_mi() {
+        let input = session(&[
+            json!({"type": "notify", "message": "stopped", "payload": {"reason": "exited-normally"}, "seq": 0}),
+            json!({"type": "done", "seq": 1}),
+        ]);
+        let options = Options {
+            pause_at: vec![PauseAt::Seq(1)],
+            ..Options::default()
+        };
+        assert_eq!(played(&input, &options, "\n"), input);
+        let options = Options {
+            mi: true,
+            ..options
+        };
+        // stdin already closed doesn't stop the replay
+        assert_eq!(
+            played(&input, &options, ""),
+            "*stopped,reason=\"exited-normally\"\n(gdb)\n"
+        );
+    }
+
+    #[test]
+    fn paced() {
+        let input = session(&[
+            json!({"type": "done", "elapsed_ns": 5_000_000_000u64}),
+            json!({"type": "done", "elapsed_ns": 5_200_000_000u64}),
+            json!({"type": "done"}),
+        ]);
+        let start = Instant::now();
+        played(&input, &Options::default(), "");
+        assert!(start.elapsed() < Duration::from_millis(100));
+
+        let start = Instant::now();
+        let options = Options {
+            speed: Some(4.0),
+            ..Options::default()
+        };
+        assert_eq!(played(&input, &options, ""), input);
+        let took = start.elapsed();
+        assert!(took >= Duration::from_millis(50), "{took:?}");
+        assert!(took < Duration::from_secs(1), "{took:?}");
+    }
+
+    #[test]
+    fn gaps_too_long_to_wait() {
+        let input = session(&[
+            json!({"type": "done", "elapsed_ns": 0}),
+            json!({"type": "done", "elapsed_ns": 1}),
+        ]);
+        let options = Options {
+            speed: Some(parse_speed("1e-300").unwrap()),
+            ..Options::default()
+        };
+        let mut out = Vec::new();
+        let err = replay(input.as_bytes(), &options, &b""[..], &mut out).unwrap_err();
+        assert!(err.to_string().starts_with("line 2: can't wait"), "{err}");
+        assert_eq!(out, b"{\"elapsed_ns\":0,\"type\":\"done\"}\n");
+    }
+}
@@ -0,0 +1,266 @@
+//! `--reverse`: turning records back into the MI lines they were made from,
+//! so a recorded session can be replayed into other MI consumers.
+//!
+//! What the records don't keep can't come back: payload fields come out in
+//! JSON order rather than gdb's, list items that were `name=value` results
+//! come out as bare values, and `notify` records get the `*` prefix for
+//! `running` and `stopped`, `+` for `download` and `=` for everything else.
+//! `correlated` records are taken apart again and records gdb-json adds on
+//! its own, like `hit-stats`, are skipped, and so are the fields it adds to
+//! gdb's, like the `class` of each of the `threads` with `--thread-class`.
+//! Numbers and bools from `--typed` go back to strings and `y`/`n`.
+//! Values turned into JSON by `--parse-values` have no MI text to go back to,
+//! so a `value` field that isn't a string is an error, as is a `null`.
+
+use std::io::{BufRead, Write};
+
+use anyhow::{bail, Context};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde_json::Value;
+
+/// Writes `bytes` as an MI C string. Bytes outside ASCII are kept as they
+/// are unless `escape_high` is set.
+fn c_string(bytes: &[u8], escape_high: bool, out: &mut Vec<u8>) {
+    out.push(b'"');
+    for &b in bytes {
+        match b {
+            b'"' => out.extend_from_slice(b"\\\""),
+            b'\\' => out.extend_from_slice(b"\\\\"),
+            b'\n' => out.extend_from_slice(b"\\n"),
+            b'\r' => out.extend_from_slice(b"\\r"),
+            b'\t' => out.extend_from_slice(b"\\t"),
+            b if b < 0x20 || b == 0x7f || (b >= 0x80 && escape_high) => {
+                out.extend_from_slice(format!("\\{b:03o}").as_bytes())
+            }
+            b => out.push(b),
+        }
+    }
+    out.push(b'"');
+}
+
+/// The text of a stream record, decoded if it's base64.
+fn message(msg: &Value) -> anyhow::Result<(Vec<u8>, bool)> {
+    let text = msg["message"]
+        .as_str()
+        .context("expected a \"message\" string")?;
+    Ok(if msg["encoding"] == "base64" {
+        (STANDARD.decode(text).context("invalid base64")?, true)
+    } else {
+        (text.as_bytes().to_owned(), false)
+    })
+}
+
+fn mi_value(v: &Value, out: &mut Vec<u8>) -> anyhow::Result<()> {
+    match v {
+        Value::String(s) => c_string(s.as_bytes(), false, out),
+        // from --typed
+        Value::Number(n) => c_string(n.to_string().as_bytes(), false, out),
+        Value::Bool(b) => c_string(if *b { b"y" } else { b"n" }, false, out),
+        Value::Array(items) => {
+            out.push(b'[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(b',');
+                }
+                mi_value(item, out)?;
+            }
+            out.push(b']');
+        }
+        Value::Object(fields) => {
+            out.push(b'{');
+            results(fields, out)?;
+            out.push(b'}');
+        }
+        Value::Null => bail!("null isn't an MI value, was it made by --parse-values?"),
+    }
+    Ok(())
+}
+
+fn results(fields: &serde_json::Map<String, Value>, out: &mut Vec<u8>) -> anyhow::Result<()> {
+    for (i, (k, v)) in fields.iter().enumerate() {
+        if i > 0 {
+            out.push(b',');
+        }
+        out.extend_from_slice(k.as_bytes());
+        out.push(b'=');
+        match (k.as_str(), v.as_u64()) {
+            // octal in MI, see --typed
+            ("exit-code", Some(code)) => c_string(format!("0{code:o}").as_bytes(), false, out),
+            ("value", _) if !v.is_string() => {
+                bail!("value {v} isn't MI text, was it made by --parse-values?")
+            }
+            _ => mi_value(v, out)?,
+        }
+    }
+    Ok(())
+}
+
+/// Appends the MI lines `msg` was made from to `out`.
+pub fn to_mi(msg: &Value, out: &mut Vec<u8>) -> anyhow::Result<()> {
+    let ty = msg["type"].as_str().context("expected a \"type\" string")?;
+    let record = |prefix: u8, out: &mut Vec<u8>| -> anyhow::Result<()> {
+        if let Some(token) = msg["token"].as_u64() {
+            out.extend_from_slice(token.to_string().as_bytes());
+        }
+        out.push(prefix);
+        out.extend_from_slice(
+            msg["message"]
+                .as_str()
+                .context("expected a \"message\" string")?
+                .as_bytes(),
+        );
+        let mut payload = msg["payload"].clone();
+        // added by --thread-class
+        if let Some(Value::Array(threads)) = payload.get_mut("threads") {
+            for thread in threads.iter_mut().filter_map(Value::as_object_mut) {
+                thread.remove("class");
+            }
+        }
+        match &payload {
+            Value::Null => {}
+            Value::Object(fields) if fields.is_empty() => {}
+            Value::Object(fields) => {
+                out.push(b',');
+                results(fields, out)?;
+            }
+            payload => bail!("expected \"payload\" to be an object, got {payload}"),
+        }
+        out.push(b'\n');
+        Ok(())
+    };
+    match ty {
+        "result" => record(b'^', out)?,
+        "notify" => {
+            let prefix = match msg["message"].as_str() {
+                Some("running" | "stopped") => b'*',
+                Some("download") => b'+',
+                _ => b'=',
+            };
+            record(prefix, out)?;
+        }
+        "console" | "target" | "log" => {
+            out.push(match ty {
+                "console" => b'~',
+                "target" => b'@',
+                _ => b'&',
+            });
+            let (bytes, binary) = message(msg)?;
+            if binary {
+                c_string(&bytes, true, out);
+            } else {
+                // the text is kept as gdb escaped it
+                out.push(b'"');
+                out.extend_from_slice(&bytes);
+                out.push(b'"');
+            }
+            out.push(b'\n');
+        }
+        "stdout" | "stderr" | "cli" => {
+            let (bytes, _) = message(msg)?;
+            out.extend_from_slice(&bytes);
+            if !bytes.ends_with(b"\n") {
+                out.push(b'\n');
+            }
+        }
+        "done" => out.extend_from_slice(b"(gdb)\n"),
+        "parse_error" => {
+            let raw = msg["raw"].as_str().context("expected a \"raw\" string")?;
+            out.extend_from_slice(raw.as_bytes());
+            out.push(b'\n');
+        }
+        "correlated" => {
+            for record in msg["records"].as_array().into_iter().flatten() {
+                to_mi(record, out)?;
+            }
+            to_mi(&msg["result"], out)?;
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Reads records, one per line, and writes the MI lines they were made
+/// from.
+pub fn reverse(input: impl BufRead, mut out: impl Write) -> anyhow::Result<()> {
+    let mut mi = Vec::new();
+    for (i, line) in input.lines().enumerate() {
+        let line = line.context("read records")?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let msg: Value =
+            serde_json::from_str(&line).with_context(|| format!("line {}: invalid JSON", i + 1))?;
+        mi.clear();
+        to_mi(&msg, &mut mi).with_context(|| format!("line {}", i + 1))?;
+        out.write_all(&mi)?;
+        out.flush()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn mi(msg: Value) -> anyhow::Result<String> {
+        let mut out = Vec::new();
+        to_mi(&msg, &mut out)?;
+        Ok(String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn typed() {
+        let msg = json!({
+            "type": "notify",
+            "message": "stopped",
+            "payload": {
+                "reason": "exited",
+                "exit-code": 8,
+                "thread-id": 2,
+                "frame": {"level": 0, "func": "main"},
+            },
+        });
+        assert_eq!(
+            mi(msg).unwrap(),
+            "*stopped,exit-code=\"010\",frame={func=\"main\",level=\"0\"},reason=\"exited\",thread-id=\"2\"\n"
+        );
+    }
+
+    #[test]
+    fn thread_classes_dropped() {
+        let msg = json!({
+            "type": "result",
+            "message": "done",
+            "token": 3,
+            "payload": {"threads": [{"id": "1", "name": "worker-1", "class": "worker"}]},
+            "thread": {"id": 1, "name": "worker-1", "class": "worker"},
+        });
+        assert_eq!(
+            mi(msg).unwrap(),
+            "3^done,threads=[{id=\"1\",name=\"worker-1\"}]\n"
+        );
+    }
+
+    #[test]
+    fn parsed_values() {
+        for value in [json!({"x": "1"}), json!(1), json!(true), json!(null)] {
+            let msg = json!({
+                "type": "result",
+                "message": "done",
+                "payload": {"value": value},
+            });
+            assert!(mi(msg).is_err(), "{value}");
+        }
+        let msg = json!({
+            "type": "result",
+            "message": "done",
+            "payload": {"variables": [{"name": "x", "value": "{a = 1}"}]},
+        });
+        assert_eq!(
+            mi(msg).unwrap(),
+            "^done,variables=[{name=\"x\",value=\"{a = 1}\"}]\n"
+        );
+    }
+}
@@ -0,0 +1,224 @@
+//! `--out`: writing records to a file that's rotated when it gets too big
+//! or too old, for long sessions that would otherwise fill the disk.
+//!
+//! Rotating renames `PATH` to `PATH.1`, `PATH.1` to `PATH.2` and so on,
+//! dropping what would go past `--rotate-keep`, and starts a new `PATH`
+//! with a "rotation" record that names the file before it. Rotation only
+//! happens between records, so a file can go past `--rotate-size` by one
+//! record.
+//!
+//! Whether a file is due is only checked when a record is written, so with
+//! `--rotate-every` a file isn't rotated while the session is quiet, and is
+//! rotated before the first record after DURATION instead.
+//!
+//! Rotation records are only written to the `--out` file, and aren't
+//! stamped by `--timestamps`, see [`crate::stamp`].
+
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+use serde_json::{json, Value};
+
+use crate::{route::Style, schema};
+
+/// When to rotate and how many old files to keep.
+#[derive(Clone, Debug)]
+pub struct Policy {
+    pub size: Option<u64>,
+    pub every: Option<Duration>,
+    pub keep: u32,
+    /// The schema version rotation records are written in.
+    pub compat: u32,
+}
+
+/// Parses sizes like `100M`, with an optional `K`, `M` or `G` suffix (powers
+/// of 1024).
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let (n, unit) = match s.find(|c: char| !c.is_ascii_digit()) {
+        Some(i) => s.split_at(i),
+        None => (s, ""),
+    };
+    let shift = match unit {
+        "" => 0,
+        "K" | "k" => 10,
+        "M" | "m" => 20,
+        "G" | "g" => 30,
+        _ => return Err(format!("expected a size like 100M, got {s:?}")),
+    };
+    let n: u64 = n.parse().map_err(|e| format!("{e}"))?;
+    n.checked_mul(1 << shift)
+        .filter(|&size| size > 0)
+        .ok_or_else(|| format!("invalid size {s:?}"))
+}
+
+/// Parses durations like `30m`, with an `s`, `m`, `h` or `d` suffix.
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let Some(i) = s.find(|c: char| !c.is_ascii_digit()) else {
+        return Err(format!("expected a duration like 1h, got {s:?}"));
+    };
+    let (n, unit) = s.split_at(i);
+    let secs = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return Err(format!("expected a duration like 1h, got {s:?}")),
+    };
+    let n: u64 = n.parse().map_err(|e| format!("{e}"))?;
+    n.checked_mul(secs)
+        .filter(|&secs| secs > 0)
+        .map(Duration::from_secs)
+        .ok_or_else(|| format!("invalid duration {s:?}"))
+}
+
+pub struct Rotating {
+    path: PathBuf,
+    policy: Policy,
+    style: Style,
+    out: BufWriter<File>,
+    written: u64,
+    opened: Instant,
+    rotations: u64,
+}
+
+impl Rotating {
+    pub fn create(path: PathBuf, policy: Policy, style: Style) -> anyhow::Result<Self> {
+        let file = File::create(&path).with_context(|| format!("create {}", path.display()))?;
+        Ok(Self {
+            path,
+            policy,
+            style,
+            out: BufWriter::new(file),
+            written: 0,
+            opened: Instant::now(),
+            rotations: 0,
+        })
+    }
+
+    fn numbered(&self, n: u32) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{n}"));
+        path.into()
+    }
+
+    fn due(&self) -> bool {
+        self.policy.size.is_some_and(|size| self.written >= size)
+            || (self.policy.every).is_some_and(|every| self.opened.elapsed() >= every)
+    }
+
+    fn rotate(&mut self) -> anyhow::Result<()> {
+        self.out.flush()?;
+        let rename = |from: &PathBuf, to: &PathBuf| match fs::rename(from, to) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                Err(e).with_context(|| format!("rename {} to {}", from.display(), to.display()))
+            }
+            _ => Ok(()),
+        };
+        for n in (1..self.policy.keep).rev() {
+            rename(&self.numbered(n), &self.numbered(n + 1))?;
+        }
+        let previous = if self.policy.keep > 0 {
+            let previous = self.numbered(1);
+            rename(&self.path, &previous)?;
+            Some(previous)
+        } else {
+            None
+        };
+        let file =
+            File::create(&self.path).with_context(|| format!("create {}", self.path.display()))?;
+        self.out = BufWriter::new(file);
+        self.written = 0;
+        self.opened = Instant::now();
+        self.rotations += 1;
+
+        let mut record = json!({
+            "type": "rotation",
+            "rotation": self.rotations,
+            "previous": previous.map(|p| p.display().to_string()),
+        });
+        schema::downgrade(&mut record, self.policy.compat);
+        self.write_record(&record)
+    }
+
+    fn write_record(&mut self, msg: &Value) -> anyhow::Result<()> {
+        let mut buf = Vec::new();
+        self.style.write(&mut buf, msg)?;
+        self.out.write_all(&buf)?;
+        self.out.flush()?;
+        self.written += buf.len() as u64;
+        Ok(())
+    }
+
+    pub fn write(&mut self, msg: &Value) -> anyhow::Result<()> {
+        if self.due() {
+            self.rotate()?;
+        }
+        self.write_record(msg)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sizes_and_durations() {
+        assert_eq!(parse_size("100"), Ok(100));
+        assert_eq!(parse_size("4k"), Ok(4 << 10));
+        assert_eq!(parse_size("100M"), Ok(100 << 20));
+        assert_eq!(parse_size("1G"), Ok(1 << 30));
+        for bad in ["", "0", "1T", "M", "99999999999G"] {
+            assert!(parse_size(bad).is_err(), "{bad:?}");
+        }
+        assert_eq!(parse_duration("30m"), Ok(Duration::from_secs(30 * 60)));
+        assert_eq!(
+            parse_duration("2d"),
+            Ok(Duration::from_secs(2 * 24 * 60 * 60))
+        );
+        for bad in ["", "30", "0s", "1w", "h"] {
+            assert!(parse_duration(bad).is_err(), "{bad:?}");
+        }
+    }
+
+    fn lines(path: &PathBuf) -> Vec<Value> {
+        fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn rotates_by_size() {
+        let dir = std::env::temp_dir().join(format!("gdb-json-rotate-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("out.json");
+        let policy = Policy {
+            size: Some(20),
+            every: None,
+            keep: 2,
+            compat: schema::SCHEMA_VERSION,
+        };
+        let mut out = Rotating::create(path.clone(), policy, Style::default()).unwrap();
+        for i in 0..4 {
+            // each record is past --rotate-size on its own
+            out.write(&json!({"type": "done", "n": i})).unwrap();
+        }
+
+        let current = lines(&path);
+        assert_eq!(current[0]["type"], "rotation");
+        assert_eq!(current[0]["rotation"], 3);
+        assert_eq!(current[0]["previous"], format!("{}.1", path.display()));
+        assert_eq!(current[1]["n"], 3);
+        assert_eq!(lines(&out.numbered(1))[1]["n"], 2);
+        assert_eq!(lines(&out.numbered(2))[1]["n"], 1);
+        // past --rotate-keep
+        assert!(!out.numbered(3).exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
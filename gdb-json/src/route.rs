@@ -0,0 +1,334 @@
+//! Sending records of different types to different outputs.
+//!
+//! A route is `TYPES=DEST` where TYPES is a comma separated list of record
+//! types (`*` for everything not otherwise routed) and DEST is one of
+//! `stdout` (or `-`), `stderr`, `null`, `journald` (with the `journald`
+//! feature), `fd:N` or a file path.
+//! Prefixing DEST with `raw:` writes just the message text instead of JSON,
+//! which is what you want for inferior output. Routes to the same DEST share
+//! one writer, raw or not, so a file is only created once.
+//! `--out` routes everything not otherwise routed to a file that's rotated,
+//! see [`crate::rotate`].
+//!
+//! JSON is written one record per line unless [`Style::pretty`] is set, and
+//! with non-ASCII characters escaped if [`Style::ascii`] is set. With
+//! [`Style::encoding`] set to MessagePack or CBOR, records are written in
+//! that instead, each prefixed with its length as a 4-byte big-endian
+//! number.
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufWriter, Write},
+    sync::{Arc, Mutex},
+};
+
+use anyhow::Context;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde_json::Value;
+
+#[cfg(feature = "journald")]
+use crate::journald::Journal;
+use crate::rotate::{self, Rotating};
+
+#[derive(Clone, Debug)]
+pub struct Route {
+    types: Vec<String>,
+    dest: String,
+    raw: bool,
+    rotate: Option<rotate::Policy>,
+}
+
+impl Route {
+    /// Routes everything not otherwise routed to a rotated file at `path`.
+    pub fn rotating(path: &str, policy: rotate::Policy) -> Self {
+        Self {
+            types: vec!["*".to_owned()],
+            dest: path.to_owned(),
+            raw: false,
+            rotate: Some(policy),
+        }
+    }
+}
+
+pub fn parse_route(s: &str) -> Result<Route, String> {
+    let (types, dest) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected TYPES=DEST, got {s:?}"))?;
+    let (dest, raw) = match dest.strip_prefix("raw:") {
+        Some(dest) => (dest, true),
+        None => (dest, false),
+    };
+    Ok(Route {
+        types: types.split(',').map(str::to_owned).collect(),
+        dest: dest.to_owned(),
+        raw,
+        rotate: None,
+    })
+}
+
+/// A writer shared by the sinks for the same destination.
+type Shared = Arc<Mutex<Box<dyn Write + Send>>>;
+
+fn open(dest: &str) -> anyhow::Result<Box<dyn Write + Send>> {
+    Ok(match dest {
+        "-" | "stdout" => Box::new(io::stdout()),
+        "stderr" => Box::new(io::stderr()),
+        "null" => Box::new(io::sink()),
+        _ => {
+            if let Some(fd) = dest.strip_prefix("fd:") {
+                use std::os::fd::FromRawFd;
+                let fd = fd.parse().with_context(|| format!("invalid fd {fd:?}"))?;
+                // SAFETY: the user asked for this fd to be written to, it's
+                // owned by this process from here on
+                Box::new(BufWriter::new(unsafe { File::from_raw_fd(fd) }))
+            } else {
+                let file = File::create(dest).with_context(|| format!("create {dest}"))?;
+                Box::new(BufWriter::new(file))
+            }
+        }
+    })
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub enum Encoding {
+    #[default]
+    Json,
+    #[cfg(feature = "msgpack")]
+    Msgpack,
+    #[cfg(feature = "cbor")]
+    Cbor,
+}
+
+pub fn parse_encoding(s: &str) -> Result<Encoding, String> {
+    match s {
+        "json" => Ok(Encoding::Json),
+        #[cfg(feature = "msgpack")]
+        "msgpack" => Ok(Encoding::Msgpack),
+        #[cfg(feature = "cbor")]
+        "cbor" => Ok(Encoding::Cbor),
+        _ if ["msgpack", "cbor"].contains(&s) => {
+            Err(format!("gdb-json was built without the {s} feature"))
+        }
+        _ => Err(format!("expected json, msgpack or cbor, got {s:?}")),
+    }
+}
+
+/// How records are written.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Style {
+    /// Indented, instead of one record per line.
+    pub pretty: bool,
+    /// With everything outside ASCII written as `\uXXXX` escapes.
+    pub ascii: bool,
+    /// JSON, or a binary encoding, for which the above don't apply.
+    pub encoding: Encoding,
+}
+
+impl Style {
+    pub fn write(self, out: &mut dyn Write, msg: &Value) -> anyhow::Result<()> {
+        match self.encoding {
+            Encoding::Json => self.write_json(out, msg),
+            #[cfg(feature = "msgpack")]
+            Encoding::Msgpack => framed(out, &rmp_serde::to_vec(msg)?),
+            #[cfg(feature = "cbor")]
+            Encoding::Cbor => {
+                let mut bytes = Vec::new();
+                ciborium::ser::into_writer(msg, &mut bytes)?;
+                framed(out, &bytes)
+            }
+        }
+    }
+
+    fn write_json(self, out: &mut dyn Write, msg: &Value) -> anyhow::Result<()> {
+        let json = if self.pretty {
+            serde_json::to_string_pretty(msg)?
+        } else {
+            serde_json::to_string(msg)?
+        };
+        if self.ascii && !json.is_ascii() {
+            // non-ASCII characters can only be in strings, where escapes
+            // mean the same thing
+            let mut escaped = String::with_capacity(json.len());
+            for c in json.chars() {
+                if c.is_ascii() {
+                    escaped.push(c);
+                } else {
+                    for unit in c.encode_utf16(&mut [0; 2]) {
+                        escaped.push_str(&format!("\\u{unit:04x}"));
+                    }
+                }
+            }
+            writeln!(out, "{escaped}")?;
+        } else {
+            writeln!(out, "{json}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes `bytes` prefixed with their length.
+#[cfg(any(feature = "msgpack", feature = "cbor"))]
+fn framed(out: &mut dyn Write, bytes: &[u8]) -> anyhow::Result<()> {
+    let len = u32::try_from(bytes.len()).context("record too big to frame")?;
+    out.write_all(&len.to_be_bytes())?;
+    out.write_all(bytes)?;
+    Ok(())
+}
+
+enum Sink {
+    Stream {
+        out: Shared,
+        raw: bool,
+        style: Style,
+    },
+    Rotating(Rotating),
+    #[cfg(feature = "journald")]
+    Journal(Journal),
+}
+
+impl Sink {
+    fn open(
+        route: &Route,
+        dest: &str,
+        style: Style,
+        writers: &mut HashMap<String, Shared>,
+    ) -> anyhow::Result<Self> {
+        if let Some(policy) = &route.rotate {
+            Ok(Self::Rotating(Rotating::create(
+                dest.into(),
+                policy.clone(),
+                style,
+            )?))
+        } else if dest == "journald" {
+            #[cfg(feature = "journald")]
+            return Ok(Self::Journal(Journal::connect()?));
+            #[cfg(not(feature = "journald"))]
+            anyhow::bail!("gdb-json was built without the journald feature");
+        } else {
+            let out = match writers.get(dest) {
+                Some(out) => out.clone(),
+                None => {
+                    let out = Arc::new(Mutex::new(open(dest)?));
+                    writers.insert(dest.to_owned(), out.clone());
+                    out
+                }
+            };
+            Ok(Self::Stream {
+                out,
+                raw: route.raw,
+                style,
+            })
+        }
+    }
+
+    fn write(&mut self, msg: &Value) -> anyhow::Result<()> {
+        let (out, raw, style) = match self {
+            Self::Stream { out, raw, style } => (out, *raw, *style),
+            Self::Rotating(file) => return file.write(msg),
+            #[cfg(feature = "journald")]
+            Self::Journal(journal) => return journal.send(msg),
+        };
+        let mut out = out.lock().unwrap();
+        if raw {
+            let text = msg["message"].as_str().unwrap_or_default();
+            let bytes = if msg["encoding"] == "base64" {
+                STANDARD.decode(text)?
+            } else {
+                text.as_bytes().to_owned()
+            };
+            out.write_all(&bytes)?;
+            if !bytes.ends_with(b"\n") {
+                out.write_all(b"\n")?;
+            }
+        } else {
+            style.write(&mut **out, msg)?;
+        }
+        out.flush()?;
+        Ok(())
+    }
+}
+
+pub struct Router {
+    sinks: Vec<Sink>,
+    by_type: HashMap<String, usize>,
+    default: usize,
+}
+
+impl Router {
+    pub fn new(routes: &[Route], style: Style) -> anyhow::Result<Self> {
+        let stdout: Shared = Arc::new(Mutex::new(open("stdout")?));
+        let mut writers = HashMap::from([("stdout".to_owned(), stdout.clone())]);
+        let mut sinks = vec![Sink::Stream {
+            out: stdout,
+            raw: false,
+            style,
+        }];
+        let mut opened = HashMap::from([(("stdout".to_owned(), false), 0)]);
+        let mut by_type = HashMap::new();
+        let mut default = 0;
+        for route in routes {
+            let dest = if route.dest == "-" {
+                "stdout"
+            } else {
+                &route.dest
+            };
+            let idx = *opened
+                .entry((dest.to_owned(), route.raw))
+                .or_insert_with(|| sinks.len());
+            if idx == sinks.len() {
+                sinks.push(Sink::open(route, dest, style, &mut writers)?);
+            }
+            for ty in &route.types {
+                if ty == "*" {
+                    default = idx;
+                } else {
+                    by_type.insert(ty.clone(), idx);
+                }
+            }
+        }
+        Ok(Self {
+            sinks,
+            by_type,
+            default,
+        })
+    }
+
+    pub fn write(&mut self, msg: &Value) -> anyhow::Result<()> {
+        let idx = msg["type"]
+            .as_str()
+            .and_then(|ty| self.by_type.get(ty))
+            .copied()
+            .unwrap_or(self.default);
+        self.sinks[idx].write(msg).context("write message")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn raw_and_json_to_one_file() {
+        let path = std::env::temp_dir().join(format!("gdb-json-route-{}", std::process::id()));
+        let path = path.to_str().unwrap();
+        let routes = [
+            parse_route(&format!("target=raw:{path}")).unwrap(),
+            parse_route(&format!("done={path}")).unwrap(),
+        ];
+        let mut router = Router::new(&routes, Style::default()).unwrap();
+        router
+            .write(&json!({"type": "target", "message": "hello"}))
+            .unwrap();
+        router.write(&json!({"type": "done"})).unwrap();
+        router
+            .write(&json!({"type": "target", "message": "bye\n"}))
+            .unwrap();
+        drop(router);
+        let written = std::fs::read_to_string(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(written, "hello\n{\"type\":\"done\"}\nbye\n");
+    }
+}
@@ -0,0 +1,289 @@
+//! Recognizing AddressSanitizer, UndefinedBehaviorSanitizer and
+//! ThreadSanitizer reports in the program's output.
+//!
+//! The lines of a report still go out as the usual stdout/stderr/target
+//! records. Once the report ends (at its SUMMARY line, or for UBSan reports
+//! without a stack trace at the next unrelated line) it is also emitted as a
+//! single record:
+//!
+//! ```json
+//! {"type": "sanitizer-report", "kind": "AddressSanitizer",
+//!  "error": "heap-use-after-free", "summary": "...", "pid": "1234",
+//!  "stacks": [{"title": "READ of size 4 at ...", "frames": [...]}],
+//!  "details": [...], "message": "<the whole report>"}
+//! ```
+
+use std::sync::OnceLock;
+
+use regex::Regex;
+use serde_json::{json, Map, Value};
+
+struct Res {
+    asan: Regex,
+    tsan: Regex,
+    ubsan: Regex,
+    frame: Regex,
+    summary: Regex,
+}
+
+fn res() -> &'static Res {
+    static RES: OnceLock<Res> = OnceLock::new();
+    RES.get_or_init(|| Res {
+        asan: Regex::new(r"^==(\d+)==ERROR: (\w+): (\S+)(.*)$").unwrap(),
+        tsan: Regex::new(r"^WARNING: (ThreadSanitizer): (.+?)(?: \(pid=(\d+)\))?$").unwrap(),
+        ubsan: Regex::new(r"^(\S+?):(\d+):(\d+): runtime error: (.*)$").unwrap(),
+        frame: Regex::new(
+            r"^\s*#(\d+)(?: (0x[0-9a-fA-F]+))?(?: in)? (.+?)(?: (\S+?):(\d+)(?::(\d+))?)?(?: \(([^)]+)\))?$",
+        )
+        .unwrap(),
+        summary: Regex::new(r"^SUMMARY: (\w+): (.*)$").unwrap(),
+    })
+}
+
+fn parse_frame(line: &str) -> Option<Value> {
+    let c = res().frame.captures(line)?;
+    let mut frame = Map::new();
+    frame.insert("level".into(), c[1].into());
+    if let Some(addr) = c.get(2) {
+        frame.insert("addr".into(), addr.as_str().into());
+    }
+    frame.insert("func".into(), c[3].into());
+    for (i, key) in [(4, "file"), (5, "line"), (6, "column"), (7, "module")] {
+        if let Some(m) = c.get(i) {
+            frame.insert(key.into(), m.as_str().into());
+        }
+    }
+    Some(frame.into())
+}
+
+struct Report {
+    record: Map<String, Value>,
+    stacks: Vec<Value>,
+    details: Vec<String>,
+    lines: Vec<String>,
+    /// UBSan reports may be a single line with no SUMMARY.
+    ubsan: bool,
+}
+
+impl Report {
+    fn finish(mut self) -> Value {
+        self.record.insert("type".into(), "sanitizer-report".into());
+        self.record.insert("stacks".into(), self.stacks.into());
+        self.record.insert("details".into(), self.details.into());
+        self.record
+            .insert("message".into(), self.lines.join("\n").into());
+        self.record.into()
+    }
+
+    fn push_line(&mut self, line: &str) {
+        self.lines.push(line.to_owned());
+        if let Some(frame) = parse_frame(line) {
+            if self.stacks.is_empty() {
+                self.stacks.push(json!({ "title": null, "frames": [] }));
+            }
+            let last = self.stacks.last_mut().unwrap();
+            last["frames"].as_array_mut().unwrap().push(frame);
+            return;
+        }
+        let line = line.trim();
+        if line.is_empty() {
+            return;
+        }
+        // a line starting a new stack stays a detail if no frames follow it
+        if let Some(empty) = self
+            .stacks
+            .last()
+            .filter(|s| s["frames"].as_array().is_some_and(|f| f.is_empty()))
+        {
+            if let Some(title) = empty["title"].as_str() {
+                self.details.push(title.to_owned());
+            }
+            self.stacks.pop();
+        }
+        self.stacks.push(json!({ "title": line, "frames": [] }));
+    }
+}
+
+#[derive(Default)]
+pub struct Sanitizers {
+    current: Option<Report>,
+    partial: String,
+}
+
+fn start(line: &str) -> Option<Report> {
+    let res = res();
+    let mut record = Map::new();
+    let ubsan = if let Some(c) = res.asan.captures(line) {
+        record.insert("pid".into(), c[1].into());
+        record.insert("kind".into(), c[2].into());
+        record.insert("error".into(), c[3].into());
+        record.insert("description".into(), c[4].trim().into());
+        false
+    } else if let Some(c) = res.tsan.captures(line) {
+        record.insert("kind".into(), c[1].into());
+        record.insert("error".into(), c[2].into());
+        if let Some(pid) = c.get(3) {
+            record.insert("pid".into(), pid.as_str().into());
+        }
+        false
+    } else if let Some(c) = res.ubsan.captures(line) {
+        record.insert("kind".into(), "UndefinedBehaviorSanitizer".into());
+        record.insert("error".into(), "runtime-error".into());
+        record.insert("description".into(), c[4].into());
+        record.insert(
+            "location".into(),
+            json!({"file": &c[1], "line": &c[2], "column": &c[3]}),
+        );
+        true
+    } else {
+        return None;
+    };
+    Some(Report {
+        record,
+        stacks: Vec::new(),
+        details: Vec::new(),
+        lines: vec![line.to_owned()],
+        ubsan,
+    })
+}
+
+impl Sanitizers {
+    fn line(&mut self, line: &str, out: &mut Vec<Value>) {
+        if let Some(report) = &mut self.current {
+            if let Some(c) = res().summary.captures(line) {
+                report.lines.push(line.to_owned());
+                report.record.insert("summary".into(), c[2].into());
+                out.push(self.current.take().unwrap().finish());
+                return;
+            }
+            if !report.ubsan || line.trim().is_empty() || parse_frame(line).is_some() {
+                report.push_line(line);
+                return;
+            }
+            out.push(self.current.take().unwrap().finish());
+        }
+        self.current = start(line);
+    }
+
+    /// Feeds a record through, returning the reports it completes.
+    pub fn process(&mut self, msg: &Value) -> Vec<Value> {
+        let mut out = Vec::new();
+        let ty = msg["type"].as_str().unwrap_or_default();
+        if !matches!(ty, "stdout" | "stderr" | "target") || msg["encoding"] == "base64" {
+            return out;
+        }
+        let Some(text) = msg["message"].as_str() else {
+            return out;
+        };
+        // stdout/stderr records are whole lines, target stream records can
+        // be split anywhere
+        self.partial.push_str(text);
+        if ty != "target" && !self.partial.ends_with('\n') {
+            self.partial.push('\n');
+        }
+        while let Some(end) = self.partial.find('\n') {
+            let line: String = self.partial.drain(..=end).collect();
+            self.line(line.trim_end_matches(['\r', '\n']), &mut out);
+        }
+        out
+    }
+
+    /// Emits a report cut off by the end of the input.
+    pub fn finish(mut self) -> Vec<Value> {
+        let mut out = Vec::new();
+        if !self.partial.is_empty() {
+            let line = std::mem::take(&mut self.partial);
+            self.line(&line, &mut out);
+        }
+        out.extend(self.current.map(Report::finish));
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed(s: &mut Sanitizers, ty: &str, text: &str) -> Vec<Value> {
+        text.split_inclusive('\n')
+            .flat_map(|line| s.process(&json!({"type": ty, "message": line})))
+            .collect()
+    }
+
+    #[test]
+    fn asan() {
+        let mut s = Sanitizers::default();
+        let reports = feed(
+            &mut s,
+            "stderr",
+            "start\n\
+             ==1234==ERROR: AddressSanitizer: heap-use-after-free on address 0x602000000010\n\
+             READ of size 4 at 0x602000000010 thread T0\n    \
+                 #0 0x401136 in main /src/t.c:9:5\n    \
+                 #1 0x7f0 in __libc_start_main (/lib/libc.so.6+0x2d)\n\
+             \n\
+             freed by thread T0 here:\n    \
+                 #0 0x7f1 in free\n\
+             SUMMARY: AddressSanitizer: heap-use-after-free /src/t.c:9:5 in main\n\
+             after\n",
+        );
+        assert_eq!(reports.len(), 1);
+        let r = &reports[0];
+        assert_eq!(r["kind"], "AddressSanitizer");
+        assert_eq!(r["error"], "heap-use-after-free");
+        assert_eq!(r["pid"], "1234");
+        assert_eq!(r["summary"], "heap-use-after-free /src/t.c:9:5 in main");
+        assert_eq!(
+            r["stacks"][0]["title"],
+            "READ of size 4 at 0x602000000010 thread T0"
+        );
+        assert_eq!(
+            r["stacks"][0]["frames"][0],
+            json!({"level": "0", "addr": "0x401136", "func": "main", "file": "/src/t.c", "line": "9", "column": "5"})
+        );
+        assert_eq!(r["stacks"][0]["frames"][1]["module"], "/lib/libc.so.6+0x2d");
+        assert_eq!(r["stacks"][1]["title"], "freed by thread T0 here:");
+        assert!(r["message"].as_str().unwrap().ends_with("in main"));
+        assert!(s.finish().is_empty());
+    }
+
+    #[test]
+    fn ubsan_without_stack() {
+        let mut s = Sanitizers::default();
+        let reports = feed(
+            &mut s,
+            "stdout",
+            "t.c:3:7: runtime error: signed integer overflow\nnext\n",
+        );
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0]["kind"], "UndefinedBehaviorSanitizer");
+        assert_eq!(reports[0]["description"], "signed integer overflow");
+        assert_eq!(
+            reports[0]["location"],
+            json!({"file": "t.c", "line": "3", "column": "7"})
+        );
+        assert_eq!(
+            reports[0]["message"],
+            "t.c:3:7: runtime error: signed integer overflow"
+        );
+    }
+
+    #[test]
+    fn split_target_records_and_cut_off() {
+        let mut s = Sanitizers::default();
+        for part in [
+            "WARNING: Thread",
+            "Sanitizer: data race (pid=7)\n  #0 f t.c:2",
+        ] {
+            assert!(s
+                .process(&json!({"type": "target", "message": part}))
+                .is_empty());
+        }
+        let reports = s.finish();
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0]["kind"], "ThreadSanitizer");
+        assert_eq!(reports[0]["error"], "data race");
+        assert_eq!(reports[0]["pid"], "7");
+        assert_eq!(reports[0]["stacks"][0]["frames"][0]["func"], "f");
+    }
+}
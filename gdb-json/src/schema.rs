@@ -0,0 +1,312 @@
+//! Record format versions.
+//!
+//! Records are always built in the latest shape and then downgraded to the
+//! version requested with `--compat`. Every change to an existing record
+//! shape bumps [`SCHEMA_VERSION`] and adds a step to [`downgrade`], and
+//! [`json_schema`] (`--print-schema`) describes the latest shape.
+//!
+//! - 1: the original format, without `schema_version`
+//! - 2: `schema_version` on every record, `thread` on thread related records
+//!   and `class` on `-thread-info` entries
+//! - 3: output that isn't valid UTF-8 is base64 encoded with
+//!   `"encoding": "base64"` instead of being converted lossily
+
+use serde_json::{json, Value};
+
+use crate::binary;
+
+pub const SCHEMA_VERSION: u32 = 3;
+
+pub fn parse_version(s: &str) -> Result<u32, String> {
+    let v: u32 = s.parse().map_err(|e| format!("{e}"))?;
+    if (1..=SCHEMA_VERSION).contains(&v) {
+        Ok(v)
+    } else {
+        Err(format!("supported versions are 1 to {SCHEMA_VERSION}"))
+    }
+}
+
+/// Stamps `msg` with its schema version and converts it to the shape of `version`.
+pub fn downgrade(msg: &mut Value, version: u32) {
+    if version < 3 {
+        binary::to_lossy_text(msg);
+    }
+    let Some(obj) = msg.as_object_mut() else {
+        return;
+    };
+    if version < 2 {
+        obj.remove("thread");
+        if let Some(threads) = obj
+            .get_mut("payload")
+            .and_then(|p| p.get_mut("threads"))
+            .and_then(Value::as_array_mut)
+        {
+            for thread in threads.iter_mut().filter_map(Value::as_object_mut) {
+                thread.remove("class");
+            }
+        }
+        return;
+    }
+    obj.insert("schema_version".to_owned(), version.into());
+}
+
+/// A record of `ty` with the given properties on top of `type`,
+/// `schema_version` and the `--timestamps` fields, all of them required
+/// except the timestamps and those in `optional`.
+fn record(ty: &str, description: &str, properties: Value, optional: &[&str]) -> Value {
+    let mut properties = properties.as_object().cloned().unwrap_or_default();
+    let mut required: Vec<_> = properties
+        .keys()
+        .filter(|k| !optional.contains(&k.as_str()))
+        .cloned()
+        .collect();
+    required.extend(["type".to_owned(), "schema_version".to_owned()]);
+    properties.insert("type".to_owned(), json!({ "const": ty }));
+    properties.insert(
+        "schema_version".to_owned(),
+        json!({ "const": SCHEMA_VERSION }),
+    );
+    // --timestamps
+    properties.insert("seq".to_owned(), json!({ "type": "integer" }));
+    properties.insert(
+        "time".to_owned(),
+        json!({ "type": "string", "format": "date-time" }),
+    );
+    properties.insert("elapsed_ns".to_owned(), json!({ "type": "integer" }));
+    json!({
+        "title": ty,
+        "description": description,
+        "type": "object",
+        "properties": properties,
+        "required": required,
+    })
+}
+
+/// A JSON Schema for every record gdb-json emits, in the latest version.
+pub fn json_schema() -> Value {
+    let text = |description: &str| {
+        json!({
+            "description": description,
+            "type": "string",
+        })
+    };
+    let stream = |ty: &str, description: &str| {
+        record(
+            ty,
+            description,
+            json!({
+                "message": text("the text, base64 encoded if it isn't valid UTF-8"),
+                "encoding": { "const": "base64" },
+            }),
+            &["encoding"],
+        )
+    };
+    let mi = |ty: &str, description: &str| {
+        record(
+            ty,
+            description,
+            json!({
+                "token": { "type": ["integer", "null"] },
+                "message": text("the class, like `done` or `stopped`"),
+                "payload": { "$ref": "#/$defs/payload" },
+                "thread": { "$ref": "#/$defs/thread" },
+            }),
+            &["thread"],
+        )
+    };
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": "gdb-json record",
+        "description": format!("A record emitted by gdb-json, schema version {SCHEMA_VERSION}"),
+        "oneOf": [
+            mi("notify", "an async record: `*` exec, `+` status and `=` notify records"),
+            mi("result", "a result record, `^done`, `^running`, `^error` and so on"),
+            stream("console", "CLI output from gdb, `~`"),
+            stream("target", "output from the target, `@`"),
+            stream("log", "gdb's own log and error messages, `&`"),
+            stream("stdout", "a line the program wrote to stdout"),
+            stream("stderr", "a line the program wrote to stderr"),
+            record("done", "the `(gdb)` prompt", json!({}), &[]),
+            record("cli", "a line that isn't MI, with --classify-cli", json!({ "message": text("the line") }), &[]),
+            record(
+                "rotation",
+                "the first record of a file --out rotated to, without --timestamps fields",
+                json!({
+                    "rotation": { "description": "how many times the file was rotated", "type": "integer" },
+                    "previous": { "description": "where the file before this one went", "type": ["string", "null"] },
+                }),
+                &[],
+            ),
+            record(
+                "parse_error",
+                "a line that didn't parse, unless --strict",
+                json!({ "raw": text("the line"), "error": text("why it didn't parse") }),
+                &[],
+            ),
+            record(
+                "correlated",
+                "a result and what came in with it, with --correlate",
+                json!({
+                    "token": { "type": "integer" },
+                    "result": { "$ref": "#" },
+                    "records": { "type": "array", "items": { "$ref": "#" } },
+                }),
+                &[],
+            ),
+            record(
+                "sanitizer-report",
+                "a sanitizer report in the program's output, with --sanitizer-reports",
+                json!({
+                    "kind": text("the sanitizer, like `AddressSanitizer`"),
+                    "error": text("like `heap-use-after-free`"),
+                    "description": text("the rest of the report's first line"),
+                    "summary": text("the SUMMARY line"),
+                    "pid": text("the process id"),
+                    "location": {
+                        "type": "object",
+                        "properties": {
+                            "file": text("the source file"),
+                            "line": text("the line number"),
+                            "column": text("the column number"),
+                        },
+                    },
+                    "stacks": {
+                        "type": "array",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "title": {
+                                    "description": "the line before the frames, if any",
+                                    "type": ["string", "null"],
+                                },
+                                "frames": { "type": "array", "items": { "type": "object" } },
+                            },
+                        },
+                    },
+                    "details": { "type": "array", "items": { "type": "string" } },
+                    "message": text("the whole report"),
+                }),
+                &["description", "summary", "pid", "location"],
+            ),
+            record(
+                "hit-stats",
+                "breakpoint hit counts at the end of the input, with --hit-stats",
+                json!({
+                    "breakpoints": {
+                        "type": "array",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "number": text("the breakpoint number"),
+                                "location": { "type": ["string", "null"] },
+                                "dprintf": { "type": "boolean" },
+                                "hits": { "type": "integer" },
+                                "interval": { "type": ["object", "null"] },
+                            },
+                        },
+                    },
+                }),
+                &[],
+            ),
+            record(
+                "command",
+                "a command typed at a prompt, from import-log",
+                json!({ "message": text("the command") }),
+                &[],
+            ),
+            record(
+                "backtrace",
+                "the threads of a `thread apply all bt` dump, from parse-bt",
+                json!({ "threads": { "type": "array", "items": { "type": "object" } } }),
+                &[],
+            ),
+            record(
+                "diff",
+                "a divergence between two sessions, from diff",
+                json!({
+                    "kind": { "enum": ["missing", "extra", "changed"] },
+                    "a": { "$ref": "#/$defs/diff_side" },
+                    "b": { "$ref": "#/$defs/diff_side" },
+                }),
+                &[],
+            ),
+        ],
+        "$defs": {
+            "payload": {
+                "description": "the MI results, with strings for values unless --parse-values or --typed is given",
+                "type": ["object", "null"],
+            },
+            "thread": {
+                "description": "the thread a record is about",
+                "type": "object",
+                "properties": {
+                    "id": { "type": "string" },
+                    "name": { "type": ["string", "null"] },
+                    "class": { "type": ["string", "null"] },
+                },
+                "required": ["id", "name", "class"],
+            },
+            "diff_side": {
+                "type": ["object", "null"],
+                "properties": {
+                    "line": { "type": "integer" },
+                    "event": { "type": "string" },
+                    "payload": {},
+                },
+            },
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn downgraded(mut msg: Value, version: u32) -> Value {
+        downgrade(&mut msg, version);
+        msg
+    }
+
+    #[test]
+    fn versions() {
+        assert_eq!(parse_version("1"), Ok(1));
+        assert_eq!(parse_version("3"), Ok(3));
+        assert!(parse_version("0").is_err());
+        assert!(parse_version("4").is_err());
+        assert!(parse_version("x").is_err());
+    }
+
+    #[test]
+    fn downgrades() {
+        // "\xffok" as base64
+        let binary = json!({"type": "target", "message": "/29r", "encoding": "base64"});
+        assert_eq!(
+            downgraded(binary.clone(), 3),
+            json!({"type": "target", "message": "/29r", "encoding": "base64", "schema_version": 3})
+        );
+        assert_eq!(
+            downgraded(binary.clone(), 2),
+            json!({"type": "target", "message": "\u{fffd}ok", "schema_version": 2})
+        );
+        assert_eq!(
+            downgraded(binary, 1),
+            json!({"type": "target", "message": "\u{fffd}ok"})
+        );
+
+        let threads = json!({
+            "type": "result",
+            "message": "done",
+            "payload": {"threads": [{"id": "1", "name": "worker-1", "class": "worker"}]},
+            "thread": {"id": "1", "name": "worker-1", "class": "worker"},
+        });
+        assert_eq!(downgraded(threads.clone(), 2)["thread"]["class"], "worker");
+        assert_eq!(
+            downgraded(threads, 1),
+            json!({
+                "type": "result",
+                "message": "done",
+                "payload": {"threads": [{"id": "1", "name": "worker-1"}]},
+            })
+        );
+    }
+}
@@ -0,0 +1,273 @@
+//! `gdb-json scrub`: making recorded sessions safe to attach to issues.
+//!
+//! Every string in every record is rewritten so that home directories and
+//! the current user name become `user`, directories become `/dirN`, and hex
+//! addresses are replaced by fake ones. The user name is only replaced as a
+//! whole word or path component, and the `type`, `token` and the `message`
+//! of result and notify records are left alone. Replacements are consistent
+//! across the session, so equal addresses and paths stay equal.
+//!
+//! With `--minimize CMD` the scrubbed records are then reduced with delta
+//! debugging: CMD is run through `sh -c` with candidate sessions on stdin,
+//! and a subset is kept whenever CMD still exits successfully.
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufRead, BufReader, Write},
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use anyhow::Context;
+use regex::{Captures, Regex};
+use serde_json::Value;
+
+struct Scrubber {
+    user_re: Option<Regex>,
+    home_re: Regex,
+    dir_re: Regex,
+    addr_re: Regex,
+    dirs: HashMap<String, String>,
+    addrs: HashMap<String, String>,
+}
+
+impl Scrubber {
+    fn new() -> Self {
+        Self {
+            user_re: std::env::var("USER")
+                .ok()
+                .filter(|u| u.len() > 1)
+                .map(|u| user_re(&u)),
+            home_re: Regex::new(r"/(home|Users)/[^/\s]+").unwrap(),
+            dir_re: Regex::new(r"(/[^/\s]+)+/").unwrap(),
+            addr_re: Regex::new(r"0x[0-9a-fA-F]{4,}").unwrap(),
+            dirs: HashMap::new(),
+            addrs: HashMap::new(),
+        }
+    }
+
+    fn scrub_str(&mut self, s: &str) -> String {
+        let mut s = self.home_re.replace_all(s, "/$1/user").into_owned();
+        if let Some(user_re) = &self.user_re {
+            s = user_re.replace_all(&s, "user").into_owned();
+        }
+        let dirs = &mut self.dirs;
+        let s = self.dir_re.replace_all(&s, |c: &Captures| {
+            let n = dirs.len();
+            dirs.entry(c[0].to_owned())
+                .or_insert_with(|| format!("/dir{n}/"))
+                .clone()
+        });
+        let addrs = &mut self.addrs;
+        self.addr_re
+            .replace_all(&s, |c: &Captures| {
+                let n = addrs.len() + 1;
+                addrs
+                    .entry(c[0].to_lowercase())
+                    .or_insert_with(|| format!("{:#x}", n * 0x1000))
+                    .clone()
+            })
+            .into_owned()
+    }
+
+    fn scrub(&mut self, v: &mut Value) {
+        match v {
+            Value::String(s) => *s = self.scrub_str(s),
+            Value::Array(a) => a.iter_mut().for_each(|v| self.scrub(v)),
+            Value::Object(o) => o.values_mut().for_each(|v| self.scrub(v)),
+            _ => {}
+        }
+    }
+
+    /// Scrubs a whole record, leaving alone the fields that say what it is.
+    fn record(&mut self, record: &mut Value) {
+        let Value::Object(fields) = record else {
+            return self.scrub(record);
+        };
+        let ty = fields.get("type").and_then(Value::as_str).unwrap_or("");
+        let keep_message = matches!(ty, "result" | "notify");
+        let correlated = ty == "correlated";
+        for (k, v) in fields.iter_mut() {
+            match k.as_str() {
+                "type" | "token" => {}
+                "message" if keep_message => {}
+                "result" if correlated => self.record(v),
+                "records" if correlated => v
+                    .as_array_mut()
+                    .into_iter()
+                    .flatten()
+                    .for_each(|r| self.record(r)),
+                _ => self.scrub(v),
+            }
+        }
+    }
+}
+
+/// Matches `user` as a whole word, so a short name like `on` doesn't turn
+/// `done` into `duser`.
+fn user_re(user: &str) -> Regex {
+    Regex::new(&format!(r"\b{}\b", regex::escape(user))).unwrap()
+}
+
+fn reproduces(cmd: &str, lines: &[String]) -> anyhow::Result<bool> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(cmd)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+        .with_context(|| format!("run {cmd:?}"))?;
+    let mut stdin = child.stdin.take().unwrap();
+    for line in lines {
+        // the command may exit without reading everything
+        if writeln!(stdin, "{line}").is_err() {
+            break;
+        }
+    }
+    drop(stdin);
+    Ok(child.wait()?.success())
+}
+
+/// Delta debugging: repeatedly tries dropping chunks of lines, halving the
+/// chunk size whenever no chunk can be dropped.
+fn minimize(cmd: &str, mut lines: Vec<String>) -> anyhow::Result<Vec<String>> {
+    anyhow::ensure!(
+        reproduces(cmd, &lines)?,
+        "{cmd:?} fails on the full session, nothing to minimize"
+    );
+    let mut chunk = lines.len().div_ceil(2);
+    while chunk >= 1 && lines.len() > 1 {
+        let mut start = 0;
+        let mut removed = false;
+        while start < lines.len() {
+            let end = (start + chunk).min(lines.len());
+            let candidate: Vec<_> = [&lines[..start], &lines[end..]].concat();
+            if !candidate.is_empty() && reproduces(cmd, &candidate)? {
+                lines = candidate;
+                removed = true;
+            } else {
+                start = end;
+            }
+        }
+        if !removed {
+            chunk /= 2;
+        }
+    }
+    Ok(lines)
+}
+
+pub fn scrub(input: &Path, minimize_with: Option<&str>, mut out: impl Write) -> anyhow::Result<()> {
+    let file = File::open(input).with_context(|| format!("open {}", input.display()))?;
+    let mut scrubber = Scrubber::new();
+    let mut lines = Vec::new();
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.with_context(|| format!("read {}", input.display()))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let mut record: Value = serde_json::from_str(&line)
+            .with_context(|| format!("{}:{}: invalid JSON", input.display(), i + 1))?;
+        scrubber.record(&mut record);
+        lines.push(record.to_string());
+    }
+    if let Some(cmd) = minimize_with {
+        lines = minimize(cmd, lines)?;
+    }
+    for line in lines {
+        writeln!(out, "{line}")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn scrubber() -> Scrubber {
+        Scrubber {
+            user_re: Some(user_re("alice")),
+            ..Scrubber::new()
+        }
+    }
+
+    #[test]
+    fn paths_and_addresses() {
+        let mut s = scrubber();
+        assert_eq!(
+            s.scrub_str("/home/alice/src/a.c at 0x401136"),
+            "/dir0/a.c at 0x1000"
+        );
+        assert_eq!(s.scrub_str("alice"), "user");
+        // consistent across the session
+        assert_eq!(s.scrub_str("/home/alice/src/b.c"), "/dir0/b.c");
+        assert_eq!(
+            s.scrub_str("0x4011AA 0x4011aa 0x401136"),
+            "0x2000 0x2000 0x1000"
+        );
+        // too short to be an address
+        assert_eq!(s.scrub_str("0x10"), "0x10");
+    }
+
+    #[test]
+    fn records() {
+        let mut s = scrubber();
+        let mut record = json!({
+            "type": "notify",
+            "token": 3,
+            "payload": {"frame": {"fullname": "/tmp/x/a.c", "args": [{"value": "0xdeadbeef"}]}},
+        });
+        s.record(&mut record);
+        assert_eq!(
+            record,
+            json!({
+                "type": "notify",
+                "token": 3,
+                "payload": {"frame": {"fullname": "/dir0/a.c", "args": [{"value": "0x1000"}]}},
+            })
+        );
+    }
+
+    #[test]
+    fn short_user_names() {
+        let mut s = Scrubber {
+            user_re: Some(user_re("on")),
+            ..Scrubber::new()
+        };
+        assert_eq!(
+            s.scrub_str("done, on /srv/on/a.c or on.c"),
+            "done, user /dir0/a.c or user.c"
+        );
+        let mut record = json!({
+            "type": "correlated",
+            "token": 1,
+            "records": [{"type": "notify", "message": "stopped", "payload": {"on": "on"}}],
+            "result": {"type": "result", "message": "done"},
+        });
+        s.record(&mut record);
+        assert_eq!(
+            record,
+            json!({
+                "type": "correlated",
+                "token": 1,
+                "records": [{"type": "notify", "message": "stopped", "payload": {"on": "user"}}],
+                "result": {"type": "result", "message": "done"},
+            })
+        );
+        let mut record = json!({"type": "console", "message": "running on on\\n"});
+        s.record(&mut record);
+        assert_eq!(record["message"], "running user user\\n");
+    }
+
+    #[test]
+    fn minimized() {
+        let lines: Vec<String> = (0..10).map(|i| format!("line {i}")).collect();
+        assert_eq!(
+            minimize("grep -q 'line 7'", lines.clone()).unwrap(),
+            ["line 7"]
+        );
+        assert!(minimize("grep -q 'line 70'", lines).is_err());
+    }
+}
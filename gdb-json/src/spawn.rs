@@ -0,0 +1,86 @@
+//! `--spawn`: running gdb as a child process, so gdb-json speaks JSON on
+//! both of its own ends and nothing needs named pipes.
+//!
+//! gdb's MI output is read in place of stdin, and JSON commands, one per
+//! line, are translated with [`command::to_mi`] and written to gdb's stdin.
+//! Commands come from stdin ([`read_commands`]) and from any other source
+//! holding a sender of the command channel, like WebSocket clients. gdb's
+//! stderr is passed through. Once every source is gone gdb's stdin is
+//! closed, which makes gdb exit.
+
+use std::process::Stdio;
+
+use anyhow::Context;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    process::{Child, ChildStdin, ChildStdout, Command},
+    sync::mpsc,
+};
+
+use crate::command;
+
+/// A running gdb whose commands come from a channel.
+pub struct Gdb {
+    child: Child,
+}
+
+impl Gdb {
+    /// Starts `args[0]` with `--interpreter=mi3` and the rest of `args`,
+    /// sending it the commands from `commands`, and returns it along with
+    /// its output.
+    pub fn spawn(
+        args: &[String],
+        commands: mpsc::Receiver<String>,
+    ) -> anyhow::Result<(Self, ChildStdout)> {
+        let (program, args) = args.split_first().context("--spawn needs a program")?;
+        let mut child = Command::new(program)
+            .arg("--interpreter=mi3")
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("spawn {program}"))?;
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = child.stdout.take().expect("stdout is piped");
+        tokio::spawn(forward_commands(commands, stdin));
+        Ok((Self { child }, stdout))
+    }
+
+    /// Waits for gdb to exit, failing if it didn't exit successfully.
+    pub async fn wait(mut self) -> anyhow::Result<()> {
+        let status = self.child.wait().await.context("wait for gdb")?;
+        anyhow::ensure!(status.success(), "gdb exited with {status}");
+        Ok(())
+    }
+}
+
+/// Sends the commands on stdin to `commands` until either side closes.
+pub async fn read_commands(commands: mpsc::Sender<String>) {
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        if commands.send(line).await.is_err() {
+            break;
+        }
+    }
+}
+
+/// Writes the commands from `commands` to gdb until either side closes. A
+/// command that can't be translated is reported on stderr and skipped.
+async fn forward_commands(mut commands: mpsc::Receiver<String>, mut gdb: ChildStdin) {
+    while let Some(line) = commands.recv().await {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let mi = serde_json::from_str(&line)
+            .context("invalid JSON")
+            .and_then(|cmd| command::to_mi(&cmd));
+        match mi {
+            Ok(mi) => {
+                if gdb.write_all(format!("{mi}\n").as_bytes()).await.is_err() {
+                    break;
+                }
+            }
+            Err(e) => eprintln!("gdb-json: skipping command {line:?}: {e:#}"),
+        }
+    }
+}
@@ -0,0 +1,105 @@
+//! `--timestamps`: stamping every record with when and in which order it
+//! was emitted, so streams from several sessions can be merged and put back
+//! in order.
+//!
+//! - `seq`: counts the records emitted, from 0
+//! - `time`: the wall-clock time, in RFC 3339 UTC with microseconds, like
+//!   `2023-02-01T12:34:56.789012Z`
+//! - `elapsed_ns`: nanoseconds since the session started, from a monotonic
+//!   clock, so it keeps going up when the wall clock is changed
+//!
+//! The `rotation` records `--out` starts its files with aren't stamped.
+//! They're only in that file, so counting them would leave gaps in `seq`
+//! everywhere else, and the records around them tell when it happened.
+
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use serde_json::Value;
+
+pub struct Stamper {
+    start: Instant,
+    seq: u64,
+}
+
+impl Stamper {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            seq: 0,
+        }
+    }
+
+    pub fn stamp(&mut self, msg: &mut Value) {
+        let Some(obj) = msg.as_object_mut() else {
+            return;
+        };
+        let elapsed = u64::try_from(self.start.elapsed().as_nanos()).unwrap_or(u64::MAX);
+        obj.insert("seq".to_owned(), self.seq.into());
+        obj.insert("time".to_owned(), rfc3339(SystemTime::now()).into());
+        obj.insert("elapsed_ns".to_owned(), elapsed.into());
+        self.seq += 1;
+    }
+}
+
+fn rfc3339(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let (days, secs) = (secs / 86400, secs % 86400);
+    // days to a civil date, from http://howardhinnant.github.io/date_algorithms.html
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{:06}Z",
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60,
+        since_epoch.subsec_micros(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serde_json::json;
+
+    use super::*;
+
+    fn at(secs: u64, micros: u32) -> String {
+        rfc3339(UNIX_EPOCH + Duration::new(secs, micros * 1000))
+    }
+
+    #[test]
+    fn dates() {
+        assert_eq!(at(0, 0), "1970-01-01T00:00:00.000000Z");
+        assert_eq!(at(1675254896, 789012), "2023-02-01T12:34:56.789012Z");
+        assert_eq!(at(1709251199, 999999), "2024-02-29T23:59:59.999999Z");
+        assert_eq!(at(946684799, 0), "1999-12-31T23:59:59.000000Z");
+        assert_eq!(at(951868800, 0), "2000-03-01T00:00:00.000000Z");
+    }
+
+    #[test]
+    fn stamps() {
+        let mut stamper = Stamper::new();
+        let mut records = [json!({"type": "done"}), json!({"type": "done"})];
+        for r in &mut records {
+            stamper.stamp(r);
+        }
+        assert_eq!(records[0]["seq"], 0);
+        assert_eq!(records[1]["seq"], 1);
+        assert!(records[0]["elapsed_ns"].as_u64() <= records[1]["elapsed_ns"].as_u64());
+        assert!(records[1]["time"].as_str().unwrap().ends_with('Z'));
+
+        let mut not_an_object = json!("x");
+        stamper.stamp(&mut not_an_object);
+        assert_eq!(not_an_object, "x");
+        assert_eq!(stamper.seq, 2);
+    }
+}
@@ -0,0 +1,222 @@
+use std::{
+    collections::HashMap,
+    io::{self, Write},
+    time::{Duration, Instant},
+};
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use gdb_types::{Breakpoint, StopEvent, StopReason};
+
+#[derive(Default)]
+struct Site {
+    location: Option<String>,
+    is_dprintf: bool,
+    times: u64,
+    hits: Vec<Instant>,
+}
+
+/// Counts breakpoint and dprintf hits over a session.
+#[derive(Default)]
+pub struct HitStats {
+    sites: HashMap<String, Site>,
+}
+
+fn location(bkpt: &Value) -> Option<String> {
+    match (
+        bkpt["func"].as_str(),
+        bkpt["file"].as_str(),
+        bkpt["line"].as_str(),
+    ) {
+        (Some(func), Some(file), Some(line)) => Some(format!("{func} at {file}:{line}")),
+        (None, Some(file), Some(line)) => Some(format!("{file}:{line}")),
+        _ => bkpt["original-location"]
+            .as_str()
+            .or_else(|| bkpt["at"].as_str())
+            .map(str::to_owned),
+    }
+}
+
+fn ms(d: Duration) -> f64 {
+    d.as_secs_f64() * 1000.0
+}
+
+impl HitStats {
+    fn update_bkpt(&mut self, value: &Value) {
+        let Ok(bkpt) = Breakpoint::deserialize(value) else {
+            return;
+        };
+        let site = self.sites.entry(bkpt.number).or_default();
+        if let Some(loc) = location(value) {
+            site.location = Some(loc);
+        }
+        site.is_dprintf = bkpt.kind.as_deref() == Some("dprintf");
+        let times = u64::from(bkpt.times);
+        // dprintf hits never stop the inferior, the hit count in
+        // =breakpoint-modified is the only sign of them
+        if site.is_dprintf {
+            for _ in site.times..times {
+                site.hits.push(Instant::now());
+            }
+        }
+        site.times = times;
+    }
+
+    pub fn process(&mut self, msg: &Value) {
+        let payload = &msg["payload"];
+        if !payload["bkpt"].is_null() {
+            self.update_bkpt(&payload["bkpt"]);
+        }
+        if msg["message"] != "stopped" {
+            return;
+        }
+        let Ok(stop) = StopEvent::deserialize(payload) else {
+            return;
+        };
+        if stop.reason == Some(StopReason::BreakpointHit) {
+            if let Some(number) = stop.bkptno {
+                let site = self.sites.entry(number.to_string()).or_default();
+                site.hits.push(Instant::now());
+                if site.location.is_none() {
+                    site.location = location(&payload["frame"]);
+                }
+            }
+        }
+    }
+
+    /// Builds the `hit-stats` record, hottest sites first.
+    pub fn report(&self) -> Value {
+        let mut sites: Vec<_> = self
+            .sites
+            .iter()
+            .filter(|(_, s)| !s.hits.is_empty())
+            .collect();
+        sites.sort_by(|a, b| b.1.hits.len().cmp(&a.1.hits.len()).then(a.0.cmp(b.0)));
+        let breakpoints: Vec<_> = sites
+            .into_iter()
+            .map(|(number, site)| {
+                let intervals: Vec<_> = site.hits.windows(2).map(|w| ms(w[1] - w[0])).collect();
+                let interval = (!intervals.is_empty()).then(|| {
+                    json!({
+                        "min_ms": intervals.iter().copied().fold(f64::INFINITY, f64::min),
+                        "mean_ms": intervals.iter().sum::<f64>() / intervals.len() as f64,
+                        "max_ms": intervals.iter().copied().fold(0.0, f64::max),
+                    })
+                });
+                json!({
+                    "number": number,
+                    "location": site.location,
+                    "dprintf": site.is_dprintf,
+                    "hits": site.hits.len(),
+                    "interval": interval,
+                })
+            })
+            .collect();
+        json!({
+            "type": "hit-stats",
+            "breakpoints": breakpoints,
+        })
+    }
+
+    /// Writes `report` as a table for humans.
+    pub fn write_table(report: &Value, mut out: impl Write) -> io::Result<()> {
+        writeln!(
+            out,
+            "{:>6} {:>8} {:>12}  location",
+            "bkpt", "hits", "mean ms"
+        )?;
+        for bp in report["breakpoints"].as_array().into_iter().flatten() {
+            let mean = bp["interval"]["mean_ms"]
+                .as_f64()
+                .map(|m| format!("{m:.3}"))
+                .unwrap_or_else(|| "-".to_owned());
+            writeln!(
+                out,
+                "{:>6} {:>8} {:>12}  {}",
+                bp["number"].as_str().unwrap_or_default(),
+                bp["hits"].as_u64().unwrap_or_default(),
+                mean,
+                bp["location"].as_str().unwrap_or("?"),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bkpt(number: &str, kind: &str, times: &str) -> Value {
+        json!({
+            "type": "notify",
+            "message": "breakpoint-modified",
+            "payload": {"bkpt": {
+                "number": number, "type": kind, "enabled": "y",
+                "func": "main", "file": "t.c", "line": "9", "times": times,
+            }},
+        })
+    }
+
+    fn hit(number: &str) -> Value {
+        json!({
+            "type": "notify",
+            "message": "stopped",
+            "payload": {
+                "reason": "breakpoint-hit", "bkptno": number, "thread-id": "1",
+                "frame": {"func": "f", "file": "u.c", "line": "3"},
+            },
+        })
+    }
+
+    #[test]
+    fn hits() {
+        let mut stats = HitStats::default();
+        stats.process(&bkpt("1", "breakpoint", "0"));
+        stats.process(&hit("1"));
+        stats.process(&bkpt("1", "breakpoint", "1"));
+        stats.process(&hit("1"));
+        // hit before anything said where it is
+        stats.process(&hit("2"));
+        stats.process(&bkpt("3", "dprintf", "0"));
+        stats.process(&bkpt("3", "dprintf", "3"));
+        // not a stop at a breakpoint
+        stats.process(&json!({
+            "type": "notify",
+            "message": "stopped",
+            "payload": {"reason": "end-stepping-range", "thread-id": "1"},
+        }));
+
+        let report = stats.report();
+        let sites: Vec<_> = report["breakpoints"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|bp| {
+                json!([
+                    bp["number"],
+                    bp["hits"],
+                    bp["dprintf"],
+                    bp["location"],
+                    bp["interval"].is_object()
+                ])
+            })
+            .collect();
+        assert_eq!(
+            sites,
+            [
+                json!(["3", 3, true, "main at t.c:9", true]),
+                json!(["1", 2, false, "main at t.c:9", true]),
+                json!(["2", 1, false, "f at u.c:3", false]),
+            ]
+        );
+
+        let mut table = Vec::new();
+        HitStats::write_table(&report, &mut table).unwrap();
+        let table = String::from_utf8(table).unwrap();
+        let lines: Vec<_> = table.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[3].ends_with("       -  f at u.c:3"), "{table}");
+    }
+}
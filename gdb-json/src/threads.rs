@@ -0,0 +1,185 @@
+use std::collections::HashMap;
+
+use regex::Regex;
+use serde_json::{json, Value};
+
+/// Keeps track of thread names seen in the MI stream and tags thread
+/// related records with the resolved name and user defined class.
+pub struct Threads {
+    names: HashMap<String, Option<String>>,
+    classes: Vec<(String, Regex)>,
+}
+
+/// Parses a `--thread-class CLASS=REGEX` argument.
+pub fn parse_class(s: &str) -> Result<(String, Regex), String> {
+    let (class, re) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected CLASS=REGEX, got {s:?}"))?;
+    let re = Regex::new(re).map_err(|e| e.to_string())?;
+    Ok((class.to_owned(), re))
+}
+
+/// Extracts the name from target ids like `Thread 0x7ffff7d89740 (LWP 1234) "worker"`.
+fn name_from_target_id(target_id: &str) -> Option<String> {
+    let rest = target_id.strip_suffix('"')?;
+    let start = rest.rfind('"')?;
+    Some(rest[start + 1..].to_owned())
+}
+
+impl Threads {
+    pub fn new(classes: Vec<(String, Regex)>) -> Self {
+        Self {
+            names: HashMap::new(),
+            classes,
+        }
+    }
+
+    fn classify(&self, name: &str) -> Option<&str> {
+        self.classes
+            .iter()
+            .find(|(_, re)| re.is_match(name))
+            .map(|(class, _)| class.as_str())
+    }
+
+    fn describe(&self, id: &str) -> Value {
+        let name = self.names.get(id).cloned().flatten();
+        let class = name.as_deref().and_then(|n| self.classify(n));
+        json!({
+            "id": id,
+            "name": name,
+            "class": class,
+        })
+    }
+
+    /// Learns thread names from `-thread-info` results and `=thread-*`
+    /// notifications, and adds a `thread` object to every record that
+    /// refers to a single thread.
+    pub fn process(&mut self, msg: &mut Value) {
+        if !matches!(msg["type"].as_str(), Some("notify" | "result")) {
+            return;
+        }
+        let message = msg["message"].as_str().unwrap_or_default().to_owned();
+        let payload = &mut msg["payload"];
+
+        if let Some(threads) = payload.get_mut("threads").and_then(Value::as_array_mut) {
+            for thread in threads {
+                let Some(id) = thread["id"].as_str().map(str::to_owned) else {
+                    continue;
+                };
+                let name = thread["name"]
+                    .as_str()
+                    .map(str::to_owned)
+                    .or_else(|| thread["target-id"].as_str().and_then(name_from_target_id));
+                if let Some(class) = name.as_deref().and_then(|n| self.classify(n)) {
+                    thread["class"] = class.into();
+                }
+                self.names.insert(id, name);
+            }
+        }
+
+        let is_thread_notify =
+            message.starts_with("thread-") && !message.starts_with("thread-group-");
+        let id = match payload.get("thread-id") {
+            Some(id) => id.as_str(),
+            None if is_thread_notify => payload["id"].as_str(),
+            None => None,
+        };
+        let Some(id) = id.filter(|id| *id != "all").map(str::to_owned) else {
+            return;
+        };
+
+        if message == "thread-created" {
+            self.names.entry(id.clone()).or_default();
+        }
+        msg["thread"] = self.describe(&id);
+        if message == "thread-exited" {
+            self.names.remove(&id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn threads() -> Threads {
+        Threads::new(vec![parse_class("worker=^worker-").unwrap()])
+    }
+
+    #[test]
+    fn names_and_classes() {
+        let mut threads = threads();
+        let mut info = json!({
+            "type": "result",
+            "message": "done",
+            "payload": {"threads": [
+                {"id": "1", "target-id": "Thread 0x7ffff7d89740 (LWP 1234) \"main\""},
+                {"id": "2", "name": "worker-1", "target-id": "LWP 1235"},
+            ]},
+        });
+        threads.process(&mut info);
+        assert_eq!(info["payload"]["threads"][0].get("class"), None);
+        assert_eq!(info["payload"]["threads"][1]["class"], "worker");
+
+        let mut stopped = json!({
+            "type": "notify",
+            "message": "stopped",
+            "payload": {"reason": "breakpoint-hit", "thread-id": "2"},
+        });
+        threads.process(&mut stopped);
+        assert_eq!(
+            stopped["thread"],
+            json!({"id": "2", "name": "worker-1", "class": "worker"})
+        );
+        let mut running = json!({
+            "type": "notify",
+            "message": "running",
+            "payload": {"thread-id": "all"},
+        });
+        threads.process(&mut running);
+        assert_eq!(running.get("thread"), None);
+    }
+
+    #[test]
+    fn created_and_exited() {
+        let mut threads = threads();
+        let mut created = json!({
+            "type": "notify",
+            "message": "thread-created",
+            "payload": {"id": "3", "group-id": "i1"},
+        });
+        threads.process(&mut created);
+        assert_eq!(
+            created["thread"],
+            json!({"id": "3", "name": null, "class": null})
+        );
+        let mut group = json!({
+            "type": "notify",
+            "message": "thread-group-started",
+            "payload": {"id": "i1", "pid": "42"},
+        });
+        threads.process(&mut group);
+        assert_eq!(group.get("thread"), None);
+
+        threads.names.insert("3".into(), Some("worker-2".into()));
+        let mut exited = json!({
+            "type": "notify",
+            "message": "thread-exited",
+            "payload": {"id": "3", "group-id": "i1"},
+        });
+        threads.process(&mut exited);
+        assert_eq!(exited["thread"]["class"], "worker");
+        assert!(!threads.names.contains_key("3"));
+    }
+
+    #[test]
+    fn classes() {
+        assert!(parse_class("worker").is_err());
+        assert!(parse_class("worker=(").is_err());
+        assert_eq!(
+            name_from_target_id("Thread 0x7ffff7d89740 (LWP 1234) \"io\""),
+            Some("io".to_owned())
+        );
+        assert_eq!(name_from_target_id("LWP 1234"), None);
+    }
+}
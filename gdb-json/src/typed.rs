@@ -0,0 +1,176 @@
+//! `--typed`: giving the payloads of the records consumers look at most
+//! JSON types instead of MI's strings.
+//!
+//! The payloads are read as the [`gdb_types`] types they are, and the values
+//! of the fields those know are written back as the numbers and bools they
+//! parse to. Fields keep their MI names and unknown fields are left alone, so
+//! the typed payloads still deserialize into the same [`gdb_types`] types:
+//!
+//! - `*stopped`, a [`StopEvent`]: `thread-id`, `bkptno` and `core` are
+//!   numbers, `exit-code` is a number (MI spells it in octal) and
+//!   `stopped-threads` is `"all"` or an array of numbers.
+//! - `=breakpoint-created` and `=breakpoint-modified`: `bkpt` and each of
+//!   its `locations` are [`Breakpoint`]s, with `line`, `times`, `ignore` and
+//!   `thread` numbers and `enabled` a bool. `number` stays a string, since a
+//!   location is numbered `N.M`.
+//! - `=breakpoint-deleted`: `id` is a number.
+//! - `=thread-created`, `=thread-exited` and `=thread-selected`: `id` is a
+//!   number.
+//! - The `frame` of any of these, a [`Frame`]: `level` and `line` are
+//!   numbers.
+//!
+//! Addresses stay `0x` strings, since JSON numbers can't hold every 64-bit
+//! address in every language. A payload or tuple that doesn't parse as its
+//! type is left as it is.
+
+use gdb_types::{mi, Breakpoint, Frame, StopEvent};
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+
+/// Types the payload of `msg` if it's one of the records above.
+pub fn type_payload(msg: &mut Value) {
+    if msg["type"] != "notify" {
+        return;
+    }
+    let message = msg["message"].as_str().unwrap_or_default().to_owned();
+    let Some(payload) = msg.get_mut("payload").and_then(Value::as_object_mut) else {
+        return;
+    };
+    match message.as_str() {
+        "stopped" => {
+            if let Some(stop) = parse::<StopEvent>(payload) {
+                set(payload, "thread-id", stop.thread_id);
+                set(payload, "core", stop.core);
+                set(payload, "bkptno", stop.bkptno);
+                set(payload, "exit-code", stop.exit_code);
+            }
+            if let Some(Value::Array(threads)) = payload.get_mut("stopped-threads") {
+                threads.iter_mut().for_each(id);
+            }
+        }
+        "breakpoint-created" | "breakpoint-modified" => {
+            if let Some(bkpt) = payload.get_mut("bkpt").and_then(Value::as_object_mut) {
+                breakpoint(bkpt);
+                if let Some(Value::Array(locations)) = bkpt.get_mut("locations") {
+                    for location in locations.iter_mut().filter_map(Value::as_object_mut) {
+                        breakpoint(location);
+                    }
+                }
+            }
+        }
+        "breakpoint-deleted" | "thread-created" | "thread-exited" | "thread-selected" => {
+            if let Some(v) = payload.get_mut("id") {
+                id(v);
+            }
+        }
+        _ => {}
+    }
+    if let Some(frame) = payload.get_mut("frame").and_then(Value::as_object_mut) {
+        if let Some(parsed) = parse::<Frame>(frame) {
+            set(frame, "level", parsed.level);
+            set(frame, "line", parsed.line);
+        }
+    }
+}
+
+fn breakpoint(bkpt: &mut Map<String, Value>) {
+    let Some(parsed) = parse::<Breakpoint>(bkpt) else {
+        return;
+    };
+    set(bkpt, "line", parsed.line);
+    set(bkpt, "times", Some(parsed.times));
+    set(bkpt, "ignore", parsed.ignore);
+    set(bkpt, "thread", parsed.thread);
+    set(bkpt, "enabled", Some(parsed.enabled));
+}
+
+fn parse<T: DeserializeOwned>(fields: &Map<String, Value>) -> Option<T> {
+    serde_json::from_value(Value::Object(fields.clone())).ok()
+}
+
+/// Replaces the value of the field `name`, if `fields` has it.
+fn set(fields: &mut Map<String, Value>, name: &str, v: Option<impl Into<Value>>) {
+    if let (Some(field), Some(v)) = (fields.get_mut(name), v) {
+        *field = v.into();
+    }
+}
+
+/// Types a thread or breakpoint id.
+fn id(v: &mut Value) {
+    if let Ok(n) = mi::string::deserialize::<u32, _>(&*v) {
+        *v = n.into();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn typed(message: &str, payload: Value) -> Value {
+        let mut msg = json!({"type": "notify", "message": message, "payload": payload});
+        type_payload(&mut msg);
+        msg["payload"].take()
+    }
+
+    #[test]
+    fn stopped() {
+        let payload = typed(
+            "stopped",
+            json!({
+                "reason": "exited",
+                "exit-code": "010",
+                "thread-id": "2",
+                "stopped-threads": ["1", "2"],
+                "frame": {"addr": "0x1149", "level": "0", "line": "7"},
+            }),
+        );
+        assert_eq!(
+            payload,
+            json!({
+                "reason": "exited",
+                "exit-code": 8,
+                "thread-id": 2,
+                "stopped-threads": [1, 2],
+                "frame": {"addr": "0x1149", "level": 0, "line": 7},
+            })
+        );
+        let stop: StopEvent = serde_json::from_value(payload).unwrap();
+        assert_eq!(stop.thread_id, Some(2));
+    }
+
+    #[test]
+    fn breakpoints() {
+        let payload = typed(
+            "breakpoint-created",
+            json!({"bkpt": {
+                "number": "1",
+                "enabled": "y",
+                "times": "0",
+                "locations": [{"number": "1.1", "enabled": "n", "line": "3"}],
+            }}),
+        );
+        assert_eq!(
+            payload,
+            json!({"bkpt": {
+                "number": "1",
+                "enabled": true,
+                "times": 0,
+                "locations": [{"number": "1.1", "enabled": false, "line": 3}],
+            }})
+        );
+        let bkpt: Breakpoint = serde_json::from_value(payload["bkpt"].clone()).unwrap();
+        assert!(bkpt.enabled);
+    }
+
+    #[test]
+    fn unparsable_left_alone() {
+        let payload = json!({"thread-id": "all", "frame": {"line": "x"}});
+        assert_eq!(typed("stopped", payload.clone()), payload);
+        assert_eq!(
+            typed("thread-created", json!({"id": "i1"})),
+            json!({"id": "i1"})
+        );
+    }
+}
@@ -0,0 +1,35 @@
+//! `--parse-values`: replacing the C-syntax `value` strings in MI payloads,
+//! like `value="{x = 1, y = 2}"` in `-data-evaluate-expression` results and
+//! frame arguments, with the JSON value-parser makes of them.
+//!
+//! Strings that don't parse as a whole are left as they are, so consumers
+//! still have to accept strings for every `value`.
+
+use serde_json::Value;
+use value_parser::Parser;
+
+/// Parses every `value` string in `msg`'s payload.
+pub fn parse_values(msg: &mut Value) {
+    if let Some(payload) = msg.get_mut("payload") {
+        parse_in(payload);
+    }
+}
+
+fn parse_in(v: &mut Value) {
+    match v {
+        Value::Object(fields) => {
+            for (key, v) in fields {
+                match v {
+                    Value::String(s) if key == "value" => {
+                        if let Ok(output) = Parser::new(s).parse_print_output() {
+                            *v = Value::from(&output.value);
+                        }
+                    }
+                    v => parse_in(v),
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(parse_in),
+        _ => {}
+    }
+}
@@ -0,0 +1,144 @@
+//! `--ws`: serving records over WebSocket, so debugger UIs running in a
+//! browser can connect without a native shim.
+//!
+//! Each client gets every record from the time it connects on, one JSON
+//! text message per record, and can send JSON commands (see `to-mi`) as
+//! text messages, which go to gdb like the ones on stdin.
+//!
+//! Clients pass the session token (see [`crate::auth`]) as a `token` query
+//! parameter, like `ws://127.0.0.1:9229/?token=...`, or as an
+//! `Authorization: Bearer ...` header. Browsers send the page's origin with
+//! the handshake, and only origins given with `--ws-allow-origin` are let
+//! in, so other pages the user has open can't connect.
+
+use std::{net::SocketAddr, sync::Arc};
+
+use anyhow::Context;
+use futures_util::{SinkExt, StreamExt};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::{broadcast, mpsc},
+};
+use tokio_tungstenite::tungstenite::{
+    handshake::server::{Callback, ErrorResponse, Request, Response},
+    http::StatusCode,
+    Message,
+};
+
+use crate::{auth::Token, clients::Subscription};
+
+/// Who may connect.
+pub struct Access {
+    pub token: Token,
+    /// Origins browsers may connect from, like `http://localhost:3000`.
+    pub origins: Vec<String>,
+}
+
+impl Callback for &Access {
+    fn on_request(self, request: &Request, response: Response) -> Result<Response, ErrorResponse> {
+        let reject = |status: StatusCode, why: &str| {
+            let mut response = ErrorResponse::new(Some(why.to_owned()));
+            *response.status_mut() = status;
+            response
+        };
+        if let Some(origin) = request.headers().get("origin") {
+            let allowed = origin
+                .to_str()
+                .is_ok_and(|origin| self.origins.iter().any(|o| o == origin));
+            if !allowed {
+                return Err(reject(StatusCode::FORBIDDEN, "origin not allowed"));
+            }
+        }
+        let from_query = request.uri().query().and_then(|query| {
+            query
+                .split('&')
+                .find_map(|param| param.strip_prefix("token="))
+        });
+        let from_header = request
+            .headers()
+            .get("authorization")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "));
+        match from_query.or(from_header) {
+            Some(token) if self.token.matches(token) => Ok(response),
+            _ => Err(reject(StatusCode::UNAUTHORIZED, "missing or wrong token")),
+        }
+    }
+}
+
+/// Listens on `addr`, sending each client the records from `records` and
+/// sending the commands clients send to `commands`.
+pub async fn listen(
+    addr: SocketAddr,
+    access: Access,
+    records: Subscription,
+    commands: mpsc::Sender<String>,
+) -> anyhow::Result<()> {
+    let listener = TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("listen on {addr}"))?;
+    tokio::spawn(accept(listener, Arc::new(access), records, commands));
+    Ok(())
+}
+
+async fn accept(
+    listener: TcpListener,
+    access: Arc<Access>,
+    mut records: Subscription,
+    commands: mpsc::Sender<String>,
+) {
+    loop {
+        tokio::select! {
+            client = listener.accept() => match client {
+                Ok((stream, peer)) => {
+                    let access = access.clone();
+                    let records = records.resubscribe();
+                    let commands = commands.clone();
+                    tokio::spawn(async move {
+                        if let Err(e) = serve(stream, &access, records, commands).await {
+                            eprintln!("gdb-json: WebSocket client {peer}: {e:#}");
+                        }
+                    });
+                }
+                Err(e) => eprintln!("gdb-json: accepting WebSocket client: {e}"),
+            },
+            () = records.closed() => return,
+        }
+    }
+}
+
+async fn serve(
+    stream: TcpStream,
+    access: &Access,
+    mut sub: Subscription,
+    commands: mpsc::Sender<String>,
+) -> anyhow::Result<()> {
+    let ws = tokio_tungstenite::accept_hdr_async(stream, access)
+        .await
+        .context("handshake")?;
+    let (mut tx, mut rx) = ws.split();
+    loop {
+        tokio::select! {
+            record = sub.records.recv() => match record {
+                Ok(record) => tx.send(Message::Text(record.to_string())).await?,
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    eprintln!("gdb-json: WebSocket client fell behind, skipped {n} records");
+                }
+                Err(broadcast::error::RecvError::Closed) => {
+                    tx.close().await?;
+                    return Ok(());
+                }
+            },
+            msg = rx.next() => match msg.transpose()? {
+                Some(Message::Text(cmd)) => {
+                    if commands.send(cmd).await.is_err() {
+                        return Ok(());
+                    }
+                }
+                Some(Message::Close(_)) | None => return Ok(()),
+                // pings are answered by tungstenite
+                Some(_) => {}
+            },
+        }
+    }
+}
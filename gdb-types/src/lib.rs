@@ -0,0 +1,349 @@
+//! The debugger entities shared by the tools in this repository.
+//!
+//! The serde representation is the one GDB/MI uses, and so the one gdb-json
+//! emits: kebab-case field names and every value a string. A `frame` field
+//! of a gdb-json record deserializes straight into a [`Frame`], and
+//! serializing a [`Frame`] gives the same JSON back (modulo leading zeros in
+//! addresses). Fields not listed here are ignored.
+
+use std::{fmt, num::ParseIntError, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+pub mod mi;
+mod stop;
+
+pub use stop::{StopReason, MI_NAMES};
+
+/// A target address, spelled `0x...` in MI.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Addr(pub u64);
+
+impl fmt::Display for Addr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:#x}", self.0)
+    }
+}
+
+impl FromStr for Addr {
+    type Err = ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex = s.strip_prefix("0x").unwrap_or(s);
+        u64::from_str_radix(hex, 16).map(Addr)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Arg {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Frame {
+    #[serde(
+        default,
+        with = "mi::opt_string",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub level: Option<u32>,
+    #[serde(
+        default,
+        with = "mi::opt_string",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub addr: Option<Addr>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub func: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<Arg>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fullname: Option<String>,
+    #[serde(
+        default,
+        with = "mi::opt_string",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub line: Option<u32>,
+    /// The shared library, for frames without debug info.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub arch: Option<String>,
+}
+
+/// A breakpoint, watchpoint, catchpoint or dprintf as in `bkpt` tuples.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Breakpoint {
+    /// `N`, or `N.M` for one location of a multi-location breakpoint.
+    pub number: String,
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disp: Option<String>,
+    #[serde(with = "mi::flag")]
+    pub enabled: bool,
+    /// An address, or `<PENDING>` / `<MULTIPLE>`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub addr: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub func: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fullname: Option<String>,
+    #[serde(
+        default,
+        with = "mi::opt_string",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub line: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub original_location: Option<String>,
+    /// The expression of a watchpoint.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub what: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cond: Option<String>,
+    /// Missing for the locations of a multi-location breakpoint.
+    #[serde(default, with = "mi::string")]
+    pub times: u32,
+    #[serde(
+        default,
+        with = "mi::opt_string",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub ignore: Option<u32>,
+    /// The thread a thread-specific breakpoint is for.
+    #[serde(
+        default,
+        with = "mi::opt_string",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub thread: Option<u32>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Thread {
+    #[serde(with = "mi::string")]
+    pub id: u32,
+    pub target_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// `stopped` or `running`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub frame: Option<Frame>,
+    #[serde(
+        default,
+        with = "mi::opt_string",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub core: Option<u32>,
+}
+
+/// A register as in `-data-list-register-values`, with its name from
+/// `-data-list-register-names` when known.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Register {
+    #[serde(with = "mi::string")]
+    pub number: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Range {
+    #[serde(with = "mi::string")]
+    pub from: Addr,
+    #[serde(with = "mi::string")]
+    pub to: Addr,
+}
+
+/// A shared library, as in `=library-loaded`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Module {
+    pub id: String,
+    pub target_name: String,
+    pub host_name: String,
+    #[serde(with = "mi::flag")]
+    pub symbols_loaded: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thread_group: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ranges: Vec<Range>,
+}
+
+/// The payload of `*stopped`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct StopEvent {
+    /// Missing when the stop was requested, e.g. by `-exec-interrupt`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<StopReason>,
+    #[serde(
+        default,
+        with = "mi::opt_string",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub thread_id: Option<u32>,
+    #[serde(
+        default,
+        with = "mi::opt_string",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub bkptno: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub frame: Option<Frame>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signal_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signal_meaning: Option<String>,
+    #[serde(
+        default,
+        with = "mi::opt_octal",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub exit_code: Option<u32>,
+    #[serde(
+        default,
+        with = "mi::opt_string",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub core: Option<u32>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn stop_event() {
+        let payload = json!({
+            "reason": "breakpoint-hit",
+            "disp": "keep",
+            "bkptno": "1",
+            "frame": {
+                "addr": "0x0000555555555171",
+                "func": "main",
+                "args": [{"name": "argc", "value": "1"}],
+                "file": "t.c",
+                "fullname": "/tmp/t.c",
+                "line": "9",
+                "arch": "i386:x86-64",
+            },
+            "thread-id": "1",
+            "stopped-threads": "all",
+            "core": "3",
+        });
+        let stop: StopEvent = serde_json::from_value(payload).unwrap();
+        assert_eq!(stop.reason, Some(StopReason::BreakpointHit));
+        assert_eq!(stop.thread_id, Some(1));
+        let frame = stop.frame.unwrap();
+        assert_eq!(frame.addr, Some(Addr(0x555555555171)));
+        assert_eq!(frame.line, Some(9));
+        assert_eq!(frame.args[0].value.as_deref(), Some("1"));
+    }
+
+    #[test]
+    fn unknown_reason() {
+        let stop: StopEvent = serde_json::from_value(json!({"reason": "new-thing"})).unwrap();
+        assert_eq!(stop.reason, Some(StopReason::Other("new-thing".into())));
+        assert_eq!(
+            serde_json::to_value(&stop).unwrap(),
+            json!({"reason": "new-thing"})
+        );
+    }
+
+    #[test]
+    fn reason_names_roundtrip() {
+        for (reason, name) in MI_NAMES {
+            assert_eq!(StopReason::from_mi(name), *reason);
+            assert_eq!(reason.as_mi(), *name);
+        }
+    }
+
+    #[test]
+    fn breakpoint_roundtrip() {
+        let bkpt = json!({
+            "number": "2",
+            "type": "breakpoint",
+            "disp": "keep",
+            "enabled": "y",
+            "addr": "0x1149",
+            "func": "foo",
+            "file": "t.c",
+            "fullname": "/tmp/t.c",
+            "line": "3",
+            "original-location": "foo",
+            "times": "4",
+        });
+        let parsed: Breakpoint = serde_json::from_value(bkpt.clone()).unwrap();
+        assert!(parsed.enabled);
+        assert_eq!(parsed.times, 4);
+        assert_eq!(serde_json::to_value(&parsed).unwrap(), bkpt);
+    }
+
+    #[test]
+    fn module() {
+        let module: Module = serde_json::from_value(json!({
+            "id": "/lib/libc.so.6",
+            "target-name": "/lib/libc.so.6",
+            "host-name": "/lib/libc.so.6",
+            "symbols-loaded": "0",
+            "thread-group": "i1",
+            "ranges": [{"from": "0x00007ffff7dae700", "to": "0x00007ffff7f4093d"}],
+        }))
+        .unwrap();
+        assert!(!module.symbols_loaded);
+        assert_eq!(module.ranges[0].from, Addr(0x7ffff7dae700));
+    }
+
+    #[test]
+    fn numbers_unquoted() {
+        let thread: Thread =
+            serde_json::from_value(json!({"id": 2, "target-id": "LWP 5", "core": 1})).unwrap();
+        assert_eq!(thread.id, 2);
+        assert_eq!(thread.core, Some(1));
+    }
+
+    #[test]
+    fn exit_code() {
+        let stop: StopEvent =
+            serde_json::from_value(json!({"reason": "exited", "exit-code": "010"})).unwrap();
+        assert_eq!(stop.exit_code, Some(8));
+        assert_eq!(serde_json::to_value(&stop).unwrap()["exit-code"], "010");
+        let typed: StopEvent = serde_json::from_value(json!({"exit-code": 8})).unwrap();
+        assert_eq!(typed.exit_code, Some(8));
+    }
+
+    #[test]
+    fn typed_values() {
+        let bkpt: Breakpoint = serde_json::from_value(
+            json!({"number": "1.2", "enabled": false, "line": 3, "thread": 2}),
+        )
+        .unwrap();
+        assert!(!bkpt.enabled);
+        assert_eq!(bkpt.line, Some(3));
+        assert_eq!(bkpt.thread, Some(2));
+        assert_eq!(bkpt.times, 0);
+    }
+
+    #[test]
+    fn invalid_number() {
+        let err = serde_json::from_value::<Register>(json!({"number": "x"})).unwrap_err();
+        assert!(err.to_string().contains("invalid value \"x\""), "{err}");
+    }
+}
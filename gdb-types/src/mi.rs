@@ -0,0 +1,118 @@
+//! Serde helpers for the way MI spells values: everything is a string, so
+//! numbers are decimal strings, addresses `0x` hex strings and flags `y`/`n`
+//! or `1`/`0`. Numbers and bools are also accepted unquoted, for JSON
+//! written by hand or by `gdb-json --typed`.
+
+use std::{fmt::Display, str::FromStr};
+
+use serde::{de, Deserialize, Deserializer, Serializer};
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StrOrNum {
+    Str(String),
+    Num(u64),
+    Bool(bool),
+}
+
+impl StrOrNum {
+    fn into_string(self) -> String {
+        match self {
+            Self::Str(s) => s,
+            Self::Num(n) => n.to_string(),
+            Self::Bool(b) => b.to_string(),
+        }
+    }
+}
+
+fn parse<T: FromStr, E: de::Error>(s: &str) -> Result<T, E>
+where
+    T::Err: Display,
+{
+    s.parse()
+        .map_err(|e| E::custom(format_args!("invalid value {s:?}: {e}")))
+}
+
+/// `#[serde(with = "mi::string")]`: a value stored as its `Display` form.
+pub mod string {
+    use super::*;
+
+    pub fn serialize<T: Display, S: Serializer>(v: &T, s: S) -> Result<S::Ok, S::Error> {
+        s.collect_str(v)
+    }
+
+    pub fn deserialize<'de, T, D>(d: D) -> Result<T, D::Error>
+    where
+        T: FromStr,
+        T::Err: Display,
+        D: Deserializer<'de>,
+    {
+        parse(&StrOrNum::deserialize(d)?.into_string())
+    }
+}
+
+/// Like [`string`], for optional fields. Use with `#[serde(default)]`.
+pub mod opt_string {
+    use super::*;
+
+    pub fn serialize<T: Display, S: Serializer>(v: &Option<T>, s: S) -> Result<S::Ok, S::Error> {
+        match v {
+            Some(v) => s.collect_str(v),
+            None => s.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, T, D>(d: D) -> Result<Option<T>, D::Error>
+    where
+        T: FromStr,
+        T::Err: Display,
+        D: Deserializer<'de>,
+    {
+        Option::<StrOrNum>::deserialize(d)?
+            .map(|v| parse(&v.into_string()))
+            .transpose()
+    }
+}
+
+/// `#[serde(with = "mi::opt_octal")]`: an optional number MI spells in
+/// octal, like `exit-code`. Unquoted numbers are taken as they are.
+pub mod opt_octal {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(v: &Option<u32>, s: S) -> Result<S::Ok, S::Error> {
+        match v {
+            Some(v) => s.collect_str(&format_args!("0{v:o}")),
+            None => s.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<u32>, D::Error> {
+        match Option::<StrOrNum>::deserialize(d)? {
+            Some(StrOrNum::Str(s)) => u32::from_str_radix(&s, 8)
+                .map(Some)
+                .map_err(|e| de::Error::custom(format_args!("invalid value {s:?}: {e}"))),
+            Some(StrOrNum::Num(n)) => u32::try_from(n)
+                .map(Some)
+                .map_err(|e| de::Error::custom(format_args!("invalid value {n}: {e}"))),
+            Some(StrOrNum::Bool(b)) => Err(de::Error::custom(format_args!("invalid value {b}"))),
+            None => Ok(None),
+        }
+    }
+}
+
+/// `#[serde(with = "mi::flag")]`: a bool spelled `y`/`n` or `1`/`0`.
+pub mod flag {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(v: &bool, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(if *v { "y" } else { "n" })
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<bool, D::Error> {
+        match StrOrNum::deserialize(d)?.into_string().as_str() {
+            "y" | "1" | "true" => Ok(true),
+            "n" | "0" | "false" => Ok(false),
+            s => Err(de::Error::custom(format_args!("invalid flag {s:?}"))),
+        }
+    }
+}
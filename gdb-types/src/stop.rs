@@ -0,0 +1,101 @@
+//! Why the inferior stopped, as given by the `reason` field of `*stopped`.
+//!
+//! Everything that needs to know about stop reasons goes through
+//! [`StopReason`] rather than comparing strings, so that the names only
+//! appear in [`MI_NAMES`]. Reasons GDB adds later end up as
+//! [`StopReason::Other`]. C++ and Ada exception catchpoints report
+//! `breakpoint-hit`, and reverse execution running out of history reports
+//! `no-history`.
+
+use std::fmt;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum StopReason {
+    BreakpointHit,
+    WatchpointTrigger,
+    ReadWatchpointTrigger,
+    AccessWatchpointTrigger,
+    WatchpointScope,
+    FunctionFinished,
+    LocationReached,
+    EndSteppingRange,
+    SignalReceived,
+    Exited,
+    ExitedNormally,
+    ExitedSignalled,
+    SolibEvent,
+    Fork,
+    Vfork,
+    SyscallEntry,
+    SyscallReturn,
+    Exec,
+    NoHistory,
+    Other(String),
+}
+
+/// The MI spelling of every reason but [`StopReason::Other`].
+pub const MI_NAMES: &[(StopReason, &str)] = &[
+    (StopReason::BreakpointHit, "breakpoint-hit"),
+    (StopReason::WatchpointTrigger, "watchpoint-trigger"),
+    (StopReason::ReadWatchpointTrigger, "read-watchpoint-trigger"),
+    (
+        StopReason::AccessWatchpointTrigger,
+        "access-watchpoint-trigger",
+    ),
+    (StopReason::WatchpointScope, "watchpoint-scope"),
+    (StopReason::FunctionFinished, "function-finished"),
+    (StopReason::LocationReached, "location-reached"),
+    (StopReason::EndSteppingRange, "end-stepping-range"),
+    (StopReason::SignalReceived, "signal-received"),
+    (StopReason::Exited, "exited"),
+    (StopReason::ExitedNormally, "exited-normally"),
+    (StopReason::ExitedSignalled, "exited-signalled"),
+    (StopReason::SolibEvent, "solib-event"),
+    (StopReason::Fork, "fork"),
+    (StopReason::Vfork, "vfork"),
+    (StopReason::SyscallEntry, "syscall-entry"),
+    (StopReason::SyscallReturn, "syscall-return"),
+    (StopReason::Exec, "exec"),
+    (StopReason::NoHistory, "no-history"),
+];
+
+impl StopReason {
+    pub fn from_mi(s: &str) -> Self {
+        MI_NAMES
+            .iter()
+            .find(|(_, name)| *name == s)
+            .map(|(reason, _)| reason.clone())
+            .unwrap_or_else(|| Self::Other(s.to_owned()))
+    }
+
+    pub fn as_mi(&self) -> &str {
+        match self {
+            Self::Other(s) => s,
+            _ => MI_NAMES
+                .iter()
+                .find(|(reason, _)| reason == self)
+                .map(|(_, name)| *name)
+                .expect("every reason but Other has an MI name"),
+        }
+    }
+}
+
+impl fmt::Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_mi())
+    }
+}
+
+impl Serialize for StopReason {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(self.as_mi())
+    }
+}
+
+impl<'de> Deserialize<'de> for StopReason {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        Ok(Self::from_mi(&String::deserialize(d)?))
+    }
+}
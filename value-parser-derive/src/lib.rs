@@ -0,0 +1,107 @@
+//! `#[derive(FromGdbValue)]`, re-exported as `value_parser::convert::FromGdbValue`
+//! with value-parser's `derive` feature, which documents what the derived
+//! impls accept.
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, parse_quote, Attribute, Data, DeriveInput, Error, Fields, LitStr};
+
+#[proc_macro_derive(FromGdbValue, attributes(gdb))]
+pub fn derive_from_gdb_value(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+/// The name given with `#[gdb(rename = "...")]`, if any.
+fn rename(attrs: &[Attribute]) -> syn::Result<Option<String>> {
+    let mut name = None;
+    for attr in attrs.iter().filter(|a| a.path().is_ident("gdb")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("rename") {
+                name = Some(meta.value()?.parse::<LitStr>()?.value());
+                Ok(())
+            } else {
+                Err(meta.error("expected `rename = \"...\"`"))
+            }
+        })?;
+    }
+    Ok(name)
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream> {
+    let ident = &input.ident;
+    // every type parameter has to convert for the type to, like serde's
+    // derives assume
+    let mut generics = input.generics.clone();
+    let params: Vec<_> = generics.type_params().map(|p| p.ident.clone()).collect();
+    let where_clause = generics.make_where_clause();
+    for param in params {
+        where_clause
+            .predicates
+            .push(parse_quote!(#param: ::value_parser::convert::FromGdbValue));
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let body = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => {
+                let fields = fields
+                    .named
+                    .iter()
+                    .map(|f| {
+                        let field = f.ident.as_ref().unwrap();
+                        let name = rename(&f.attrs)?.unwrap_or_else(|| field.to_string());
+                        Ok(quote!(#field: v.convert_field(#name)?))
+                    })
+                    .collect::<syn::Result<Vec<_>>>()?;
+                quote!(Ok(Self { #(#fields),* }))
+            }
+            Fields::Unnamed(fields) => {
+                let fields = (0..fields.unnamed.len()).map(|i| quote!(v.convert_element(#i)?));
+                quote!(Ok(Self(#(#fields),*)))
+            }
+            Fields::Unit => quote!(Ok(Self)),
+        },
+        Data::Enum(data) => {
+            let arms = data
+                .variants
+                .iter()
+                .map(|variant| {
+                    if !matches!(variant.fields, Fields::Unit) {
+                        return Err(Error::new_spanned(
+                            variant,
+                            "FromGdbValue can only be derived for enums without fields",
+                        ));
+                    }
+                    let ident = &variant.ident;
+                    let name = rename(&variant.attrs)?.unwrap_or_else(|| ident.to_string());
+                    Ok(quote!(#name => Ok(Self::#ident)))
+                })
+                .collect::<syn::Result<Vec<_>>>()?;
+            quote! {
+                match v.enumerator()? {
+                    #(#arms,)*
+                    name => Err(::value_parser::convert::FromValueError::new(
+                        format!("unknown enumerator {name}"),
+                    )),
+                }
+            }
+        }
+        Data::Union(_) => {
+            return Err(Error::new_spanned(
+                input,
+                "FromGdbValue can't be derived for unions",
+            ))
+        }
+    };
+    Ok(quote! {
+        impl #impl_generics ::value_parser::convert::FromGdbValue for #ident #ty_generics #where_clause {
+            fn from_gdb_value(
+                v: &::value_parser::Value,
+            ) -> Result<Self, ::value_parser::convert::FromValueError> {
+                #body
+            }
+        }
+    })
+}
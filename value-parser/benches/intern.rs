@@ -0,0 +1,55 @@
+//! Allocations and time of owning a large array of structs, with and without
+//! interning names. Run with `cargo bench --bench intern`.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    hint::black_box,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Instant,
+};
+
+use value_parser::{intern::Interner, Parser, Value};
+
+struct Counting;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+fn measure<'v>(name: &str, f: impl FnOnce() -> Value<'v>) {
+    let (bytes, count) = (
+        ALLOCATED.load(Ordering::Relaxed),
+        ALLOCATIONS.load(Ordering::Relaxed),
+    );
+    let start = Instant::now();
+    black_box(f());
+    let elapsed = start.elapsed();
+    println!(
+        "{name:>9}: {:>8} allocations, {:>9} bytes, {elapsed:?}",
+        ALLOCATIONS.load(Ordering::Relaxed) - count,
+        ALLOCATED.load(Ordering::Relaxed) - bytes,
+    );
+}
+
+fn main() {
+    let element = "{position = {x = 1.5, y = -2}, velocity = {x = 0, y = 0}, state = ALIVE}";
+    let text = format!("{{{}}}", vec![element; 100_000].join(", "));
+    let v = Parser::new(&text).parse_value_or_panic();
+    measure("owned", || v.clone().into_owned());
+    let interner = Interner::new();
+    measure("interned", || v.clone().into_owned_interned(&interner));
+}
@@ -0,0 +1,136 @@
+//! Parsing the `name = value` lines printed by `info locals` and `info args`.
+//!
+//! Each binding starts at the beginning of a line, and its value may go on
+//! over the following lines when `set print pretty` is on:
+//!
+//! ```text
+//! p = {
+//!   x = 1,
+//!   y = 2
+//! }
+//! n = 5
+//! ```
+//!
+//! Lines that aren't bindings, like `No locals.`, are skipped.
+
+use crate::{Parser, ParserConfig, Value};
+
+/// The bindings in `src`, in order. Shadowed variables appear once for each
+/// block they are in, innermost first, as GDB prints them. A value that
+/// doesn't parse becomes a [`Value::Raw`] of its text, up to the next line
+/// that starts a binding.
+pub fn parse_bindings(src: &str) -> Vec<(String, Value<'_>)> {
+    parse_bindings_with_config(src, ParserConfig::default())
+}
+
+/// [`parse_bindings`], parsing the values with `config`.
+pub fn parse_bindings_with_config(src: &str, config: ParserConfig) -> Vec<(String, Value<'_>)> {
+    let mut p = Parser::with_config(src, config.clone());
+    let mut bindings = Vec::new();
+    loop {
+        p.eat_ws();
+        if p.at_eof() {
+            return bindings;
+        }
+        if !p.at_field() {
+            skip_line(&mut p);
+            continue;
+        }
+        p.eat("static ");
+        p.eat_ws();
+        let name = p.parse_field_name().to_owned();
+        p.eat_ws();
+        p.eat("=");
+        let start = p.pos;
+        let value = match p.parse_value() {
+            Ok(v) if at_line_end(&mut p) => v,
+            _ => {
+                let end = next_binding(src, start, &config);
+                p.goto(end);
+                Parser::with_config(&src[start..end], config.clone())
+                    .parse_value_lossy()
+                    .0
+            }
+        };
+        bindings.push((name, value));
+    }
+}
+
+fn skip_line(p: &mut Parser) {
+    while !p.at_eof() && p.eat_current() != '\n' {}
+}
+
+/// Whether only blanks are left on the current line.
+fn at_line_end(p: &mut Parser) -> bool {
+    while matches!(p.current(), ' ' | '\t' | '\r') {
+        p.advance();
+    }
+    p.at_eof() || p.at("\n")
+}
+
+/// The start of the first line after `from` that starts a binding, or the
+/// end of `src`.
+fn next_binding(src: &str, from: usize, config: &ParserConfig) -> usize {
+    let mut p = Parser::with_config(src, config.clone());
+    src[from..]
+        .match_indices('\n')
+        .map(|(i, _)| from + i + 1)
+        .find(|&line| {
+            p.goto(line);
+            !p.current().is_whitespace() && p.at_field()
+        })
+        .unwrap_or(src.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::value;
+
+    #[test]
+    fn pretty_values() {
+        let bindings =
+            parse_bindings("p = {\n  x = 1,\n  y = {\n    2\n  }\n}\ns = \"a\"\nn = 5\n");
+        assert_eq!(
+            bindings,
+            [
+                ("p".to_owned(), value!({"x" = 1, "y" = [2]})),
+                ("s".to_owned(), value!("a")),
+                ("n".to_owned(), value!(5)),
+            ]
+        );
+    }
+
+    #[test]
+    fn shadowed_and_empty() {
+        let bindings = parse_bindings("i = 2\ni = 1\n");
+        assert_eq!(bindings.len(), 2);
+        assert_eq!(bindings[1], ("i".to_owned(), value!(1)));
+        assert!(parse_bindings("No locals.\n").is_empty());
+    }
+
+    #[test]
+    fn unparsable_values() {
+        let bindings = parse_bindings("a = {x = 1,\n  y = ???}\nb = 2 3\nc = 4");
+        assert_eq!(
+            bindings,
+            [
+                (
+                    "a".to_owned(),
+                    Value::Struct(vec![
+                        ("x".into(), value!(1)),
+                        ("y".into(), Value::Raw("???".into())),
+                    ])
+                ),
+                ("b".to_owned(), Value::Raw("2 3".into())),
+                ("c".to_owned(), value!(4)),
+            ]
+        );
+    }
+
+    #[test]
+    fn backslash_at_end() {
+        let bindings = parse_bindings("e='\\");
+        assert_eq!(bindings, [("e".to_owned(), Value::Raw("'\\".into()))]);
+    }
+}
@@ -0,0 +1,183 @@
+//! Decoding status registers and other packed words into named fields.
+//!
+//! Fields are described as `name: bit` or `name: lo..hi` (inclusive),
+//! separated by `;`, optionally followed by names for specific values:
+//!
+//! ```text
+//! CF: 0; ZF: 6; IOPL: 12..13;
+//! MODE: 4..5 { 0 = user, 3 = kernel };
+//! ```
+//!
+//! Single bit fields decode to bools, wider fields to numbers, and fields
+//! with named values to the matching name (or the number if none matches).
+
+use crate::{ParseError, Parser, Value};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BitField {
+    pub name: String,
+    pub lo: u32,
+    pub hi: u32,
+    pub values: Vec<(u64, String)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Register {
+    pub fields: Vec<BitField>,
+}
+
+fn parse_int(p: &mut Parser) -> Result<u64, ParseError> {
+    let start = p.pos;
+    let text = p.parse_ident();
+    let n = match text.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => text.parse(),
+    };
+    n.map_err(|_| {
+        p.goto(start);
+        p.error("expected a number", &[])
+    })
+}
+
+fn parse_bit(p: &mut Parser) -> Result<u32, ParseError> {
+    let start = p.pos;
+    let bit = parse_int(p)?;
+    if bit >= 64 {
+        p.goto(start);
+        return Err(p.error("invalid bit range", &[]));
+    }
+    Ok(bit as u32)
+}
+
+impl BitField {
+    pub fn extract(&self, word: u64) -> u64 {
+        let width = self.hi - self.lo + 1;
+        let mask = if width >= 64 {
+            u64::MAX
+        } else {
+            (1 << width) - 1
+        };
+        (word >> self.lo) & mask
+    }
+
+    pub fn decode(&self, word: u64) -> Value<'_> {
+        let bits = self.extract(word);
+        if let Some((_, name)) = self.values.iter().find(|(v, _)| *v == bits) {
+            Value::String(name.as_str().into())
+        } else if self.lo == self.hi && self.values.is_empty() {
+            Value::Bool(bits != 0)
+        } else {
+            Value::Uint(bits as u128)
+        }
+    }
+}
+
+impl Register {
+    pub fn parse(src: &str) -> Result<Self, ParseError> {
+        let mut p = Parser::new(src);
+        let mut fields = Vec::new();
+        loop {
+            p.eat_ws();
+            if p.at_eof() {
+                break;
+            }
+            let name = p.parse_ident().to_owned();
+            if name.is_empty() {
+                return Err(p.error("expected a field name", &[]));
+            }
+            p.eat_ws();
+            p.expect(":", "expected a : after field name")?;
+            p.eat_ws();
+            let start = p.pos;
+            let lo = parse_bit(&mut p)?;
+            let hi = if p.eat("..") { parse_bit(&mut p)? } else { lo };
+            if lo > hi {
+                p.goto(start);
+                return Err(p.error("invalid bit range", &[]));
+            }
+            p.eat_ws();
+            let mut values = Vec::new();
+            if p.eat("{") {
+                loop {
+                    p.eat_ws();
+                    if p.eat("}") {
+                        break;
+                    }
+                    let v = parse_int(&mut p)?;
+                    p.eat_ws();
+                    p.expect("=", "expected a = after value")?;
+                    p.eat_ws();
+                    values.push((v, p.parse_ident().to_owned()));
+                    p.eat_ws();
+                    p.eat(",");
+                }
+                p.eat_ws();
+            }
+            if !p.at_eof() {
+                p.expect(";", "expected a ; after field")?;
+            }
+            fields.push(BitField {
+                name,
+                lo,
+                hi,
+                values,
+            });
+        }
+        Ok(Self { fields })
+    }
+
+    /// Decodes `word` into a map from field name to field value.
+    pub fn decode(&self, word: u64) -> Value<'_> {
+        Value::Struct(
+            self.fields
+                .iter()
+                .map(|f| (f.name.as_str().into(), f.decode(word)))
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::value;
+
+    #[test]
+    fn flags() {
+        let reg = Register::parse("CF: 0; ZF: 6").unwrap();
+        assert_eq!(reg.decode(0x246), value!({"CF" = false, "ZF" = true}));
+    }
+
+    #[test]
+    fn ranges() {
+        let reg = Register::parse("IOPL: 12..13;\n low: 0..7").unwrap();
+        assert_eq!(reg.decode(0x3046), value!({"IOPL" = 3, "low" = 70}));
+    }
+
+    #[test]
+    fn named_values() {
+        let reg = Register::parse("MODE: 4..5 { 0 = user, 3 = kernel }; EN: 0 { 1 = on }").unwrap();
+        assert_eq!(reg.decode(0x31), value!({"MODE" = "kernel", "EN" = "on"}));
+        assert_eq!(reg.decode(0x10), value!({"MODE" = 1, "EN" = 0}));
+    }
+
+    #[test]
+    fn full_width() {
+        let reg = Register::parse("all: 0..63; top: 0x3f").unwrap();
+        assert_eq!(reg.fields[0].extract(u64::MAX), u64::MAX);
+        assert_eq!(reg.fields[1].extract(1 << 63), 1);
+    }
+
+    #[test]
+    fn errors() {
+        let err = |src| Register::parse(src).unwrap_err();
+        let e = err("x: 5..2");
+        assert_eq!((e.message, e.offset), ("invalid bit range", 3));
+        assert_eq!(err("x: 64").message, "invalid bit range");
+        assert_eq!(err("x: 4294967296").message, "invalid bit range");
+        assert_eq!(err("x: y").message, "expected a number");
+        assert_eq!(err("x 1").message, "expected a : after field name");
+        assert_eq!(err("x: 1 { 0 off }").message, "expected a = after value");
+        assert_eq!(err("x: 1 y: 2").message, "expected a ; after field");
+    }
+}
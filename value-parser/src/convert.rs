@@ -0,0 +1,401 @@
+//! Converting [`Value`]s into Rust types, like the struct a `print *config`
+//! shows.
+//!
+//! [`FromGdbValue`] is implemented for numbers, `bool`, `char`, `String`,
+//! `Option`, `Box`, `Vec` and tuples, and with the `derive` feature can be derived
+//! for structs and fieldless enums:
+//!
+//! - a struct with named fields is read from a struct (including the fields
+//!   of its base classes) or a map with string keys, by field name or the
+//!   name given with `#[gdb(rename = "...")]`; fields of GDB's value that
+//!   the Rust struct doesn't have are ignored
+//! - a tuple struct is read from a list, by position
+//! - an enum is read from an enumerator, by variant name or rename, with or
+//!   without the enumerator's `::` qualification
+//!
+//! The derived impls of generic types require every type parameter to
+//! implement [`FromGdbValue`] too.
+//!
+//! Conversions look through truncation, `static` flags, references, type
+//! casts, smart pointers and pretty-printer headers. Numbers convert between
+//! each other when the value fits, and pointers convert to integers as
+//! their address.
+
+use std::{borrow::Cow, fmt};
+
+use crate::Value;
+#[cfg(feature = "derive")]
+pub use value_parser_derive::FromGdbValue;
+
+/// Why a value couldn't be converted, and where.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FromValueError {
+    /// The path to the value that didn't convert, in the syntax of
+    /// [`Value::get_path`].
+    pub path: String,
+    pub message: Cow<'static, str>,
+}
+
+impl FromValueError {
+    pub fn new(message: impl Into<Cow<'static, str>>) -> Self {
+        Self {
+            path: String::new(),
+            message: message.into(),
+        }
+    }
+
+    /// This error for a value at `step` (a `.field` or `[index]`) inside the
+    /// one it was for.
+    pub fn inside(mut self, step: &str) -> Self {
+        self.path.insert_str(0, step);
+        self
+    }
+}
+
+impl fmt::Display for FromValueError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.path.strip_prefix('.') {
+            Some(path) => write!(f, "{path}: {}", self.message),
+            None if self.path.is_empty() => write!(f, "{}", self.message),
+            None => write!(f, "{}: {}", self.path, self.message),
+        }
+    }
+}
+
+impl std::error::Error for FromValueError {}
+
+pub trait FromGdbValue: Sized {
+    fn from_gdb_value(v: &Value) -> Result<Self, FromValueError>;
+
+    /// The value of a struct field GDB didn't print at all, if that's fine.
+    fn missing() -> Option<Self> {
+        None
+    }
+}
+
+impl Value<'_> {
+    /// Converts this value with [`FromGdbValue`].
+    pub fn to<T: FromGdbValue>(&self) -> Result<T, FromValueError> {
+        T::from_gdb_value(self)
+    }
+
+    /// Converts the field `name` of a struct, for derived [`FromGdbValue`]
+    /// impls.
+    pub fn convert_field<T: FromGdbValue>(&self, name: &str) -> Result<T, FromValueError> {
+        match self.field(name) {
+            Some(v) => T::from_gdb_value(v).map_err(|e| e.inside(&format!(".{name}"))),
+            None => T::missing()
+                .ok_or_else(|| FromValueError::new("missing field").inside(&format!(".{name}"))),
+        }
+    }
+
+    /// Converts element `i` of a list, for derived [`FromGdbValue`] impls.
+    pub fn convert_element<T: FromGdbValue>(&self, i: usize) -> Result<T, FromValueError> {
+        let v = self
+            .element(i)
+            .ok_or_else(|| FromValueError::new("missing element").inside(&format!("[{i}]")))?;
+        T::from_gdb_value(v).map_err(|e| e.inside(&format!("[{i}]")))
+    }
+
+    /// The enumerator this value is, without its `::` qualification, for
+    /// derived [`FromGdbValue`] impls.
+    pub fn enumerator(&self) -> Result<&str, FromValueError> {
+        match self.unwrapped() {
+            Value::Enum(name) => Ok(name.rsplit("::").next().unwrap_or(name)),
+            v => Err(mismatch("an enumerator", v)),
+        }
+    }
+}
+
+fn mismatch(expected: &str, v: &Value) -> FromValueError {
+    FromValueError::new(format!("expected {expected}, found {}", v.to_gdb_string()))
+}
+
+/// The value of an integer, or of the address of a pointer.
+fn integer(v: &Value) -> Option<i128> {
+    match v.unwrapped() {
+        Value::Int(n) => Some(*n),
+        Value::Uint(n) => i128::try_from(*n).ok(),
+        Value::Char { code, .. } => Some(i128::from(*code)),
+        Value::Pointer { address, .. } => Some(i128::from(*address)),
+        Value::Encoded { value, .. } => integer(value),
+        _ => None,
+    }
+}
+
+macro_rules! integers {
+    ($($t:ty)*) => {$(
+        impl FromGdbValue for $t {
+            fn from_gdb_value(v: &Value) -> Result<Self, FromValueError> {
+                let n = integer(v).ok_or_else(|| mismatch("an integer", v))?;
+                n.try_into().map_err(|_| {
+                    FromValueError::new(format!("{n} doesn't fit in {}", stringify!($t)))
+                })
+            }
+        }
+    )*};
+}
+
+integers!(i8 i16 i32 i64 i128 isize u8 u16 u32 u64 usize);
+
+impl FromGdbValue for u128 {
+    fn from_gdb_value(v: &Value) -> Result<Self, FromValueError> {
+        match v.unwrapped() {
+            Value::Uint(n) => Ok(*n),
+            _ => {
+                let n = integer(v).ok_or_else(|| mismatch("an integer", v))?;
+                n.try_into()
+                    .map_err(|_| FromValueError::new(format!("{n} doesn't fit in u128")))
+            }
+        }
+    }
+}
+
+impl FromGdbValue for f64 {
+    fn from_gdb_value(v: &Value) -> Result<Self, FromValueError> {
+        v.unwrapped()
+            .as_f64()
+            .ok_or_else(|| mismatch("a number", v))
+    }
+}
+
+impl FromGdbValue for f32 {
+    fn from_gdb_value(v: &Value) -> Result<Self, FromValueError> {
+        f64::from_gdb_value(v).map(|n| n as f32)
+    }
+}
+
+impl FromGdbValue for bool {
+    fn from_gdb_value(v: &Value) -> Result<Self, FromValueError> {
+        match v.unwrapped() {
+            Value::Bool(b) => Ok(*b),
+            _ => integer(v)
+                .map(|n| n != 0)
+                .ok_or_else(|| mismatch("a bool", v)),
+        }
+    }
+}
+
+impl FromGdbValue for char {
+    fn from_gdb_value(v: &Value) -> Result<Self, FromValueError> {
+        match v.unwrapped() {
+            Value::Char { ch, .. } => Ok(*ch),
+            Value::Encoded { value, .. } => Self::from_gdb_value(value),
+            _ => Err(mismatch("a character", v)),
+        }
+    }
+}
+
+impl FromGdbValue for String {
+    fn from_gdb_value(v: &Value) -> Result<Self, FromValueError> {
+        match v.unwrapped() {
+            Value::Pointer {
+                pointee: Some(pointee),
+                ..
+            } => Self::from_gdb_value(pointee),
+            Value::Enum(name) => Ok(name.to_string()),
+            v => match v.expanded() {
+                Value::String(s) => Ok(s.into_owned()),
+                Value::Encoded { value, .. } => Self::from_gdb_value(&value),
+                _ => Err(mismatch("a string", v)),
+            },
+        }
+    }
+}
+
+/// `None` for `<optimized out>` and a missing field.
+impl<T: FromGdbValue> FromGdbValue for Option<T> {
+    fn from_gdb_value(v: &Value) -> Result<Self, FromValueError> {
+        match v.unwrapped() {
+            Value::OptimizedOut => Ok(None),
+            _ => T::from_gdb_value(v).map(Some),
+        }
+    }
+
+    fn missing() -> Option<Self> {
+        Some(None)
+    }
+}
+
+impl<T: FromGdbValue> FromGdbValue for Box<T> {
+    fn from_gdb_value(v: &Value) -> Result<Self, FromValueError> {
+        T::from_gdb_value(v).map(Box::new)
+    }
+
+    fn missing() -> Option<Self> {
+        T::missing().map(Box::new)
+    }
+}
+
+impl<T: FromGdbValue> FromGdbValue for Vec<T> {
+    fn from_gdb_value(v: &Value) -> Result<Self, FromValueError> {
+        let Value::List(items) = v.unwrapped().expanded() else {
+            return Err(mismatch("a list", v));
+        };
+        items
+            .iter()
+            .enumerate()
+            .map(|(i, item)| T::from_gdb_value(item).map_err(|e| e.inside(&format!("[{i}]"))))
+            .collect()
+    }
+}
+
+macro_rules! tuples {
+    ($(($($t:ident $i:tt),*))*) => {$(
+        impl<$($t: FromGdbValue),*> FromGdbValue for ($($t,)*) {
+            fn from_gdb_value(v: &Value) -> Result<Self, FromValueError> {
+                Ok(($(v.convert_element::<$t>($i)?,)*))
+            }
+        }
+    )*};
+}
+
+tuples! {
+    (A 0)
+    (A 0, B 1)
+    (A 0, B 1, C 2)
+    (A 0, B 1, C 2, D 3)
+}
+
+impl FromGdbValue for Value<'static> {
+    fn from_gdb_value(v: &Value) -> Result<Self, FromValueError> {
+        Ok(v.clone().into_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Parser;
+
+    fn parse(text: &str) -> Value<'_> {
+        Parser::new(text).parse_value_or_panic()
+    }
+
+    #[test]
+    fn scalars() {
+        assert_eq!(parse("-5").to::<i8>(), Ok(-5));
+        assert_eq!(parse("97 'a'").to::<u8>(), Ok(97));
+        assert_eq!(parse("97 'a'").to::<char>(), Ok('a'));
+        assert_eq!(parse("(Foo *) 0x10 <foo>").to::<u64>(), Ok(16));
+        assert_eq!(parse("3").to::<f32>(), Ok(3.0));
+        assert_eq!(parse("1").to::<bool>(), Ok(true));
+        assert_eq!(parse("@0x10: true").to::<bool>(), Ok(true));
+        assert_eq!(
+            parse("300").to::<u8>().unwrap_err().message,
+            "300 doesn't fit in u8"
+        );
+        assert_eq!(
+            parse("{1}").to::<u8>().unwrap_err().message,
+            "expected an integer, found {1}"
+        );
+    }
+
+    #[test]
+    fn strings() {
+        assert_eq!(
+            parse(r#""ab", 'c' <repeats 3 times>"#).to::<String>(),
+            Ok("abccc".into())
+        );
+        assert_eq!(parse(r#"0x402004 "lit""#).to::<String>(), Ok("lit".into()));
+        assert_eq!(parse(r#"L"wide""#).to::<String>(), Ok("wide".into()));
+        assert_eq!(parse("ns::RED").to::<String>(), Ok("ns::RED".into()));
+    }
+
+    #[test]
+    fn containers() {
+        assert_eq!(
+            parse("std::vector of length 3, capacity 3 = {1, 2 <repeats 2 times>}")
+                .to::<Vec<u32>>(),
+            Ok(vec![1, 2, 2])
+        );
+        assert_eq!(parse("{1, -2}").to::<(u8, i8)>(), Ok((1, -2)));
+        assert_eq!(parse("<optimized out>").to::<Option<u8>>(), Ok(None));
+        assert_eq!(parse("7").to::<Option<u8>>(), Ok(Some(7)));
+        let err = parse("{{1}, {2, x}}").to::<Vec<Vec<u8>>>().unwrap_err();
+        assert_eq!(err.path, "[1][1]");
+        assert_eq!(err.to_string(), "[1][1]: expected an integer, found x");
+    }
+
+    #[cfg(feature = "derive")]
+    #[test]
+    fn derived() {
+        #[derive(Debug, PartialEq, FromGdbValue)]
+        enum Level {
+            Debug,
+            #[gdb(rename = "LEVEL_WARN")]
+            Warn,
+        }
+
+        #[derive(Debug, PartialEq, FromGdbValue)]
+        struct Point(i32, i32);
+
+        #[derive(Debug, PartialEq, FromGdbValue)]
+        struct Config {
+            name: String,
+            port: u16,
+            #[gdb(rename = "_M_level")]
+            level: Level,
+            origin: Point,
+            tags: Vec<String>,
+            parent: Option<Box<Config>>,
+        }
+
+        #[derive(Debug, PartialEq, FromGdbValue)]
+        struct Pair<K: Copy, V>
+        where
+            V: fmt::Debug,
+        {
+            key: K,
+            value: V,
+        }
+
+        let v = parse(
+            r#"{<Base> = {name = 0x402004 "srv"}, port = 8080, _M_level = log::LEVEL_WARN, origin = {-1, 2}, tags = std::vector of length 1, capacity 1 = {"a"}, extra = 1}"#,
+        );
+        assert_eq!(
+            v.to::<Config>(),
+            Ok(Config {
+                name: "srv".into(),
+                port: 8080,
+                level: Level::Warn,
+                origin: Point(-1, 2),
+                tags: vec!["a".into()],
+                parent: None,
+            })
+        );
+        assert_eq!(parse("Debug").to::<Level>(), Ok(Level::Debug));
+        assert_eq!(
+            parse("{key = 1, value = {2, 3}}").to::<Pair<u8, Point>>(),
+            Ok(Pair {
+                key: 1,
+                value: Point(2, 3)
+            })
+        );
+        assert_eq!(
+            parse("{name = \"\", port = 1, _M_level = Info, origin = {0, 0}, tags = {}}")
+                .to::<Config>()
+                .unwrap_err()
+                .to_string(),
+            "_M_level: unknown enumerator Info"
+        );
+    }
+
+    #[test]
+    fn fields() {
+        let v = parse("{<Base> = {id = 4}, name = \"n\", inner = {x = 300}}");
+        assert_eq!(v.convert_field::<u8>("id"), Ok(4));
+        assert_eq!(v.convert_field::<Option<u8>>("nope"), Ok(None));
+        assert_eq!(
+            v.convert_field::<u8>("nope").unwrap_err().to_string(),
+            "nope: missing field"
+        );
+        let err = v
+            .field("inner")
+            .unwrap()
+            .convert_field::<u8>("x")
+            .map_err(|e| e.inside(".inner"))
+            .unwrap_err();
+        assert_eq!(err.to_string(), "inner.x: 300 doesn't fit in u8");
+    }
+}
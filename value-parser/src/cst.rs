@@ -0,0 +1,200 @@
+//! Parsing with the location of every value, for tools that highlight or
+//! edit parts of a printed value.
+//!
+//! [`Parser::parse_cst`] returns the [`Value`] together with a tree of
+//! [`Node`]s holding byte ranges into the input. There is a node for every
+//! value written in the input and its children are the values written
+//! inside it, in order: list elements, the values of struct fields and map
+//! entries, the value after a pretty-printer header, type cast or reference
+//! address. Names of fields and `[key]`s of map entries are ranges on the
+//! node of their value rather than nodes of their own.
+
+use std::ops::Range;
+
+use crate::{ParseError, Parser, Value};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Node {
+    /// The value's text, including `<repeats N times>` and the segments of a
+    /// character array printed as several.
+    pub range: Range<usize>,
+    /// The name of the field, `<Base>` of the base class or `[key]` of the
+    /// map entry this is the value of.
+    pub key: Option<Range<usize>>,
+    pub children: Vec<Node>,
+}
+
+impl Node {
+    /// The innermost node whose value or key contains `offset`.
+    pub fn node_at(&self, offset: usize) -> Option<&Node> {
+        let contains = |r: &Range<usize>| r.contains(&offset);
+        if !contains(&self.range) && !self.key.as_ref().is_some_and(contains) {
+            return None;
+        }
+        Some(
+            self.children
+                .iter()
+                .find_map(|child| child.node_at(offset))
+                .unwrap_or(self),
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cst<'a> {
+    pub value: Value<'a>,
+    pub root: Node,
+}
+
+impl<'a> Parser<'a> {
+    /// [`Parser::parse_value`], also recording where each value is.
+    pub fn parse_cst(&mut self) -> Result<Cst<'a>, ParseError> {
+        self.spans = Some(vec![Vec::new()]);
+        let value = self.parse_value();
+        let root = self.spans.take().and_then(|mut spans| spans.pop()?.pop());
+        Ok(Cst {
+            value: value?,
+            root: root.expect("a parsed value has a node"),
+        })
+    }
+
+    /// Starts the node of a value.
+    pub(crate) fn span_open(&mut self) {
+        if let Some(spans) = &mut self.spans {
+            spans.push(Vec::new());
+        }
+    }
+
+    /// Finishes the node of a value that started at `start`, adding it to
+    /// its parent's children if it parsed.
+    pub(crate) fn span_close(&mut self, start: usize, ok: bool) {
+        let end = self.pos;
+        if let Some(spans) = &mut self.spans {
+            let children = spans.pop().expect("span_close without span_open");
+            if ok {
+                spans.last_mut().unwrap().push(Node {
+                    range: start..end,
+                    key: None,
+                    children,
+                });
+            }
+        }
+    }
+
+    /// Forgets the nodes of values parsed inside the current one, when the
+    /// parser backtracks over them.
+    pub(crate) fn span_discard_children(&mut self) {
+        if let Some(frame) = self.spans.as_mut().and_then(|spans| spans.last_mut()) {
+            frame.clear();
+        }
+    }
+
+    /// Extends the last finished node to the cursor, over what followed the
+    /// value itself.
+    pub(crate) fn span_extend(&mut self) {
+        let end = self.pos;
+        if let Some(node) = self.last_node() {
+            node.range.end = end;
+        }
+    }
+
+    /// Sets the key of the last finished node. With `replaces_node`, the
+    /// node before it is the key's own, which the key range replaces.
+    pub(crate) fn span_key(&mut self, key: Range<usize>, replaces_node: bool) {
+        if let Some(frame) = self.spans.as_mut().and_then(|spans| spans.last_mut()) {
+            if replaces_node && frame.len() >= 2 {
+                frame.remove(frame.len() - 2);
+            }
+        }
+        if let Some(node) = self.last_node() {
+            node.key = Some(key);
+        }
+    }
+
+    fn last_node(&mut self) -> Option<&mut Node> {
+        self.spans.as_mut()?.last_mut()?.last_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text<'t>(src: &'t str, r: &Range<usize>) -> &'t str {
+        &src[r.clone()]
+    }
+
+    #[test]
+    fn ranges() {
+        let src = r#" {x = 1, [2] = {3, 'a' <repeats 4 times>}, <B> = {s = "ab", 'c' <repeats 9 times>}}"#;
+        let cst = Parser::new(src).parse_cst().unwrap();
+        assert_eq!(cst.value, Parser::new(src).parse_value_or_panic());
+        let root = &cst.root;
+        assert_eq!(text(src, &root.range), src.trim());
+        assert_eq!(root.key, None);
+        let keys: Vec<_> = root
+            .children
+            .iter()
+            .map(|n| (text(src, n.key.as_ref().unwrap()), text(src, &n.range)))
+            .collect();
+        assert_eq!(
+            keys,
+            [
+                ("x", "1"),
+                ("[2]", "{3, 'a' <repeats 4 times>}"),
+                ("<B>", r#"{s = "ab", 'c' <repeats 9 times>}"#),
+            ]
+        );
+        let list: Vec<_> = root.children[1]
+            .children
+            .iter()
+            .map(|n| text(src, &n.range))
+            .collect();
+        assert_eq!(list, ["3", "'a' <repeats 4 times>"]);
+        assert_eq!(
+            text(src, &root.children[2].children[0].range),
+            r#""ab", 'c' <repeats 9 times>"#
+        );
+    }
+
+    #[test]
+    fn wrapped_values() {
+        let src = "{p = (int &) @0x10: 5, v = std::vector of length 1 = {7}}";
+        let root = Parser::new(src).parse_cst().unwrap().root;
+        let cast = &root.children[0];
+        assert_eq!(text(src, &cast.range), "(int &) @0x10: 5");
+        assert_eq!(text(src, &cast.children[0].children[0].range), "5");
+        let header = &root.children[1];
+        assert_eq!(text(src, &header.children[0].range), "{7}");
+        assert_eq!(text(src, &header.children[0].children[0].range), "7");
+    }
+
+    #[test]
+    fn node_at() {
+        let src = "{a = {1, 22}, b = 3}";
+        let root = Parser::new(src).parse_cst().unwrap().root;
+        let at = |offset| root.node_at(offset).map(|n| text(src, &n.range));
+        assert_eq!(at(10), Some("22"));
+        assert_eq!(at(1), Some("{1, 22}"));
+        assert_eq!(at(12), Some(src));
+        assert_eq!(at(18), Some("3"));
+        assert_eq!(at(40), None);
+    }
+
+    #[test]
+    fn errors_and_recovery() {
+        assert!(Parser::new("{1, ?}").parse_cst().is_err());
+        let src = "{s = std::unique_ptr<A> = {x = 1}, e = ?}";
+        let mut p = Parser::new(src);
+        p.recover = true;
+        let cst = p.parse_cst().unwrap();
+        let nodes: Vec<_> = cst
+            .root
+            .children
+            .iter()
+            .map(|n| text(src, &n.range))
+            .collect();
+        assert_eq!(nodes, ["std::unique_ptr<A> = {x = 1}", "?"]);
+        assert_eq!(cst.root.children[0].children.len(), 1);
+    }
+}
@@ -0,0 +1,278 @@
+//! Structural differences between two values, like two snapshots of the
+//! same variable.
+//!
+//! Struct fields are matched by name, map entries by key and list elements
+//! by index (after expanding `<repeats N times>`), so a change deep inside a
+//! value is reported at its path rather than as a change of the whole value.
+//! Paths use the syntax of [`Value::get_path`].
+
+use std::fmt;
+
+use crate::Value;
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(tag = "kind", rename_all = "snake_case")
+)]
+pub enum Change<'a> {
+    Added {
+        path: String,
+        value: Value<'a>,
+    },
+    Removed {
+        path: String,
+        value: Value<'a>,
+    },
+    Changed {
+        path: String,
+        old: Value<'a>,
+        new: Value<'a>,
+    },
+}
+
+impl Change<'_> {
+    pub fn path(&self) -> &str {
+        match self {
+            Self::Added { path, .. } | Self::Removed { path, .. } | Self::Changed { path, .. } => {
+                path
+            }
+        }
+    }
+}
+
+/// The changes between two values, in the order of the fields and elements
+/// of the new one, with removals at the position they had in the old one.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize), serde(transparent))]
+pub struct ValueDiff<'a> {
+    pub changes: Vec<Change<'a>>,
+}
+
+impl ValueDiff<'_> {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+/// One line per change: `+ path = value`, `- path = value` or
+/// `~ path = old -> new`, with the values in GDB's syntax.
+impl fmt::Display for ValueDiff<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for change in &self.changes {
+            let path = match change.path() {
+                "" => "<value>",
+                path => path,
+            };
+            match change {
+                Change::Added { value, .. } => writeln!(f, "+ {path} = {}", value.to_gdb_string())?,
+                Change::Removed { value, .. } => {
+                    writeln!(f, "- {path} = {}", value.to_gdb_string())?
+                }
+                Change::Changed { old, new, .. } => writeln!(
+                    f,
+                    "~ {path} = {} -> {}",
+                    old.to_gdb_string(),
+                    new.to_gdb_string()
+                )?,
+            }
+        }
+        Ok(())
+    }
+}
+
+pub fn diff<'a>(old: &Value<'a>, new: &Value<'a>) -> ValueDiff<'a> {
+    let mut changes = Vec::new();
+    diff_at("", old, new, &mut changes);
+    ValueDiff { changes }
+}
+
+pub(crate) fn field_path(path: &str, name: &str) -> String {
+    if path.is_empty() {
+        name.to_owned()
+    } else {
+        format!("{path}.{name}")
+    }
+}
+
+pub(crate) fn index_path(path: &str, key: &Value) -> String {
+    format!("{path}[{}]", key.to_gdb_string())
+}
+
+fn diff_at<'a>(path: &str, old: &Value<'a>, new: &Value<'a>, out: &mut Vec<Change<'a>>) {
+    match (old, new) {
+        (Value::Struct(old), Value::Struct(new)) => {
+            for (name, v) in new {
+                let path = field_path(path, name);
+                match old.iter().find(|(k, _)| k == name) {
+                    Some((_, old)) => diff_at(&path, old, v, out),
+                    None => out.push(Change::Added {
+                        path,
+                        value: v.clone(),
+                    }),
+                }
+            }
+            for (name, v) in old {
+                if !new.iter().any(|(k, _)| k == name) {
+                    out.push(Change::Removed {
+                        path: field_path(path, name),
+                        value: v.clone(),
+                    });
+                }
+            }
+        }
+        (Value::Map(old), Value::Map(new)) => {
+            for (key, v) in new {
+                let path = index_path(path, key);
+                match old.iter().find(|(k, _)| k == key) {
+                    Some((_, old)) => diff_at(&path, old, v, out),
+                    None => out.push(Change::Added {
+                        path,
+                        value: v.clone(),
+                    }),
+                }
+            }
+            for (key, v) in old {
+                if !new.iter().any(|(k, _)| k == key) {
+                    out.push(Change::Removed {
+                        path: index_path(path, key),
+                        value: v.clone(),
+                    });
+                }
+            }
+        }
+        (Value::List(_), Value::List(_)) if old != new => {
+            let (Value::List(old), Value::List(new)) = (old.expanded(), new.expanded()) else {
+                unreachable!("lists expand to lists");
+            };
+            for (i, v) in new.iter().enumerate() {
+                let path = format!("{path}[{i}]");
+                match old.get(i) {
+                    Some(old) => diff_at(&path, old, v, out),
+                    None => out.push(Change::Added {
+                        path,
+                        value: v.clone(),
+                    }),
+                }
+            }
+            for (i, v) in old.iter().enumerate().skip(new.len()) {
+                out.push(Change::Removed {
+                    path: format!("{path}[{i}]"),
+                    value: v.clone(),
+                });
+            }
+        }
+        (Value::Truncated(old), Value::Truncated(new))
+        | (Value::Static(old), Value::Static(new)) => diff_at(path, old, new, out),
+        (
+            Value::Encoded {
+                encoding: old_encoding,
+                value: old,
+            },
+            Value::Encoded {
+                encoding,
+                value: new,
+            },
+        ) if old_encoding == encoding => diff_at(path, old, new, out),
+        (
+            Value::Reference {
+                address: old_address,
+                value: old,
+            },
+            Value::Reference {
+                address,
+                value: new,
+            },
+        ) if old_address == address => diff_at(path, old, new, out),
+        (
+            Value::Typed {
+                ty: old_ty,
+                value: old,
+            },
+            Value::Typed { ty, value: new },
+        ) if old_ty == ty => diff_at(path, old, new, out),
+        (
+            Value::Described {
+                description: old_description,
+                value: Some(old),
+            },
+            Value::Described {
+                description,
+                value: Some(new),
+            },
+        ) if old_description == description => diff_at(path, old, new, out),
+        (old, new) if old != new => out.push(Change::Changed {
+            path: path.to_owned(),
+            old: old.clone(),
+            new: new.clone(),
+        }),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Parser;
+
+    fn diff_text(old: &str, new: &str) -> String {
+        let old = Parser::new(old).parse_value_or_panic();
+        let new = Parser::new(new).parse_value_or_panic();
+        diff(&old, &new).to_string()
+    }
+
+    #[test]
+    fn nested_changes() {
+        assert_eq!(
+            diff_text(
+                r#"{pos = {x = 1, y = 2}, name = "a", gone = 0}"#,
+                r#"{pos = {x = 1, y = 3}, name = "a", new = {1}}"#,
+            ),
+            "~ pos.y = 2 -> 3\n+ new = {1}\n- gone = 0\n"
+        );
+    }
+
+    #[test]
+    fn lists_and_maps() {
+        assert_eq!(
+            diff_text(
+                r#"{xs = {0 <repeats 3 times>, 1}, m = {["a"] = 1, ["b"] = 2}}"#,
+                r#"{xs = {0, 5, 0}, m = {["a"] = 1, ["c"] = 2}}"#,
+            ),
+            "~ xs[1] = 0 -> 5\n- xs[3] = 1\n+ m[\"c\"] = 2\n- m[\"b\"] = 2\n"
+        );
+    }
+
+    #[test]
+    fn type_change_and_equal() {
+        assert_eq!(diff_text("{a = 1}", "{a = {1}}"), "~ a = 1 -> {1}\n");
+        assert_eq!(diff_text("5", "6"), "~ <value> = 5 -> 6\n");
+        assert!(diff_text("{a = {1, 2}}", "{a = {1, 2}}").is_empty());
+    }
+
+    #[test]
+    fn paths_resolve() {
+        let old = Parser::new("{t = {{f = 1}, {f = 2}}}").parse_value_or_panic();
+        let new = Parser::new("{t = {{f = 1}, {f = 4}}}").parse_value_or_panic();
+        let d = diff(&old, &new);
+        assert_eq!(d.changes.len(), 1);
+        assert_eq!(
+            new.get_path(d.changes[0].path()).and_then(Value::as_uint),
+            Some(4)
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serialize() {
+        let old = Parser::new("{a = 1}").parse_value_or_panic();
+        let new = Parser::new("{a = 2}").parse_value_or_panic();
+        assert_eq!(
+            serde_json::to_value(diff(&old, &new)).unwrap(),
+            serde_json::json!([
+                {"kind": "changed", "path": "a", "old": {"uint": 1}, "new": {"uint": 2}},
+            ])
+        );
+    }
+}
@@ -0,0 +1,89 @@
+//! Sharing the storage of names across owned values.
+//!
+//! [`Value::into_owned`] copies every field name, so a million elements of
+//! an array of structs hold a million copies of each field name of the
+//! struct. [`Value::into_owned_interned`] copies each distinct name once
+//! into an [`Interner`] and makes every value that uses it borrow that copy.
+
+use std::{borrow::Cow, cell::RefCell, collections::HashSet};
+
+use crate::Value;
+
+/// The set of names interned so far.
+///
+/// Values made with the interner borrow their names from it, so it lives as
+/// long as they do, and the names are freed when it's dropped.
+#[derive(Debug, Default)]
+pub struct Interner {
+    names: RefCell<HashSet<Box<str>>>,
+}
+
+impl Interner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The interned copy of `name`, copying it if it wasn't interned yet.
+    pub fn intern(&self, name: &str) -> &str {
+        let mut names = self.names.borrow_mut();
+        let interned: *const str = match names.get(name) {
+            Some(interned) => &**interned,
+            None => {
+                let interned: Box<str> = name.into();
+                let ptr: *const str = &*interned;
+                names.insert(interned);
+                ptr
+            }
+        };
+        // SAFETY: names are only ever added, never removed or changed, until
+        // the interner is dropped, which the borrow of `self` prevents, and
+        // the text of a boxed name doesn't move when the set grows
+        unsafe { &*interned }
+    }
+
+    /// How many distinct names were interned.
+    pub fn len(&self) -> usize {
+        self.names.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.borrow().is_empty()
+    }
+}
+
+impl Value<'_> {
+    /// [`Value::into_owned`], with the names of fields, enumerators, symbols
+    /// and types taken from `interner` instead of copied.
+    pub fn into_owned_interned<'i>(self, interner: &'i Interner) -> Value<'i> {
+        self.into_owned_with(&mut |name| Cow::Borrowed(interner.intern(&name)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Parser;
+
+    #[test]
+    fn names_are_shared() {
+        let interner = Interner::new();
+        let text = "{{x = 1, e = RED, p = 0x10 <f>}, {x = 2, e = RED, p = 0x10 <f>}}";
+        let v = Parser::new(text).parse_value_or_panic();
+        let owned = v.clone().into_owned_interned(&interner);
+        assert_eq!(owned, v);
+        assert_eq!(interner.len(), 5);
+        let Value::List(items) = &owned else {
+            panic!("{owned:?}");
+        };
+        let (Value::Struct(a), Value::Struct(b)) = (&items[0], &items[1]) else {
+            panic!("{owned:?}");
+        };
+        assert!(matches!(a[0].0, Cow::Borrowed(_)));
+        assert_eq!(a[0].0.as_ptr(), b[0].0.as_ptr());
+        // a second value reuses the names of the first
+        Parser::new("{x = 3}")
+            .parse_value_or_panic()
+            .into_owned_interned(&interner);
+        assert_eq!(interner.len(), 5);
+    }
+}
@@ -0,0 +1,306 @@
+//! Converting [`Value`]s into plain JSON, as opposed to the `serde`
+//! feature's representation which keeps every detail of the value.
+//!
+//! - numbers, strings and bools map to themselves; integers too large for
+//!   JSON numbers and non-finite floats become strings
+//! - enumerators are strings, chars one-character strings and pointers to
+//!   members strings like `"&Klass::field"`
+//! - lists are arrays with `<repeats N times>` expanded, string runs are
+//!   joined into one string, and the bytes of an `<incomplete sequence>` are
+//!   appended to the string as the characters with the same codes
+//! - runs that would take the value past [`MAX_EXPANDED`] values or
+//!   characters are kept as `{"repeat": value, "count": N}` instead, and a
+//!   string with such a run becomes an array of its strings and runs
+//! - structs are objects, with base classes under their `<Base>` names
+//! - maps whose keys are all strings are objects, other maps are arrays of
+//!   `[key, value]` pairs: `{[1] = 2}` is `[[1, 2]]`, and a run in a sparse
+//!   array is a pair for each index it covers
+//! - pointers are objects with an `address` (a `0x` string like in MI) and
+//!   the `symbol` and `pointee` if GDB printed them
+//! - `<optimized out>` is `null`, errors are `{"error": message}` and text
+//!   that didn't parse is `{"raw": text}`
+//! - references are the value they refer to and smart pointers the pointer
+//!   they hold
+//! - truncation markers, `static` flags, type casts and encoding prefixes
+//!   are dropped, and pretty-printer headers are replaced by the value after
+//!   them, or are the description string if there is none
+
+use serde_json::{json, Map, Number, Value as Json};
+
+use crate::Value;
+
+impl From<Value<'_>> for Json {
+    fn from(v: Value) -> Self {
+        Self::from(&v)
+    }
+}
+
+/// How many values and characters runs are expanded into at most in one
+/// conversion, so a huge or corrupt `<repeats N times>` can't use up all
+/// the memory.
+pub const MAX_EXPANDED: usize = 1 << 20;
+
+impl From<&Value<'_>> for Json {
+    fn from(v: &Value) -> Self {
+        Converter {
+            budget: MAX_EXPANDED,
+        }
+        .convert(v)
+    }
+}
+
+struct Converter {
+    /// What's left of [`MAX_EXPANDED`].
+    budget: usize,
+}
+
+impl Converter {
+    fn convert(&mut self, v: &Value) -> Json {
+        match v {
+            Value::Bool(b) => (*b).into(),
+            Value::Int(n) => i64::try_from(*n).map_or_else(|_| n.to_string().into(), Into::into),
+            Value::Uint(n) => u64::try_from(*n).map_or_else(|_| n.to_string().into(), Into::into),
+            Value::Float(n) => {
+                Number::from_f64(*n).map_or_else(|| v.to_gdb_string().into(), Json::Number)
+            }
+            Value::String(s) | Value::Enum(s) => s.as_ref().into(),
+            Value::Char { ch, .. } => ch.to_string().into(),
+            Value::MemberPointer(name) => format!("&{name}").into(),
+            Value::List(items) => {
+                let mut list = Vec::with_capacity(items.len());
+                for item in items {
+                    match item {
+                        Value::Repeated(v, n) => self.run(&mut list, v, *n),
+                        v => list.push(self.convert(v)),
+                    }
+                }
+                list.into()
+            }
+            Value::Repeated(v, n) => {
+                let mut list = Vec::new();
+                self.run(&mut list, v, *n);
+                list.into()
+            }
+            Value::StringRuns(segments) => self.string_runs(segments),
+            Value::Incomplete { value, bytes } => match self.convert(value) {
+                Json::String(mut s) => {
+                    s.extend(bytes.iter().map(|&b| char::from(b)));
+                    s.into()
+                }
+                v => v,
+            },
+            Value::Struct(fields) => fields
+                .iter()
+                .map(|(k, v)| (k.to_string(), self.convert(v)))
+                .collect::<Map<_, _>>()
+                .into(),
+            Value::Map(entries) if entries.iter().all(|(k, _)| k.as_str().is_some()) => entries
+                .iter()
+                .map(|(k, v)| (k.as_str().unwrap().to_owned(), self.convert(v)))
+                .collect::<Map<_, _>>()
+                .into(),
+            Value::Map(entries) => {
+                let mut pairs = Vec::with_capacity(entries.len());
+                for (k, v) in entries {
+                    match (k.as_int(), v) {
+                        (Some(i), Value::Repeated(v, n)) => self.indexed_run(&mut pairs, i, v, *n),
+                        _ => pairs.push(json!([self.convert(k), self.convert(v)])),
+                    }
+                }
+                pairs.into()
+            }
+            Value::Pointer {
+                address,
+                symbol,
+                pointee,
+            } => {
+                let mut pointer = Map::new();
+                pointer.insert("address".into(), format!("{address:#x}").into());
+                if let Some(symbol) = symbol {
+                    pointer.insert("symbol".into(), symbol.as_ref().into());
+                }
+                if let Some(pointee) = pointee {
+                    pointer.insert("pointee".into(), self.convert(pointee));
+                }
+                pointer.into()
+            }
+            Value::OptimizedOut => Json::Null,
+            Value::Error(message) => json!({ "error": message }),
+            Value::Raw(text) => json!({ "raw": text }),
+            Value::Truncated(v)
+            | Value::Static(v)
+            | Value::Encoded { value: v, .. }
+            | Value::Reference { value: v, .. }
+            | Value::Typed { value: v, .. }
+            | Value::SmartPointer { pointer: v, .. } => self.convert(v),
+            Value::Described { value: Some(v), .. } => self.convert(v),
+            Value::Described { description, .. } => description.as_ref().into(),
+        }
+    }
+
+    /// Takes `size` from the budget, if there's that much left.
+    fn spend(&mut self, size: Option<usize>) -> bool {
+        match size {
+            Some(size) if size <= self.budget => {
+                self.budget -= size;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Appends `n` copies of `v` to `list`, or the run if they don't fit in
+    /// the budget.
+    fn run(&mut self, list: &mut Vec<Json>, v: &Value, n: usize) {
+        let v = self.convert(v);
+        if self.spend(size(&v).checked_mul(n)) {
+            list.extend(std::iter::repeat_n(v, n));
+        } else {
+            list.push(json!({ "repeat": v, "count": n }));
+        }
+    }
+
+    /// Appends a `[index, v]` pair to `pairs` for each of the `n` indices
+    /// from `start` a run in a sparse array covers, or a pair of `start` and
+    /// the run if they don't fit in the budget.
+    fn indexed_run(&mut self, pairs: &mut Vec<Json>, start: i128, v: &Value, n: usize) {
+        let v = self.convert(v);
+        // each pair is an array, an index and the value
+        if self.spend((size(&v) + 2).checked_mul(n)) {
+            for i in start..start + n as i128 {
+                pairs.push(json!([self.convert(&Value::from(i)), v.clone()]));
+            }
+        } else {
+            let start = self.convert(&Value::from(start));
+            pairs.push(json!([start, { "repeat": v, "count": n }]));
+        }
+    }
+
+    fn string_runs(&mut self, segments: &[Value]) -> Json {
+        let mut parts = Vec::new();
+        let mut s = String::new();
+        for segment in segments {
+            match segment {
+                Value::Repeated(v, n) => {
+                    let Value::Char { ch, .. } = **v else {
+                        continue;
+                    };
+                    if self.spend(Some(*n)) {
+                        s.extend(std::iter::repeat_n(ch, *n));
+                    } else {
+                        if !s.is_empty() {
+                            parts.push(std::mem::take(&mut s).into());
+                        }
+                        parts.push(json!({ "repeat": ch.to_string(), "count": n }));
+                    }
+                }
+                v => {
+                    if let Json::String(v) = self.convert(v) {
+                        s.push_str(&v);
+                    }
+                }
+            }
+        }
+        if parts.is_empty() {
+            return s.into();
+        }
+        if !s.is_empty() {
+            parts.push(s.into());
+        }
+        parts.into()
+    }
+}
+
+/// How many values there are in `v`, counting itself.
+fn size(v: &Json) -> usize {
+    match v {
+        Json::Array(items) => 1 + items.iter().map(size).sum::<usize>(),
+        Json::Object(fields) => 1 + fields.values().map(size).sum::<usize>(),
+        _ => 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::Parser;
+
+    fn to_json(text: &str) -> serde_json::Value {
+        Parser::new(text).parse_value_or_panic().into()
+    }
+
+    #[test]
+    fn structs_and_lists() {
+        assert_eq!(
+            to_json(
+                r#"{<B> = {id = 1}, xs = {0 <repeats 2 times>, -1...}, f = 1.5, s = "a", 'b' <repeats 2 times>, e = RED}"#
+            ),
+            json!({"<B>": {"id": 1}, "xs": [0, 0, -1], "f": 1.5, "s": "abb", "e": "RED"})
+        );
+    }
+
+    #[test]
+    fn huge_runs() {
+        assert_eq!(
+            to_json("{1, 0 <repeats 4294967295 times>}"),
+            json!([1, {"repeat": 0, "count": 4294967295u64}])
+        );
+        // each run fits on its own, but not all of their copies together
+        assert_eq!(
+            to_json("{{0 <repeats 2000 times>} <repeats 2000 times>}"),
+            json!([{"repeat": vec![0; 2000], "count": 2000}])
+        );
+        assert_eq!(
+            to_json("\"ab\", 'x' <repeats 4294967295 times>, \"c\""),
+            json!(["ab", {"repeat": "x", "count": 4294967295u64}, "c"])
+        );
+    }
+
+    #[test]
+    fn map_keys() {
+        assert_eq!(
+            to_json(r#"{["a"] = 1, ["b"] = 2}"#),
+            json!({"a": 1, "b": 2})
+        );
+        assert_eq!(to_json("{[1] = 2, [RED] = 3}"), json!([[1, 2], ["RED", 3]]));
+    }
+
+    #[test]
+    fn sparse_arrays() {
+        assert_eq!(
+            to_json("{[0] = 0 <repeats 3 times>, [100] = 5}"),
+            json!([[0, 0], [1, 0], [2, 0], [100, 5]])
+        );
+        let pairs = to_json("{[0] = 0 <repeats 64 times>, [100] = 5}");
+        assert_eq!(pairs.as_array().unwrap().len(), 65);
+        assert_eq!(pairs[63], json!([63, 0]));
+        assert_eq!(
+            to_json("{[1] = 7 <repeats 4294967295 times>}"),
+            json!([[1, {"repeat": 7, "count": 4294967295u64}]])
+        );
+    }
+
+    #[test]
+    fn special_values() {
+        assert_eq!(
+            to_json(
+                r#"{p = 0x4005 <buf> "hi", c = 97 'a', o = <optimized out>, r = <error reading variable>}"#
+            ),
+            json!({
+                "p": {"address": "0x4005", "symbol": "buf", "pointee": "hi"},
+                "c": "a",
+                "o": null,
+                "r": {"error": "error reading variable"},
+            })
+        );
+        assert_eq!(
+            to_json("340282366920938463463374607431768211455"),
+            json!("340282366920938463463374607431768211455")
+        );
+        assert_eq!(
+            to_json("{v = std::vector of length 0, capacity 0, w = std::vector of length 1, capacity 1 = {1}}"),
+            json!({"v": "std::vector of length 0, capacity 0", "w": [1]})
+        );
+    }
+}
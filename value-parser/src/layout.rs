@@ -0,0 +1,345 @@
+//! Decoding raw memory into [`Value`]s without debug info.
+//!
+//! A layout is a list of `name: type` fields separated by `;`:
+//!
+//! ```text
+//! endian big;
+//! magic: u32;
+//! version: u16le @ 6;
+//! name: char[8];
+//! samples: i16[4];
+//! ```
+//!
+//! Types are `u8`..`u64`, `i8`..`i64`, `f32`, `f64`, `bool` and `char`,
+//! optionally suffixed with `le`/`be` to override the layout's endianness
+//! (little by default) and followed by `[N]` for arrays. Fields are laid out
+//! back to back unless an explicit `@ offset` is given. `char` arrays decode
+//! to strings cut at the first NUL, other arrays to lists.
+
+use crate::memory::read;
+pub use crate::memory::Endian;
+use crate::{ParseError, Parser, Value};
+
+fn parse_usize(p: &mut Parser) -> Result<usize, ParseError> {
+    let start = p.pos;
+    let n = p.parse_number()?;
+    n.as_uint().and_then(|n| n.try_into().ok()).ok_or_else(|| {
+        p.goto(start);
+        p.error("expected a count or offset", &[])
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Scalar {
+    U8,
+    U16,
+    U32,
+    U64,
+    I8,
+    I16,
+    I32,
+    I64,
+    F32,
+    F64,
+    Bool,
+    Char,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    pub name: String,
+    pub ty: Scalar,
+    pub endian: Endian,
+    pub count: Option<usize>,
+    pub offset: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Layout {
+    pub fields: Vec<Field>,
+}
+
+impl Scalar {
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "u8" => Self::U8,
+            "u16" => Self::U16,
+            "u32" => Self::U32,
+            "u64" => Self::U64,
+            "i8" => Self::I8,
+            "i16" => Self::I16,
+            "i32" => Self::I32,
+            "i64" => Self::I64,
+            "f32" => Self::F32,
+            "f64" => Self::F64,
+            "bool" => Self::Bool,
+            "char" => Self::Char,
+            _ => return None,
+        })
+    }
+
+    pub fn size(self) -> usize {
+        match self {
+            Self::U8 | Self::I8 | Self::Bool | Self::Char => 1,
+            Self::U16 | Self::I16 => 2,
+            Self::U32 | Self::I32 | Self::F32 => 4,
+            Self::U64 | Self::I64 | Self::F64 => 8,
+        }
+    }
+
+    fn decode(self, bytes: &[u8], endian: Endian) -> Value<'static> {
+        macro_rules! int {
+            ($t:ty) => {
+                Value::from(read::<$t>(bytes, 0, endian).unwrap() as i128)
+            };
+        }
+        macro_rules! float {
+            ($t:ty) => {
+                Value::Float(read::<$t>(bytes, 0, endian).unwrap() as f64)
+            };
+        }
+        match self {
+            Self::U8 => int!(u8),
+            Self::I8 => int!(i8),
+            Self::U16 => int!(u16),
+            Self::I16 => int!(i16),
+            Self::U32 => int!(u32),
+            Self::I32 => int!(i32),
+            Self::U64 => int!(u64),
+            Self::I64 => int!(i64),
+            Self::F32 => float!(f32),
+            Self::F64 => float!(f64),
+            Self::Bool => Value::Bool(bytes[0] != 0),
+            Self::Char => Value::from((bytes[0] as char).to_string()),
+        }
+    }
+}
+
+impl Field {
+    /// The size of the field, or `None` if it doesn't fit in a `usize`.
+    pub fn size(&self) -> Option<usize> {
+        self.ty.size().checked_mul(self.count.unwrap_or(1))
+    }
+
+    /// The offset just past the field.
+    fn end(&self) -> Option<usize> {
+        self.offset.checked_add(self.size()?)
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Option<Value<'static>> {
+        let bytes = bytes.get(self.offset..self.end()?)?;
+        Some(match (self.ty, self.count) {
+            (ty, None) => ty.decode(bytes, self.endian),
+            (Scalar::Char, Some(_)) => {
+                let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+                Value::from(String::from_utf8_lossy(&bytes[..end]).into_owned())
+            }
+            (ty, Some(_)) => Value::List(
+                bytes
+                    .chunks(ty.size())
+                    .map(|b| ty.decode(b, self.endian))
+                    .collect(),
+            ),
+        })
+    }
+}
+
+impl Layout {
+    pub fn parse(src: &str) -> Result<Self, ParseError> {
+        let mut p = Parser::new(src);
+        let mut endian = Endian::Little;
+        let mut fields = Vec::new();
+        let mut offset = 0;
+        loop {
+            p.eat_ws();
+            if p.at_eof() {
+                break;
+            }
+            let name = p.parse_ident();
+            if name.is_empty() {
+                return Err(p.error("expected a field name", &[]));
+            }
+            p.eat_ws();
+            if name == "endian" && !p.at(":") {
+                let start = p.pos;
+                endian = match p.parse_ident() {
+                    "little" => Endian::Little,
+                    "big" => Endian::Big,
+                    _ => {
+                        p.goto(start);
+                        return Err(p.error("expected little or big", &["little", "big"]));
+                    }
+                };
+            } else {
+                p.expect(":", "expected a : after field name")?;
+                p.eat_ws();
+                let start = p.pos;
+                let field = Self::parse_field(&mut p, name.to_owned(), endian, offset)?;
+                offset = field.end().ok_or_else(|| {
+                    p.goto(start);
+                    p.error("field too large", &[])
+                })?;
+                fields.push(field);
+            }
+            p.eat_ws();
+            if !p.at_eof() {
+                p.expect(";", "expected a ; after field")?;
+            }
+        }
+        Ok(Self { fields })
+    }
+
+    fn parse_field(
+        p: &mut Parser,
+        name: String,
+        endian: Endian,
+        offset: usize,
+    ) -> Result<Field, ParseError> {
+        let start = p.pos;
+        let ty_name = p.parse_ident();
+        let (ty_name, endian) = if let Some(t) = ty_name.strip_suffix("le") {
+            (t, Endian::Little)
+        } else if let Some(t) = ty_name.strip_suffix("be") {
+            (t, Endian::Big)
+        } else {
+            (ty_name, endian)
+        };
+        let Some(ty) = Scalar::from_name(ty_name) else {
+            p.goto(start);
+            return Err(p.error("unknown type", &[]));
+        };
+        p.eat_ws();
+        let count = if p.eat("[") {
+            p.eat_ws();
+            let n = parse_usize(p)?;
+            p.eat_ws();
+            p.expect("]", "expected a ]")?;
+            p.eat_ws();
+            Some(n)
+        } else {
+            None
+        };
+        let offset = if p.eat("@") {
+            p.eat_ws();
+            parse_usize(p)?
+        } else {
+            offset
+        };
+        Ok(Field {
+            name,
+            ty,
+            endian,
+            count,
+            offset,
+        })
+    }
+
+    /// Number of bytes needed to decode every field, or `None` if that
+    /// doesn't fit in a `usize`.
+    pub fn size(&self) -> Option<usize> {
+        self.fields
+            .iter()
+            .map(Field::end)
+            .try_fold(0, |size, end| Some(size.max(end?)))
+    }
+
+    /// Decodes `bytes` into a map from field name to value, like a struct
+    /// printed by GDB, or `None` if there aren't enough bytes for every
+    /// field.
+    pub fn decode(&self, bytes: &[u8]) -> Option<Value<'_>> {
+        let fields = self
+            .fields
+            .iter()
+            .map(|f| Some((f.name.as_str().into(), f.decode(bytes)?)))
+            .collect::<Option<_>>()?;
+        Some(Value::Struct(fields))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::value;
+
+    #[test]
+    fn sequential_fields() {
+        let layout = Layout::parse("a: u8; b: u16; c: i32").unwrap();
+        assert_eq!(layout.size(), Some(7));
+        assert_eq!(
+            layout.decode(&[1, 2, 0, 3, 0, 0, 0]).unwrap(),
+            value!({"a" = 1, "b" = 2, "c" = 3})
+        );
+    }
+
+    #[test]
+    fn endianness() {
+        let layout = Layout::parse("endian big; a: u16; b: u16le").unwrap();
+        assert_eq!(
+            layout.decode(&[1, 2, 1, 2]),
+            Some(value!({"a" = 258, "b" = 513}))
+        );
+    }
+
+    #[test]
+    fn explicit_offset() {
+        let layout = Layout::parse("magic: u8 @ 3;\nnext: u8").unwrap();
+        assert_eq!(layout.size(), Some(5));
+        assert_eq!(
+            layout.decode(&[0, 0, 0, 7, 8]),
+            Some(value!({"magic" = 7, "next" = 8}))
+        );
+    }
+
+    #[test]
+    fn arrays() {
+        let layout = Layout::parse("name: char[4]; xs: i16be[2]; ok: bool").unwrap();
+        assert_eq!(
+            layout.decode(b"hi\0\0\xff\xfe\x00\x01\x01"),
+            Some(Value::Struct(vec![
+                ("name".into(), value!("hi")),
+                ("xs".into(), Value::List(vec![value!(-2), value!(1)])),
+                ("ok".into(), value!(true)),
+            ]))
+        );
+    }
+
+    #[test]
+    fn floats() {
+        let layout = Layout::parse("x: f32; y: f64be").unwrap();
+        let mut bytes = 1.5f32.to_le_bytes().to_vec();
+        bytes.extend(0.25f64.to_be_bytes());
+        assert_eq!(layout.decode(&bytes), Some(value!({"x" = 1.5, "y" = 0.25})));
+    }
+
+    #[test]
+    fn field_names_with_underscores() {
+        let layout = Layout::parse("first_field: u8").unwrap();
+        assert_eq!(layout.fields[0].name, "first_field");
+    }
+
+    #[test]
+    fn errors() {
+        let err = |src| Layout::parse(src).unwrap_err();
+        let e = err("a: u128");
+        assert_eq!((e.message, e.offset), ("unknown type", 3));
+        assert_eq!(err("endian middle").message, "expected little or big");
+        assert_eq!(err("a u8").message, "expected a : after field name");
+        assert_eq!(err("a: u8[2").message, "expected a ]");
+        assert_eq!(err("a: u8[-1]").message, "expected a count or offset");
+        assert_eq!(err("a: u8 b: u8").message, "expected a ; after field");
+        assert_eq!(err(": u8").message, "expected a field name");
+    }
+
+    #[test]
+    fn overflowing_sizes() {
+        let e = Layout::parse("a: u64[2305843009213693952]").unwrap_err();
+        assert_eq!(e.message, "field too large");
+        let e = Layout::parse("a: u8 @ 18446744073709551615; b: u8").unwrap_err();
+        assert_eq!(e.message, "field too large");
+    }
+
+    #[test]
+    fn short_input() {
+        assert_eq!(Layout::parse("a: u32").unwrap().decode(&[1, 2]), None);
+    }
+}
@@ -1,19 +1,333 @@
+use std::{borrow::Cow, fmt};
+
+pub mod bindings;
+pub mod bits;
+pub mod convert;
+pub mod cst;
+pub mod diff;
+pub mod intern;
+#[cfg(feature = "json")]
+pub mod json;
+pub mod layout;
+pub mod memory;
+#[cfg(feature = "ord")]
+pub mod ord;
+pub mod path;
+pub mod print;
+pub mod stats;
+pub mod stream;
+pub mod visit;
+
+// lets the derive macro's `::value_parser` paths resolve inside this crate
+extern crate self as value_parser;
+
 pub struct Parser<'a> {
     src: &'a str,
     pos: usize,
+    config: ParserConfig,
+    /// How many values the parser is inside of.
+    depth: usize,
+    /// Whether values that fail to parse become [`Value::Raw`], see
+    /// [`Parser::parse_value_lossy`].
+    recover: bool,
+    diagnostics: Vec<ParseError>,
+    /// The nodes of the values being parsed and of those finished inside
+    /// them, see [`Parser::parse_cst`].
+    spans: Option<Vec<Vec<cst::Node>>>,
+}
+
+/// The field name given to members of anonymous structs and unions that GDB
+/// prints without a name, like the `{a = 1}` in `{{a = 1}, x = 2}`. Like base
+/// classes, [`Value::field`] looks inside them.
+pub const ANONYMOUS: &str = "<anonymous>";
+
+/// What a [`Parser`] accepts and how it represents what it parses.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParserConfig {
+    /// How deeply values may nest, counting every value on the way down: in
+    /// `{{1}}` the `1` is at depth 3. Deeper input is an error rather than a
+    /// stack overflow. The default of 128 fits in the 2 MiB stack of a
+    /// spawned thread even in debug builds.
+    pub max_depth: usize,
+    /// How many elements of each list, struct or map to parse. The rest are
+    /// skipped over without being parsed and the aggregate is returned
+    /// [`Value::Truncated`], as if GDB had stopped printing it. A
+    /// `<repeats N times>` run counts as one element. `None`, the default,
+    /// parses everything.
+    pub max_elements: Option<usize>,
+    /// Accept a `,` before a closing `}`, as in `{1, 2,}`. GDB never prints
+    /// one but hand-written values often have it. On by default.
+    pub trailing_comma: bool,
+    /// Only accept C identifiers, made of ASCII letters, digits and `_`. By
+    /// default identifiers may contain any letters and digits, as GDB prints
+    /// them for languages that allow that.
+    pub strict_identifiers: bool,
+    /// Parse `@0x7ffe: value` into a [`Value::Reference`]. When off, the
+    /// address is dropped and only the value is kept. On by default.
+    pub keep_references: bool,
+    /// The language whose pretty-printer output to recognize.
+    pub dialect: Dialect,
+    /// Which variants integers parse into.
+    pub numbers: Numbers,
+}
+
+impl Default for ParserConfig {
+    fn default() -> Self {
+        Self {
+            max_depth: 128,
+            max_elements: None,
+            trailing_comma: true,
+            strict_identifiers: false,
+            keep_references: true,
+            dialect: Dialect::Any,
+            numbers: Numbers::Exact,
+        }
+    }
+}
+
+/// The language GDB prints values of, which decides what the parser makes
+/// of syntax only some languages' pretty-printers use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Dialect {
+    /// Everything below.
+    #[default]
+    Any,
+    C,
+    /// C plus smart pointers like `std::shared_ptr<A> (use count 1, weak
+    /// count 0) = {get() = 0x10}` and pointers to members like `&A::f`.
+    Cpp,
+    /// C plus headers without a space before the parenthesis, like
+    /// `Vec(size=2) = {1, 2}`, and slices like `&[u8](size=0)`.
+    Rust,
+}
+
+impl Dialect {
+    fn cpp(self) -> bool {
+        matches!(self, Self::Any | Self::Cpp)
+    }
+
+    fn rust(self) -> bool {
+        matches!(self, Self::Any | Self::Rust)
+    }
+}
+
+/// How integers are represented, see [`ParserConfig::numbers`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Numbers {
+    /// Negative integers as [`Value::Int`], others as [`Value::Uint`].
+    #[default]
+    Exact,
+    /// Every integer that fits in an `i128` as [`Value::Int`], for callers
+    /// that don't care about the sign.
+    Signed,
+    /// Every number as [`Value::Float`], like JavaScript would read them.
+    /// Integers above 2^53 lose precision.
+    Float,
 }
 
-#[derive(Debug, PartialEq)]
-pub enum Value {
+impl Numbers {
+    fn apply<'a>(self, n: Value<'a>) -> Value<'a> {
+        match (self, n) {
+            (Self::Signed, Value::Uint(n)) if n <= i128::MAX as u128 => Value::Int(n as i128),
+            (Self::Float, Value::Int(n)) => Value::Float(n as f64),
+            (Self::Float, Value::Uint(n)) => Value::Float(n as f64),
+            (_, n) => n,
+        }
+    }
+}
+
+/// A value printed by GDB's `print` command, see
+/// [`Parser::parse_print_output`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct PrintOutput<'a> {
+    /// The `N` of the `$N` the value was recorded as in GDB's value history.
+    pub history: Option<usize>,
+    pub value: Value<'a>,
+}
+
+/// Why and where [`Parser::parse_value`] gave up.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub message: &'static str,
+    /// Byte offset into the input.
+    pub offset: usize,
+    /// The tokens that would have been accepted at `offset`, if the parser
+    /// was looking for specific ones.
+    pub expected: Vec<&'static str>,
+    /// The input around `offset`.
+    pub snippet: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at offset {}", self.message, self.offset)?;
+        if !self.expected.is_empty() {
+            write!(f, " (expected one of {})", self.expected.join(" "))?;
+        }
+        write!(f, " near {:?}", self.snippet)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Strings and names borrow from the parsed text unless they had to be
+/// unescaped; [`Value::into_owned`] detaches a value from it.
+///
+/// With the `serde` feature, values serialize externally tagged with
+/// snake_case variant names: `{"uint": 1}`, `"optimized_out"`,
+/// `{"pointer": {"address": 4198694, "symbol": "main+6", "pointee": null}}`.
+/// Structs and maps are arrays of `[key, value]` entries since map keys can
+/// be any value: `{x = 1}` is `{"struct": [["x", {"uint": 1}]]}` and
+/// `{[1] = 2}` is `{"map": [[{"uint": 1}, {"uint": 2}]]}`.
+///
+/// With the `ord` feature, values have a total order, [`Value::total_cmp`],
+/// and `ord::OrdValue` wraps them to be `Eq`, `Ord` and `Hash` by it.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Value<'a> {
     Bool(bool),
-    Number(f64),
-    String(String),
-    Map(Vec<(Value, Value)>),
-    List(Vec<Value>),
+    /// A negative integer, or any integer with [`Numbers::Signed`]. Integers
+    /// are exact, so 64-bit addresses and `__int128` values survive.
+    Int(i128),
+    /// A non-negative integer.
+    Uint(u128),
+    /// A number with a fractional part or exponent.
+    Float(f64),
+    String(Cow<'a, str>),
+    /// An aggregate with `[key] = value` entries, like an associative
+    /// container. If it also has named fields their keys are
+    /// [`Value::String`]s.
+    Map(Vec<(Value<'a>, Value<'a>)>),
+    /// A struct, union or class, `{x = 1, y = 2}`. A base class subobject
+    /// is a field named after the class in angle brackets, `<Base>`.
+    Struct(Vec<(Cow<'a, str>, Value<'a>)>),
+    List(Vec<Value<'a>>),
+    /// A list element GDB compressed as `0 <repeats 100 times>`.
+    Repeated(Box<Value<'a>>, usize),
+    /// `<optimized out>`, a value the compiler didn't keep around.
+    OptimizedOut,
+    /// A `char` printed as `97 'a'`: its numeric value and the character.
+    Char {
+        code: i64,
+        ch: char,
+    },
+    /// An address GDB annotated with the symbol it points into
+    /// (`0x401126 <main+6>`) and/or the string it points to
+    /// (`0x555555556004 "literal"`). Plain addresses parse as numbers.
+    Pointer {
+        address: u64,
+        symbol: Option<Cow<'a, str>>,
+        pointee: Option<Box<Value<'a>>>,
+    },
+    /// A pointer to a data member or method, `&Klass::field`, holding what
+    /// follows the `&`.
+    MemberPointer(Cow<'a, str>),
+    /// An enumerator, printed as a bare (possibly `::`-qualified) name.
+    Enum(Cow<'a, str>),
+    /// The value of a static member, printed among the fields of an object
+    /// as `static count = 3`.
+    Static(Box<Value<'a>>),
+    /// A list, map or string GDB stopped printing because of `set print
+    /// elements`, marked with a trailing `...`.
+    Truncated(Box<Value<'a>>),
+    /// A string ending in bytes that don't make up a whole character in the
+    /// target charset, which GDB prints after it as `"abc" <incomplete
+    /// sequence \360>`. `value` is the [`Value::String`] before them.
+    Incomplete {
+        value: Box<Value<'a>>,
+        bytes: Vec<u8>,
+    },
+    /// A character array GDB printed as string literals and runs of a
+    /// repeated character, `'x' <repeats 200 times>, "tail"`. The segments
+    /// are [`Value::String`]s (or [`Value::Incomplete`] ones) and
+    /// [`Value::Repeated`] [`Value::Char`]s.
+    StringRuns(Vec<Value<'a>>),
+    /// A value a pretty-printer prefixed with a description of the
+    /// container, `std::vector of length 3, capacity 4 = {1, 2, 3}`. `value`
+    /// is `None` when the header is all there is, as for an empty
+    /// `std::vector of length 0, capacity 0`.
+    Described {
+        description: Cow<'a, str>,
+        value: Option<Box<Value<'a>>>,
+    },
+    /// A `std::unique_ptr`, `std::shared_ptr` or `std::weak_ptr` as printed
+    /// by libstdc++'s pretty-printers, `std::shared_ptr<Foo> (use count 2,
+    /// weak count 0) = {get() = 0x4172b0}` or `std::unique_ptr<Foo>
+    /// containing 0x4172b0`. `ty` is the smart pointer type, `pointer` the
+    /// address it holds (a number or [`Value::Pointer`]). The counts are
+    /// there for shared and weak pointers; an empty or expired one has a use
+    /// count of 0.
+    SmartPointer {
+        ty: Cow<'a, str>,
+        use_count: Option<u64>,
+        weak_count: Option<u64>,
+        pointer: Box<Value<'a>>,
+    },
+    /// A value with the type cast `print` puts in front of pointers and
+    /// references, `(Foo *) 0x4052a0`. `ty` is the text between the
+    /// parentheses.
+    Typed {
+        ty: Cow<'a, str>,
+        value: Box<Value<'a>>,
+    },
+    /// A C++ reference, printed as the address it refers to and the value
+    /// there, `@0x7fffffffde44: 1`.
+    Reference {
+        address: u64,
+        value: Box<Value<'a>>,
+    },
+    /// A string or character literal with an encoding prefix, like
+    /// `L"wide"` or `97 u'a'`. `value` is what the literal would be without
+    /// the prefix: a [`Value::String`], [`Value::Char`] or
+    /// [`Value::StringRuns`], possibly truncated.
+    Encoded {
+        encoding: Encoding,
+        value: Box<Value<'a>>,
+    },
+    /// Text [`Parser::parse_value_lossy`] couldn't parse, up to the `,` or
+    /// `}` after it.
+    Raw(Cow<'a, str>),
+    /// A value GDB couldn't read, printed inline as `<error: Cannot access
+    /// memory at address 0x0>` or `<error reading variable>`. Holds the text
+    /// inside the brackets without the leading `error: `.
+    Error(Cow<'a, str>),
+}
+
+/// The character type of a prefixed string or character literal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Encoding {
+    /// `u8"..."`, `char8_t`.
+    Utf8,
+    /// `L"..."`, `wchar_t`.
+    Wide,
+    /// `u"..."`, `char16_t`.
+    Utf16,
+    /// `U"..."`, `char32_t`.
+    Utf32,
+}
+
+impl Encoding {
+    pub fn prefix(self) -> &'static str {
+        match self {
+            Self::Utf8 => "u8",
+            Self::Wide => "L",
+            Self::Utf16 => "u",
+            Self::Utf32 => "U",
+        }
+    }
 }
 
-impl Value {
-    pub fn as_list(&self) -> Option<&[Value]> {
+impl<'a> Value<'a> {
+    pub fn as_list(&self) -> Option<&[Value<'a>]> {
         if let Self::List(v) = self {
             Some(v)
         } else {
@@ -21,7 +335,7 @@ impl Value {
         }
     }
 
-    pub fn as_string(&self) -> Option<&str> {
+    pub fn as_str(&self) -> Option<&str> {
         if let Self::String(v) = self {
             Some(v)
         } else {
@@ -37,26 +351,274 @@ impl Value {
         }
     }
 
-    pub fn as_number(&self) -> Option<f64> {
-        if let Self::Number(v) = self {
+    /// Any number as an `f64`, rounding large integers.
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            Self::Int(n) => Some(n as f64),
+            Self::Uint(n) => Some(n as f64),
+            Self::Float(n) => Some(n),
+            _ => None,
+        }
+    }
+
+    /// An integer that fits an `i128`.
+    pub fn as_int(&self) -> Option<i128> {
+        match *self {
+            Self::Int(n) => Some(n),
+            Self::Uint(n) => n.try_into().ok(),
+            _ => None,
+        }
+    }
+
+    pub fn as_uint(&self) -> Option<u128> {
+        if let Self::Uint(v) = self {
             Some(*v)
         } else {
             None
         }
     }
 
-    pub fn as_map(&self) -> Option<&[(Value, Value)]> {
+    pub fn as_map(&self) -> Option<&[(Value<'a>, Value<'a>)]> {
         if let Self::Map(v) = self {
             Some(v)
         } else {
             None
         }
     }
+
+    pub fn as_struct(&self) -> Option<&[(Cow<'a, str>, Value<'a>)]> {
+        if let Self::Struct(v) = self {
+            Some(v)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_address(&self) -> Option<u64> {
+        if let Self::Pointer { address, .. } = self {
+            Some(*address)
+        } else {
+            None
+        }
+    }
+
+    /// This value with every [`Value::Repeated`] in it replaced by that many
+    /// copies of the element.
+    pub fn expanded(&self) -> Value<'a> {
+        match self {
+            Self::List(items) => {
+                let mut list = Vec::with_capacity(items.len());
+                for item in items {
+                    match item {
+                        Self::Repeated(v, n) => list.extend(std::iter::repeat_n(v.expanded(), *n)),
+                        v => list.push(v.expanded()),
+                    }
+                }
+                Self::List(list)
+            }
+            Self::Map(entries) => {
+                let mut map = Vec::with_capacity(entries.len());
+                for (k, v) in entries {
+                    match (k.as_int(), v) {
+                        // a run in a sparse array covers consecutive indices
+                        (Some(i), Self::Repeated(v, n)) => {
+                            map.extend((i..i + *n as i128).map(|i| (Self::from(i), v.expanded())))
+                        }
+                        _ => map.push((k.expanded(), v.expanded())),
+                    }
+                }
+                Self::Map(map)
+            }
+            Self::Struct(fields) => Self::Struct(
+                fields
+                    .iter()
+                    .map(|(k, v)| (k.clone(), v.expanded()))
+                    .collect(),
+            ),
+            Self::Repeated(v, n) => Self::List(vec![v.expanded(); *n]),
+            Self::Truncated(v) => Self::Truncated(Box::new(v.expanded())),
+            Self::Static(v) => Self::Static(Box::new(v.expanded())),
+            Self::Encoded { encoding, value } => Self::Encoded {
+                encoding: *encoding,
+                value: Box::new(value.expanded()),
+            },
+            Self::Reference { address, value } => Self::Reference {
+                address: *address,
+                value: Box::new(value.expanded()),
+            },
+            Self::Typed { ty, value } => Self::Typed {
+                ty: ty.clone(),
+                value: Box::new(value.expanded()),
+            },
+            Self::StringRuns(segments) => {
+                let mut s = String::new();
+                for segment in segments {
+                    match segment {
+                        Self::String(v) => s.push_str(v),
+                        Self::Repeated(v, n) => {
+                            if let Self::Char { ch, .. } = **v {
+                                s.extend(std::iter::repeat_n(ch, *n));
+                            }
+                        }
+                        Self::Incomplete { .. } => {
+                            if let Self::String(v) = segment.expanded() {
+                                s.push_str(&v);
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                Self::String(s.into())
+            }
+            // the bytes become characters with the same codes, like
+            // undecodable escapes
+            Self::Incomplete { value, bytes } => match value.expanded() {
+                Self::String(v) => {
+                    let mut s = v.into_owned();
+                    s.extend(bytes.iter().map(|&b| char::from(b)));
+                    Self::String(s.into())
+                }
+                v => v,
+            },
+            v => v.clone(),
+        }
+    }
+
+    /// This value with everything borrowed from the input copied, so it can
+    /// outlive it.
+    pub fn into_owned(self) -> Value<'static> {
+        self.into_owned_with(&mut |s| Cow::Owned(s.into_owned()))
+    }
+
+    /// [`Value::into_owned`], copying names, like those of fields,
+    /// enumerators, symbols and types, with `name`.
+    pub(crate) fn into_owned_with<'b>(
+        self,
+        name: &mut dyn FnMut(Cow<str>) -> Cow<'b, str>,
+    ) -> Value<'b> {
+        fn owned<'b>(s: Cow<str>) -> Cow<'b, str> {
+            Cow::Owned(s.into_owned())
+        }
+        macro_rules! owned {
+            ($v:expr) => {
+                $v.into_owned_with(name)
+            };
+        }
+        macro_rules! boxed {
+            ($v:expr) => {
+                Box::new($v.into_owned_with(name))
+            };
+        }
+        match self {
+            Self::Bool(b) => Value::Bool(b),
+            Self::Int(n) => Value::Int(n),
+            Self::Uint(n) => Value::Uint(n),
+            Self::Float(n) => Value::Float(n),
+            Self::String(s) => Value::String(owned(s)),
+            Self::Map(entries) => Value::Map(
+                entries
+                    .into_iter()
+                    .map(|(k, v)| (owned!(k), owned!(v)))
+                    .collect(),
+            ),
+            Self::Struct(fields) => Value::Struct(
+                fields
+                    .into_iter()
+                    .map(|(k, v)| (name(k), owned!(v)))
+                    .collect(),
+            ),
+            Self::List(items) => Value::List(items.into_iter().map(|v| owned!(v)).collect()),
+            Self::Repeated(v, n) => Value::Repeated(boxed!(v), n),
+            Self::OptimizedOut => Value::OptimizedOut,
+            Self::Char { code, ch } => Value::Char { code, ch },
+            Self::Pointer {
+                address,
+                symbol,
+                pointee,
+            } => Value::Pointer {
+                address,
+                symbol: symbol.map(&mut *name),
+                pointee: pointee.map(|v| boxed!(v)),
+            },
+            Self::MemberPointer(member) => Value::MemberPointer(name(member)),
+            Self::Enum(enumerator) => Value::Enum(name(enumerator)),
+            Self::Static(v) => Value::Static(boxed!(v)),
+            Self::Truncated(v) => Value::Truncated(boxed!(v)),
+            Self::Incomplete { value, bytes } => Value::Incomplete {
+                value: boxed!(value),
+                bytes,
+            },
+            Self::StringRuns(segments) => {
+                Value::StringRuns(segments.into_iter().map(|v| owned!(v)).collect())
+            }
+            Self::Described { description, value } => Value::Described {
+                description: owned(description),
+                value: value.map(|v| boxed!(v)),
+            },
+            Self::Encoded { encoding, value } => Value::Encoded {
+                encoding,
+                value: boxed!(value),
+            },
+            Self::Reference { address, value } => Value::Reference {
+                address,
+                value: boxed!(value),
+            },
+            Self::Typed { ty, value } => Value::Typed {
+                ty: name(ty),
+                value: boxed!(value),
+            },
+            Self::SmartPointer {
+                ty,
+                use_count,
+                weak_count,
+                pointer,
+            } => Value::SmartPointer {
+                ty: name(ty),
+                use_count,
+                weak_count,
+                pointer: boxed!(pointer),
+            },
+            Self::Raw(text) => Value::Raw(owned(text)),
+            Self::Error(message) => Value::Error(owned(message)),
+        }
+    }
+}
+
+/// What an escape sequence stands for: a byte of the encoded text, or a
+/// whole character.
+enum Escape {
+    Byte(u8),
+    Char(char),
+}
+
+/// Appends the escaped `bytes` to `s` as UTF-8 where they are valid, and as
+/// the characters with the same codes where they aren't.
+fn push_bytes(s: &mut String, bytes: &mut Vec<u8>) {
+    for chunk in bytes.utf8_chunks() {
+        s.push_str(chunk.valid());
+        s.extend(chunk.invalid().iter().map(|&b| char::from(b)));
+    }
+    bytes.clear();
 }
 
 impl<'a> Parser<'a> {
     pub fn new(src: &'a str) -> Self {
-        Self { src, pos: 0 }
+        Self {
+            src,
+            pos: 0,
+            config: ParserConfig::default(),
+            depth: 0,
+            recover: false,
+            diagnostics: Vec::new(),
+            spans: None,
+        }
+    }
+
+    pub fn with_config(src: &'a str, config: ParserConfig) -> Self {
+        Self {
+            config,
+            ..Self::new(src)
+        }
     }
 
     pub fn at_eof(&self) -> bool {
@@ -64,7 +626,10 @@ impl<'a> Parser<'a> {
     }
 
     pub fn at(&self, tok: &str) -> bool {
-        self.pos + tok.len() <= self.src.len() && &self.src[self.pos..self.pos + tok.len()] == tok
+        self.src
+            .as_bytes()
+            .get(self.pos..)
+            .is_some_and(|rest| rest.starts_with(tok.as_bytes()))
     }
 
     pub fn goto(&mut self, pos: usize) {
@@ -79,220 +644,2133 @@ impl<'a> Parser<'a> {
         is_at
     }
 
-    pub fn eat_ws(&mut self) {
-        while !self.at_eof() && self.current().is_ascii_whitespace() {
-            self.pos += 1;
-        }
+    pub fn eat_ws(&mut self) {
+        while !self.at_eof() && self.current().is_ascii_whitespace() {
+            self.pos += 1;
+        }
+    }
+
+    /// The character at the cursor, or `'\0'` at the end of the input.
+    pub fn current(&self) -> char {
+        self.src
+            .get(self.pos..)
+            .and_then(|rest| rest.chars().next())
+            .unwrap_or('\0')
+    }
+
+    /// Moves past the current character, which may be several bytes long,
+    /// so the cursor always stays on a character boundary.
+    pub fn advance(&mut self) {
+        self.goto(self.pos + self.current().len_utf8());
+    }
+
+    pub fn eat_current(&mut self) -> char {
+        let curr = self.current();
+        self.advance();
+        curr
+    }
+
+    pub fn error(&self, message: &'static str, expected: &[&'static str]) -> ParseError {
+        const CONTEXT: usize = 16;
+        let floor = |mut i: usize| {
+            while !self.src.is_char_boundary(i) {
+                i -= 1;
+            }
+            i
+        };
+        let pos = self.pos.min(self.src.len());
+        let start = floor(pos.saturating_sub(CONTEXT));
+        let end = floor((pos + CONTEXT).min(self.src.len()));
+        ParseError {
+            message,
+            offset: pos,
+            expected: expected.to_vec(),
+            snippet: self.src[start..end].to_owned(),
+        }
+    }
+
+    fn expect(&mut self, tok: &'static str, message: &'static str) -> Result<(), ParseError> {
+        if self.eat(tok) {
+            Ok(())
+        } else {
+            Err(self.error(message, &[tok]))
+        }
+    }
+
+    pub fn parse_ident(&mut self) -> &'a str {
+        let start = self.pos;
+        while self.at_ident_char() {
+            self.advance();
+        }
+        &self.src[start..self.pos]
+    }
+
+    fn at_ident_start(&self) -> bool {
+        self.at_ident_char() && !self.current().is_numeric()
+    }
+
+    fn at_ident_char(&self) -> bool {
+        let ch = self.current();
+        if self.config.strict_identifiers {
+            ch.is_ascii_alphanumeric() || ch == '_'
+        } else {
+            ch.is_alphanumeric() || ch == '_'
+        }
+    }
+
+    /// Whether a `name =` struct field starts here, as opposed to an
+    /// identifier value like an enumerator.
+    pub fn at_field(&mut self) -> bool {
+        let start = self.pos;
+        if self.eat("static ") {
+            self.eat_ws();
+        }
+        if !self.at_ident_start() {
+            self.goto(start);
+            return false;
+        }
+        self.parse_field_name();
+        self.eat_ws();
+        let is_field = self.at("=") && !self.at("==");
+        self.goto(start);
+        is_field
+    }
+
+    /// Parses a field name, including the `()` of pretty-printer pseudo
+    /// fields like `get()` and the class name in vtable pointers like
+    /// `_vptr.Base` (`_vptr$Base` in older GDBs).
+    fn parse_field_name(&mut self) -> &'a str {
+        let start = self.pos;
+        self.parse_ident();
+        while (self.at(".") || self.at("$")) && {
+            self.advance();
+            self.at_ident_start()
+        } {
+            self.parse_ident();
+        }
+        if self.src[start..self.pos].ends_with(['.', '$']) {
+            self.goto(self.pos - 1);
+        }
+        self.eat("()");
+        &self.src[start..self.pos]
+    }
+
+    /// Whether a `<Base> =` base class entry starts here.
+    pub fn at_base_class(&mut self) -> bool {
+        if !self.at("<") {
+            return false;
+        }
+        let start = self.pos;
+        self.advance();
+        let is_base = self.parse_angled().is_ok() && {
+            self.eat_ws();
+            self.at("=") && !self.at("==")
+        };
+        self.goto(start);
+        is_base
+    }
+
+    /// Parses the rest of a `<...>` after the `<`, allowing nested angle
+    /// brackets as in template names, and returns what was inside.
+    pub fn parse_angled(&mut self) -> Result<&'a str, ParseError> {
+        let start = self.pos;
+        let mut depth = 1;
+        while depth > 0 {
+            if self.at_eof() {
+                return Err(self.error("missing closing >", &[">"]));
+            }
+            match self.eat_current() {
+                '<' => depth += 1,
+                '>' => depth -= 1,
+                _ => {}
+            }
+        }
+        Ok(&self.src[start..self.pos - 1])
+    }
+
+    /// Whether a pretty-printer description header starts here: an
+    /// identifier followed by template arguments, parenthesized details like
+    /// rust-gdb's `Vec(size=3)` or more words, or a Rust slice header
+    /// `&[i32](size=2)`, as opposed to an enumerator.
+    pub fn at_description(&mut self) -> bool {
+        let rust = self.config.dialect.rust();
+        if rust && self.at("&[") {
+            return true;
+        }
+        if !self.at_ident_start() {
+            return false;
+        }
+        let start = self.pos;
+        self.parse_qualified_ident();
+        let is_description = self.at("<") || (rust && self.at("(")) || {
+            let ident_end = self.pos;
+            self.eat_ws();
+            self.pos > ident_end
+                && (self.at_ident_start() || self.at("(") || self.at("["))
+                && !self.at_field()
+        };
+        self.goto(start);
+        is_description
+    }
+
+    /// Parses a description header and the value after its ` = `, if any.
+    pub fn parse_described(&mut self) -> Result<Value<'a>, ParseError> {
+        let start = self.pos;
+        let mut depth = 0usize;
+        let end = loop {
+            let here = self.pos;
+            if depth == 0 {
+                self.eat_ws();
+                if self.at("=") && !self.at("==") {
+                    self.advance();
+                    break here;
+                }
+                self.goto(here);
+                if self.at_eof() || self.at("}") {
+                    break here;
+                }
+                // a comma ends a header without a value if the next item
+                // follows it
+                if self.eat(",") {
+                    self.eat_ws();
+                    let next_item =
+                        self.at("}") || self.at("[") || self.at_field() || self.at_base_class();
+                    self.goto(here);
+                    if next_item {
+                        break here;
+                    }
+                }
+            } else if self.at_eof() {
+                return Err(self.error("unbalanced brackets in description", &[]));
+            }
+            match self.eat_current() {
+                '<' | '(' | '[' => depth += 1,
+                '>' | ')' | ']' => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+        };
+        let description = self.src[start..end].trim_end().into();
+        let value = if self.pos > end {
+            Some(Box::new(self.parse_value()?))
+        } else {
+            None
+        };
+        Ok(Value::Described { description, value })
+    }
+
+    /// Parses a smart pointer's pretty-printed form if one starts here,
+    /// returning `None` and leaving the cursor where it was otherwise.
+    pub fn parse_smart_pointer(&mut self) -> Result<Option<Value<'a>>, ParseError> {
+        if !self.config.dialect.cpp() {
+            return Ok(None);
+        }
+        let start = self.pos;
+        let is_smart_pointer = matches!(
+            self.parse_qualified_ident(),
+            "std::unique_ptr" | "std::shared_ptr" | "std::weak_ptr"
+        );
+        if !is_smart_pointer || !self.eat("<") {
+            self.goto(start);
+            return Ok(None);
+        }
+        self.parse_angled()?;
+        let ty = &self.src[start..self.pos];
+        self.eat_ws();
+        let (mut use_count, mut weak_count) = (None, None);
+        if self.eat("(") {
+            let counts_start = self.pos;
+            for count in self.parse_cast()?.split(", ") {
+                let number = |prefix| {
+                    count
+                        .strip_prefix(prefix)
+                        .and_then(|n: &str| n.parse().ok())
+                };
+                if let Some(n) = number("use count ") {
+                    use_count = Some(n);
+                } else if let Some(n) = number("weak count ") {
+                    weak_count = Some(n);
+                } else if count == "empty" {
+                    (use_count, weak_count) = (Some(0), Some(0));
+                } else if count == "expired" {
+                    use_count = Some(0);
+                } else {
+                    let mut err = self.error("unknown smart pointer count", &[]);
+                    err.offset = counts_start;
+                    return Err(err);
+                }
+            }
+            self.eat_ws();
+        }
+        let pointer = if self.eat("containing ") {
+            self.eat_ws();
+            self.parse_single_value()?
+        } else if self.eat("=") {
+            self.eat_ws();
+            let diagnostics = self.diagnostics.len();
+            match self.parse_single_value()? {
+                Value::Struct(mut fields) if fields.len() == 1 && fields[0].0 == "get()" => {
+                    fields.pop().unwrap().1
+                }
+                // some other printer's output
+                _ => {
+                    self.diagnostics.truncate(diagnostics);
+                    self.span_discard_children();
+                    self.goto(start);
+                    return Ok(None);
+                }
+            }
+        } else {
+            self.goto(start);
+            return Ok(None);
+        };
+        Ok(Some(Value::SmartPointer {
+            ty: ty.into(),
+            use_count,
+            weak_count,
+            pointer: Box::new(pointer),
+        }))
+    }
+
+    /// Parses a possibly `::`-qualified identifier.
+    pub fn parse_qualified_ident(&mut self) -> &'a str {
+        let start = self.pos;
+        loop {
+            self.parse_ident();
+            let before_colons = self.pos;
+            if self.eat("::") && self.at_ident_start() {
+                continue;
+            }
+            self.goto(before_colons);
+            break;
+        }
+        &self.src[start..self.pos]
+    }
+
+    pub fn parse_list_or_map(&mut self) -> Result<Value<'a>, ParseError> {
+        let mut first = true;
+        let mut list = Vec::new();
+        let mut map = Vec::new();
+        let mut is_map = false;
+        // whether there were `[key] =` entries, as opposed to only fields
+        let mut indexed = false;
+        let mut truncated = false;
+        loop {
+            self.eat_ws();
+            if !first && self.eat("...") {
+                truncated = true;
+                self.eat_ws();
+                self.expect("}", "expected } after ...")?;
+                break;
+            }
+            let has_comma = self.eat(",");
+            self.eat_ws();
+            if first && has_comma {
+                return Err(self.error(", not allowed before first item", &[]));
+            }
+            if self.at("}") && has_comma && !self.config.trailing_comma {
+                return Err(self.error("trailing , not allowed", &[]));
+            }
+            if self.eat("}") {
+                break;
+            }
+            if !first && !has_comma {
+                return Err(self.error("expected , after list item", &[",", "}"]));
+            }
+            if self
+                .config
+                .max_elements
+                .is_some_and(|max| list.len() + map.len() >= max)
+            {
+                loop {
+                    self.skip_to_delimiter();
+                    if !self.eat(",") {
+                        break;
+                    }
+                }
+                self.expect("}", "missing closing }")?;
+                truncated = true;
+                break;
+            }
+
+            self.eat_ws();
+            let bracket = self.eat("[");
+            let is_base = !bracket && self.at_base_class();
+            let is_field = !bracket && !is_base && self.at_field();
+            // a member of an anonymous struct or union in a struct, printed
+            // without a name
+            let is_anonymous =
+                !bracket && !is_base && !is_field && is_map && !indexed && self.at("{");
+            if bracket {
+                indexed = true;
+                if first {
+                    is_map = true;
+                } else if !is_map {
+                    return Err(self.error("can't mix list and map", &[]));
+                }
+            } else if is_map && !is_field && !is_base && !is_anonymous {
+                return Err(self.error("can't mix list and map", &["[", "field name"]));
+            }
+            if is_base {
+                if !first && !is_map {
+                    self.anonymous_members(&mut list, &mut map)?;
+                }
+                is_map = true;
+                let start = self.pos;
+                self.advance();
+                self.parse_angled()?;
+                let k = Value::String(self.src[start..self.pos].into());
+                let key = start..self.pos;
+                self.eat_ws();
+                self.expect("=", "expected a = after base class")?;
+                let v = self.parse_value()?;
+                self.span_key(key, false);
+                map.push((k, v));
+            } else if is_field {
+                if !first && !is_map {
+                    self.anonymous_members(&mut list, &mut map)?;
+                }
+                is_map = true;
+                let is_static = self.eat("static ");
+                self.eat_ws();
+                let key = self.pos;
+                let k = Value::String(self.parse_field_name().into());
+                let key = key..self.pos;
+                self.eat_ws();
+                self.expect("=", "expected a = after field")?;
+                let v = self.parse_value()?;
+                self.span_key(key, false);
+                map.push((
+                    k,
+                    if is_static {
+                        Value::Static(Box::new(v))
+                    } else {
+                        v
+                    },
+                ));
+            } else if is_anonymous {
+                let v = self.parse_value()?;
+                map.push((Value::String(ANONYMOUS.into()), v));
+            } else if is_map {
+                let key = self.pos - 1;
+                let k = self.parse_value()?;
+                self.eat_ws();
+                self.expect("]", "expected a ]")?;
+                let key = key..self.pos;
+                self.eat_ws();
+                self.expect("=", "expected a = after list key")?;
+                // sparse arrays: `[0] = 0 <repeats 64 times>`
+                let v = self.parse_value()?;
+                let v = self.parse_repeats(v)?;
+                self.span_extend();
+                self.span_key(key, true);
+                map.push((k, v));
+            } else {
+                // elements of a list of char arrays are never joined into
+                // string runs
+                let v = self.parse_single_value()?;
+                list.push(self.parse_repeats(v)?);
+                self.span_extend();
+            }
+            first = false;
+        }
+        let v = if is_map && !indexed {
+            let fields = map.into_iter().map(|(k, v)| match k {
+                Value::String(k) => (k, v),
+                _ => unreachable!("fields have string keys"),
+            });
+            Value::Struct(fields.collect())
+        } else if is_map {
+            Value::Map(map)
+        } else {
+            Value::List(list)
+        };
+        Ok(if truncated {
+            Value::Truncated(Box::new(v))
+        } else {
+            v
+        })
+    }
+
+    /// Turns the elements parsed so far into the anonymous members of a
+    /// struct, when it turns out the braces were a struct after all, as in
+    /// `{{a = 1}, x = 2}`.
+    fn anonymous_members(
+        &self,
+        list: &mut Vec<Value<'a>>,
+        map: &mut Vec<(Value<'a>, Value<'a>)>,
+    ) -> Result<(), ParseError> {
+        if !list.iter().all(|v| matches!(v, Value::Struct(_))) {
+            return Err(self.error("can't mix list and map", &[]));
+        }
+        map.extend(list.drain(..).map(|v| (Value::String(ANONYMOUS.into()), v)));
+        Ok(())
+    }
+
+    /// Parses a string literal after the opening `"`, including the
+    /// `<incomplete sequence ...>` and `...` after it if GDB printed them.
+    fn parse_string_value(&mut self) -> Result<Value<'a>, ParseError> {
+        let mut v = Value::String(self.parse_string()?);
+        let start = self.pos;
+        self.eat_ws();
+        if self.eat("<incomplete sequence ") {
+            let mut bytes = Vec::new();
+            while self.eat("\\") {
+                match self.parse_escape()? {
+                    Escape::Byte(b) => bytes.push(b),
+                    Escape::Char(ch) => bytes.extend(ch.to_string().bytes()),
+                }
+            }
+            self.expect(">", "expected > after incomplete sequence")?;
+            v = Value::Incomplete {
+                value: Box::new(v),
+                bytes,
+            };
+        } else {
+            self.goto(start);
+        }
+        Ok(if self.eat("...") {
+            Value::Truncated(Box::new(v))
+        } else {
+            v
+        })
+    }
+
+    /// Wraps `v` in [`Value::Repeated`] if a `<repeats N times>` follows.
+    pub fn parse_repeats(&mut self, v: Value<'a>) -> Result<Value<'a>, ParseError> {
+        let start = self.pos;
+        self.eat_ws();
+        if !self.eat("<repeats") {
+            self.goto(start);
+            return Ok(v);
+        }
+        self.eat_ws();
+        let digits = self.pos;
+        while self.current().is_ascii_digit() {
+            self.advance();
+        }
+        let n = self.src[digits..self.pos]
+            .parse()
+            .map_err(|_| self.error("expected a repeat count", &["number"]))?;
+        self.eat_ws();
+        self.expect("times>", "expected times> after repeat count")?;
+        Ok(Value::Repeated(Box::new(v), n))
+    }
+
+    /// Parses the rest of a string literal after the opening `"`, borrowing
+    /// it from the input unless it contains escapes.
+    ///
+    /// Consecutive octal and hex escapes of bytes are decoded together as
+    /// UTF-8, so `"\303\251"` is `"é"`. Bytes that aren't part of valid UTF-8
+    /// become the character with the same code, `"\377"` is `"\u{ff}"`.
+    pub fn parse_string(&mut self) -> Result<Cow<'a, str>, ParseError> {
+        let mut s = Cow::Borrowed("");
+        let mut bytes = Vec::new();
+        let mut run = self.pos;
+        while !self.at_eof() && !self.at("\"") {
+            if self.at("\\") {
+                s.to_mut().push_str(&self.src[run..self.pos]);
+                self.advance();
+                match self.parse_escape()? {
+                    Escape::Byte(b) => bytes.push(b),
+                    Escape::Char(ch) => {
+                        push_bytes(s.to_mut(), &mut bytes);
+                        s.to_mut().push(ch);
+                    }
+                }
+                run = self.pos;
+            } else {
+                if !bytes.is_empty() {
+                    push_bytes(s.to_mut(), &mut bytes);
+                }
+                self.advance();
+            }
+        }
+        if !bytes.is_empty() {
+            push_bytes(s.to_mut(), &mut bytes);
+        }
+        let rest = &self.src[run..self.pos.min(self.src.len())];
+        self.expect("\"", "missing closing \"")?;
+        match &mut s {
+            Cow::Borrowed(_) => s = Cow::Borrowed(rest),
+            Cow::Owned(s) => s.push_str(rest),
+        }
+        Ok(s)
+    }
+
+    /// Parses the `<symbol+offset>` and `"string"` annotations that may follow
+    /// an address, returning `None` if there are none.
+    pub fn parse_pointer(&mut self, address: u64) -> Result<Option<Value<'a>>, ParseError> {
+        let start = self.pos;
+        self.eat_ws();
+        let mut symbol = None;
+        if self.at("<error") {
+            self.advance();
+            let pointee = self.parse_error_text()?;
+            return Ok(Some(Value::Pointer {
+                address,
+                symbol,
+                pointee: Some(Box::new(pointee)),
+            }));
+        }
+        if self.at("<") && !self.at("<repeats ") {
+            self.advance();
+            symbol = Some(self.parse_angled()?.into());
+            self.eat_ws();
+        }
+        let encoding = self.eat_encoding_prefix();
+        let pointee = if self.eat("\"") {
+            let pointee = self.parse_string_value()?;
+            Some(Box::new(match encoding {
+                Some(encoding) => Value::Encoded {
+                    encoding,
+                    value: Box::new(pointee),
+                },
+                None => pointee,
+            }))
+        } else {
+            None
+        };
+        if symbol.is_none() && pointee.is_none() {
+            self.goto(start);
+            return Ok(None);
+        }
+        Ok(Some(Value::Pointer {
+            address,
+            symbol,
+            pointee,
+        }))
+    }
+
+    /// Parses the rest of a pointer to member after the `&`: `Klass::field`,
+    /// or a method like `virtual Klass::method(int)`.
+    pub fn parse_member_pointer(&mut self) -> Result<Value<'a>, ParseError> {
+        let start = self.pos;
+        self.eat("virtual ");
+        if !self.at_ident_start() {
+            return Err(self.error("expected a member name after &", &["identifier"]));
+        }
+        self.parse_qualified_ident();
+        if self.eat("(") {
+            while !self.eat(")") {
+                if self.at_eof() {
+                    return Err(self.error("missing closing )", &[")"]));
+                }
+                self.advance();
+            }
+        }
+        Ok(Value::MemberPointer(self.src[start..self.pos].into()))
+    }
+
+    /// Parses the rest of an `<error...>` diagnostic after the `<`.
+    pub fn parse_error_text(&mut self) -> Result<Value<'a>, ParseError> {
+        let text = self.parse_angled()?;
+        Ok(Value::Error(
+            text.strip_prefix("error: ").unwrap_or(text).into(),
+        ))
+    }
+
+    /// Parses an escape sequence in a string or character literal after the
+    /// `\\`: one of C's simple escapes, an octal escape of up to three
+    /// digits, a hex escape `\xNN` or a universal character name `\uNNNN` or
+    /// `\UNNNNNNNN`. Octal and hex escapes of values above 0xff are
+    /// characters, as in wide strings.
+    fn parse_escape(&mut self) -> Result<Escape, ParseError> {
+        let e = self.current();
+        let numeric = if e.is_digit(8) {
+            Some((8, 3))
+        } else if e == 'x' {
+            Some((16, usize::MAX))
+        } else if e == 'u' {
+            Some((16, 4))
+        } else if e == 'U' {
+            Some((16, 8))
+        } else {
+            None
+        };
+        if let Some((radix, max_digits)) = numeric {
+            if radix == 16 {
+                self.advance();
+            }
+            let start = self.pos;
+            while self.pos - start < max_digits && self.current().is_digit(radix) {
+                self.advance();
+            }
+            let digits = &self.src[start..self.pos];
+            // universal character names take exactly their number of digits
+            let universal = e == 'u' || e == 'U';
+            let code = u32::from_str_radix(digits, radix)
+                .ok()
+                .filter(|_| !digits.is_empty() && (!universal || digits.len() == max_digits))
+                .ok_or_else(|| self.error("invalid escape", &[]))?;
+            return match u8::try_from(code) {
+                Ok(b) if !universal => Ok(Escape::Byte(b)),
+                _ => char::from_u32(code)
+                    .map(Escape::Char)
+                    .ok_or_else(|| self.error("invalid escape", &[])),
+            };
+        }
+        let ch = match e {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            'a' => '\x07',
+            'b' => '\x08',
+            'f' => '\x0c',
+            'v' => '\x0b',
+            'e' => '\x1b',
+            '\\' | '\'' | '"' | '?' => e,
+            _ => return Err(self.error("unknown escape", &[])),
+        };
+        self.advance();
+        Ok(Escape::Char(ch))
+    }
+
+    /// Parses the rest of a character literal after the opening `'`.
+    pub fn parse_char(&mut self) -> Result<char, ParseError> {
+        let ch = if self.eat("\\") {
+            match self.parse_escape()? {
+                Escape::Byte(b) => char::from(b),
+                Escape::Char(ch) => ch,
+            }
+        } else if self.at_eof() {
+            return Err(self.error("missing closing '", &["'"]));
+        } else {
+            self.eat_current()
+        };
+        self.expect("'", "missing closing '")?;
+        Ok(ch)
+    }
+
+    /// Parses a decimal number with an optional exponent, or an integer with a `0x`, `0o` or `0b`
+    /// prefix as printed with a non-default `output-radix`. Integers parse to [`Value::Int`] or
+    /// [`Value::Uint`], anything with a fraction or exponent to [`Value::Float`]. The special
+    /// floats `inf`, `-inf` and `nan(0x8000000000000)` parse too, keeping the payload of NaNs.
+    pub fn parse_number(&mut self) -> Result<Value<'a>, ParseError> {
+        let start = self.pos;
+        let invalid = |p: &Self| {
+            let mut err = p.error("invalid number", &[]);
+            err.offset = start;
+            err
+        };
+        let negative = self.eat("-");
+        if let Some(n) = self.parse_special_float(negative)? {
+            return Ok(Value::Float(n));
+        }
+        let radix = if self.eat("0x") || self.eat("0X") {
+            16
+        } else if self.eat("0o") {
+            8
+        } else if self.eat("0b") {
+            2
+        } else {
+            10
+        };
+        if radix != 10 {
+            let digits = self.pos;
+            while self.current().is_digit(radix) {
+                self.advance();
+            }
+            let n = u128::from_str_radix(&self.src[digits..self.pos], radix)
+                .map_err(|_| invalid(self))?;
+            if !negative {
+                return Ok(Value::Uint(n));
+            }
+            return i128::try_from(n)
+                .map(|n| Value::from(-n))
+                .map_err(|_| invalid(self));
+        }
+        let mut float = false;
+        while !self.at_eof() {
+            let curr = self.current();
+            // a .. is the start of a `...` after the number
+            if curr.is_ascii_digit() || (curr == '.' && !self.at("..")) {
+                float |= curr == '.';
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+        // an exponent, only if digits follow so `1e` isn't eaten
+        let mantissa_end = self.pos;
+        if self.eat("e") || self.eat("E") {
+            if !self.eat("+") {
+                self.eat("-");
+            }
+            if self.current().is_ascii_digit() {
+                float = true;
+                while self.current().is_ascii_digit() {
+                    self.advance();
+                }
+            } else {
+                self.goto(mantissa_end);
+            }
+        }
+        let text = &self.src[start..self.pos];
+        let n = if float {
+            text.parse().map(Value::Float).ok()
+        } else if negative {
+            text.parse::<i128>().map(Value::from).ok()
+        } else {
+            text.parse().map(Value::Uint).ok()
+        };
+        n.ok_or_else(|| invalid(self))
+    }
+
+    /// Parses `inf`, `nan` or `nan(0x...)`, how GDB prints floats that
+    /// aren't numbers, after the sign if there was one. The hex digits are the
+    /// NaN's payload, the low 52 bits of the double.
+    fn parse_special_float(&mut self, negative: bool) -> Result<Option<f64>, ParseError> {
+        const MANTISSA: u64 = (1 << 52) - 1;
+        let start = self.pos;
+        let n = if self.eat("inf") {
+            f64::INFINITY
+        } else if self.eat("nan") {
+            let payload = if self.eat("(0x") {
+                let digits = self.pos;
+                while self.current().is_ascii_hexdigit() {
+                    self.advance();
+                }
+                let payload = u64::from_str_radix(&self.src[digits..self.pos], 16)
+                    .ok()
+                    .filter(|&n| n != 0 && n <= MANTISSA)
+                    .ok_or_else(|| self.error("invalid nan payload", &["hex digits"]))?;
+                self.expect(")", "expected ) after nan payload")?;
+                payload
+            } else {
+                f64::NAN.to_bits() & MANTISSA
+            };
+            f64::from_bits(f64::INFINITY.to_bits() | payload)
+        } else {
+            return Ok(None);
+        };
+        if self.at_ident_char() {
+            // an identifier like `information`
+            self.goto(start);
+            return Ok(None);
+        }
+        Ok(Some(if negative { -n } else { n }))
+    }
+
+    /// Parses the rest of a type cast after the `(`, like `Foo *)` or
+    /// `void (*)(int))`, and returns the type.
+    pub fn parse_cast(&mut self) -> Result<&'a str, ParseError> {
+        let start = self.pos;
+        let mut depth = 1;
+        while depth > 0 {
+            if self.at_eof() {
+                return Err(self.error("missing closing )", &[")"]));
+            }
+            match self.eat_current() {
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                _ => {}
+            }
+        }
+        Ok(self.src[start..self.pos - 1].trim())
+    }
+
+    /// Parses the rest of a reference after the `@`: the address, a `:` and
+    /// the value referred to.
+    pub fn parse_reference(&mut self) -> Result<Value<'a>, ParseError> {
+        if !self.at("0x") {
+            return Err(self.error("expected an address after @", &["0x"]));
+        }
+        let address = self
+            .parse_number()?
+            .as_uint()
+            .and_then(|n| u64::try_from(n).ok())
+            .ok_or_else(|| self.error("address out of range", &[]))?;
+        self.eat_ws();
+        self.expect(":", "expected a : after reference address")?;
+        let value = self.parse_value()?;
+        Ok(Value::Reference {
+            address,
+            value: Box::new(value),
+        })
+    }
+
+    pub fn remove_reference(&mut self) {
+        while !self.at_eof() && !self.eat(":") {
+            self.advance()
+        }
+    }
+
+    pub fn parse_value(&mut self) -> Result<Value<'a>, ParseError> {
+        let v = match self.parse_single_value()? {
+            Value::Encoded { encoding, value } => Value::Encoded {
+                encoding,
+                value: Box::new(self.parse_runs_after(*value)?),
+            },
+            v => self.parse_runs_after(v)?,
+        };
+        self.span_extend();
+        Ok(v)
+    }
+
+    /// Parses the rest of a character array if `v` starts one.
+    fn parse_runs_after(&mut self, v: Value<'a>) -> Result<Value<'a>, ParseError> {
+        match v {
+            Value::String(_) => self.parse_string_runs(v),
+            Value::Char { .. } => {
+                let v = self.parse_repeats(v)?;
+                if matches!(v, Value::Repeated(..)) {
+                    self.parse_string_runs(v)
+                } else {
+                    Ok(v)
+                }
+            }
+            v => Ok(v),
+        }
+    }
+
+    /// Collects the `, "..."` and `, 'c' <repeats N times>` segments that
+    /// follow `first` in a character array into [`Value::StringRuns`]. A
+    /// lone string is returned as is.
+    pub fn parse_string_runs(&mut self, first: Value<'a>) -> Result<Value<'a>, ParseError> {
+        let mut segments = vec![first];
+        let mut truncated = false;
+        loop {
+            if matches!(segments.last(), Some(Value::Repeated(..))) && self.eat("...") {
+                truncated = true;
+            }
+            // a string cut short ends the whole array
+            if let Some(Value::Truncated(_)) = segments.last() {
+                if let Some(Value::Truncated(s)) = segments.pop() {
+                    segments.push(*s);
+                }
+                truncated = true;
+            }
+            if truncated {
+                break;
+            }
+            let start = self.pos;
+            self.eat_ws();
+            if !self.eat(",") {
+                self.goto(start);
+                break;
+            }
+            self.eat_ws();
+            // later segments repeat the prefix of the first
+            self.eat_encoding_prefix();
+            if self.eat("\"") {
+                segments.push(self.parse_string_value()?);
+            } else if self.eat("'") {
+                let ch = self.parse_char()?;
+                match self.parse_repeats(Value::from(ch))? {
+                    v @ Value::Repeated(..) => segments.push(v),
+                    _ => {
+                        self.goto(start);
+                        break;
+                    }
+                }
+            } else {
+                self.goto(start);
+                break;
+            }
+        }
+        let v = if segments.len() == 1 && matches!(segments[0], Value::String(_)) {
+            segments.pop().unwrap()
+        } else {
+            Value::StringRuns(segments)
+        };
+        Ok(if truncated {
+            Value::Truncated(Box::new(v))
+        } else {
+            v
+        })
+    }
+
+    /// Eats the encoding prefix of a string or character literal, if a
+    /// quote follows it.
+    pub fn eat_encoding_prefix(&mut self) -> Option<Encoding> {
+        let encoding = [
+            Encoding::Utf8,
+            Encoding::Wide,
+            Encoding::Utf16,
+            Encoding::Utf32,
+        ]
+        .into_iter()
+        .find(|e| {
+            let prefix = e.prefix();
+            self.at(prefix) && {
+                let rest = &self.src[self.pos + prefix.len()..];
+                rest.starts_with('"') || rest.starts_with('\'')
+            }
+        })?;
+        self.eat(encoding.prefix());
+        Some(encoding)
+    }
+
+    /// Parses one value, without joining a character array printed as
+    /// several segments.
+    fn parse_single_value(&mut self) -> Result<Value<'a>, ParseError> {
+        self.eat_ws();
+        let start = self.pos;
+        self.span_open();
+        let v = if self.depth >= self.config.max_depth {
+            Err(self.error("value nested too deeply", &[]))
+        } else {
+            self.depth += 1;
+            let v = self.parse_nested_value();
+            self.depth -= 1;
+            v
+        };
+        let v = match v {
+            Err(e) if self.recover => {
+                self.diagnostics.push(e);
+                self.goto(start);
+                self.skip_to_delimiter();
+                let raw = self.src[start..self.pos].trim_end();
+                self.goto(start + raw.len());
+                self.span_discard_children();
+                Ok(Value::Raw(raw.into()))
+            }
+            v => v,
+        };
+        self.span_close(start, v.is_ok());
+        v
+    }
+
+    /// Moves to the next `,` or `}` that isn't inside braces or a string or
+    /// character literal, or to the end of the input.
+    fn skip_to_delimiter(&mut self) {
+        let mut depth = 0usize;
+        let mut quote = None;
+        while !self.at_eof() {
+            let ch = self.current();
+            match quote {
+                // a `\` at the end escapes nothing, and skipping it and what
+                // it escapes would go past the end
+                Some(_) if ch == '\\' && self.pos + 1 < self.src.len() => self.advance(),
+                Some(q) if ch == q => quote = None,
+                Some(_) => {}
+                None => match ch {
+                    ',' | '}' if depth == 0 => return,
+                    '{' => depth += 1,
+                    '}' => depth -= 1,
+                    '"' | '\'' => quote = Some(ch),
+                    _ => {}
+                },
+            }
+            self.advance();
+        }
+    }
+
+    fn parse_nested_value(&mut self) -> Result<Value<'a>, ParseError> {
+        self.eat_ws();
+        if self.eat("{") {
+            self.parse_list_or_map()
+        } else if self.eat("(") {
+            let ty = self.parse_cast()?;
+            self.eat_ws();
+            Ok(Value::Typed {
+                ty: ty.into(),
+                value: Box::new(self.parse_single_value()?),
+            })
+        } else if let Some(encoding) = self.eat_encoding_prefix() {
+            Ok(Value::Encoded {
+                encoding,
+                value: Box::new(self.parse_single_value()?),
+            })
+        } else if self.eat("\"") {
+            self.parse_string_value()
+        } else if self.current().is_ascii_digit() || self.current() == '-' {
+            let literal = self.pos;
+            let n = self.parse_number()?;
+            if self.src[literal..self.pos].starts_with("0x") {
+                if let Some(address) = n.as_uint().and_then(|n| u64::try_from(n).ok()) {
+                    if let Some(pointer) = self.parse_pointer(address)? {
+                        return Ok(pointer);
+                    }
+                }
+            }
+            let start = self.pos;
+            self.eat_ws();
+            let code = n.as_int().and_then(|n| i64::try_from(n).ok());
+            let encoding = self.eat_encoding_prefix();
+            if let Some(code) = code.filter(|_| self.eat("'")) {
+                let ch = self.parse_char()?;
+                Ok(match encoding {
+                    Some(encoding) => Value::Encoded {
+                        encoding,
+                        value: Box::new(Value::Char { code, ch }),
+                    },
+                    None => Value::Char { code, ch },
+                })
+            } else {
+                self.goto(start);
+                Ok(self.config.numbers.apply(n))
+            }
+        } else if self.eat("'") {
+            let ch = self.parse_char()?;
+            Ok(Value::Char {
+                code: ch as i64,
+                ch,
+            })
+        } else if let Some(n) = self.parse_special_float(false)? {
+            Ok(Value::Float(n))
+        } else if let Some(pointer) = self.parse_smart_pointer()? {
+            Ok(pointer)
+        } else if self.at_description() {
+            self.parse_described()
+        } else if self.at_ident_start() {
+            Ok(match self.parse_qualified_ident() {
+                "true" => Value::Bool(true),
+                "false" => Value::Bool(false),
+                name => Value::Enum(name.into()),
+            })
+        } else if self.eat("<optimized out>") {
+            Ok(Value::OptimizedOut)
+        } else if self.at("<error") {
+            self.advance();
+            self.parse_error_text()
+        } else if !self.config.keep_references && self.eat("@0x") {
+            self.remove_reference();
+            self.parse_value()
+        } else if self.eat("@") {
+            self.parse_reference()
+        } else if self.config.dialect.cpp() && self.eat("&") {
+            self.parse_member_pointer()
+        } else {
+            Err(self.error(
+                "expected a value",
+                &[
+                    "{",
+                    "\"",
+                    "'",
+                    "(",
+                    "number",
+                    "identifier",
+                    "<optimized out>",
+                    "&",
+                    "@",
+                    "<error>",
+                ],
+            ))
+        }
+    }
+
+    /// Parses the whole input without giving up. A value that doesn't parse
+    /// becomes a [`Value::Raw`] of its text and parsing goes on after it, at
+    /// the next `,` or `}` of the list or struct it's in. If an aggregate
+    /// can't be made sense of, it's the whole aggregate that becomes raw
+    /// text. Returns the errors that were recovered from along with the
+    /// value.
+    pub fn parse_value_lossy(&mut self) -> (Value<'a>, Vec<ParseError>) {
+        self.recover = true;
+        let start = self.pos;
+        // parse_single_value never fails while recovering, but what follows
+        // it in a string or `<repeats>` can
+        let v = self.parse_value().and_then(|v| {
+            self.eat_ws();
+            if self.at_eof() {
+                Ok(v)
+            } else {
+                Err(self.error("unexpected text after value", &[]))
+            }
+        });
+        let v = v.unwrap_or_else(|e| {
+            self.diagnostics.push(e);
+            Value::Raw(self.src[start..].trim().into())
+        });
+        self.goto(self.src.len());
+        self.recover = false;
+        (v, std::mem::take(&mut self.diagnostics))
+    }
+
+    /// Parses a line of `print` output from GDB's console, like
+    /// `$1 = {x = 1}`, to the end of the input. The `$N = ` prefix is
+    /// optional.
+    pub fn parse_print_output(&mut self) -> Result<PrintOutput<'a>, ParseError> {
+        self.eat_ws();
+        let mut history = None;
+        if self.eat("$") {
+            let digits = self.pos;
+            while self.current().is_ascii_digit() {
+                self.advance();
+            }
+            history = Some(
+                self.src[digits..self.pos]
+                    .parse()
+                    .map_err(|_| self.error("expected a history number", &["number"]))?,
+            );
+            self.eat_ws();
+            self.expect("=", "expected a = after history number")?;
+        }
+        let value = self.parse_value()?;
+        self.eat_ws();
+        if !self.at_eof() {
+            return Err(self.error("unexpected text after value", &[]));
+        }
+        Ok(PrintOutput { history, value })
+    }
+
+    /// [`Parser::parse_value`] for tests and trusted input, panicking with
+    /// the error message if it fails.
+    pub fn parse_value_or_panic(&mut self) -> Value<'a> {
+        self.parse_value().unwrap_or_else(|e| panic!("{e}"))
+    }
+}
+
+impl<'a> From<&'a str> for Value<'a> {
+    fn from(v: &'a str) -> Self {
+        Self::String(v.into())
+    }
+}
+
+impl From<String> for Value<'_> {
+    fn from(v: String) -> Self {
+        Self::String(v.into())
+    }
+}
+
+impl From<f64> for Value<'_> {
+    fn from(n: f64) -> Self {
+        Self::Float(n)
+    }
+}
+
+impl From<i128> for Value<'_> {
+    fn from(n: i128) -> Self {
+        if n < 0 {
+            Self::Int(n)
+        } else {
+            Self::Uint(n as u128)
+        }
+    }
+}
+
+impl From<u128> for Value<'_> {
+    fn from(n: u128) -> Self {
+        Self::Uint(n)
+    }
+}
+
+impl From<i64> for Value<'_> {
+    fn from(n: i64) -> Self {
+        Self::from(n as i128)
+    }
+}
+
+impl From<u64> for Value<'_> {
+    fn from(n: u64) -> Self {
+        Self::Uint(n as u128)
+    }
+}
+
+impl From<i32> for Value<'_> {
+    fn from(n: i32) -> Self {
+        Self::from(n as i128)
+    }
+}
+
+impl From<char> for Value<'_> {
+    fn from(ch: char) -> Self {
+        Self::Char {
+            code: ch as i64,
+            ch,
+        }
+    }
+}
+
+impl From<bool> for Value<'_> {
+    fn from(b: bool) -> Self {
+        Self::Bool(b)
+    }
+}
+
+#[macro_export]
+macro_rules! value {
+    ({
+        $($k:tt => $v:tt),*
+    }) => {
+        Value::Map(vec![$(($crate::value!($k), $crate::value!($v))),*])
+    };
+    ({
+        $($k:literal = $v:tt),+
+    }) => {
+        Value::Struct(vec![$(($k.into(), $crate::value!($v))),+])
+    };
+    ([$($va:tt),*]) => {{
+        Value::List(vec![$($crate::value!($va)),*])
+    }};
+    (optimized_out) => {
+        Value::OptimizedOut
+    };
+    ($s:literal) => {
+        Value::from($s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_value_completely(text: &str) -> Value<'_> {
+        let mut p = Parser::new(text);
+        let val = p.parse_value_or_panic();
+        assert!(p.at_eof(), "parser didn't parse complete input");
+        val
+    }
+
+    fn check_parser(text: &str, expect_value: Value) {
+        let parsed_value = parse_value_completely(text);
+        assert_eq!(parsed_value, expect_value);
+    }
+
+    #[test]
+    fn bool() {
+        check_parser("true", value!(true));
+        check_parser("false", value!(false));
+    }
+
+    #[test]
+    fn number() {
+        check_parser("1", value!(1));
+    }
+
+    #[test]
+    fn negative_number() {
+        check_parser("-1", value!(-1));
+    }
+
+    #[test]
+    fn truncated_list() {
+        check_parser(
+            "{1, 2, 3...}",
+            Value::Truncated(Box::new(value!([1, 2, 3]))),
+        );
+    }
+
+    #[test]
+    fn truncated_map_and_repeats() {
+        check_parser(
+            "{[0] = 1, [1] = 2...}",
+            Value::Truncated(Box::new(value!({0 => 1, 1 => 2}))),
+        );
+        check_parser(
+            "{0 <repeats 200 times>...}",
+            Value::Truncated(Box::new(Value::List(vec![Value::Repeated(
+                Box::new(value!(0)),
+                200,
+            )]))),
+        );
+    }
+
+    #[test]
+    fn truncated_string() {
+        check_parser(
+            r#"{s = "abcdef"..., p = 0x4005 "xyz"...}"#,
+            Value::Struct(vec![
+                ("s".into(), Value::Truncated(Box::new(value!("abcdef")))),
+                (
+                    "p".into(),
+                    Value::Pointer {
+                        address: 0x4005,
+                        symbol: None,
+                        pointee: Some(Box::new(Value::Truncated(Box::new(value!("xyz"))))),
+                    },
+                ),
+            ]),
+        );
+    }
+
+    #[test]
+    fn ellipsis_must_end_list() {
+        let err = Parser::new("{1..., 2}").parse_value().unwrap_err();
+        assert_eq!(err.message, "expected } after ...");
+    }
+
+    #[test]
+    fn string_runs() {
+        let v = parse_value_completely(r#"'x' <repeats 20 times>, "tail""#);
+        assert_eq!(
+            v,
+            Value::StringRuns(vec![
+                Value::Repeated(Box::new(value!('x')), 20),
+                value!("tail"),
+            ])
+        );
+        assert_eq!(v.expanded(), Value::from(format!("{}tail", "x".repeat(20))));
+    }
+
+    #[test]
+    fn string_runs_in_struct() {
+        check_parser(
+            r#"{buf = "ab", '\000' <repeats 6 times>, "cd", n = 1}"#,
+            Value::Struct(vec![
+                (
+                    "buf".into(),
+                    Value::StringRuns(vec![
+                        value!("ab"),
+                        Value::Repeated(Box::new(value!('\0')), 6),
+                        value!("cd"),
+                    ]),
+                ),
+                ("n".into(), value!(1)),
+            ]),
+        );
+    }
+
+    #[test]
+    fn lone_char_run() {
+        check_parser(
+            r"'\000' <repeats 255 times>",
+            Value::StringRuns(vec![Value::Repeated(Box::new(value!('\0')), 255)]),
+        );
+    }
+
+    #[test]
+    fn truncated_string_runs() {
+        let v = parse_value_completely(r#""ab", 'z' <repeats 10 times>, "cdef"..."#);
+        assert_eq!(
+            v.expanded(),
+            Value::Truncated(Box::new(value!("abzzzzzzzzzzcdef")))
+        );
+        check_parser(
+            "'a' <repeats 200 times>...",
+            Value::Truncated(Box::new(Value::StringRuns(vec![Value::Repeated(
+                Box::new(value!('a')),
+                200,
+            )]))),
+        );
+    }
+
+    #[test]
+    fn string_list_not_joined() {
+        check_parser(r#"{"ab", "cd"}"#, value!(["ab", "cd"]));
+        check_parser(
+            r#"{x = "ab", [1] = 2}"#,
+            Value::Map(vec![(value!("x"), value!("ab")), (value!(1), value!(2))]),
+        );
+    }
+
+    fn described<'a>(description: &'a str, value: Option<Value<'a>>) -> Value<'a> {
+        Value::Described {
+            description: description.into(),
+            value: value.map(Box::new),
+        }
+    }
+
+    #[test]
+    fn vector_header() {
+        check_parser(
+            "std::vector of length 3, capacity 4 = {1, 2, 3}",
+            described(
+                "std::vector of length 3, capacity 4",
+                Some(value!([1, 2, 3])),
+            ),
+        );
+    }
+
+    #[test]
+    fn map_header_in_struct() {
+        check_parser(
+            "{m = std::map with 1 element = {[1] = 2}, n = 3}",
+            Value::Struct(vec![
+                (
+                    "m".into(),
+                    described("std::map with 1 element", Some(value!({1 => 2}))),
+                ),
+                ("n".into(), value!(3)),
+            ]),
+        );
+    }
+
+    #[test]
+    fn header_without_value() {
+        check_parser(
+            "{v = std::vector of length 0, capacity 0, w = empty std::__cxx11::list}",
+            Value::Struct(vec![
+                (
+                    "v".into(),
+                    described("std::vector of length 0, capacity 0", None),
+                ),
+                ("w".into(), described("empty std::__cxx11::list", None)),
+            ]),
+        );
+    }
+
+    #[test]
+    fn template_headers() {
+        check_parser(
+            "std::unique_lock<std::mutex> = {_M_device = 0x0, _M_owns = false}",
+            described(
+                "std::unique_lock<std::mutex>",
+                Some(value!({"_M_device" = 0, "_M_owns" = false})),
+            ),
+        );
+        check_parser(
+            "std::optional<int> [no contained value]",
+            described("std::optional<int> [no contained value]", None),
+        );
+    }
+
+    #[test]
+    fn smart_pointers() {
+        let smart = |ty: &'static str, use_count, weak_count, pointer| Value::SmartPointer {
+            ty: ty.into(),
+            use_count,
+            weak_count,
+            pointer: Box::new(pointer),
+        };
+        check_parser(
+            "std::shared_ptr<int> (use count 1, weak count 0) = {get() = 0x4172b0}",
+            smart("std::shared_ptr<int>", Some(1), Some(0), value!(0x4172b0)),
+        );
+        check_parser(
+            "{a = std::unique_ptr<Foo> containing 0x55555556aeb0, b = std::unique_ptr<Foo> = {get() = 0x0}}",
+            Value::Struct(vec![
+                (
+                    "a".into(),
+                    smart("std::unique_ptr<Foo>", None, None, value!(0x55555556aeb0_u64)),
+                ),
+                ("b".into(), smart("std::unique_ptr<Foo>", None, None, value!(0))),
+            ]),
+        );
+        check_parser(
+            "{std::shared_ptr<std::map<int, int>> (empty) = {get() = 0x0}, std::weak_ptr<Foo> (expired, weak count 1) = {get() = 0x4172b0 <g_foo>}}",
+            Value::List(vec![
+                smart("std::shared_ptr<std::map<int, int>>", Some(0), Some(0), value!(0)),
+                smart(
+                    "std::weak_ptr<Foo>",
+                    Some(0),
+                    Some(1),
+                    Value::Pointer {
+                        address: 0x4172b0,
+                        symbol: Some("g_foo".into()),
+                        pointee: None,
+                    },
+                ),
+            ]),
+        );
+        // forms other than the libstdc++ ones stay descriptions
+        check_parser(
+            "std::unique_ptr<Foo> = {_M_t = 0x0}",
+            described("std::unique_ptr<Foo>", Some(value!({ "_M_t" = 0 }))),
+        );
+    }
+
+    #[test]
+    fn rust_headers() {
+        check_parser(
+            "Vec(size=3) = {1, 2, 3}",
+            described("Vec(size=3)", Some(value!([1, 2, 3]))),
+        );
+        check_parser(
+            "&[i32](size=2) = {-1, 2}",
+            described(
+                "&[i32](size=2)",
+                Some(Value::List(vec![value!(-1), value!(2)])),
+            ),
+        );
+        check_parser(
+            r#"{s = "hello", v = Vec(size=0), m = HashMap(size=1) = {["a"] = 1}, r = Rc(strong=2, weak=0) = 5}"#,
+            Value::Struct(vec![
+                ("s".into(), value!("hello")),
+                ("v".into(), described("Vec(size=0)", None)),
+                (
+                    "m".into(),
+                    described("HashMap(size=1)", Some(value!({"a" => 1}))),
+                ),
+                (
+                    "r".into(),
+                    described("Rc(strong=2, weak=0)", Some(value!(5))),
+                ),
+            ]),
+        );
+    }
+
+    #[test]
+    fn enum_is_not_header() {
+        check_parser(
+            "{RED <repeats 2 times>, GREEN}",
+            Value::List(vec![
+                Value::Repeated(Box::new(Value::Enum("RED".into())), 2),
+                Value::Enum("GREEN".into()),
+            ]),
+        );
+    }
+
+    #[test]
+    fn error_value() {
+        check_parser(
+            "<error: Cannot access memory at address 0x0>",
+            Value::Error("Cannot access memory at address 0x0".into()),
+        );
+        check_parser(
+            "<error reading variable>",
+            Value::Error("error reading variable".into()),
+        );
+    }
+
+    #[test]
+    fn error_fields_dont_abort() {
+        check_parser(
+            "{a = 1, b = <error: Cannot access memory at address 0x8>, c = {<error reading variable>, 3}}",
+            Value::Struct(vec![
+                ("a".into(), value!(1)),
+                ("b".into(),
+                    Value::Error("Cannot access memory at address 0x8".into()),
+                ),
+                ("c".into(),
+                    Value::List(vec![
+                        Value::Error("error reading variable".into()),
+                        value!(3),
+                    ]),
+                ),
+            ]),
+        );
+    }
+
+    #[test]
+    fn pointer_to_unreadable_string() {
+        check_parser(
+            "0x1 <error: Cannot access memory at address 0x1>",
+            Value::Pointer {
+                address: 1,
+                symbol: None,
+                pointee: Some(Box::new(Value::Error(
+                    "Cannot access memory at address 0x1".into(),
+                ))),
+            },
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_representation() {
+        let v =
+            parse_value_completely(r#"{x = 1, [2] = {'a' <repeats 3 times>, <optimized out>}}"#);
+        let json = serde_json::json!({"map": [
+            [{"string": "x"}, {"uint": 1}],
+            [{"uint": 2}, {"list": [
+                {"repeated": [{"char": {"code": 97, "ch": "a"}}, 3]},
+                "optimized_out",
+            ]}],
+        ]});
+        assert_eq!(serde_json::to_value(&v).unwrap(), json);
+        assert_eq!(serde_json::from_value::<Value>(json).unwrap(), v);
+    }
+
+    #[test]
+    fn strings_borrow_input() {
+        let text = String::from(r#"{name = "plain", esc = "a\tb", e = RED}"#);
+        let v = Parser::new(&text).parse_value_or_panic();
+        let fields = v.as_struct().unwrap();
+        assert!(matches!(&fields[0].0, Cow::Borrowed("name")));
+        assert!(matches!(
+            &fields[0].1,
+            Value::String(Cow::Borrowed("plain"))
+        ));
+        assert!(matches!(&fields[1].1, Value::String(Cow::Owned(s)) if s == "a\tb"));
+        assert!(matches!(&fields[2].1, Value::Enum(Cow::Borrowed("RED"))));
+
+        let owned = v.clone().into_owned();
+        drop(text);
+        assert_eq!(owned.as_struct().unwrap()[1].1.as_str(), Some("a\tb"));
+    }
+
+    #[test]
+    fn vtable_pointer() {
+        check_parser(
+            "{_vptr.Base = 0x555555557d40 <vtable for Derived+16>, _vptr$Old = 0x0, x = 1}",
+            Value::Struct(vec![
+                (
+                    "_vptr.Base".into(),
+                    Value::Pointer {
+                        address: 0x555555557d40,
+                        symbol: Some("vtable for Derived+16".into()),
+                        pointee: None,
+                    },
+                ),
+                ("_vptr$Old".into(), value!(0)),
+                ("x".into(), value!(1)),
+            ]),
+        );
+    }
+
+    #[test]
+    fn static_members() {
+        check_parser(
+            "{static count = 3, static npos = 18446744073709551615, x = {static inner = 0x0}}",
+            Value::Struct(vec![
+                ("count".into(), Value::Static(Box::new(value!(3)))),
+                (
+                    "npos".into(),
+                    Value::Static(Box::new(value!(18446744073709551615_u64))),
+                ),
+                (
+                    "x".into(),
+                    Value::Struct(vec![("inner".into(), Value::Static(Box::new(value!(0))))]),
+                ),
+            ]),
+        );
+        check_parser(
+            "{[1] = 2, static max = 4}",
+            Value::Map(vec![
+                (value!(1), value!(2)),
+                (value!("max"), Value::Static(Box::new(value!(4)))),
+            ]),
+        );
+    }
+
+    #[test]
+    fn member_pointers() {
+        check_parser(
+            "{data = &Klass::count, method = &virtual ns::Klass::run(int), nested = &A::B::c}",
+            Value::Struct(vec![
+                ("data".into(), Value::MemberPointer("Klass::count".into())),
+                (
+                    "method".into(),
+                    Value::MemberPointer("virtual ns::Klass::run(int)".into()),
+                ),
+                ("nested".into(), Value::MemberPointer("A::B::c".into())),
+            ]),
+        );
+        let err = Parser::new("&1").parse_value().unwrap_err();
+        assert_eq!(err.message, "expected a member name after &");
+    }
+
+    #[test]
+    fn base_class() {
+        check_parser(
+            "{<Base> = {x = 1}, y = 2}",
+            value!({"<Base>" = {"x" = 1}, "y" = 2}),
+        );
+    }
+
+    #[test]
+    fn template_base_classes() {
+        check_parser(
+            "{<std::_Vector_base<int, std::allocator<int> >> = {_M_impl = 0}, <Empty> = {}}",
+            value!({
+                "<std::_Vector_base<int, std::allocator<int> >>" = {"_M_impl" = 0},
+                "<Empty>" = []
+            }),
+        );
+    }
+
+    #[test]
+    fn struct_and_map_differ() {
+        check_parser("{x = 1}", Value::Struct(vec![("x".into(), value!(1))]));
+        check_parser(r#"{["x"] = 1}"#, Value::Map(vec![(value!("x"), value!(1))]));
+        // both in one aggregate: fields keep string keys in the map
+        check_parser(
+            "{<B> = {}, [0] = 1}",
+            Value::Map(vec![(value!("<B>"), value!([])), (value!(0), value!(1))]),
+        );
+    }
+
+    #[test]
+    fn base_class_after_list_item() {
+        let err = Parser::new("{1, <Base> = {}}").parse_value().unwrap_err();
+        assert_eq!(err.message, "can't mix list and map");
+    }
+
+    #[test]
+    fn enum_value() {
+        check_parser("RED", Value::Enum("RED".into()));
+        check_parser(
+            "std::memory_order_seq_cst",
+            Value::Enum("std::memory_order_seq_cst".into()),
+        );
+    }
+
+    #[test]
+    fn enum_field() {
+        check_parser(
+            "{color = RED, order = std::memory_order_relaxed}",
+            Value::Struct(vec![
+                ("color".into(), Value::Enum("RED".into())),
+                (
+                    "order".into(),
+                    Value::Enum("std::memory_order_relaxed".into()),
+                ),
+            ]),
+        );
+    }
+
+    #[test]
+    fn enum_list_and_keys() {
+        check_parser(
+            "{RED, _GREEN}",
+            Value::List(vec![
+                Value::Enum("RED".into()),
+                Value::Enum("_GREEN".into()),
+            ]),
+        );
+        check_parser(
+            "{[RED] = 1, [BLUE] = 2}",
+            Value::Map(vec![
+                (Value::Enum("RED".into()), value!(1)),
+                (Value::Enum("BLUE".into()), value!(2)),
+            ]),
+        );
+    }
+
+    #[test]
+    fn enum_starting_with_bool() {
+        check_parser("true_color", Value::Enum("true_color".into()));
+        check_parser("{falsey = false}", value!({ "falsey" = false }));
+    }
+
+    #[test]
+    fn trailing_colons_not_part_of_enum() {
+        let mut p = Parser::new("ns::A::");
+        assert_eq!(p.parse_value(), Ok(Value::Enum("ns::A".into())));
+        assert!(p.at("::"));
+    }
+
+    #[test]
+    fn pointer_to_string() {
+        check_parser(
+            r#"0x555555556004 "literal""#,
+            Value::Pointer {
+                address: 0x555555556004,
+                symbol: None,
+                pointee: Some(Box::new(value!("literal"))),
+            },
+        );
+    }
+
+    #[test]
+    fn pointer_to_symbol() {
+        check_parser(
+            "0x401126 <main+6>",
+            Value::Pointer {
+                address: 0x401126,
+                symbol: Some("main+6".into()),
+                pointee: None,
+            },
+        );
+    }
+
+    #[test]
+    fn pointer_to_symbol_and_string() {
+        let v = parse_value_completely(r#"{p = 0x601040 <buf> "hi", q = 0x0}"#);
+        assert_eq!(
+            v,
+            Value::Struct(vec![
+                (
+                    "p".into(),
+                    Value::Pointer {
+                        address: 0x601040,
+                        symbol: Some("buf".into()),
+                        pointee: Some(Box::new(value!("hi"))),
+                    }
+                ),
+                ("q".into(), value!(0)),
+            ])
+        );
+        assert_eq!(v.as_struct().unwrap()[0].1.as_address(), Some(0x601040));
+    }
+
+    #[test]
+    fn pointer_to_template_symbol() {
+        check_parser(
+            "0x401200 <std::vector<int, std::allocator<int> >::size() const+4>",
+            Value::Pointer {
+                address: 0x401200,
+                symbol: Some("std::vector<int, std::allocator<int> >::size() const+4".into()),
+                pointee: None,
+            },
+        );
+    }
+
+    #[test]
+    fn pointer_repeats_are_not_symbols() {
+        check_parser(
+            "{0x0 <repeats 4 times>}",
+            Value::List(vec![Value::Repeated(Box::new(value!(0)), 4)]),
+        );
+    }
+
+    #[test]
+    fn pointer_unclosed_symbol() {
+        let err = Parser::new("0x10 <main").parse_value().unwrap_err();
+        assert_eq!(err.message, "missing closing >");
+    }
+
+    #[test]
+    fn char_literal() {
+        check_parser("97 'a'", value!('a'));
+        check_parser("{c = 65 'A'}", value!({ "c" = 'A' }));
+    }
+
+    #[test]
+    fn char_escapes() {
+        check_parser(r"0 '\000'", value!('\0'));
+        check_parser(r"10 '\n'", value!('\n'));
+        check_parser(r"39 '\''", value!('\''));
+        check_parser(r"65 '\x41'", value!('A'));
+        check_parser(r"233 '\u00e9'", value!('é'));
+    }
+
+    #[test]
+    fn signed_char() {
+        check_parser(
+            r"-1 '\377'",
+            Value::Char {
+                code: -1,
+                ch: '\u{ff}',
+            },
+        );
+    }
+
+    #[test]
+    fn char_without_code() {
+        check_parser(
+            r"{'\000' <repeats 3 times>, 'x'}",
+            Value::List(vec![
+                Value::Repeated(Box::new(value!('\0')), 3),
+                value!('x'),
+            ]),
+        );
+    }
+
+    #[test]
+    fn non_ascii_char() {
+        check_parser("233 'é'", value!('é'));
+    }
+
+    #[test]
+    fn number_followed_by_list_item() {
+        check_parser("{1, 2}", value!([1, 2]));
+    }
+
+    #[test]
+    fn optimized_out() {
+        check_parser("<optimized out>", value!(optimized_out));
+        check_parser(
+            "{x = <optimized out>, y = 2}",
+            value!({"x" = optimized_out, "y" = 2}),
+        );
+    }
+
+    #[test]
+    fn optimized_out_in_list() {
+        check_parser("{1, <optimized out>, 3}", value!([1, optimized_out, 3]));
+    }
+
+    #[test]
+    fn optimized_out_is_not_a_value() {
+        assert_ne!(value!(optimized_out), value!(0));
+        assert_eq!(value!(optimized_out).as_f64(), None);
     }
 
-    pub fn current(&self) -> char {
-        self.src.as_bytes().get(self.pos).copied().unwrap_or(b'\0') as char
+    #[test]
+    fn repeats() {
+        check_parser(
+            "{0 <repeats 100 times>, 1, 2}",
+            Value::List(vec![
+                Value::Repeated(Box::new(value!(0)), 100),
+                value!(1),
+                value!(2),
+            ]),
+        );
     }
 
-    pub fn advance(&mut self) {
-        self.goto(self.pos + 1);
+    #[test]
+    fn repeats_of_struct() {
+        check_parser(
+            "{{x = 1} <repeats 3 times>}",
+            Value::List(vec![Value::Repeated(Box::new(value!({ "x" = 1 })), 3)]),
+        );
     }
 
-    pub fn eat_current(&mut self) -> char {
-        let curr = self.current();
-        self.advance();
-        curr
+    #[test]
+    fn repeats_expanded() {
+        let v = parse_value_completely("{1, 0 <repeats 3 times>, {5 <repeats 2 times>}}");
+        assert_eq!(v.expanded(), value!([1, 0, 0, 0, [5, 5]]));
     }
 
-    pub fn parse_ident(&mut self) -> String {
-        let start = self.pos;
-        while self.current().is_ascii_alphanumeric() {
-            self.advance();
-        }
-        self.src[start..self.pos].to_owned()
+    #[test]
+    fn sparse_repeats() {
+        let v = parse_value_completely(
+            "{[0] = 0 <repeats 3 times>, [100] = 5, [101] = {x = 1} <repeats 2 times>}",
+        );
+        assert_eq!(
+            v,
+            Value::Map(vec![
+                (value!(0), Value::Repeated(Box::new(value!(0)), 3)),
+                (value!(100), value!(5)),
+                (
+                    value!(101),
+                    Value::Repeated(Box::new(value!({ "x" = 1 })), 2)
+                ),
+            ])
+        );
+        assert_eq!(
+            v.expanded(),
+            value!({0 => 0, 1 => 0, 2 => 0, 100 => 5, 101 => {"x" = 1}, 102 => {"x" = 1}})
+        );
     }
 
-    pub fn parse_list_or_map(&mut self) -> Value {
-        let mut first = true;
-        let mut list = Vec::new();
-        let mut map = Vec::new();
-        let mut is_map = false;
-        loop {
-            self.eat_ws();
-            let has_comma = self.eat(",");
-            self.eat_ws();
-            if first {
-                assert!(!has_comma, ", not allowed before first item")
-            }
-            if self.eat("}") {
-                break;
-            }
-            if !first {
-                assert!(has_comma, "expected , after list item");
-            }
+    #[test]
+    fn repeats_bad_count() {
+        let err = Parser::new("{0 <repeats many times>}")
+            .parse_value()
+            .unwrap_err();
+        assert_eq!(err.message, "expected a repeat count");
+        assert_eq!(err.offset, 12);
+    }
 
-            self.eat_ws();
-            if self.eat("[") {
-                if first {
-                    is_map = true;
-                } else {
-                    assert!(is_map, "can't mix list and map");
-                }
-            } else {
-                assert!(!is_map, "can't mix list and map");
-            }
-            if self.current().is_ascii_alphabetic() {
-                is_map = true;
-                let k = Value::String(self.parse_ident());
-                self.eat_ws();
-                assert!(self.eat("="), "expected a = after field");
-                let v = self.parse_value();
-                map.push((k, v));
-            } else if is_map {
-                let k = self.parse_value();
-                self.eat_ws();
-                assert!(self.eat("]"), "expected a ]");
-                self.eat_ws();
-                assert!(self.eat("="), "expected a = after list key");
-                let v = self.parse_value();
-                map.push((k, v));
-            } else {
-                list.push(self.parse_value());
-            }
-            first = false;
-        }
-        if is_map {
-            Value::Map(map)
-        } else {
-            Value::List(list)
-        }
+    #[test]
+    fn special_floats() {
+        check_parser(
+            "{inf, -inf}",
+            Value::List(vec![
+                Value::Float(f64::INFINITY),
+                Value::Float(-f64::INFINITY),
+            ]),
+        );
+        let bits = |text| match parse_value_completely(text) {
+            Value::Float(n) => n.to_bits(),
+            v => panic!("{v:?}"),
+        };
+        assert_eq!(bits("nan(0x8000000000000)"), f64::NAN.to_bits());
+        assert_eq!(bits("-nan(0x8000000000001)"), 0xfff8_0000_0000_0001);
+        assert_eq!(bits("nan"), f64::NAN.to_bits());
+        check_parser(
+            "{x = infinity, y = nano}",
+            Value::Struct(vec![
+                ("x".into(), Value::Enum("infinity".into())),
+                ("y".into(), Value::Enum("nano".into())),
+            ]),
+        );
+        let err = Parser::new("nan(0x0)").parse_value().unwrap_err();
+        assert_eq!(err.message, "invalid nan payload");
     }
 
-    pub fn parse_string(&mut self) -> String {
-        let mut s = String::new();
-        while !self.at_eof() && !self.at("\"") {
-            if self.eat("\\") {
-                let re = match self.eat_current() {
-                    '\\' => '\\',
-                    'n' => '\n',
-                    'r' => '\r',
-                    't' => '\t',
-                    _ => unimplemented!("unknown escape"),
-                };
-                s.push(re);
-            } else {
-                s.push(self.eat_current());
-            }
-        }
-        assert!(self.eat("\""), "missing closing \"");
-        s
+    #[test]
+    fn exponent() {
+        check_parser("1.5e-300", value!(1.5e-300));
+        check_parser("6.02e+23", value!(6.02e23));
+        check_parser("1E10", value!(1e10));
+        check_parser("-2.5e3", value!(-2500.));
     }
 
-    pub fn parse_number(&mut self) -> f64 {
-        let start = self.pos;
-        self.eat("-");
-        while !self.at_eof() {
-            let curr = self.current();
-            if curr.is_ascii_digit() || curr == '.' {
-                self.pos += 1;
-            } else {
-                break;
-            }
+    #[test]
+    fn exponent_roundtrip() {
+        for n in [
+            f64::MAX,
+            f64::MIN_POSITIVE,
+            1.7976931348623157e-308,
+            -3.14e-7,
+        ] {
+            check_parser(&format!("{n:e}"), Value::Float(n));
         }
-        self.src[start..self.pos].parse().unwrap()
     }
 
-    pub fn remove_reference(&mut self) {
-        while !self.at_eof() && !self.eat(":") {
-            self.advance()
-        }
+    #[test]
+    fn exponent_in_list() {
+        check_parser("{1e3, 2.5E-1}", value!([1000., 0.25]));
     }
 
-    pub fn parse_value(&mut self) -> Value {
-        self.eat_ws();
-        if self.eat("{") {
-            self.parse_list_or_map()
-        } else if self.eat("\"") {
-            Value::String(self.parse_string())
-        } else if self.current().is_ascii_digit() || self.current() == '-'  {
-            Value::Number(self.parse_number())
-        } else if self.eat("true") {
-            Value::Bool(true)
-        } else if self.eat("false") {
-            Value::Bool(false)
-        } else if self.eat("@0x") {
-            self.remove_reference();
-            self.parse_value()
-        } else {
-            panic!("expected a value");
-        }
+    #[test]
+    fn e_without_digits_is_not_exponent() {
+        let mut p = Parser::new("1e");
+        assert_eq!(p.parse_value(), Ok(value!(1)));
+        assert!(p.at("e"));
     }
-}
 
-impl<'a> From<&'a str> for Value {
-    fn from(v: &'a str) -> Self {
-        Self::String(v.to_owned())
+    #[test]
+    fn hex() {
+        check_parser("0x7fffdeadbeef", value!(0x7fffdeadbeef_u64));
+        check_parser("0XFF", value!(255));
     }
-}
 
-impl From<f64> for Value {
-    fn from(n: f64) -> Self {
-        Self::Number(n)
+    #[test]
+    fn octal() {
+        check_parser("0o755", value!(493));
     }
-}
 
-impl From<i32> for Value {
-    fn from(n: i32) -> Self {
-        Self::Number(n as f64)
+    #[test]
+    fn binary() {
+        check_parser("0b1010", value!(10));
     }
-}
 
-impl From<bool> for Value {
-    fn from(b: bool) -> Self {
-        Self::Bool(b)
+    #[test]
+    fn radix_prefixes_in_list() {
+        check_parser("{0x10, 0o10, 0b10, 10}", value!([16, 8, 2, 10]));
+        check_parser("-0x10", value!(-16));
     }
-}
 
-#[macro_export]
-macro_rules! value {
-    ({
-        $($k:tt => $v:tt),*
-    }) => {
-        Value::Map(vec![$(($crate::value!($k), $crate::value!($v))),*])
-    };
-    ([$($va:tt),*]) => {{
-        Value::List(vec![$($crate::value!($va)),*])
-    }};
-    ($s:literal) => {
-        Value::from($s)
+    #[test]
+    fn radix_prefix_without_digits() {
+        let err = Parser::new("{1, 0x}").parse_value().unwrap_err();
+        assert_eq!(err.message, "invalid number");
+        assert_eq!(err.offset, 4);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn integers_are_exact() {
+        check_parser("18446744073709551615", Value::Uint(u64::MAX as u128));
+        check_parser("0xffffffffffffffff", Value::Uint(u64::MAX as u128));
+        check_parser(
+            "-170141183460469231731687303715884105728",
+            Value::Int(i128::MIN),
+        );
+        check_parser(
+            "340282366920938463463374607431768211455",
+            Value::Uint(u128::MAX),
+        );
+        assert_eq!(
+            Parser::new("9007199254740993")
+                .parse_value_or_panic()
+                .as_int(),
+            Some(9007199254740993)
+        );
+    }
 
-    fn parse_value_completely(text: &str) -> Value {
-        let mut p = Parser::new(text);
-        let val = p.parse_value();
-        assert!(p.at_eof(), "parser didn't parse complete input");
-        val
+    #[test]
+    fn integers_and_floats_differ() {
+        check_parser("1", Value::Uint(1));
+        check_parser("1.0", Value::Float(1.));
+        check_parser("1e0", Value::Float(1.));
+        check_parser("-0", Value::Uint(0));
+        assert_eq!(value!(-1).as_uint(), None);
+        assert_eq!(value!(2).as_f64(), Some(2.));
     }
 
-    fn check_parser(text: &str, expect_value: Value) {
-        let parsed_value = parse_value_completely(text);
-        assert_eq!(parsed_value, expect_value);
+    #[test]
+    fn integer_overflow() {
+        let err = Parser::new("-0x80000000000000000000000000000001")
+            .parse_value()
+            .unwrap_err();
+        assert_eq!(err.message, "invalid number");
     }
 
     #[test]
-    fn bool() {
-        check_parser("true", value!(true));
-        check_parser("false", value!(false));
+    fn negative_decimal() {
+        check_parser("-0.5", value!(-0.5));
     }
 
+    // value! takes a single token per element, so negative elements are
+    // spelled out
     #[test]
-    fn number() {
-        check_parser("1", value!(1.));
+    fn negative_list_elements() {
+        check_parser(
+            "{-1, 2, -3.5}",
+            Value::List(vec![value!(-1), value!(2), value!(-3.5)]),
+        );
     }
 
     #[test]
-    fn negative_number() {
-        check_parser("-1", value!(-1.));
+    fn negative_map_values() {
+        check_parser(
+            "{[-1] = -2, [3] = -0.25}",
+            Value::Map(vec![(value!(-1), value!(-2)), (value!(3), value!(-0.25))]),
+        );
+        check_parser("{x = -7}", Value::Struct(vec![("x".into(), value!(-7))]));
+    }
+
+    #[test]
+    fn lone_minus() {
+        let err = Parser::new("{-}").parse_value().unwrap_err();
+        assert_eq!(err.message, "invalid number");
+        assert_eq!(err.offset, 1);
     }
 
     #[test]
@@ -322,6 +2800,32 @@ mod tests {
         check_parser(r#""\n\t\r\n""#, value!("\n\t\r\n"))
     }
 
+    #[test]
+    fn string_all_escapes() {
+        check_parser(
+            r#""\a\b\f\v\e\\\'\"\?\0\12\x41\x7fz\u00e9\U0001F600""#,
+            value!("\x07\x08\x0c\x0b\x1b\\'\"?\0\nA\x7fzé😀"),
+        );
+        // octal digits stop after three, hex ones don't
+        check_parser(r#""\1011\x414""#, value!("A1\u{414}"));
+    }
+
+    #[test]
+    fn string_escaped_bytes() {
+        check_parser(r#""\303\251t\303\251""#, value!("été"));
+        check_parser(r#""\360\237\230\200""#, value!("😀"));
+        check_parser(r#""a\377\303\251\303""#, value!("a\u{ff}é\u{c3}"));
+        check_parser(r#""\xc3\xa9""#, value!("é"));
+    }
+
+    #[test]
+    fn invalid_escapes() {
+        for text in [r#""\x""#, r#""\u12""#, r#""\U0011ffff""#, r#""\q""#] {
+            let err = Parser::new(text).parse_value().unwrap_err();
+            assert!(err.message.ends_with("escape"), "{text}: {err}");
+        }
+    }
+
     #[test]
     #[should_panic(expected = "unknown escape")]
     fn string_unending_escape() {
@@ -341,10 +2845,7 @@ mod tests {
 
     #[test]
     fn list_of_numbers() {
-        check_parser(
-            r#"{1  , 2, 5,4,  3,2,3}"#,
-            value!([1., 2., 5., 4., 3., 2., 3.]),
-        )
+        check_parser(r#"{1  , 2, 5,4,  3,2,3}"#, value!([1, 2, 5, 4, 3, 2, 3]))
     }
     #[test]
     fn list_single_string() {
@@ -367,13 +2868,13 @@ mod tests {
     fn list_hetero() {
         check_parser(
             r#"{{        }, 1       ,     "xyz",       {  1, "bb"} , 2.5 }"#,
-            value!([[], 1., "xyz", [1., "bb"], 2.5]),
+            value!([[], 1, "xyz", [1, "bb"], 2.5]),
         )
     }
 
     #[test]
     fn list_with_trailing_comma() {
-        check_parser(r#"{5,}"#, value!([5.]))
+        check_parser(r#"{5,}"#, value!([5]))
     }
 
     #[test]
@@ -399,8 +2900,8 @@ mod tests {
         check_parser(
             "{\n   [1] = 2,  [2] = 4,\n}",
             value!({
-                1. => 2.,
-                2. => 4.
+                1 => 2,
+                2 => 4
             }),
         )
     }
@@ -410,7 +2911,7 @@ mod tests {
         check_parser(
             "{[1] = 2}",
             value!({
-                1. => 2.
+                1 => 2
             }),
         )
     }
@@ -460,8 +2961,8 @@ mod tests {
         check_parser(
             r#"{["1"] = {1, 2},  ["5"] = {5, 6}}"#,
             value!({
-                "1" => [1., 2.],
-                "5" => [5., 6.]
+                "1" => [1, 2],
+                "5" => [5, 6]
             }),
         )
     }
@@ -471,8 +2972,8 @@ mod tests {
         check_parser(
             r#"{["1"] = {[1] = 2},  ["5"] = {[3] = 4}}"#,
             value!({
-                "1" => { 1. => 2. },
-                "5" => { 3. => 4. }
+                "1" => { 1 => 2 },
+                "5" => { 3 => 4 }
             }),
         )
     }
@@ -482,8 +2983,8 @@ mod tests {
         check_parser(
             r#"{[{1, 2}] = 1,  [{3, 4}] = {[3] = 4}}"#,
             value!({
-                [1., 2.] => 1.,
-                [3., 4.] => { 3. => 4. }
+                [1, 2] => 1,
+                [3, 4] => { 3 => 4 }
             }),
         )
     }
@@ -493,25 +2994,44 @@ mod tests {
         check_parser(
             r#"{{[1] = 2}, {[3] = 4, [5] = 6}}"#,
             value!([
-                {1. => 2.},
-                {3. => 4., 5. => 6.}
+                {1 => 2},
+                {3 => 4, 5 => 6}
             ]),
         )
     }
 
     #[test]
     fn structure() {
-        check_parser(r#"{ x = 5 }"#, value!({"x" => 5.}))
+        check_parser(r#"{ x = 5 }"#, value!({ "x" = 5 }))
     }
 
     #[test]
     fn structure_field_numbers() {
-        check_parser(r#"{ x5xe = 5 }"#, value!({"x5xe" => 5.}))
+        check_parser(r#"{ x5xe = 5 }"#, value!({ "x5xe" = 5 }))
+    }
+
+    #[test]
+    fn structure_field_underscore() {
+        check_parser(r#"{ first_field = 5 }"#, value!({ "first_field" = 5 }))
+    }
+
+    #[test]
+    fn structure_multiple_fields() {
+        check_parser(
+            r#"{ x = 5, y = "a", z = {1} }"#,
+            value!({"x" = 5, "y" = "a", "z" = [1]}),
+        )
+    }
+
+    #[test]
+    #[should_panic(expected = "can't mix list and map")]
+    fn mix_list_and_struct() {
+        parse_value_completely("{5, x = 2}");
     }
 
     #[test]
     fn mix_struct_and_map() {
-        check_parser(r#"{ x5xe = 5, [3] = 2 }"#, value!({"x5xe" => 5., 3. => 2. }))
+        check_parser(r#"{ x5xe = 5, [3] = 2 }"#, value!({"x5xe" => 5, 3 => 2 }))
     }
 
     #[test]
@@ -520,9 +3040,478 @@ mod tests {
         parse_value_completely("{x = 2, 5}");
     }
 
+    #[test]
+    fn error_details() {
+        let err = Parser::new("{[1] = 2, [3] 4}").parse_value().unwrap_err();
+        assert_eq!(err.message, "expected a = after list key");
+        assert_eq!(err.offset, 14);
+        assert_eq!(err.expected, ["="]);
+        assert_eq!(err.snippet, "{[1] = 2, [3] 4}");
+    }
+
+    #[test]
+    fn error_snippet_is_bounded() {
+        let text = format!("{{{}?}}", "1, ".repeat(20));
+        let err = Parser::new(&text).parse_value().unwrap_err();
+        assert_eq!(err.message, "expected a value");
+        assert_eq!(err.offset, 61);
+        assert_eq!(err.snippet, " 1, 1, 1, 1, 1, ?}");
+    }
+
+    #[test]
+    fn error_display() {
+        let err = Parser::new("\"abc").parse_value().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            r#"missing closing " at offset 4 (expected one of ") near "\"abc""#
+        );
+    }
+
+    #[test]
+    fn non_ascii_text() {
+        check_parser(
+            "{imię = \"Michał 😀\", [\"ń\"] = 128512 '😀', s = \"ż\", 'ó' <repeats 12 times>, f = 0x401126 <główna+6>}",
+            Value::Map(vec![
+                (value!("imię"), value!("Michał 😀")),
+                (value!("ń"), Value::Char { code: 128512, ch: '😀' }),
+                (
+                    value!("s"),
+                    Value::StringRuns(vec![
+                        value!("ż"),
+                        Value::Repeated(Box::new(Value::Char { code: 243, ch: 'ó' }), 12),
+                    ]),
+                ),
+                (
+                    value!("f"),
+                    Value::Pointer {
+                        address: 0x401126,
+                        symbol: Some("główna+6".into()),
+                        pointee: None,
+                    },
+                ),
+            ]),
+        );
+        check_parser(
+            "{kolor = Barwa::Żółty, v = wektor długości 1 = {ś}}",
+            Value::Struct(vec![
+                ("kolor".into(), Value::Enum("Barwa::Żółty".into())),
+                (
+                    "v".into(),
+                    described(
+                        "wektor długości 1",
+                        Some(Value::List(vec![Value::Enum("ś".into())])),
+                    ),
+                ),
+            ]),
+        );
+    }
+
+    #[test]
+    fn non_ascii_errors() {
+        for text in ["{x = 😀}", "{ń = 1, 2}", "\"😀", "'ń", "{x = 😀 <repeats}"] {
+            let err = Parser::new(text).parse_value().unwrap_err();
+            assert!(text.is_char_boundary(err.offset), "{text}: {err}");
+        }
+        let err = Parser::new("{x = 😀}").parse_value().unwrap_err();
+        assert_eq!(err.message, "expected a value");
+        assert_eq!(err.offset, 5);
+    }
+
+    #[test]
+    fn prefixed_strings() {
+        let encoded = |encoding, value| Value::Encoded {
+            encoding,
+            value: Box::new(value),
+        };
+        check_parser(
+            r#"{w = L"hello", a = u"hi", b = U"\U0001F600", c = u8"ok", d = 97 L'a'}"#,
+            Value::Struct(vec![
+                ("w".into(), encoded(Encoding::Wide, value!("hello"))),
+                ("a".into(), encoded(Encoding::Utf16, value!("hi"))),
+                ("b".into(), encoded(Encoding::Utf32, value!("😀"))),
+                ("c".into(), encoded(Encoding::Utf8, value!("ok"))),
+                ("d".into(), encoded(Encoding::Wide, value!('a'))),
+            ]),
+        );
+        check_parser(
+            r#"L"ab", L'x' <repeats 20 times>, L"cd"..."#,
+            encoded(
+                Encoding::Wide,
+                Value::Truncated(Box::new(Value::StringRuns(vec![
+                    value!("ab"),
+                    Value::Repeated(Box::new(value!('x')), 20),
+                    value!("cd"),
+                ]))),
+            ),
+        );
+        check_parser(
+            r#"0x402004 U"wide""#,
+            Value::Pointer {
+                address: 0x402004,
+                symbol: None,
+                pointee: Some(Box::new(encoded(Encoding::Utf32, value!("wide")))),
+            },
+        );
+        // a bare prefix is still an enumerator
+        check_parser(
+            "{L, u8}",
+            Value::List(vec![Value::Enum("L".into()), Value::Enum("u8".into())]),
+        );
+    }
+
+    #[test]
+    fn typed_values() {
+        let typed = |ty: &'static str, value| Value::Typed {
+            ty: ty.into(),
+            value: Box::new(value),
+        };
+        check_parser("(Foo *) 0x4052a0", typed("Foo *", value!(0x4052a0)));
+        check_parser(
+            "{f = (void (*)(int)) 0x401126 <handler(int)>, r = (int &) @0x7ffe: 5, n = (Node *) 0x0}",
+            Value::Struct(vec![
+                (
+                    "f".into(),
+                    typed(
+                        "void (*)(int)",
+                        Value::Pointer {
+                            address: 0x401126,
+                            symbol: Some("handler(int)".into()),
+                            pointee: None,
+                        },
+                    ),
+                ),
+                (
+                    "r".into(),
+                    typed(
+                        "int &",
+                        Value::Reference {
+                            address: 0x7ffe,
+                            value: Box::new(value!(5)),
+                        },
+                    ),
+                ),
+                ("n".into(), typed("Node *", value!(0))),
+            ]),
+        );
+        let err = Parser::new("(Foo *").parse_value().unwrap_err();
+        assert_eq!(err.message, "missing closing )");
+    }
+
+    #[test]
+    fn depth_limit() {
+        let config = ParserConfig {
+            max_depth: 3,
+            ..Default::default()
+        };
+        assert_eq!(
+            Parser::with_config("{{1}, 2}", config.clone()).parse_value(),
+            Ok(Value::List(vec![value!([1]), value!(2)]))
+        );
+        let err = Parser::with_config("{{{1}}}", config)
+            .parse_value()
+            .unwrap_err();
+        assert_eq!(err.message, "value nested too deeply");
+        assert_eq!(err.offset, 3);
+    }
+
+    #[test]
+    fn anonymous_members() {
+        let v = parse_value_completely("{<anonymous union> = {a = 1, b = 2}, x = 3}");
+        assert_eq!(v.get_path("b").and_then(Value::as_uint), Some(2));
+        let anonymous = |v| (ANONYMOUS.into(), v);
+        check_parser(
+            "{{a = 1}, x = 3, {b = 2, c = 4}}",
+            Value::Struct(vec![
+                anonymous(value!({ "a" = 1 })),
+                ("x".into(), value!(3)),
+                anonymous(value!({"b" = 2, "c" = 4})),
+            ]),
+        );
+        check_parser(
+            "{{a = 1}, {b = 2}, <Base> = {}}",
+            Value::Struct(vec![
+                anonymous(value!({ "a" = 1 })),
+                anonymous(value!({ "b" = 2 })),
+                ("<Base>".into(), value!([])),
+            ]),
+        );
+        check_parser("{{a = 1}, {a = 2}}", value!([{ "a" = 1 }, { "a" = 2 }]));
+        let err = Parser::new("{1, x = 3}").parse_value().unwrap_err();
+        assert_eq!(err.message, "can't mix list and map");
+        let err = Parser::new("{[0] = 1, {a = 1}}").parse_value().unwrap_err();
+        assert_eq!(err.message, "can't mix list and map");
+    }
+
+    #[test]
+    fn strictness() {
+        let strict = ParserConfig {
+            trailing_comma: false,
+            strict_identifiers: true,
+            ..Default::default()
+        };
+        let parse = |text| Parser::with_config(text, strict.clone()).parse_value();
+        assert_eq!(Parser::new("{1, 2,}").parse_value(), Ok(value!([1, 2])));
+        assert_eq!(
+            parse("{1, 2,}").unwrap_err().message,
+            "trailing , not allowed"
+        );
+        assert_eq!(parse("{a_1 = 1}"), Ok(value!({ "a_1" = 1 })));
+        assert!(parse("{imię = 1}").is_err());
+        let keep = ParserConfig {
+            keep_references: false,
+            ..Default::default()
+        };
+        assert_eq!(
+            Parser::with_config("@0x10: 1", keep).parse_value(),
+            Ok(value!(1))
+        );
+    }
+
+    #[test]
+    fn dialects() {
+        let parse = |text, dialect| {
+            let config = ParserConfig {
+                dialect,
+                ..Default::default()
+            };
+            Parser::with_config(text, config).parse_value()
+        };
+        let rust = "Vec(size=1) = {1}";
+        assert!(matches!(
+            parse(rust, Dialect::Rust),
+            Ok(Value::Described { .. })
+        ));
+        // only the identifier is a value in C++
+        assert_eq!(parse(rust, Dialect::Cpp), Ok(Value::Enum("Vec".into())));
+        let cpp = "std::unique_ptr<A> = {get() = 0x0}";
+        assert!(matches!(
+            parse(cpp, Dialect::Cpp),
+            Ok(Value::SmartPointer { .. })
+        ));
+        assert!(matches!(
+            parse(cpp, Dialect::C),
+            Ok(Value::Described { .. })
+        ));
+        assert!(parse("&A::x", Dialect::C).is_err());
+        assert_eq!(
+            parse("&A::x", Dialect::Any),
+            Ok(Value::MemberPointer("A::x".into()))
+        );
+    }
+
+    #[test]
+    fn numeric_typing() {
+        let parse = |text, numbers| {
+            let config = ParserConfig {
+                numbers,
+                ..Default::default()
+            };
+            Parser::with_config(text, config).parse_value()
+        };
+        assert_eq!(
+            parse("{1, -2, 0x10 <f>}", Numbers::Signed),
+            Ok(Value::List(vec![
+                Value::Int(1),
+                Value::Int(-2),
+                Value::Pointer {
+                    address: 16,
+                    symbol: Some("f".into()),
+                    pointee: None
+                },
+            ]))
+        );
+        assert_eq!(
+            parse("{1, -2, 97 'a'}", Numbers::Float),
+            Ok(Value::List(vec![
+                value!(1.0),
+                value!(-2.0),
+                Value::from('a')
+            ]))
+        );
+    }
+
+    #[test]
+    fn print_output() {
+        let parse = |text| Parser::new(text).parse_print_output();
+        assert_eq!(
+            parse("$12 = {x = 1}\n"),
+            Ok(PrintOutput {
+                history: Some(12),
+                value: value!({ "x" = 1 }),
+            })
+        );
+        assert_eq!(
+            parse("5"),
+            Ok(PrintOutput {
+                history: None,
+                value: value!(5),
+            })
+        );
+        assert_eq!(parse("$1 = 2 3").unwrap_err().offset, 7);
+        assert_eq!(
+            parse("$ = 1").unwrap_err().message,
+            "expected a history number"
+        );
+    }
+
+    #[test]
+    fn element_limit() {
+        let config = ParserConfig {
+            max_elements: Some(2),
+            ..Default::default()
+        };
+        let parse = |text| Parser::with_config(text, config.clone()).parse_value();
+        assert_eq!(
+            parse("{1, 0 <repeats 9 times>, 3, {\"}\", '}'}, x}"),
+            Ok(Value::Truncated(Box::new(Value::List(vec![
+                value!(1),
+                Value::Repeated(Box::new(value!(0)), 9),
+            ]))))
+        );
+        assert_eq!(
+            parse("{a = {1, 2, 3}, b = 2, c = 3...}").map(|v| v.to_gdb_string()),
+            Ok("{a = {1, 2...}, b = 2...}".to_owned())
+        );
+        assert_eq!(parse("{1, 2}"), Ok(value!([1, 2])));
+        assert_eq!(parse("{1, 2, 3").unwrap_err().message, "missing closing }");
+    }
+
+    #[test]
+    fn deep_input_doesnt_overflow() {
+        for text in [
+            "{".repeat(100_000),
+            "(int *) ".repeat(100_000),
+            "@0x1: ".repeat(100_000),
+        ] {
+            let err = Parser::new(&text).parse_value().unwrap_err();
+            assert_eq!(err.message, "value nested too deeply");
+        }
+    }
+
+    #[test]
+    fn lossy_parsing() {
+        let lossy = |text| Parser::new(text).parse_value_lossy();
+        let (v, errors) = lossy("{x = 1, y = <synthetic pointer>, z = {1, ?? \"a, }\" 2}, w = 3}");
+        assert_eq!(
+            v,
+            Value::Struct(vec![
+                ("x".into(), value!(1)),
+                ("y".into(), Value::Raw("<synthetic pointer>".into())),
+                (
+                    "z".into(),
+                    Value::List(vec![value!(1), Value::Raw("?? \"a, }\" 2".into())]),
+                ),
+                ("w".into(), value!(3)),
+            ])
+        );
+        let offsets: Vec<_> = errors.iter().map(|e| (e.message, e.offset)).collect();
+        assert_eq!(
+            offsets,
+            [("expected a value", 12), ("expected a value", 41)]
+        );
+    }
+
+    #[test]
+    fn lossy_parsing_gives_up_on_aggregates() {
+        let (v, errors) = Parser::new("{a = {1, 2 3}, b = 4}").parse_value_lossy();
+        assert_eq!(
+            v,
+            Value::Struct(vec![
+                ("a".into(), Value::Raw("{1, 2 3}".into())),
+                ("b".into(), value!(4)),
+            ])
+        );
+        assert_eq!(errors[0].message, "expected , after list item");
+
+        let (v, errors) = Parser::new(" 1 2 ").parse_value_lossy();
+        assert_eq!(v, Value::Raw("1 2".into()));
+        assert_eq!(errors[0].message, "unexpected text after value");
+
+        let text = "{x = {1, 2}, s = \"ok\"}";
+        let (v, errors) = Parser::new(text).parse_value_lossy();
+        assert_eq!(v, Parser::new(text).parse_value_or_panic());
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn lossy_parsing_backslash_at_end() {
+        let (v, errors) = Parser::new("\"*x\\377\\377)\\").parse_value_lossy();
+        assert_eq!(v, Value::Raw("\"*x\\377\\377)\\".into()));
+        assert!(!errors.is_empty());
+    }
+
+    #[test]
+    fn incomplete_sequence() {
+        let incomplete = |s: &'static str, bytes: &[u8]| Value::Incomplete {
+            value: Box::new(Value::from(s)),
+            bytes: bytes.to_vec(),
+        };
+        check_parser(
+            r#"{s = "abc" <incomplete sequence \360>, t = "x" <incomplete sequence \342\202>...}"#,
+            Value::Struct(vec![
+                ("s".into(), incomplete("abc", b"\xf0")),
+                (
+                    "t".into(),
+                    Value::Truncated(Box::new(incomplete("x", b"\xe2\x82"))),
+                ),
+            ]),
+        );
+        let v = Parser::new(r#""ab", 'c' <repeats 10 times>, "d" <incomplete sequence \303>"#)
+            .parse_value_or_panic();
+        assert_eq!(v.expanded(), value!("abccccccccccd\u{c3}"));
+        let err = Parser::new(r#""a" <incomplete sequence \360"#)
+            .parse_value()
+            .unwrap_err();
+        assert_eq!(err.message, "expected > after incomplete sequence");
+    }
+
     #[test]
     fn reference_number() {
-        check_parser(r#"@0x7fffffffde44: 1"#, value!(1.))
+        check_parser(
+            r#"@0x7fffffffde44: 1"#,
+            Value::Reference {
+                address: 0x7fffffffde44,
+                value: Box::new(value!(1)),
+            },
+        )
+    }
+
+    #[test]
+    fn reference_to_aggregate() {
+        check_parser(
+            r#"{r = @0x7fffffffde40: {x = 1}, s = @0x10: "ab", 'c' <repeats 3 times>}"#,
+            Value::Struct(vec![
+                (
+                    "r".into(),
+                    Value::Reference {
+                        address: 0x7fffffffde40,
+                        value: Box::new(value!({ "x" = 1 })),
+                    },
+                ),
+                (
+                    "s".into(),
+                    Value::Reference {
+                        address: 0x10,
+                        value: Box::new(Value::StringRuns(vec![
+                            value!("ab"),
+                            Value::Repeated(Box::new(value!('c')), 3),
+                        ])),
+                    },
+                ),
+            ]),
+        );
+        let err = Parser::new("@0x10 1").parse_value().unwrap_err();
+        assert_eq!(err.message, "expected a : after reference address");
+    }
+
+    #[test]
+    fn reference_stripped() {
+        let config = ParserConfig {
+            keep_references: false,
+            ..Default::default()
+        };
+        let mut p = Parser::with_config("{r = @0x7fffffffde44: 1}", config);
+        assert_eq!(p.parse_value(), Ok(value!({ "r" = 1 })));
     }
 
     #[test]
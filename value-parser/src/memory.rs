@@ -0,0 +1,207 @@
+//! Typed reads over raw memory, e.g. the `contents` of a
+//! `-data-read-memory-bytes` result.
+//!
+//! All reads are bounds checked and return `None` instead of panicking when
+//! the requested bytes aren't available (or, for the `aligned` variants, when
+//! the address isn't a multiple of the type's size).
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Endian {
+    Little,
+    Big,
+}
+
+/// Plain numeric types that can be read from memory.
+pub trait FromBytes: Sized + Copy {
+    const SIZE: usize;
+
+    fn from_bytes(bytes: &[u8], endian: Endian) -> Self;
+}
+
+macro_rules! impl_from_bytes {
+    ($($t:ty),*) => {$(
+        impl FromBytes for $t {
+            const SIZE: usize = std::mem::size_of::<$t>();
+
+            fn from_bytes(bytes: &[u8], endian: Endian) -> Self {
+                let b = bytes[..Self::SIZE].try_into().unwrap();
+                match endian {
+                    Endian::Little => <$t>::from_le_bytes(b),
+                    Endian::Big => <$t>::from_be_bytes(b),
+                }
+            }
+        }
+    )*};
+}
+
+impl_from_bytes!(u8, u16, u32, u64, i8, i16, i32, i64, f32, f64);
+
+/// Reads a `T` at byte `offset` of `bytes`.
+pub fn read<T: FromBytes>(bytes: &[u8], offset: usize, endian: Endian) -> Option<T> {
+    let end = offset.checked_add(T::SIZE)?;
+    Some(T::from_bytes(bytes.get(offset..end)?, endian))
+}
+
+/// Reads `count` consecutive `T`s starting at byte `offset` of `bytes`.
+pub fn read_array<T: FromBytes>(
+    bytes: &[u8],
+    offset: usize,
+    count: usize,
+    endian: Endian,
+) -> Option<Vec<T>> {
+    let end = offset.checked_add(T::SIZE.checked_mul(count)?)?;
+    Some(
+        bytes
+            .get(offset..end)?
+            .chunks(T::SIZE)
+            .map(|b| T::from_bytes(b, endian))
+            .collect(),
+    )
+}
+
+macro_rules! read_fns {
+    ($($t:ty => $le:ident, $be:ident;)*) => {$(
+        pub fn $le(bytes: &[u8], offset: usize) -> Option<$t> {
+            read(bytes, offset, Endian::Little)
+        }
+
+        pub fn $be(bytes: &[u8], offset: usize) -> Option<$t> {
+            read(bytes, offset, Endian::Big)
+        }
+    )*};
+}
+
+read_fns! {
+    u16 => read_u16_le, read_u16_be;
+    u32 => read_u32_le, read_u32_be;
+    u64 => read_u64_le, read_u64_be;
+    i16 => read_i16_le, read_i16_be;
+    i32 => read_i32_le, read_i32_be;
+    i64 => read_i64_le, read_i64_be;
+    f32 => read_f32_le, read_f32_be;
+    f64 => read_f64_le, read_f64_be;
+}
+
+/// A block of target memory starting at `base`, read by absolute address.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemoryView {
+    pub base: u64,
+    pub bytes: Vec<u8>,
+    pub endian: Endian,
+}
+
+impl MemoryView {
+    pub fn new(base: u64, bytes: Vec<u8>, endian: Endian) -> Self {
+        Self {
+            base,
+            bytes,
+            endian,
+        }
+    }
+
+    /// Builds a view from the hex string GDB puts in the `contents` field
+    /// of `-data-read-memory-bytes` results.
+    pub fn from_hex(base: u64, hex: &str, endian: Endian) -> Option<Self> {
+        if !hex.len().is_multiple_of(2) {
+            return None;
+        }
+        let bytes = (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+            .collect::<Option<_>>()?;
+        Some(Self::new(base, bytes, endian))
+    }
+
+    pub fn end(&self) -> u64 {
+        self.base + self.bytes.len() as u64
+    }
+
+    fn offset_of(&self, addr: u64) -> Option<usize> {
+        addr.checked_sub(self.base)?.try_into().ok()
+    }
+
+    pub fn read<T: FromBytes>(&self, addr: u64) -> Option<T> {
+        read(&self.bytes, self.offset_of(addr)?, self.endian)
+    }
+
+    /// Like [`MemoryView::read`], but fails if `addr` isn't aligned to the
+    /// size of `T`.
+    pub fn read_aligned<T: FromBytes>(&self, addr: u64) -> Option<T> {
+        if !addr.is_multiple_of(T::SIZE as u64) {
+            return None;
+        }
+        self.read(addr)
+    }
+
+    pub fn read_array<T: FromBytes>(&self, addr: u64, count: usize) -> Option<Vec<T>> {
+        read_array(&self.bytes, self.offset_of(addr)?, count, self.endian)
+    }
+
+    pub fn read_bytes(&self, addr: u64, len: usize) -> Option<&[u8]> {
+        let offset = self.offset_of(addr)?;
+        self.bytes.get(offset..offset.checked_add(len)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn endianness() {
+        let bytes = [1, 2, 3, 4];
+        assert_eq!(read_u16_le(&bytes, 0), Some(0x0201));
+        assert_eq!(read_u16_be(&bytes, 0), Some(0x0102));
+        assert_eq!(read_u32_le(&bytes, 0), Some(0x04030201));
+        assert_eq!(read_u32_be(&bytes, 0), Some(0x01020304));
+        assert_eq!(read_i16_be(&[0xff, 0xfe], 0), Some(-2));
+    }
+
+    #[test]
+    fn floats() {
+        assert_eq!(read_f64_be(&2.5f64.to_be_bytes(), 0), Some(2.5));
+        assert_eq!(read_f32_le(&0.5f32.to_le_bytes(), 0), Some(0.5));
+    }
+
+    #[test]
+    fn out_of_bounds() {
+        assert_eq!(read_u32_le(&[1, 2, 3], 0), None);
+        assert_eq!(read_u16_le(&[1, 2, 3], 2), None);
+        assert_eq!(read_u16_le(&[1, 2, 3], usize::MAX), None);
+    }
+
+    #[test]
+    fn arrays() {
+        let bytes = [0, 1, 0, 2, 0, 3];
+        assert_eq!(
+            read_array::<u16>(&bytes, 2, 2, Endian::Big),
+            Some(vec![2, 3])
+        );
+        assert_eq!(read_array::<u16>(&bytes, 2, 3, Endian::Big), None);
+        assert_eq!(read_array::<u16>(&bytes, 0, usize::MAX, Endian::Big), None);
+    }
+
+    #[test]
+    fn view_by_address() {
+        let view = MemoryView::from_hex(0x1000, "0100000002000000", Endian::Little).unwrap();
+        assert_eq!(view.end(), 0x1008);
+        assert_eq!(view.read::<u32>(0x1004), Some(2));
+        assert_eq!(view.read::<u32>(0x0ffc), None);
+        assert_eq!(view.read_array::<u32>(0x1000, 2), Some(vec![1, 2]));
+        assert_eq!(view.read_bytes(0x1001, 2), Some(&[0, 0][..]));
+    }
+
+    #[test]
+    fn view_alignment() {
+        let view = MemoryView::new(0x1000, vec![0; 8], Endian::Little);
+        assert_eq!(view.read_aligned::<u32>(0x1004), Some(0));
+        assert_eq!(view.read_aligned::<u32>(0x1002), None);
+        assert_eq!(view.read::<u32>(0x1002), Some(0));
+    }
+
+    #[test]
+    fn bad_hex() {
+        assert_eq!(MemoryView::from_hex(0, "abc", Endian::Little), None);
+        assert_eq!(MemoryView::from_hex(0, "zz", Endian::Little), None);
+    }
+}
@@ -0,0 +1,334 @@
+//! A total order on [`Value`]s, and [`OrdValue`], which is `Eq`, `Ord` and
+//! `Hash` by it, so values can be deduplicated and used as keys in maps.
+//!
+//! Values of different variants order by variant, negative integers before
+//! non-negative ones before floats. Floats are compared with
+//! [`f64::total_cmp`], which makes `NaN` equal to itself and `-0.0` different
+//! from `0.0`. `==` on [`Value`] itself is unchanged and compares floats as
+//! floats.
+
+use std::{
+    borrow::Cow,
+    cmp::Ordering,
+    hash::{Hash, Hasher},
+};
+
+use crate::Value;
+
+/// A [`Value`] that is `Eq`, `Ord` and `Hash` by [`Value::total_cmp`].
+#[derive(Debug, Clone)]
+pub struct OrdValue<'a>(pub Value<'a>);
+
+impl PartialEq for OrdValue<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for OrdValue<'_> {}
+
+impl PartialOrd for OrdValue<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for OrdValue<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+impl Hash for OrdValue<'_> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Total(&self.0).hash(state)
+    }
+}
+
+/// [`OrdValue`] for values inside other values, which are only borrowed.
+struct Total<'r, 'a>(&'r Value<'a>);
+
+impl PartialEq for Total<'_, '_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Total<'_, '_> {}
+
+impl PartialOrd for Total<'_, '_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Total<'_, '_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(other.0)
+    }
+}
+
+fn total<'r, 'a>(v: &'r Value<'a>) -> Total<'r, 'a> {
+    Total(v)
+}
+
+fn entry<'r, 'a>((k, v): &'r (Value<'a>, Value<'a>)) -> (Total<'r, 'a>, Total<'r, 'a>) {
+    (Total(k), Total(v))
+}
+
+fn field<'r, 'a>((name, v): &'r (Cow<'a, str>, Value<'a>)) -> (&'r str, Total<'r, 'a>) {
+    (name, Total(v))
+}
+
+/// Hashes `items` like a slice of them.
+fn hash_all<H: Hasher>(items: impl ExactSizeIterator<Item = impl Hash>, state: &mut H) {
+    state.write_usize(items.len());
+    items.for_each(|item| item.hash(state));
+}
+
+impl Value<'_> {
+    fn rank(&self) -> u8 {
+        match self {
+            Self::Bool(_) => 0,
+            Self::Int(_) => 1,
+            Self::Uint(_) => 2,
+            Self::Float(_) => 3,
+            Self::String(_) => 4,
+            Self::Map(_) => 5,
+            Self::Struct(_) => 6,
+            Self::List(_) => 7,
+            Self::Repeated(..) => 8,
+            Self::OptimizedOut => 9,
+            Self::Char { .. } => 10,
+            Self::Pointer { .. } => 11,
+            Self::MemberPointer(_) => 12,
+            Self::Enum(_) => 13,
+            Self::Static(_) => 14,
+            Self::Truncated(_) => 15,
+            Self::Incomplete { .. } => 16,
+            Self::StringRuns(_) => 17,
+            Self::Described { .. } => 18,
+            Self::SmartPointer { .. } => 19,
+            Self::Typed { .. } => 20,
+            Self::Reference { .. } => 21,
+            Self::Encoded { .. } => 22,
+            Self::Raw(_) => 23,
+            Self::Error(_) => 24,
+        }
+    }
+
+    /// Sorts the entries of maps by key, here and in every value inside this
+    /// one, so maps with the same entries compare equal whatever order GDB
+    /// printed them in. Struct fields keep their order.
+    pub fn canonicalize(&mut self) {
+        match self {
+            Self::Map(entries) => {
+                for (k, v) in entries.iter_mut() {
+                    k.canonicalize();
+                    v.canonicalize();
+                }
+                entries.sort_by(|(a, x), (b, y)| a.total_cmp(b).then_with(|| x.total_cmp(y)));
+            }
+            Self::Struct(fields) => fields.iter_mut().for_each(|(_, v)| v.canonicalize()),
+            Self::List(items) | Self::StringRuns(items) => {
+                items.iter_mut().for_each(Value::canonicalize)
+            }
+            Self::Repeated(v, _)
+            | Self::Static(v)
+            | Self::Truncated(v)
+            | Self::Incomplete { value: v, .. }
+            | Self::Described { value: Some(v), .. }
+            | Self::SmartPointer { pointer: v, .. }
+            | Self::Typed { value: v, .. }
+            | Self::Reference { value: v, .. }
+            | Self::Encoded { value: v, .. }
+            | Self::Pointer {
+                pointee: Some(v), ..
+            } => v.canonicalize(),
+            _ => {}
+        }
+    }
+}
+
+impl Value<'_> {
+    /// Compares in the total order described in [`crate::ord`].
+    pub fn total_cmp(&self, other: &Self) -> Ordering {
+        use Value::*;
+        match (self, other) {
+            (Bool(a), Bool(b)) => a.cmp(b),
+            (Int(a), Int(b)) => a.cmp(b),
+            (Uint(a), Uint(b)) => a.cmp(b),
+            (Float(a), Float(b)) => a.total_cmp(b),
+            (String(a), String(b))
+            | (MemberPointer(a), MemberPointer(b))
+            | (Enum(a), Enum(b))
+            | (Raw(a), Raw(b))
+            | (Error(a), Error(b)) => a.cmp(b),
+            (Map(a), Map(b)) => a.iter().map(entry).cmp(b.iter().map(entry)),
+            (Struct(a), Struct(b)) => a.iter().map(field).cmp(b.iter().map(field)),
+            (List(a), List(b)) | (StringRuns(a), StringRuns(b)) => {
+                a.iter().map(total).cmp(b.iter().map(total))
+            }
+            (Repeated(a, n), Repeated(b, m)) => (Total(a), n).cmp(&(Total(b), m)),
+            (Char { code: a, ch: c }, Char { code: b, ch: d }) => (a, c).cmp(&(b, d)),
+            (
+                Pointer {
+                    address: a,
+                    symbol: s,
+                    pointee: p,
+                },
+                Pointer {
+                    address: b,
+                    symbol: t,
+                    pointee: q,
+                },
+            ) => (a, s, p.as_deref().map(total)).cmp(&(b, t, q.as_deref().map(total))),
+            (Static(a), Static(b)) | (Truncated(a), Truncated(b)) => a.total_cmp(b),
+            (Incomplete { value: a, bytes: x }, Incomplete { value: b, bytes: y }) => {
+                (Total(a), x).cmp(&(Total(b), y))
+            }
+            (
+                Described {
+                    description: d,
+                    value: a,
+                },
+                Described {
+                    description: e,
+                    value: b,
+                },
+            ) => (d, a.as_deref().map(total)).cmp(&(e, b.as_deref().map(total))),
+            (
+                SmartPointer {
+                    ty: s,
+                    use_count: u,
+                    weak_count: w,
+                    pointer: a,
+                },
+                SmartPointer {
+                    ty: t,
+                    use_count: v,
+                    weak_count: x,
+                    pointer: b,
+                },
+            ) => (s, u, w, Total(a)).cmp(&(t, v, x, Total(b))),
+            (Typed { ty: s, value: a }, Typed { ty: t, value: b }) => {
+                (s, Total(a)).cmp(&(t, Total(b)))
+            }
+            (
+                Reference {
+                    address: x,
+                    value: a,
+                },
+                Reference {
+                    address: y,
+                    value: b,
+                },
+            ) => (x, Total(a)).cmp(&(y, Total(b))),
+            (
+                Encoded {
+                    encoding: e,
+                    value: a,
+                },
+                Encoded {
+                    encoding: f,
+                    value: b,
+                },
+            ) => (e, Total(a)).cmp(&(f, Total(b))),
+            _ => self.rank().cmp(&other.rank()),
+        }
+    }
+}
+
+impl Hash for Total<'_, '_> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.rank().hash(state);
+        match self.0 {
+            Value::Bool(b) => b.hash(state),
+            Value::Int(n) => n.hash(state),
+            Value::Uint(n) => n.hash(state),
+            // equal floats have the same bits under total_cmp
+            Value::Float(n) => n.to_bits().hash(state),
+            Value::String(s)
+            | Value::MemberPointer(s)
+            | Value::Enum(s)
+            | Value::Raw(s)
+            | Value::Error(s) => s.hash(state),
+            Value::Map(entries) => hash_all(entries.iter().map(entry), state),
+            Value::Struct(fields) => hash_all(fields.iter().map(field), state),
+            Value::List(items) | Value::StringRuns(items) => {
+                hash_all(items.iter().map(total), state)
+            }
+            Value::Repeated(v, n) => (Total(v), n).hash(state),
+            Value::OptimizedOut => {}
+            Value::Char { code, ch } => (code, ch).hash(state),
+            Value::Pointer {
+                address,
+                symbol,
+                pointee,
+            } => (address, symbol, pointee.as_deref().map(total)).hash(state),
+            Value::Static(v) | Value::Truncated(v) => Total(v).hash(state),
+            Value::Incomplete { value, bytes } => (Total(value), bytes).hash(state),
+            Value::Described { description, value } => {
+                (description, value.as_deref().map(total)).hash(state)
+            }
+            Value::SmartPointer {
+                ty,
+                use_count,
+                weak_count,
+                pointer,
+            } => (ty, use_count, weak_count, Total(pointer)).hash(state),
+            Value::Typed { ty, value } => (ty, Total(value)).hash(state),
+            Value::Reference { address, value } => (address, Total(value)).hash(state),
+            Value::Encoded { encoding, value } => (encoding, Total(value)).hash(state),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeSet, HashSet};
+
+    use super::OrdValue;
+    use crate::{value, Parser, Value};
+
+    fn parse(text: &str) -> Value<'_> {
+        Parser::new(text).parse_value_or_panic()
+    }
+
+    #[test]
+    fn floats_are_totally_ordered() {
+        let nan = Value::Float(f64::NAN);
+        assert_ne!(nan, nan);
+        assert_eq!(OrdValue(nan.clone()), OrdValue(nan));
+        assert_eq!(Value::Float(0.0), Value::Float(-0.0));
+        assert_ne!(OrdValue(value!(0.0)), OrdValue(value!(-0.0)));
+        let mut values = vec![value!(1.5), value!(-1), value!(3), value!(true)];
+        values.sort_by(Value::total_cmp);
+        assert_eq!(values, [value!(true), value!(-1), value!(3), value!(1.5)]);
+    }
+
+    #[test]
+    fn canonical_maps() {
+        let mut a = parse("{m = {[2] = {[\"y\"] = 1, [\"x\"] = 2}, [1] = 0}}");
+        let mut b = parse("{m = {[1] = 0, [2] = {[\"x\"] = 2, [\"y\"] = 1}}}");
+        assert_ne!(a, b);
+        a.canonicalize();
+        b.canonicalize();
+        assert_eq!(a, b);
+        assert_eq!(a.to_gdb_string(), "{m = {[1] = 0, [2] = {x = 2, y = 1}}}");
+    }
+
+    #[test]
+    fn as_keys() {
+        let mut values: Vec<_> = ["{x = 1}", "{x = 1.0}", "{x = 1}"]
+            .iter()
+            .map(|t| OrdValue(parse(t)))
+            .collect();
+        values.push(OrdValue(Value::Float(f64::NAN)));
+        values.push(OrdValue(Value::Float(f64::NAN)));
+        let unique: HashSet<_> = values.iter().collect();
+        assert_eq!(unique.len(), 3);
+        let sorted: BTreeSet<_> = values.iter().collect();
+        assert_eq!(sorted.len(), 3);
+    }
+}
@@ -0,0 +1,272 @@
+//! Looking up values nested in structs, maps and lists by path.
+//!
+//! A path is a sequence of `.field` and `[index]` steps, like
+//! `threads[2].frame.func`; the leading `.` may be left out. An index is a
+//! number (negative ones count from the end of a list), a string literal or
+//! an enumerator, and is looked up as a key in maps, including the runs of
+//! sparse arrays. Fields are looked up in structs, including inherited fields
+//! of base classes and members of anonymous structs and unions, and as string
+//! keys in maps. Truncation, `static` flags, references, type casts, smart
+//! pointers and pretty-printer headers are looked through.
+
+use std::ops::Range;
+
+use crate::{Parser, Value};
+
+enum Step<'p> {
+    Field(&'p str),
+    Index(Value<'p>),
+}
+
+fn parse_path(path: &str) -> Option<Vec<Step<'_>>> {
+    let mut p = Parser::new(path);
+    let mut steps = Vec::new();
+    let mut first = true;
+    loop {
+        p.eat_ws();
+        if p.at_eof() {
+            return Some(steps);
+        }
+        if p.eat("[") {
+            let key = p.parse_value().ok()?;
+            p.eat_ws();
+            if !p.eat("]") {
+                return None;
+            }
+            steps.push(Step::Index(key));
+        } else {
+            if !p.eat(".") && !first {
+                return None;
+            }
+            let start = p.pos;
+            if p.parse_ident().is_empty() {
+                return None;
+            }
+            // pretty-printer pseudo fields like `get()`
+            p.eat("()");
+            steps.push(Step::Field(&path[start..p.pos]));
+        }
+        first = false;
+    }
+}
+
+impl<'a> Value<'a> {
+    /// The value at `path` in this one, or `None` if there's nothing there
+    /// or the path is malformed.
+    pub fn get_path(&self, path: &str) -> Option<&Value<'a>> {
+        parse_path(path)?
+            .iter()
+            .try_fold(self, |v, step| match step {
+                Step::Field(name) => v.field(name),
+                Step::Index(key) => v.index(key),
+            })
+    }
+
+    /// The field `name` of a struct, searching base classes too, or the
+    /// entry with the string key `name` of a map.
+    pub fn field(&self, name: &str) -> Option<&Value<'a>> {
+        match self.unwrapped() {
+            Value::Struct(fields) => fields
+                .iter()
+                .find(|(k, _)| k == name)
+                .map(|(_, v)| v)
+                .or_else(|| {
+                    fields
+                        .iter()
+                        .filter(|(k, _)| k.starts_with('<'))
+                        .find_map(|(_, base)| base.field(name))
+                }),
+            Value::Map(entries) => entries
+                .iter()
+                .find(|(k, _)| k.as_str() == Some(name))
+                .map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    /// Element `i` of a list, counting the elements of `<repeats N times>`
+    /// runs individually.
+    pub fn element(&self, i: usize) -> Option<&Value<'a>> {
+        let Value::List(items) = self.unwrapped() else {
+            return None;
+        };
+        let mut i = i;
+        for item in items {
+            let (v, n) = match item {
+                Value::Repeated(v, n) => (&**v, *n),
+                v => (v, 1),
+            };
+            if i < n {
+                return Some(v);
+            }
+            i -= n;
+        }
+        None
+    }
+
+    fn len(&self) -> usize {
+        match self.unwrapped() {
+            Value::List(items) => items
+                .iter()
+                .map(|item| match item {
+                    Value::Repeated(_, n) => *n,
+                    _ => 1,
+                })
+                .sum(),
+            _ => 0,
+        }
+    }
+
+    fn index(&self, key: &Value) -> Option<&Value<'a>> {
+        match self.unwrapped() {
+            Value::Map(entries) => entries
+                .iter()
+                .find(|(k, _)| k == key)
+                .map(|(_, v)| v)
+                .or_else(|| {
+                    let i = key.as_int()?;
+                    let ranges = self.index_ranges()?;
+                    ranges
+                        .into_iter()
+                        .find(|(range, _)| range.contains(&i))
+                        .map(|(_, v)| v)
+                }),
+            Value::List(_) => {
+                let i = key.as_int()?;
+                let i = if i < 0 { self.len() as i128 + i } else { i };
+                self.element(i.try_into().ok()?)
+            }
+            _ => None,
+        }
+    }
+
+    /// The indices each element of a list or sparse array covers, in order,
+    /// with `<repeats N times>` runs covering `N` indices. A sparse array is a
+    /// map with integer keys, like `{[0] = 0 <repeats 64 times>, [100] = 5}`,
+    /// which covers `0..64` and `100..101`; indices in between are missing.
+    /// `None` for anything else.
+    pub fn index_ranges(&self) -> Option<Vec<(Range<i128>, &Value<'a>)>> {
+        fn run<'v, 'a>(start: i128, v: &'v Value<'a>) -> (Range<i128>, &'v Value<'a>) {
+            match v {
+                Value::Repeated(v, n) => (start..start + *n as i128, v),
+                v => (start..start + 1, v),
+            }
+        }
+        match self.unwrapped() {
+            Value::List(items) => {
+                let mut start = 0;
+                Some(
+                    items
+                        .iter()
+                        .map(|v| {
+                            let (range, v) = run(start, v);
+                            start = range.end;
+                            (range, v)
+                        })
+                        .collect(),
+                )
+            }
+            Value::Map(entries) => entries
+                .iter()
+                .map(|(k, v)| Some(run(k.as_int()?, v)))
+                .collect(),
+            _ => None,
+        }
+    }
+
+    /// This value without the truncation marker, `static` flag, reference,
+    /// type cast, smart pointer or pretty-printer header around it.
+    pub(crate) fn unwrapped(&self) -> &Value<'a> {
+        match self {
+            Value::Truncated(v)
+            | Value::Static(v)
+            | Value::Encoded { value: v, .. }
+            | Value::Reference { value: v, .. }
+            | Value::Typed { value: v, .. }
+            | Value::SmartPointer { pointer: v, .. }
+            | Value::Described { value: Some(v), .. } => v.unwrapped(),
+            v => v,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Value<'_> {
+        Parser::new(text).parse_value_or_panic()
+    }
+
+    #[test]
+    fn fields_and_indices() {
+        let v = parse(
+            r#"{threads = {{id = 1}, {id = 2}, {id = 3, frame = {func = "main", line = 9}}}}"#,
+        );
+        assert_eq!(
+            v.get_path("threads[2].frame.func").and_then(Value::as_str),
+            Some("main")
+        );
+        assert_eq!(
+            v.get_path(".threads[-3].id").and_then(Value::as_uint),
+            Some(1)
+        );
+        assert_eq!(v.get_path("threads[3]"), None);
+        assert_eq!(v.get_path("threads.frame"), None);
+    }
+
+    #[test]
+    fn map_keys() {
+        let v = parse(r#"{["a"] = 1, [RED] = 2, [-1] = 3}"#);
+        assert_eq!(v.get_path(r#"["a"]"#).and_then(Value::as_uint), Some(1));
+        assert_eq!(v.get_path("a").and_then(Value::as_uint), Some(1));
+        assert_eq!(v.get_path("[RED]").and_then(Value::as_uint), Some(2));
+        assert_eq!(v.get_path("[-1]").and_then(Value::as_uint), Some(3));
+    }
+
+    #[test]
+    fn repeats_and_wrappers() {
+        let v = parse("{xs = std::vector of length 5, capacity 5 = {0 <repeats 4 times>, 7...}}");
+        assert_eq!(v.get_path("xs[3]").and_then(Value::as_uint), Some(0));
+        assert_eq!(v.get_path("xs[4]").and_then(Value::as_uint), Some(7));
+        assert_eq!(v.get_path("xs[-1]").and_then(Value::as_uint), Some(7));
+        let v = parse("{v = Vec(size=2) = {4, 5}}");
+        assert_eq!(v.get_path("v[1]").and_then(Value::as_uint), Some(5));
+    }
+
+    #[test]
+    fn sparse_arrays() {
+        let v = parse("{[0] = 0 <repeats 64 times>, [100] = 5, [101] = {1, 2}}");
+        assert_eq!(v.get_path("[63]").and_then(Value::as_uint), Some(0));
+        assert_eq!(v.get_path("[64]"), None);
+        assert_eq!(v.get_path("[100]").and_then(Value::as_uint), Some(5));
+        assert_eq!(v.get_path("[101][1]").and_then(Value::as_uint), Some(2));
+        let ranges: Vec<_> = v
+            .index_ranges()
+            .unwrap()
+            .into_iter()
+            .map(|(range, _)| range)
+            .collect();
+        assert_eq!(ranges, [0..64, 100..101, 101..102]);
+        let list = parse("{1, 0 <repeats 3 times>}");
+        assert_eq!(list.index_ranges().unwrap()[1].0, 1..4);
+        assert_eq!(parse(r#"{["a"] = 1}"#).index_ranges(), None);
+    }
+
+    #[test]
+    fn inherited_fields() {
+        let v = parse("{<Base> = {<Root> = {id = 4}, x = 1}, y = 2, p = {get() = 0x10}}");
+        assert_eq!(v.get_path("id").and_then(Value::as_uint), Some(4));
+        assert_eq!(v.get_path("x").and_then(Value::as_uint), Some(1));
+        assert_eq!(v.get_path("p.get()").and_then(Value::as_uint), Some(16));
+    }
+
+    #[test]
+    fn malformed_paths() {
+        let v = parse("{x = 1}");
+        for path in ["x.", "[", "x[0", "x y", ".."] {
+            assert_eq!(v.get_path(path), None, "{path}");
+        }
+        assert_eq!(v.get_path(""), Some(&v));
+    }
+}
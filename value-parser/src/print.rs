@@ -0,0 +1,371 @@
+//! Rendering [`Value`]s back into the syntax GDB prints them in, for `set
+//! var` commands and golden files.
+//!
+//! The output parses back into the same value, with one exception: a map
+//! key that is a string spelled like an identifier prints as a struct field,
+//! so `{["x"] = 1}` comes back as the struct `{x = 1}`. Numbers print in decimal, floats
+//! always with a fraction or exponent, and the addresses of pointers in hex,
+//! even without a symbol like `(Foo *) 0x601010`.
+
+use std::{borrow::Cow, fmt::Write};
+
+use crate::Value;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PrintOptions {
+    /// Spaces per nesting level, putting every list element and struct
+    /// field on its own line, or `None` to print everything on one line.
+    pub indent: Option<usize>,
+    /// Escape everything outside printable ASCII, writing non-ASCII
+    /// characters as the octal escapes of their UTF-8 bytes like GDB does
+    /// with an ASCII host charset.
+    pub ascii_only: bool,
+}
+
+impl Value<'_> {
+    /// This value in GDB's syntax, on one line.
+    pub fn to_gdb_string(&self) -> String {
+        self.to_gdb_string_with(&PrintOptions::default())
+    }
+
+    pub fn to_gdb_string_with(&self, options: &PrintOptions) -> String {
+        let mut printer = Printer {
+            options,
+            out: String::new(),
+            depth: 0,
+            prefix: "",
+        };
+        printer.value(self);
+        printer.out
+    }
+}
+
+fn is_field_name(s: &str) -> bool {
+    let name = s.strip_suffix("()").unwrap_or(s);
+    name.starts_with(|c: char| c.is_alphabetic() || c == '_')
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '_' | '.' | '$'))
+        && !matches!(name, "true" | "false")
+}
+
+struct Printer<'a> {
+    options: &'a PrintOptions,
+    out: String,
+    depth: usize,
+    /// The encoding prefix to write before quotes.
+    prefix: &'static str,
+}
+
+impl Printer<'_> {
+    fn value(&mut self, v: &Value) {
+        match v {
+            Value::Bool(b) => write!(self.out, "{b}").unwrap(),
+            Value::Int(n) => write!(self.out, "{n}").unwrap(),
+            Value::Uint(n) => write!(self.out, "{n}").unwrap(),
+            Value::Float(n) if n.is_nan() => {
+                let sign = if n.is_sign_negative() { "-" } else { "" };
+                let payload = n.to_bits() & ((1 << 52) - 1);
+                write!(self.out, "{sign}nan({payload:#x})").unwrap()
+            }
+            Value::Float(n) if n.is_infinite() => write!(self.out, "{n}").unwrap(),
+            // Debug keeps the `.0` that makes it parse back as a float
+            Value::Float(n) => write!(self.out, "{n:?}").unwrap(),
+            Value::String(s) => self.quoted(s, '"'),
+            Value::List(items) => self.braces(items, false, |p, v| p.value(v)),
+            Value::Map(entries) => self.braces(entries, false, |p, e| p.entry(e)),
+            Value::Struct(fields) => self.braces(fields, false, |p, f| p.field(f)),
+            Value::Repeated(v, n) => {
+                self.value(v);
+                write!(self.out, " <repeats {n} times>").unwrap();
+            }
+            Value::OptimizedOut => self.out.push_str("<optimized out>"),
+            Value::Char { code, ch } => {
+                write!(self.out, "{code} ").unwrap();
+                self.char(*code, *ch);
+            }
+            Value::Pointer {
+                address,
+                symbol,
+                pointee,
+            } => {
+                write!(self.out, "{address:#x}").unwrap();
+                if let Some(symbol) = symbol {
+                    write!(self.out, " <{symbol}>").unwrap();
+                }
+                if let Some(pointee) = pointee {
+                    self.out.push(' ');
+                    self.value(pointee);
+                }
+            }
+            Value::MemberPointer(name) => write!(self.out, "&{name}").unwrap(),
+            Value::Enum(name) => self.out.push_str(name),
+            // the `static` goes before the field name
+            Value::Static(v) => self.value(v),
+            Value::Truncated(v) => match &**v {
+                Value::List(items) => self.braces(items, true, |p, v| p.value(v)),
+                Value::Map(entries) => self.braces(entries, true, |p, e| p.entry(e)),
+                Value::Struct(fields) => self.braces(fields, true, |p, f| p.field(f)),
+                v => {
+                    self.value(v);
+                    self.out.push_str("...");
+                }
+            },
+            Value::Incomplete { value, bytes } => {
+                self.value(value);
+                self.out.push_str(" <incomplete sequence ");
+                for b in bytes {
+                    write!(self.out, "\\{b:03o}").unwrap();
+                }
+                self.out.push('>');
+            }
+            Value::StringRuns(segments) => {
+                for (i, segment) in segments.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push_str(", ");
+                    }
+                    match segment {
+                        // runs are printed without the numeric code
+                        Value::Repeated(v, n) => {
+                            if let Value::Char { code, ch } = **v {
+                                self.char(code, ch);
+                            }
+                            write!(self.out, " <repeats {n} times>").unwrap();
+                        }
+                        v => self.value(v),
+                    }
+                }
+            }
+            Value::Described { description, value } => {
+                self.out.push_str(description);
+                if let Some(value) = value {
+                    self.out.push_str(" = ");
+                    self.value(value);
+                }
+            }
+            Value::SmartPointer {
+                ty,
+                use_count,
+                weak_count,
+                pointer,
+            } => {
+                self.out.push_str(ty);
+                match (use_count, weak_count) {
+                    (Some(uses), Some(weak)) => {
+                        write!(self.out, " (use count {uses}, weak count {weak})").unwrap()
+                    }
+                    (Some(uses), None) => write!(self.out, " (use count {uses})").unwrap(),
+                    (None, Some(weak)) => write!(self.out, " (weak count {weak})").unwrap(),
+                    (None, None) => {}
+                }
+                self.out.push_str(" = {get() = ");
+                self.value(pointer);
+                self.out.push('}');
+            }
+            Value::Typed { ty, value } => {
+                write!(self.out, "({ty}) ").unwrap();
+                // a pointer without a symbol, which parses as a number
+                match **value {
+                    Value::Uint(address) => write!(self.out, "{address:#x}").unwrap(),
+                    Value::Int(address) if address >= 0 => {
+                        write!(self.out, "{address:#x}").unwrap()
+                    }
+                    _ => self.value(value),
+                }
+            }
+            Value::Reference { address, value } => {
+                write!(self.out, "@{address:#x}: ").unwrap();
+                self.value(value);
+            }
+            Value::Encoded { encoding, value } => {
+                let outer = std::mem::replace(&mut self.prefix, encoding.prefix());
+                self.value(value);
+                self.prefix = outer;
+            }
+            Value::Raw(text) => self.out.push_str(text),
+            Value::Error(message) if message.starts_with("error") => {
+                write!(self.out, "<{message}>").unwrap()
+            }
+            Value::Error(message) => write!(self.out, "<error: {message}>").unwrap(),
+        }
+    }
+
+    fn field(&mut self, (name, v): &(Cow<str>, Value)) {
+        self.static_prefix(v);
+        self.out.push_str(name);
+        self.out.push_str(" = ");
+        self.value(v);
+    }
+
+    fn entry(&mut self, (k, v): &(Value, Value)) {
+        match k {
+            Value::String(name) if is_field_name(name) || name.starts_with('<') => {
+                self.static_prefix(v);
+                self.out.push_str(name)
+            }
+            k => {
+                self.out.push('[');
+                self.value(k);
+                self.out.push(']');
+            }
+        }
+        self.out.push_str(" = ");
+        self.value(v);
+    }
+
+    fn static_prefix(&mut self, v: &Value) {
+        if let Value::Static(_) = v {
+            self.out.push_str("static ");
+        }
+    }
+
+    fn braces<T>(&mut self, items: &[T], truncated: bool, mut item: impl FnMut(&mut Self, &T)) {
+        self.out.push('{');
+        self.depth += 1;
+        for (i, v) in items.iter().enumerate() {
+            if i > 0 {
+                self.out.push(',');
+                if self.options.indent.is_none() {
+                    self.out.push(' ');
+                }
+            }
+            self.newline();
+            item(self, v);
+        }
+        if truncated {
+            self.out.push_str("...");
+        }
+        self.depth -= 1;
+        if !items.is_empty() {
+            self.newline();
+        }
+        self.out.push('}');
+    }
+
+    fn newline(&mut self) {
+        if let Some(indent) = self.options.indent {
+            self.out.push('\n');
+            self.out
+                .extend(std::iter::repeat_n(' ', indent * self.depth));
+        }
+    }
+
+    /// A character literal. A plain `char` outside ASCII is a byte, which
+    /// GDB prints as an octal escape, like `'\377'`.
+    fn char(&mut self, code: i64, ch: char) {
+        if self.prefix.is_empty() && !ch.is_ascii() && (-128..=255).contains(&code) {
+            write!(self.out, "'\\{:03o}'", code as u8).unwrap();
+        } else {
+            self.quoted(&ch.to_string(), '\'');
+        }
+    }
+
+    fn quoted(&mut self, s: &str, quote: char) {
+        self.out.push_str(self.prefix);
+        self.out.push(quote);
+        for ch in s.chars() {
+            match ch {
+                '\\' => self.out.push_str("\\\\"),
+                '\n' => self.out.push_str("\\n"),
+                '\t' => self.out.push_str("\\t"),
+                '\r' => self.out.push_str("\\r"),
+                c if c == quote => {
+                    self.out.push('\\');
+                    self.out.push(c);
+                }
+                c if c.is_ascii_control() => write!(self.out, "\\{:03o}", c as u32).unwrap(),
+                c if !c.is_ascii() && (c.is_control() || self.options.ascii_only) => {
+                    for b in c.to_string().bytes() {
+                        write!(self.out, "\\{b:03o}").unwrap();
+                    }
+                }
+                c => self.out.push(c),
+            }
+        }
+        self.out.push(quote);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{value, Parser};
+
+    fn roundtrip(text: &str) {
+        let v = Parser::new(text).parse_value_or_panic();
+        assert_eq!(v.to_gdb_string(), text);
+        let pretty = v.to_gdb_string_with(&PrintOptions {
+            indent: Some(2),
+            ..Default::default()
+        });
+        assert_eq!(Parser::new(&pretty).parse_value_or_panic(), v, "{pretty}");
+    }
+
+    #[test]
+    fn roundtrips() {
+        roundtrip(r#"{x = 1, [2] = "y"}"#);
+        roundtrip(r#"{<Base> = {a = -1.5}, p = 0x401126 <main+6>, s = "ab\"c\n"...}"#);
+        roundtrip(r"{c = 97 'a', q = 39 '\'', nul = 0 '\000', e = <optimized out>}");
+        roundtrip(r#"{buf = "ab", 'x' <repeats 20 times>, "", color = ns::RED}"#);
+        roundtrip("{0 <repeats 16 times>, 1, 2...}");
+        roundtrip("{v = std::vector of length 2, capacity 2 = {1, 2}, w = empty std::list}");
+        roundtrip(
+            "{a = <error: Cannot access memory at address 0x0>, b = <error reading variable>}",
+        );
+        roundtrip("{{}, {1, {true}}}");
+        roundtrip("{imię = \"ń😀\", k = Kolor::Żółty}");
+        roundtrip("{static n = 1, x = {static s = \"a\", [0] = 1}}");
+        roundtrip(r#"{w = L"ab", L'x' <repeats 20 times>, c = 97 u'a', p = 0x10 U"s"}"#);
+        roundtrip("{r = @0x7ffe: {x = 1}, s = @0x10: \"a\"}");
+        roundtrip("{p = (Foo *) 0x10 <foo>, f = (void (*)(int)) 0x20 <f(int)>}");
+        roundtrip("{p = std::shared_ptr<A> (use count 2, weak count 0) = {get() = 0x10 <a>}, u = std::unique_ptr<A> = {get() = 0}}");
+        roundtrip("{v = Vec(size=2) = {1, 2}, s = &[u8](size=0)}");
+        roundtrip(
+            r#"{s = "ab" <incomplete sequence \360\237>, t = "x", 'y' <repeats 20 times>, "z" <incomplete sequence \303>...}"#,
+        );
+        roundtrip("{_vptr.A = 0x10 <vtable for B+16>, m = &A::f(int)}");
+        roundtrip("{[0] = 0 <repeats 64 times>, [100] = 5}");
+        roundtrip("{-1, 2.0, 0.5, 1e300, 340282366920938463463374607431768211455}");
+        roundtrip("{inf, -inf, -1.5}");
+        roundtrip("{p = (Foo *) 0x601010, n = (Node *) 0x0, c = (char *) 0x10 \"a\"}");
+        roundtrip(r"{c = -1 '\377', d = 128 '\200', e = -23 '\351', w = 233 L'é'}");
+        roundtrip(r#"{s = "ab", '\377' <repeats 20 times>, t = "a\302\200b"}"#);
+        assert_eq!(
+            Parser::new("{-nan(0x8000000000000), nan(0x1)}")
+                .parse_value_or_panic()
+                .to_gdb_string(),
+            "{-nan(0x8000000000000), nan(0x1)}"
+        );
+    }
+
+    #[test]
+    fn indented() {
+        let v = value!({"x" = 1, "ys" = [1, "a"], "e" = []});
+        let pretty = v.to_gdb_string_with(&PrintOptions {
+            indent: Some(2),
+            ..Default::default()
+        });
+        assert_eq!(
+            pretty,
+            "{\n  x = 1,\n  ys = {\n    1,\n    \"a\"\n  },\n  e = {}\n}"
+        );
+    }
+
+    #[test]
+    fn ascii_only() {
+        let v = value!({ "s" = "é\u{1}" });
+        assert_eq!(v.to_gdb_string(), "{s = \"é\\001\"}");
+        let ascii = v.to_gdb_string_with(&PrintOptions {
+            ascii_only: true,
+            ..Default::default()
+        });
+        assert_eq!(ascii, r#"{s = "\303\251\001"}"#);
+        assert_eq!(Parser::new(&ascii).parse_value_or_panic(), v);
+    }
+
+    #[test]
+    fn string_keys_that_arent_fields() {
+        let v = value!({"1" => 2, "ok" => 3});
+        assert_eq!(v.to_gdb_string(), r#"{["1"] = 2, ok = 3}"#);
+    }
+}
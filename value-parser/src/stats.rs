@@ -0,0 +1,190 @@
+//! Measuring values, to decide whether one is small enough to show in full.
+
+use std::{borrow::Cow, mem::size_of};
+
+use crate::{
+    diff::{field_path, index_path},
+    Value,
+};
+
+impl<'a> Value<'a> {
+    /// How deeply lists, structs and maps nest in this value, counting the
+    /// value itself like [`ParserConfig::max_depth`](crate::ParserConfig)
+    /// does: a number has depth 1 and `{{1}}` depth 3. Wrappers like
+    /// references and casts don't add to it.
+    pub fn depth(&self) -> usize {
+        match self.unwrapped() {
+            Value::List(items) => 1 + items.iter().map(Value::depth).max().unwrap_or(0),
+            Value::Struct(fields) => 1 + fields.iter().map(|(_, v)| v.depth()).max().unwrap_or(0),
+            Value::Map(entries) => 1 + entries.iter().map(|(_, v)| v.depth()).max().unwrap_or(0),
+            Value::Repeated(v, _) => v.depth(),
+            _ => 1,
+        }
+    }
+
+    /// The number of values [`Value::leaves`] yields, counting the elements
+    /// of `<repeats N times>` runs individually.
+    pub fn leaf_count(&self) -> usize {
+        match self.unwrapped() {
+            Value::List(items) => items.iter().map(Value::leaf_count).sum(),
+            Value::Struct(fields) => fields.iter().map(|(_, v)| v.leaf_count()).sum(),
+            Value::Map(entries) => entries.iter().map(|(_, v)| v.leaf_count()).sum(),
+            Value::Repeated(v, n) => v.leaf_count().saturating_mul(*n),
+            _ => 1,
+        }
+    }
+
+    /// Roughly how many bytes this value takes up in memory, counting the
+    /// text of borrowed strings as if it were owned.
+    pub fn approx_size_bytes(&self) -> usize {
+        let nested = match self {
+            Value::String(s)
+            | Value::MemberPointer(s)
+            | Value::Enum(s)
+            | Value::Raw(s)
+            | Value::Error(s) => s.len(),
+            Value::Map(entries) => entries
+                .iter()
+                .map(|(k, v)| k.approx_size_bytes() + v.approx_size_bytes())
+                .sum(),
+            Value::Struct(fields) => fields
+                .iter()
+                .map(|(k, v)| size_of::<Cow<str>>() + k.len() + v.approx_size_bytes())
+                .sum(),
+            Value::List(items) | Value::StringRuns(items) => {
+                items.iter().map(Value::approx_size_bytes).sum()
+            }
+            Value::Pointer {
+                symbol, pointee, ..
+            } => {
+                symbol.as_ref().map_or(0, |s| s.len())
+                    + pointee.as_ref().map_or(0, |v| v.approx_size_bytes())
+            }
+            Value::Described { description, value } => {
+                description.len() + value.as_ref().map_or(0, |v| v.approx_size_bytes())
+            }
+            Value::SmartPointer { ty, pointer: v, .. } | Value::Typed { ty, value: v } => {
+                ty.len() + v.approx_size_bytes()
+            }
+            Value::Incomplete { value, bytes } => bytes.len() + value.approx_size_bytes(),
+            Value::Repeated(v, _)
+            | Value::Static(v)
+            | Value::Truncated(v)
+            | Value::Reference { value: v, .. }
+            | Value::Encoded { value: v, .. } => v.approx_size_bytes(),
+            Value::Bool(_)
+            | Value::Int(_)
+            | Value::Uint(_)
+            | Value::Float(_)
+            | Value::OptimizedOut
+            | Value::Char { .. } => 0,
+        };
+        size_of::<Self>() + nested
+    }
+
+    /// Every value in this one that isn't a list, struct or map, with its
+    /// path in the syntax of [`Value::get_path`], depth first. The elements
+    /// of `<repeats N times>` runs are yielded one by one, and fields of base
+    /// classes under the paths that look them up from the derived class.
+    pub fn leaves(&self) -> Leaves<'_, 'a> {
+        Leaves {
+            stack: vec![(String::new(), self)],
+        }
+    }
+}
+
+/// The iterator returned by [`Value::leaves`].
+pub struct Leaves<'v, 'a> {
+    stack: Vec<(String, &'v Value<'a>)>,
+}
+
+impl<'v, 'a> Iterator for Leaves<'v, 'a> {
+    type Item = (String, &'v Value<'a>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (path, v) = self.stack.pop()?;
+            let start = self.stack.len();
+            match v.unwrapped() {
+                Value::List(items) => {
+                    let mut i = 0;
+                    for item in items {
+                        let (item, n) = match item {
+                            Value::Repeated(item, n) => (&**item, *n),
+                            item => (item, 1),
+                        };
+                        for _ in 0..n {
+                            self.stack.push((format!("{path}[{i}]"), item));
+                            i += 1;
+                        }
+                    }
+                }
+                Value::Struct(fields) => self.stack.extend(fields.iter().map(|(name, v)| {
+                    if name.starts_with('<') {
+                        (path.clone(), v)
+                    } else {
+                        (field_path(&path, name), v)
+                    }
+                })),
+                Value::Map(entries) => self
+                    .stack
+                    .extend(entries.iter().map(|(k, v)| (index_path(&path, k), v))),
+                leaf => return Some((path, leaf)),
+            }
+            // children are popped in order
+            self.stack[start..].reverse();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Parser, Value};
+
+    fn parse(text: &str) -> Value<'_> {
+        Parser::new(text).parse_value_or_panic()
+    }
+
+    #[test]
+    fn depth_and_count() {
+        assert_eq!(parse("1").depth(), 1);
+        assert_eq!(parse("{}").depth(), 1);
+        let v = parse("{a = {1, {2} <repeats 3 times>}, r = @0x10: {x = {}}}");
+        assert_eq!(v.depth(), 4);
+        assert_eq!(v.leaf_count(), 4);
+    }
+
+    #[test]
+    fn leaves() {
+        let v = parse(
+            r#"{<Base> = {id = 1}, xs = {7, 0 <repeats 2 times>}, m = {["k"] = "v"}, e = {}}"#,
+        );
+        let leaves: Vec<_> = v
+            .leaves()
+            .map(|(path, leaf)| (path, leaf.to_gdb_string()))
+            .collect();
+        assert_eq!(
+            leaves,
+            [
+                ("id", "1"),
+                ("xs[0]", "7"),
+                ("xs[1]", "0"),
+                ("xs[2]", "0"),
+                ("m[\"k\"]", "\"v\""),
+            ]
+            .map(|(p, v)| (p.to_owned(), v.to_owned()))
+        );
+        assert_eq!(v.leaf_count(), leaves.len());
+        for (path, _) in &leaves {
+            assert!(v.get_path(path).is_some(), "{path}");
+        }
+    }
+
+    #[test]
+    fn size_grows_with_content() {
+        let small = parse(r#"{s = "a"}"#).approx_size_bytes();
+        let large = parse(r#"{s = "abcdefghijklmnopqrstuvwxyz", t = 1}"#).approx_size_bytes();
+        assert!(small >= std::mem::size_of::<Value>());
+        assert!(large > small + 25);
+    }
+}
@@ -0,0 +1,222 @@
+//! Parsing values that arrive in pieces, like a large array read from a
+//! pipe.
+//!
+//! [`StreamParser`] buffers what it is fed and tracks brace nesting and
+//! string literals as the text comes in, so the (non-incremental) parser only
+//! runs once a value can be complete. A value is complete once something
+//! other than its own continuation follows it, usually the newline GDB ends
+//! it with; [`StreamParser::finish`] takes the last one at end of input.
+
+use crate::{intern::Interner, ParseError, Parser, ParserConfig, Value};
+
+/// What feeding a [`StreamParser`] gave. Values are owned, or borrow only
+/// their names from the parser's [`Interner`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Feed<'i> {
+    /// The buffered text doesn't hold a whole value yet.
+    NeedMoreInput,
+    Value(Value<'i>),
+}
+
+#[derive(Debug, Default)]
+pub struct StreamParser<'i> {
+    buf: String,
+    /// How much of `buf` the nesting state below accounts for.
+    scanned: usize,
+    depth: usize,
+    /// The quote of the string or char literal being scanned, if any.
+    quote: Option<char>,
+    escaped: bool,
+    config: ParserConfig,
+    /// Where the names in values come from, if they are interned.
+    interner: Option<&'i Interner>,
+}
+
+impl<'i> StreamParser<'i> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A stream parser whose values are parsed with `config`.
+    pub fn with_config(config: ParserConfig) -> Self {
+        Self {
+            config,
+            ..Self::default()
+        }
+    }
+
+    /// Shares the storage of field names and other names between values by
+    /// taking them from `interner`, see [`Value::into_owned_interned`].
+    pub fn intern_names(mut self, interner: &'i Interner) -> Self {
+        self.interner = Some(interner);
+        self
+    }
+
+    /// Adds `chunk` to the buffered text and returns the next value if it
+    /// is now complete. One chunk can complete several values; call
+    /// [`StreamParser::next_value`] to get the rest.
+    pub fn feed(&mut self, chunk: &str) -> Result<Feed<'i>, ParseError> {
+        self.buf.push_str(chunk);
+        self.next_value()
+    }
+
+    /// Returns the next complete value in the buffered text. After an error
+    /// the buffered text is left as is, so the parser can't go on.
+    pub fn next_value(&mut self) -> Result<Feed<'i>, ParseError> {
+        self.scan();
+        let text = self.buf.trim_start();
+        if text.is_empty() || self.depth > 0 || self.quote.is_some() {
+            return Ok(Feed::NeedMoreInput);
+        }
+        let skipped = self.buf.len() - text.len();
+        let mut p = Parser::with_config(text, self.config.clone());
+        match p.parse_value() {
+            // until the line ends the value may go on in the next chunk, as
+            // in `"ab"` `...`, `"ab",` ` 'x' <repeats 9 times>` or `"ab" <inc`
+            // `omplete sequence \360>`
+            Ok(_)
+                if !text[p.pos..].contains('\n')
+                    && (matches!(text[p.pos..].trim(), "" | "." | ".." | ",")
+                        || "<incomplete sequence ".starts_with(text[p.pos..].trim())) =>
+            {
+                Ok(Feed::NeedMoreInput)
+            }
+            Ok(v) => {
+                let v = owned(self.interner, v);
+                self.consume(skipped + p.pos);
+                Ok(Feed::Value(v))
+            }
+            Err(e) if e.offset >= text.len() => Ok(Feed::NeedMoreInput),
+            Err(mut e) => {
+                e.offset += skipped;
+                Err(e)
+            }
+        }
+    }
+
+    /// Parses the value left in the buffer at the end of the input, if any.
+    pub fn finish(self) -> Result<Option<Value<'i>>, ParseError> {
+        let text = self.buf.trim();
+        if text.is_empty() {
+            return Ok(None);
+        }
+        let mut p = Parser::with_config(text, self.config.clone());
+        let v = p.parse_value()?;
+        p.eat_ws();
+        if !p.at_eof() {
+            return Err(p.error("unexpected text after value", &[]));
+        }
+        Ok(Some(owned(self.interner, v)))
+    }
+
+    fn consume(&mut self, n: usize) {
+        self.buf.drain(..n);
+        self.scanned -= n;
+    }
+
+    fn scan(&mut self) {
+        for ch in self.buf[self.scanned..].chars() {
+            if let Some(quote) = self.quote {
+                if self.escaped {
+                    self.escaped = false;
+                } else if ch == '\\' {
+                    self.escaped = true;
+                } else if ch == quote {
+                    self.quote = None;
+                }
+                continue;
+            }
+            match ch {
+                '{' => self.depth += 1,
+                '}' => self.depth = self.depth.saturating_sub(1),
+                '"' | '\'' => self.quote = Some(ch),
+                _ => {}
+            }
+        }
+        self.scanned = self.buf.len();
+    }
+}
+
+fn owned<'i>(interner: Option<&'i Interner>, v: Value) -> Value<'i> {
+    match interner {
+        Some(interner) => v.into_owned_interned(interner),
+        None => v.into_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::value;
+
+    fn feed_all(chunks: &[&str]) -> (Vec<Value<'static>>, StreamParser<'static>) {
+        let mut s = StreamParser::new();
+        let mut values = Vec::new();
+        for chunk in chunks {
+            let mut feed = s.feed(chunk).unwrap();
+            while let Feed::Value(v) = feed {
+                values.push(v);
+                feed = s.next_value().unwrap();
+            }
+        }
+        (values, s)
+    }
+
+    #[test]
+    fn split_anywhere() {
+        let text = r#"{x = 1, s = "a, {b}\"", c = 39 '\'', ys = {1, 2...}, i = "a" <incomplete sequence \360>}"#;
+        let expected = Parser::new(text).parse_value_or_panic();
+        for split in 1..text.len() {
+            let (values, s) = feed_all(&[&text[..split], &text[split..], "\n"]);
+            assert_eq!(values, std::slice::from_ref(&expected), "split at {split}");
+            assert_eq!(s.finish(), Ok(None));
+        }
+    }
+
+    #[test]
+    fn several_values_per_chunk() {
+        let (values, s) = feed_all(&["{1}\n{2", "}\n3\n4"]);
+        assert_eq!(values, [value!([1]), value!([2]), value!(3)]);
+        assert_eq!(s.finish(), Ok(Some(value!(4))));
+    }
+
+    #[test]
+    fn continuation_waits() {
+        let mut s = StreamParser::new();
+        assert_eq!(s.feed("\"ab\""), Ok(Feed::NeedMoreInput));
+        assert_eq!(s.feed("."), Ok(Feed::NeedMoreInput));
+        assert_eq!(s.feed(".. "), Ok(Feed::NeedMoreInput));
+        assert_eq!(
+            s.feed("\n"),
+            Ok(Feed::Value(Value::Truncated(Box::new(value!("ab")))))
+        );
+    }
+
+    #[test]
+    fn interned_names() {
+        let interner = Interner::new();
+        let mut s = StreamParser::new().intern_names(&interner);
+        let Ok(Feed::Value(Value::Struct(a))) = s.feed("{x = 1}\n{x = 2}") else {
+            panic!("expected a struct");
+        };
+        let Ok(Some(Value::Struct(b))) = s.finish() else {
+            panic!("expected a struct");
+        };
+        assert_eq!(a[0].0.as_ptr(), b[0].0.as_ptr());
+    }
+
+    #[test]
+    fn string_runs_across_chunks() {
+        let (values, _) = feed_all(&["\"ab\",", " 'x' <repeats 3 times>", "\n"]);
+        assert_eq!(values[0].expanded(), value!("abxxx"));
+    }
+
+    #[test]
+    fn errors_are_reported() {
+        let mut s = StreamParser::new();
+        assert_eq!(s.feed("\n{1, 2"), Ok(Feed::NeedMoreInput));
+        let err = s.feed(", x = 3}\n").unwrap_err();
+        assert_eq!(err.message, "can't mix list and map");
+        assert_eq!(err.offset, 8);
+    }
+}
@@ -0,0 +1,261 @@
+//! Walking over every value nested in a value, so searches, conversions and
+//! redactions don't each have to match on every variant.
+//!
+//! Paths passed to the hooks use the syntax of [`Value::get_path`]. Wrappers
+//! like truncation, references, casts and pretty-printer headers are looked
+//! through, the value inside them is visited at the same path. The elements
+//! of a `<repeats N times>` run are visited once, at the path of the first
+//! of them. Map keys aren't visited.
+
+use std::borrow::Cow;
+
+use crate::{
+    diff::{field_path, index_path},
+    Value,
+};
+
+/// Hooks called by [`Value::walk`], all doing nothing by default.
+pub trait Visitor<'v, 'a> {
+    fn visit_bool(&mut self, _path: &str, _b: bool) {}
+
+    /// An integer or float.
+    fn visit_number(&mut self, _path: &str, _n: &'v Value<'a>) {}
+
+    /// A string: a [`Value::String`], [`Value::StringRuns`] or
+    /// [`Value::Incomplete`], whose [`Value::expanded`] is the whole text.
+    fn visit_string(&mut self, _path: &str, _s: &'v Value<'a>) {}
+
+    fn visit_char(&mut self, _path: &str, _ch: char) {}
+
+    /// Any other value that isn't a list, struct or map, like a pointer or
+    /// an enumerator.
+    fn visit_other(&mut self, _path: &str, _v: &'v Value<'a>) {}
+
+    /// Called before the fields of a struct are visited. Returning `false`
+    /// skips them, and [`Visitor::exit_struct`] isn't called.
+    fn enter_struct(&mut self, _path: &str, _fields: &'v [(Cow<'a, str>, Value<'a>)]) -> bool {
+        true
+    }
+
+    fn exit_struct(&mut self, _path: &str) {}
+
+    /// Called before the values of a map are visited. Returning `false`
+    /// skips them, and [`Visitor::exit_map`] isn't called.
+    fn enter_map(&mut self, _path: &str, _entries: &'v [(Value<'a>, Value<'a>)]) -> bool {
+        true
+    }
+
+    fn exit_map(&mut self, _path: &str) {}
+
+    /// Called before the elements of a list are visited. Returning `false`
+    /// skips them, and [`Visitor::exit_list`] isn't called.
+    fn enter_list(&mut self, _path: &str, _items: &'v [Value<'a>]) -> bool {
+        true
+    }
+
+    fn exit_list(&mut self, _path: &str) {}
+}
+
+/// Called by [`Value::walk_mut`] on every value before the values in it,
+/// returning whether to go on into them. Implemented for closures.
+pub trait VisitorMut<'a> {
+    fn visit_mut(&mut self, path: &str, v: &mut Value<'a>) -> bool;
+}
+
+impl<'a, F: FnMut(&str, &mut Value<'a>) -> bool> VisitorMut<'a> for F {
+    fn visit_mut(&mut self, path: &str, v: &mut Value<'a>) -> bool {
+        self(path, v)
+    }
+}
+
+/// The path of a struct field, where the fields of base classes and
+/// anonymous members are looked up as if they were the struct's own.
+fn member_path(path: &str, name: &str) -> String {
+    if name.starts_with('<') {
+        path.to_owned()
+    } else {
+        field_path(path, name)
+    }
+}
+
+impl<'a> Value<'a> {
+    /// Calls the hooks of `visitor` for this value and every value in it,
+    /// depth first.
+    pub fn walk<'v>(&'v self, visitor: &mut impl Visitor<'v, 'a>) {
+        walk_at("", self, visitor);
+    }
+
+    /// Calls `visitor` on this value and every value in it, depth first,
+    /// letting it change them. What the visitor leaves in place of a value
+    /// is what's walked into.
+    pub fn walk_mut(&mut self, visitor: &mut impl VisitorMut<'a>) {
+        walk_mut_at("", self, visitor);
+    }
+}
+
+fn walk_at<'v, 'a>(path: &str, v: &'v Value<'a>, visitor: &mut impl Visitor<'v, 'a>) {
+    match v.unwrapped() {
+        Value::Bool(b) => visitor.visit_bool(path, *b),
+        v @ (Value::Int(_) | Value::Uint(_) | Value::Float(_)) => visitor.visit_number(path, v),
+        v @ (Value::String(_) | Value::StringRuns(_) | Value::Incomplete { .. }) => {
+            visitor.visit_string(path, v)
+        }
+        Value::Char { ch, .. } => visitor.visit_char(path, *ch),
+        Value::Struct(fields) => {
+            if visitor.enter_struct(path, fields) {
+                for (name, v) in fields {
+                    walk_at(&member_path(path, name), v, visitor);
+                }
+                visitor.exit_struct(path);
+            }
+        }
+        Value::Map(entries) => {
+            if visitor.enter_map(path, entries) {
+                for (k, v) in entries {
+                    walk_at(&index_path(path, k), v, visitor);
+                }
+                visitor.exit_map(path);
+            }
+        }
+        Value::List(items) => {
+            if visitor.enter_list(path, items) {
+                let mut i = 0;
+                for item in items {
+                    let (item, n) = match item {
+                        Value::Repeated(item, n) => (&**item, *n),
+                        item => (item, 1),
+                    };
+                    walk_at(&format!("{path}[{i}]"), item, visitor);
+                    i += n;
+                }
+                visitor.exit_list(path);
+            }
+        }
+        Value::Repeated(v, _) => walk_at(path, v, visitor),
+        v => visitor.visit_other(path, v),
+    }
+}
+
+fn walk_mut_at<'a>(path: &str, v: &mut Value<'a>, visitor: &mut impl VisitorMut<'a>) {
+    if !visitor.visit_mut(path, v) {
+        return;
+    }
+    match v {
+        Value::Struct(fields) => {
+            for (name, v) in fields {
+                walk_mut_at(&member_path(path, name), v, visitor);
+            }
+        }
+        Value::Map(entries) => {
+            for (k, v) in entries {
+                walk_mut_at(&index_path(path, k), v, visitor);
+            }
+        }
+        Value::List(items) => {
+            let mut i = 0;
+            for item in items {
+                let n = match item {
+                    Value::Repeated(_, n) => *n,
+                    _ => 1,
+                };
+                walk_mut_at(&format!("{path}[{i}]"), item, visitor);
+                i += n;
+            }
+        }
+        Value::Repeated(v, _)
+        | Value::Truncated(v)
+        | Value::Static(v)
+        | Value::Encoded { value: v, .. }
+        | Value::Reference { value: v, .. }
+        | Value::Typed { value: v, .. }
+        | Value::SmartPointer { pointer: v, .. }
+        | Value::Described { value: Some(v), .. } => walk_mut_at(path, v, visitor),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{value, Parser};
+
+    fn parse(text: &str) -> Value<'_> {
+        Parser::new(text).parse_value_or_panic()
+    }
+
+    #[derive(Default)]
+    struct Events(Vec<String>);
+
+    impl<'v, 'a> Visitor<'v, 'a> for Events {
+        fn visit_number(&mut self, path: &str, n: &'v Value<'a>) {
+            self.0.push(format!("{path} = {}", n.to_gdb_string()));
+        }
+
+        fn visit_string(&mut self, path: &str, s: &'v Value<'a>) {
+            self.0.push(format!("{path} = {}", s.to_gdb_string()));
+        }
+
+        fn visit_other(&mut self, path: &str, v: &'v Value<'a>) {
+            self.0.push(format!("{path} = {}", v.to_gdb_string()));
+        }
+
+        fn enter_list(&mut self, path: &str, _: &'v [Value<'a>]) -> bool {
+            self.0.push(format!("{path} ["));
+            !path.is_empty()
+        }
+
+        fn exit_list(&mut self, path: &str) {
+            self.0.push(format!("{path} ]"));
+        }
+
+        fn enter_struct(&mut self, path: &str, _: &'v [(Cow<'a, str>, Value<'a>)]) -> bool {
+            self.0.push(format!("{path} {{"));
+            true
+        }
+    }
+
+    #[test]
+    fn events() {
+        let v = parse(
+            r#"{<Base> = {id = 1}, xs = {7, 0 <repeats 2 times>, 1}, m = {["k"] = RED}, r = @0x10: "a"}"#,
+        );
+        let mut events = Events::default();
+        v.walk(&mut events);
+        assert_eq!(
+            events.0,
+            [
+                " {",
+                " {",
+                "id = 1",
+                "xs [",
+                "xs[0] = 7",
+                "xs[1] = 0",
+                "xs[3] = 1",
+                "xs ]",
+                "m[\"k\"] = RED",
+                "r = \"a\"",
+            ]
+        );
+        let mut events = Events::default();
+        parse("{1, 2}").walk(&mut events);
+        assert_eq!(events.0, [" ["]);
+    }
+
+    #[test]
+    fn redaction() {
+        let mut v = parse(r#"{user = {name = "bob", password = "hunter2"}, keys = {"a", "b"}}"#);
+        v.walk_mut(&mut |path: &str, v: &mut Value| {
+            if path.ends_with("password") || path.starts_with("keys[") {
+                *v = Value::from("<redacted>");
+            }
+            true
+        });
+        assert_eq!(
+            v,
+            value!({
+                "user" = {"name" = "bob", "password" = "<redacted>"},
+                "keys" = ["<redacted>", "<redacted>"]
+            })
+        );
+    }
+}
@@ -1,7 +1,10 @@
-from typing import TextIO
+from typing import Callable, Iterator, TextIO
 import gdb
 import graphviz
+import json
 import math
+import operator
+import re
 from graphviz.graphs import Digraph
 
 def as_list(expr: gdb.Value) -> list[gdb.Value] | None:
@@ -15,6 +18,75 @@ def as_list(expr: gdb.Value) -> list[gdb.Value] | None:
         size = math.floor(expr.type.sizeof / expr.type.target().sizeof)
         return [expr[i] for i in range(size)]
 
+def deref(expr: gdb.Value) -> gdb.Value | None:
+    """Follow a (possibly null) pointer, returning None for null"""
+    if expr.type.strip_typedefs().code != gdb.TYPE_CODE_PTR:
+        return expr
+    if int(expr) == 0:
+        return None
+    return expr.dereference()
+
+def walk_list(head: gdb.Value, next_field: str, limit: int = 1000) -> Iterator[gdb.Value]:
+    """Yield the nodes of a linked list, stopping at null, a cycle or `limit` nodes"""
+    seen = set()
+    node = deref(head)
+    while node is not None and len(seen) < limit:
+        addr = int(node.address)
+        if addr in seen:
+            print(f"cycle detected at {addr:#x}")
+            return
+        seen.add(addr)
+        yield node
+        node = deref(node[next_field])
+
+def walk_tree(root: gdb.Value, left: str, right: str, limit: int = 1000) -> Iterator[gdb.Value]:
+    """Yield the nodes of a binary tree in order, skipping nodes already visited"""
+    seen = set()
+    # an explicit stack, since recursing through generators runs out of
+    # Python stack before `limit` on deep trees
+    stack: list[gdb.Value] = []
+    node = deref(root)
+    while node is not None or stack:
+        while node is not None and len(seen) < limit:
+            addr = int(node.address)
+            if addr in seen:
+                print(f"cycle detected at {addr:#x}")
+                break
+            seen.add(addr)
+            stack.append(node)
+            node = deref(node[left])
+        if not stack:
+            return
+        node = stack.pop()
+        yield node
+        node = deref(node[right])
+
+class WalkList(gdb.Command):
+    """Print the nodes of a linked list\nUsage: walk-list EXPR NEXT [LIMIT]"""
+    def __init__(self) -> None:
+        super(WalkList, self).__init__("walk-list", gdb.COMMAND_USER, gdb.COMPLETE_EXPRESSION)
+
+    def invoke(self, argument: str, from_tty: bool) -> None:
+        [expr, next_field, *limit] = argument.split(' ')
+        limit = int(limit[0]) if limit else 1000
+        for i, node in enumerate(walk_list(gdb.parse_and_eval(expr), next_field, limit)):
+            print(f"[{i}] = {node}")
+
+WalkList()
+
+class WalkTree(gdb.Command):
+    """Print the nodes of a binary tree in order\nUsage: walk-tree EXPR LEFT RIGHT [LIMIT]"""
+    def __init__(self) -> None:
+        super(WalkTree, self).__init__("walk-tree", gdb.COMMAND_USER, gdb.COMPLETE_EXPRESSION)
+
+    def invoke(self, argument: str, from_tty: bool) -> None:
+        [expr, left, right, *limit] = argument.split(' ')
+        limit = int(limit[0]) if limit else 1000
+        for i, node in enumerate(walk_tree(gdb.parse_and_eval(expr), left, right, limit)):
+            print(f"[{i}] = {node}")
+
+WalkTree()
+
 class GraphViz(gdb.Command):
     """Visualize a graph into a dot file\nUsage: graph-viz EXPR FILE """
     def __init__(self) -> None:
@@ -40,6 +112,77 @@ class GraphViz(gdb.Command):
 
 GraphViz()
 
+def escape_record(s: str) -> str:
+    for c in "\\{}|<>\"":
+        s = s.replace(c, "\\" + c)
+    return s
+
+class PointerGraphViz(gdb.Command):
+    """Visualize the structs reachable from EXPR through pointers into a dot file\nUsage: ptr-viz EXPR [DEPTH] FILE\n\nDEPTH defaults to 3"""
+    def __init__(self) -> None:
+        super(PointerGraphViz, self).__init__("ptr-viz", gdb.COMMAND_USER, gdb.COMPLETE_EXPRESSION)
+
+    def invoke(self, argument: str, from_tty: bool) -> None:
+        parts = argument.rsplit(' ', 2)
+        if len(parts) == 3 and parts[1].isdigit():
+            [expr, depth, file] = parts
+            depth = int(depth)
+        else:
+            [expr, file] = argument.rsplit(' ', 1)
+            depth = 3
+        root = gdb.parse_and_eval(expr)
+        if root.type.strip_typedefs().code == gdb.TYPE_CODE_PTR:
+            root = root.dereference()
+
+        gr = Digraph()
+        gr.attr("node", fontcolor="#cccccc", color="#cccccc", fontname="Input Mono", shape="record")
+        gr.attr("edge", color="#999999")
+        gr.attr("graph", bgcolor="#191919")
+        seen = set()
+
+        def visit(val: gdb.Value, depth: int) -> str:
+            name = str(int(val.address)) if val.address is not None else f"tmp{len(seen)}"
+            if name in seen:
+                return name
+            seen.add(name)
+
+            ty = val.type.strip_typedefs()
+            fields = []
+            edges = []
+            if ty.code == gdb.TYPE_CODE_STRUCT:
+                for f in ty.fields():
+                    fv = val[f]
+                    fty = fv.type.strip_typedefs()
+                    if fty.code == gdb.TYPE_CODE_PTR and fty.target().strip_typedefs().code == gdb.TYPE_CODE_STRUCT:
+                        port = f"f{len(fields)}"
+                        if int(fv) == 0:
+                            fields.append(f"<{port}> {escape_record(f.name)} = NULL")
+                        else:
+                            fields.append(f"<{port}> {escape_record(f.name)}")
+                            edges.append((port, fv))
+                    else:
+                        fields.append(escape_record(f"{f.name} = {fv}"))
+            else:
+                fields.append(escape_record(str(val)))
+
+            header = escape_record(str(val.type))
+            if val.address is not None:
+                header += f" @ {int(val.address):#x}"
+            gr.node(name, "{" + "|".join([header, *fields]) + "}")
+            if depth > 0:
+                for port, ptr in edges:
+                    try:
+                        target = visit(ptr.dereference(), depth - 1)
+                    except gdb.MemoryError:
+                        continue
+                    gr.edge(f"{name}:{port}", target)
+            return name
+
+        visit(root, depth)
+        gr.render(outfile=file)
+
+PointerGraphViz()
+
 tbl_style = """
 <style>
   html,
@@ -180,3 +323,255 @@ class TableViz(gdb.Command):
                 f.write(f'<span class="label" style="--color: {color}">{expr}</span>')
             f.write('</div>');
 TableViz()
+
+# filled in with the minimum string length
+utf8_pattern = rb"(?:[\x20-\x7e\t]|[\xc2-\xdf][\x80-\xbf]|[\xe0-\xef][\x80-\xbf]{2}|[\xf0-\xf4][\x80-\xbf]{3}){%d,}"
+utf16_pattern = rb"(?:[\x20-\x7e\t]\x00){%d,}"
+
+def memory_mappings() -> list[tuple[int, int, str]]:
+    """(start, end, objfile) of every mapping reported by `info proc mappings`"""
+    out = gdb.execute("info proc mappings", to_string=True)
+    maps = []
+    for line in out.splitlines():
+        cols = line.split()
+        if len(cols) >= 4 and cols[0].startswith("0x") and cols[1].startswith("0x"):
+            name = cols[-1] if cols[-1].startswith(("/", "[")) else ""
+            maps.append((int(cols[0], 16), int(cols[1], 16), name))
+    return maps
+
+def find_strings(start: int, end: int, min_len: int = 4) -> Iterator[tuple[int, str, str]]:
+    """Yield (address, encoding, text) of printable strings in [start, end)"""
+    inferior = gdb.selected_inferior()
+    chunk = 1 << 20
+    for base in range(start, end, chunk):
+        try:
+            data = bytes(inferior.read_memory(base, min(chunk, end - base)))
+        except gdb.MemoryError:
+            continue
+        for m in re.finditer(utf8_pattern % min_len, data):
+            text = m.group().decode("utf-8")
+            yield (base + m.start(), "ascii" if text.isascii() else "utf-8", text)
+        for m in re.finditer(utf16_pattern % min_len, data):
+            yield (base + m.start(), "utf-16le", m.group().decode("utf-16le"))
+
+class MemStrings(gdb.Command):
+    """Print printable ASCII/UTF-8/UTF-16 strings found in inferior memory\nUsage: mem-strings [MIN_LEN] [START END]\n\nWithout START and END every mapping from `info proc mappings` is scanned"""
+    def __init__(self) -> None:
+        super(MemStrings, self).__init__("mem-strings", gdb.COMMAND_USER, gdb.COMPLETE_EXPRESSION)
+
+    def invoke(self, argument: str, from_tty: bool) -> None:
+        args = argument.split()
+        min_len = int(args.pop(0)) if len(args) % 2 == 1 else 4
+        maps = memory_mappings()
+        if args:
+            regions = [(int(gdb.parse_and_eval(args[0])), int(gdb.parse_and_eval(args[1])))]
+        else:
+            regions = [(start, end) for start, end, _ in maps]
+
+        def mapping_of(addr: int) -> str:
+            for start, end, name in maps:
+                if start <= addr < end:
+                    return name or f"{start:#x}-{end:#x}"
+            return "?"
+
+        for start, end in regions:
+            for addr, encoding, text in find_strings(start, end, min_len):
+                print(f"{addr:#x} {encoding:8} {mapping_of(addr)}: {text!r}")
+
+MemStrings()
+
+def flatten(val: gdb.Value, path: str, out: dict[str, str], depth: int = 4, limit: int = 64) -> None:
+    """Adds the fields of structs and the items of arrays in `val` to `out` by path, like `p.pos.x`
+    or `buf[3]`, and anything else, pointers included, as its whole value"""
+    ty = val.type.strip_typedefs()
+    if depth > 0 and ty.code in (gdb.TYPE_CODE_STRUCT, gdb.TYPE_CODE_UNION):
+        for f in ty.fields():
+            if f.is_base_class or f.name is None:
+                # inherited and anonymous members read as if they were the struct's own
+                flatten(val[f], path, out, depth, limit)
+            else:
+                flatten(val[f], f"{path}.{f.name}", out, depth - 1, limit)
+    elif depth > 0 and ty.code == gdb.TYPE_CODE_ARRAY:
+        low, high = ty.range()
+        for i in range(low, min(high, low + limit - 1) + 1):
+            flatten(val[i], f"{path}[{i}]", out, depth - 1, limit)
+    else:
+        out[path] = str(val)
+
+def frame_locals(frame: gdb.Frame) -> dict[str, str]:
+    """The locals and arguments of `frame` by path, inner blocks shadowing outer ones"""
+    values = {}
+    seen = set()
+    try:
+        block = frame.block()
+    except RuntimeError:
+        # no debug info
+        return values
+    while block is not None:
+        for sym in block:
+            if (sym.is_variable or sym.is_argument) and sym.name not in seen:
+                seen.add(sym.name)
+                try:
+                    flatten(sym.value(frame), sym.name, values)
+                except gdb.error as e:
+                    values[sym.name] = f"<error: {e}>"
+        if block.function is not None:
+            break
+        block = block.superblock
+    return values
+
+def frame_key(frame: gdb.Frame) -> str:
+    """The function of `frame` and its depth, to tell a frame apart between stops"""
+    depth = 0
+    older = frame.older()
+    while older is not None:
+        depth += 1
+        older = older.older()
+    return f"{frame.name() or hex(frame.pc())}#{depth}"
+
+def diff_locals(old: dict[str, str], new: dict[str, str]) -> dict:
+    return {
+        "changed": {k: [old[k], v] for k, v in new.items() if k in old and old[k] != v},
+        "added": {k: v for k, v in new.items() if k not in old},
+        "removed": [k for k in old if k not in new],
+    }
+
+class LocalsDiff(gdb.Command):
+    """Print what changed in the locals of the selected frame at each stop\nUsage: locals-diff on|off\n\nEach stop prints a JSON line with the locals that changed, were added\nor were removed since the last stop in the same frame. Struct fields and\narray items are compared one by one and reported by path, like `p.pos.x`\nor `buf[3]`, down to 4 levels and 64 items per array"""
+    def __init__(self) -> None:
+        super(LocalsDiff, self).__init__("locals-diff", gdb.COMMAND_USER)
+        self.enabled = False
+        self.snapshots: dict[str, dict[str, str]] = {}
+
+    def invoke(self, argument: str, from_tty: bool) -> None:
+        if argument == "on" and not self.enabled:
+            gdb.events.stop.connect(self.on_stop)
+            self.enabled = True
+        elif argument == "off" and self.enabled:
+            gdb.events.stop.disconnect(self.on_stop)
+            self.enabled = False
+            self.snapshots.clear()
+        elif argument not in ("on", "off"):
+            print("usage: locals-diff on|off")
+
+    def on_stop(self, event: gdb.StopEvent) -> None:
+        try:
+            frame = gdb.selected_frame()
+        except gdb.error:
+            # the inferior exited
+            return
+        key = frame_key(frame)
+        new = frame_locals(frame)
+        diff = diff_locals(self.snapshots.get(key, {}), new)
+        self.snapshots[key] = new
+        if any(diff.values()):
+            print(json.dumps({"event": "locals", "frame": key, **diff}))
+
+LocalsDiff()
+
+comparisons = {">": operator.gt, "<": operator.lt, ">=": operator.ge, "<=": operator.le}
+
+class Trigger:
+    """Runs `commands` or calls `hook(old, new)` when `expr` changes, or when
+    it crosses `threshold` if `op` is given"""
+    def __init__(self, expr: str, commands: list[str], hook: Callable[[str, str], None] | None = None,
+                 op: str | None = None, threshold: float | None = None) -> None:
+        self.expr = expr
+        self.commands = commands
+        self.hook = hook
+        self.op = op
+        self.threshold = threshold
+        self.last: str | None = None
+        self.crossed = False
+
+    def check(self) -> tuple[str, str] | None:
+        """Evaluates `expr`, returning (old, new) if the trigger fires"""
+        try:
+            value = gdb.parse_and_eval(self.expr)
+            new = str(value)
+            crossed = self.op is not None and comparisons[self.op](float(value), self.threshold)
+        except gdb.error:
+            # out of scope at this stop, or not a number
+            return None
+        old, self.last = self.last, new
+        if self.op is None:
+            return (old, new) if old is not None and old != new else None
+        fired = crossed and not self.crossed and old is not None
+        self.crossed = crossed
+        return (old, new) if fired else None
+
+    def __str__(self) -> str:
+        cond = f" {self.op} {self.threshold:g}" if self.op else ""
+        action = "; ".join(self.commands) if self.hook is None else self.hook.__name__
+        return f"{self.expr}{cond} -- {action}"
+
+triggers: dict[int, Trigger] = {}
+next_trigger = 1
+
+def add_trigger(trigger: Trigger) -> int:
+    """Adds a trigger, for use from scripts with a `hook`, returning its number"""
+    global next_trigger
+    if not triggers:
+        gdb.events.stop.connect(check_triggers)
+    n = next_trigger
+    next_trigger += 1
+    triggers[n] = trigger
+    return n
+
+def delete_trigger(n: int) -> None:
+    del triggers[n]
+    if not triggers:
+        gdb.events.stop.disconnect(check_triggers)
+
+def check_triggers(event: gdb.StopEvent) -> None:
+    for n, trigger in list(triggers.items()):
+        if (change := trigger.check()) is None:
+            continue
+        old, new = change
+        print(json.dumps({"event": "trigger", "trigger": n, "expr": trigger.expr, "old": old, "new": new}))
+        if trigger.hook is not None:
+            trigger.hook(old, new)
+        for command in trigger.commands:
+            gdb.execute(command)
+
+class TriggerCommand(gdb.Command):
+    """Run commands when an expression changes or crosses a threshold at a stop\nUsage: trigger EXPR [OP THRESHOLD] -- COMMAND[; COMMAND...]\n       trigger\n\nOP is one of > < >= <=, with spaces around it. Without it the commands run\nwhen the value of EXPR changes between stops, with it when the comparison\nbecomes true. Each firing prints a JSON line. Without arguments, lists\nthe triggers\n\nExample: trigger len > 100 -- print buf; bt 1"""
+    def __init__(self) -> None:
+        super(TriggerCommand, self).__init__("trigger", gdb.COMMAND_USER, gdb.COMPLETE_EXPRESSION)
+
+    def invoke(self, argument: str, from_tty: bool) -> None:
+        if not argument.strip():
+            for n, trigger in triggers.items():
+                print(f"[{n}] {trigger}")
+            return
+        cond, sep, commands = argument.partition(" -- ")
+        if not sep:
+            print("usage: trigger EXPR [OP THRESHOLD] -- COMMAND[; COMMAND...]")
+            return
+        if m := re.fullmatch(r"(.+?)\s+(>=|<=|>|<)\s+(\S+)", cond.strip()):
+            expr, op, threshold = m.group(1), m.group(2), m.group(3)
+            try:
+                threshold = float(threshold)
+            except ValueError:
+                print(f"threshold {threshold} isn't a number")
+                return
+        else:
+            expr, op, threshold = cond.strip(), None, None
+        commands = [c.strip() for c in commands.split(";") if c.strip()]
+        n = add_trigger(Trigger(expr, commands, op=op, threshold=threshold))
+        print(f"trigger {n}: {triggers[n]}")
+
+TriggerCommand()
+
+class DeleteTrigger(gdb.Command):
+    """Delete a trigger\nUsage: trigger-delete N"""
+    def __init__(self) -> None:
+        super(DeleteTrigger, self).__init__("trigger-delete", gdb.COMMAND_USER)
+
+    def invoke(self, argument: str, from_tty: bool) -> None:
+        try:
+            delete_trigger(int(argument))
+        except (ValueError, KeyError):
+            print(f"no trigger {argument}")
+
+DeleteTrigger()